@@ -0,0 +1,60 @@
+// 探索の最内ループで呼ばれる盤の操作と TT の micro-benchmark。
+// ビット演算を書き換えたときに、cargo bench --bench board の前後の結果を比べる。
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use connect4_solver::internals::{hash_key, Table};
+use connect4_solver::{Board, HashFunction};
+
+// 終盤寄りの、勝ちの並びがまだ無い局面
+const POSITION: &str = "2252576253462244111563365343671351441";
+
+fn board_benches(c: &mut Criterion) {
+    let board = Board::from_moves(POSITION).unwrap();
+
+    c.bench_function("can_play", |b| {
+        b.iter(|| (0..7).filter(|&col| black_box(&board).can_play(col)).count())
+    });
+    c.bench_function("play", |b| {
+        b.iter(|| {
+            let mut next = *black_box(&board);
+            next.play(black_box(3));
+            next
+        })
+    });
+    c.bench_function("is_win", |b| b.iter(|| black_box(&board).is_win()));
+    c.bench_function("can_win_next", |b| b.iter(|| black_box(&board).can_win_next()));
+    c.bench_function("non_losing_moves", |b| b.iter(|| black_box(&board).non_losing_moves()));
+    c.bench_function("canonical_key", |b| b.iter(|| black_box(&board).canonical_key()));
+
+    // 1 つの局面だけだと分岐が偏るので、手数の違うランダムな局面でも測る
+    let mut rng = 1;
+    let boards: Vec<Board> = (0..256).filter_map(|i| Board::new().random_unfinished(4 + i % 32, &mut rng)).map(|(b, _)| b).collect();
+    c.bench_function("non_losing_moves/random", |b| {
+        b.iter(|| boards.iter().fold(0, |acc, board| acc ^ black_box(board).non_losing_moves()))
+    });
+}
+
+fn table_benches(c: &mut Criterion) {
+    // キャッシュに収まらない大きさ (16MB) にして、実際の探索に近いランダムアクセスにする
+    const ENTRIES: usize = 1 << 20;
+    let keys: Vec<u64> = (0..4096u64).map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 15).collect();
+
+    for hash in HashFunction::ALL {
+        c.bench_function(&format!("hash_key/{}", hash.name()), |b| {
+            b.iter(|| keys.iter().fold(0, |acc, &key| acc ^ hash_key(hash, black_box(key), ENTRIES - 1)))
+        });
+    }
+
+    let table = Table::new(ENTRIES);
+    c.bench_function("tt_store", |b| {
+        b.iter(|| for &key in &keys { table.store(black_box(key), 3, 4, 20); })
+    });
+    c.bench_function("tt_lookup", |b| {
+        b.iter(|| keys.iter().filter(|&&key| table.lookup(black_box(key)).is_some()).count())
+    });
+}
+
+criterion_group!(benches, board_benches, table_benches);
+criterion_main!(benches);
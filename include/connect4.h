@@ -0,0 +1,48 @@
+/*
+ * C API of connect4_solver (build with: cargo build --release --lib --features ffi).
+ * Link against target/release/libconnect4_solver.so (or .dylib / .dll).
+ *
+ * Columns are 1-based and scores are from the point of view of the side to move,
+ * in the Pascal Pons convention: (22 - stones of the winner) for a win, 0 for a draw.
+ * Move sequences are NUL-terminated strings of column digits such as "4453".
+ *
+ * Keep in sync with src/ffi.rs.
+ */
+#ifndef CONNECT4_H
+#define CONNECT4_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Returned by c4_solve for an invalid or already finished position. */
+#define C4_INVALID_POSITION INT32_MIN
+
+typedef struct C4Solver C4Solver;
+
+/* Creates a solver with a transposition table of table_entries entries (8 bytes each,
+ * rounded down to a power of two). Release it with c4_free. */
+C4Solver *c4_new_solver(size_t table_entries);
+
+void c4_free(C4Solver *solver);
+
+/* Score of the position reached by moves, or C4_INVALID_POSITION. */
+int c4_solve(const C4Solver *solver, const char *moves);
+
+/* Best column (1-7) for the side to move, or 0 for an invalid or finished position.
+ * If score is not NULL, the score of the position is written to it. */
+int c4_best_move(const C4Solver *solver, const char *moves, int *score);
+
+/* Stops a search running on another thread. Searches keep stopping immediately
+ * until c4_clear_abort is called. */
+void c4_abort(const C4Solver *solver);
+void c4_clear_abort(const C4Solver *solver);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,35 @@
+use std::path::Path;
+
+// Pascal Pons の公開しているテストセット (http://blog.gamesolver.org/solving-connect-four/02-test-protocol/)。
+// 各ファイルは 1 行に 1 局面で "手順 評価値"。L は局面の手数の多さ (3 が終盤)、R は難しさ。
+pub const SETS: [(&str, &str); 6] = [
+    ("endgame_easy", "Test_L3_R1"),
+    ("middle_easy", "Test_L2_R1"),
+    ("middle_medium", "Test_L2_R2"),
+    ("begin_easy", "Test_L1_R1"),
+    ("begin_medium", "Test_L1_R2"),
+    ("begin_hard", "Test_L1_R3"),
+];
+
+// セット名からファイル名。"middle" は "middle_easy" と同じ。
+pub fn file_name(set: &str) -> Option<&'static str> {
+    let set = if set == "middle" { "middle_easy" } else { set };
+    SETS.iter().find(|&&(name, _)| name == set).map(|&(_, file)| file)
+}
+
+// dir にあるセットのファイルを読む。(手順, 期待される評価値) の並び。
+pub fn read_set(dir: &str, set: &str) -> Result<Vec<(String, i8)>, String> {
+    let file = file_name(set).ok_or_else(|| {
+        let names: Vec<&str> = SETS.iter().map(|&(name, _)| name).collect();
+        format!("unknown benchmark set '{}' (expected one of {})", set, names.join(", "))
+    })?;
+    let path = Path::new(dir).join(file);
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).map(|(i, line)| {
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next().and_then(|s| s.parse().ok())) {
+            (Some(moves), Some(score)) => Ok((moves.to_string(), score)),
+            _ => Err(format!("{}:{}: expected '<moves> <score>'", path.display(), i + 1)),
+        }
+    }).collect()
+}
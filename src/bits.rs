@@ -0,0 +1,97 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops::{Add, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Mul, Not, Shl, Shr, Sub};
+
+// 盤のビット列に使う整数型。u64 は (H+1)*W が 64 以下の盤（標準の 7x6 を含む）、
+// u128 はそれより大きい 8x8 や 9x7 の盤に使う。
+pub trait Bits:
+    Copy + Eq + Ord + Hash + Debug + Default + Send + Sync + 'static
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+    + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self> + Not<Output = Self>
+    + BitAndAssign + BitOrAssign + BitXorAssign
+    + Shl<u32, Output = Self> + Shr<u32, Output = Self>
+{
+    const BITS: u32;
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn count_ones(self) -> u32;
+    fn trailing_zeros(self) -> u32;
+    fn wrapping_sub(self, rhs: Self) -> Self;
+    // TT と定跡で使う 64 ビットのキー。64 ビットに収まる値はそのまま返す。
+    fn fold(self) -> u64;
+    // 値を変えずに u128 にする（局面データベースのように衝突させたくないキーに使う）
+    fn widen(self) -> u128;
+
+    // 石の集合 self に、シフト量 dirs（縦・横・斜め 2 つ）のどれかの向きで 4 つ並んだ石があるか
+    #[inline(always)]
+    fn has_four(self, dirs: [u32; 4]) -> bool {
+        dirs.iter().any(|&d| {
+            let m = self & (self >> d);
+            (m & (m >> (2 * d))) != Self::ZERO
+        })
+    }
+
+    // 石を 1 つ足せば 4 つ並ぶマス。空きマスかどうかは呼び出し側で絞る。dirs の先頭は縦 (1)。
+    #[inline(always)]
+    fn four_threats(self, dirs: [u32; 4]) -> Self {
+        let p = self;
+        // 縦は上に積むしかないので、空きマスが並びの上端にある場合だけ
+        let mut r = (p << 1) & (p << 2) & (p << 3);
+        // 横・斜め 2 方向。空きマスが並びの端にある場合と途中にある場合の両方を見る。
+        for &d in &dirs[1..] {
+            let q = (p << d) & (p << (2 * d));
+            r |= q & (p << (3 * d));
+            r |= q & (p >> d);
+            let q = (p >> d) & (p >> (2 * d));
+            r |= q & (p << d);
+            r |= q & (p >> (3 * d));
+        }
+        r
+    }
+}
+
+impl Bits for u64 {
+    const BITS: u32 = 64;
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+
+    #[inline(always)]
+    fn count_ones(self) -> u32 { u64::count_ones(self) }
+    #[inline(always)]
+    fn trailing_zeros(self) -> u32 { u64::trailing_zeros(self) }
+    #[inline(always)]
+    fn wrapping_sub(self, rhs: Self) -> Self { u64::wrapping_sub(self, rhs) }
+    #[inline(always)]
+    fn fold(self) -> u64 { self }
+    #[inline(always)]
+    fn widen(self) -> u128 { self as u128 }
+
+    // simd feature を付け、AVX2 を有効にしてビルドしたとき (-C target-cpu=native など) だけ 4 方向を並列に調べる
+    #[cfg(all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"))]
+    #[inline(always)]
+    fn has_four(self, dirs: [u32; 4]) -> bool { crate::simd::has_four(self, dirs) }
+    #[cfg(all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"))]
+    #[inline(always)]
+    fn four_threats(self, dirs: [u32; 4]) -> Self { crate::simd::four_threats(self, dirs) }
+}
+
+impl Bits for u128 {
+    const BITS: u32 = 128;
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+
+    #[inline(always)]
+    fn count_ones(self) -> u32 { u128::count_ones(self) }
+    #[inline(always)]
+    fn trailing_zeros(self) -> u32 { u128::trailing_zeros(self) }
+    #[inline(always)]
+    fn wrapping_sub(self, rhs: Self) -> Self { u128::wrapping_sub(self, rhs) }
+    // 上位 64 ビットを混ぜて畳み込む。単射ではない（衝突の確率は tt.rs のエントリのレイアウトの説明）。
+    #[inline(always)]
+    fn fold(self) -> u64 {
+        (self as u64) ^ ((self >> 64) as u64).wrapping_mul(0x9e3779b97f4a7c15)
+    }
+    #[inline(always)]
+    fn widen(self) -> u128 { self }
+}
@@ -0,0 +1,806 @@
+use std::fmt;
+
+use crate::bits::Bits;
+use crate::game::Game;
+
+// 標準の盤の大きさ
+pub const WIDTH: u32 = 7;
+pub const HEIGHT: u32 = 6;
+pub const SIZE: u32 = WIDTH * HEIGHT;
+// 手順は 1 桁の列番号で書くので 9 列まで。盤は各列に番兵を 1 ビット足して u64 か u128 に収める。
+// 高さは斜めの 3 マス分のシフト (3 * (HEIGHT + 2)) が 64 未満に収まる範囲。
+pub const MAX_WIDTH: u32 = 9;
+pub const MAX_HEIGHT: u32 = 16;
+pub const MAX_BITS: u32 = 128;
+// 何目並べで勝ちか。標準は 4。
+pub const CONNECT: u32 = 4;
+pub const MAX_CONNECT: u32 = 8;
+
+// Board::random_unfinished で選び直す回数の上限
+const RANDOM_ATTEMPTS: u32 = 100;
+
+// splitmix64。state を進めて次の値を返す。乱数はどこでもこれを使う（Zobrist の表を作る const fn からも）。
+pub const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// 各列の最下段のビット
+fn bottom_mask<B: Bits>(width: u32, height: u32) -> B {
+    (0..width).fold(B::ZERO, |m, col| m | (B::ONE << (col * (height + 1))))
+}
+
+// 盤の大きさは実行時に決める。position は手番側の石、mask は全ての石。
+// (H+1)*W が 64 ビットを超える盤は Board128 を使う。
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Board<B: Bits = u64> {
+    position: B,
+    mask: B,
+    bottom: B,
+    moves: u32,
+    width: u8,
+    height: u8,
+    connect: u8,
+    // 手数が偶数のときに O の手番なら 1（with_grid で石の数の偶奇と違う手番を指定した局面）。石の色はこれで決める。
+    parity: u8,
+    // [そのままの盤, 左右反転した盤] の Zobrist キー
+    #[cfg(feature = "zobrist")]
+    zobrist: [u64; 2],
+}
+
+pub type Board128 = Board<u128>;
+
+impl<B: Bits> Default for Board<B> {
+    fn default() -> Self { Self::with_size(WIDTH, HEIGHT).expect("the standard board fits in any word") }
+}
+
+impl Board {
+    // 標準の 7x6 の空の盤
+    pub fn new() -> Self { Self::default() }
+
+    // "44453" のような 1 始まりの列番号の並びから標準の盤の局面を作る
+    pub fn from_moves(moves: &str) -> Result<Self, String> {
+        let mut board = Self::new();
+        board.play_moves(moves)?;
+        Ok(board)
+    }
+
+    // 64 ビットに収まらず Board128 が必要な大きさか。with_size と with_connect と同じ条件で判定する。
+    pub fn needs_wide(width: u32, height: u32, connect: u32) -> bool {
+        width * (height + 1) > u64::BITS || connect.saturating_sub(1) * (height + 2) >= u64::BITS
+    }
+
+    // with_grid に渡す図の (幅, 高さ)
+    pub fn grid_size(grid: &str) -> Result<(u32, u32), String> {
+        grid_dims(grid).map_err(|e| e.to_string())
+    }
+}
+
+// 先手 (X) と後手 (O)。手番は手数の偶奇で決まる（with_grid で手番を指定した局面は、その手番から交互になる）。
+// engine の強さを表す Player (level.rs) とは別物。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    X,
+    O,
+}
+
+impl Side {
+    pub fn other(self) -> Self {
+        match self {
+            Side::X => Side::O,
+            Side::O => Side::X,
+        }
+    }
+
+    pub fn symbol(self) -> char {
+        match self {
+            Side::X => 'X',
+            Side::O => 'O',
+        }
+    }
+}
+
+// 盤のマス
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Cell {
+    #[default]
+    Empty,
+    X,
+    O,
+}
+
+impl Cell {
+    // 図での記号（X / O / .）
+    pub fn symbol(self) -> char {
+        match self {
+            Cell::Empty => '.',
+            Cell::X => 'X',
+            Cell::O => 'O',
+        }
+    }
+}
+
+impl From<Side> for Cell {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::X => Cell::X,
+            Side::O => Cell::O,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GameState {
+    InProgress,
+    WonBy(Side),
+    Draw,
+}
+
+impl GameState {
+    pub fn is_over(self) -> bool { self != GameState::InProgress }
+}
+
+// 打てる列を中央寄りから並べたもの（Board::legal_moves）。盤の幅は MAX_WIDTH までなので固定長に収まる。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveList {
+    cols: [u32; MAX_WIDTH as usize],
+    len: usize,
+}
+
+impl MoveList {
+    fn push(&mut self, col: u32) {
+        self.cols[self.len] = col;
+        self.len += 1;
+    }
+}
+
+impl std::ops::Deref for MoveList {
+    type Target = [u32];
+    fn deref(&self) -> &[u32] { &self.cols[..self.len] }
+}
+
+impl IntoIterator for MoveList {
+    type Item = u32;
+    type IntoIter = std::iter::Take<std::array::IntoIter<u32, { MAX_WIDTH as usize }>>;
+    fn into_iter(self) -> Self::IntoIter { self.cols.into_iter().take(self.len) }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a u32;
+    type IntoIter = std::slice::Iter<'a, u32>;
+    fn into_iter(self) -> Self::IntoIter { self.iter() }
+}
+
+// 図から局面を作れなかった理由。行・列は 1 始まり。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GridError {
+    Empty,
+    RaggedRow { row: u32, cells: u32, expected: u32 },
+    // 盤の大きさ・何目並べが扱えない（with_size / with_connect のエラー）
+    Size(String),
+    WrongSize { width: u32, height: u32, expected_width: u32, expected_height: u32 },
+    InvalidCell { row: u32, cell: char },
+    FloatingPiece { col: u32 },
+    // 先手 X が後手 O と同数か 1 つ多い、になっていない
+    PieceCount { x: u32, o: u32 },
+    InvalidSideToMove(char),
+    BothConnected,
+    // 手番の側が既に並べている（並べた時点で終局しているので、相手の手番にはならない）
+    SideToMoveConnected { side: char },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::Empty => write!(f, "the grid is empty"),
+            GridError::RaggedRow { row, cells, expected } =>
+                write!(f, "grid row {} has {} cells, but the first row has {}", row, cells, expected),
+            GridError::Size(msg) => write!(f, "{}", msg),
+            GridError::WrongSize { expected_width, expected_height, .. } =>
+                write!(f, "the grid must have {} rows of {} cells", expected_height, expected_width),
+            GridError::InvalidCell { row, cell } => write!(f, "invalid cell '{}' in grid row {} (expected X, O or .)", cell, row),
+            GridError::FloatingPiece { col } => write!(f, "column {} has a floating piece", col),
+            GridError::PieceCount { x, o } =>
+                write!(f, "{} X and {} O cannot arise in play (X has as many pieces as O or one more)", x, o),
+            GridError::InvalidSideToMove(c) => write!(f, "invalid side to move '{}' (expected X or O)", c),
+            GridError::BothConnected => write!(f, "both X and O have a line"),
+            GridError::SideToMoveConnected { side } => write!(f, "{} is to move but already has a line", side),
+        }
+    }
+}
+
+impl std::error::Error for GridError {}
+
+fn grid_dims(grid: &str) -> Result<(u32, u32), GridError> {
+    let rows = grid_rows(grid);
+    let width = rows.first().map_or(0, Vec::len);
+    if width == 0 { return Err(GridError::Empty); }
+    if let Some(i) = rows.iter().position(|row| row.len() != width) {
+        return Err(GridError::RaggedRow { row: i as u32 + 1, cells: rows[i].len() as u32, expected: width as u32 });
+    }
+    Ok((width as u32, rows.len() as u32))
+}
+
+// 図の各行のマス。空行と # で始まる行は飛ばし、空白と | は無視する。
+fn grid_rows(grid: &str) -> Vec<Vec<char>> {
+    grid.lines()
+        .map(|line| line.chars().filter(|&c| !c.is_whitespace() && c != '|').collect::<Vec<_>>())
+        .filter(|row| !row.is_empty() && row[0] != '#')
+        .collect()
+}
+
+impl<B: Bits> Board<B> {
+    // width x height の空の盤（4 マス以上）。width * (height + 1) が B のビット数に収まること。
+    pub fn with_size(width: u32, height: u32) -> Result<Self, String> {
+        if !(1..=MAX_WIDTH).contains(&width) { return Err(format!("width must be between 1 and {}", MAX_WIDTH)); }
+        if !(1..=MAX_HEIGHT).contains(&height) { return Err(format!("height must be between 1 and {}", MAX_HEIGHT)); }
+        // 探索は評価値の範囲を size - 3 などから求める
+        if width * height < 4 { return Err(format!("a {}x{} board is too small (at least 4 squares)", width, height)); }
+        if width * (height + 1) > B::BITS {
+            return Err(format!("a {}x{} board does not fit in {} bits (width * (height + 1) <= {})",
+                width, height, B::BITS, B::BITS));
+        }
+        Ok(Self {
+            position: B::ZERO, mask: B::ZERO, bottom: bottom_mask(width, height), moves: 0,
+            width: width as u8, height: height as u8, connect: CONNECT as u8, parity: 0,
+            #[cfg(feature = "zobrist")]
+            zobrist: [0, 0],
+        })
+    }
+
+    // k 目並べにした空の盤。斜めの k - 1 マス分のシフトが B のビット数に収まること。
+    pub fn with_connect(mut self, k: u32) -> Result<Self, String> {
+        if self.moves != 0 { return Err("the connect length must be set on an empty board".into()); }
+        if !(2..=MAX_CONNECT).contains(&k) { return Err(format!("connect length must be between 2 and {}", MAX_CONNECT)); }
+        if (k - 1) * (self.height() + 2) >= B::BITS {
+            return Err(format!("connect {} on a board of height {} does not fit in {} bits", k, self.height(), B::BITS));
+        }
+        self.connect = k as u8;
+        Ok(self)
+    }
+
+    // X (先手) と O (後手) と . (空き) の図から局面を作る。図は上の段から 1 行ずつで、空の盤に対して呼ぶ。
+    // 手合い割りや詰め四目のような、実戦では現れない石の数の局面も作れる。
+    // 手番は to_move ('X' か 'O') で指定する。None なら石の数が同じなら X、X が 1 つ多ければ O。
+    pub fn with_grid(self, grid: &str, to_move: Option<char>) -> Result<Self, String> {
+        if self.moves != 0 { return Err("a grid must be set on an empty board".into()); }
+        self.place_grid(grid, to_move, false).map_err(|e| match e {
+            GridError::PieceCount { x, o } => format!("cannot tell whose move it is from {} X and {} O; give the side to move", x, o),
+            e => e.to_string(),
+        })
+    }
+
+    // 実戦で現れうる局面だけを受け付ける with_grid。盤の大きさは図から決め、k 目並べにする。
+    // 石の数は X が O と同数か 1 つ多く（手番はそれで決まる）、両方が並べていたり、手番の側が並べていたりしてはいけない。
+    pub fn try_from_grid(grid: &str, k: u32) -> Result<Self, GridError> {
+        let (width, height) = grid_dims(grid)?;
+        let board = Self::with_size(width, height).and_then(|b| b.with_connect(k)).map_err(GridError::Size)?;
+        board.place_grid(grid, None, true)
+    }
+
+    fn place_grid(self, grid: &str, to_move: Option<char>, strict: bool) -> Result<Self, GridError> {
+        let (w, h) = (self.width(), self.height());
+        let (width, height) = grid_dims(grid)?;
+        if (width, height) != (w, h) {
+            return Err(GridError::WrongSize { width, height, expected_width: w, expected_height: h });
+        }
+        let (mut x, mut o) = (B::ZERO, B::ZERO);
+        for (i, row) in grid_rows(grid).iter().enumerate() {
+            for (col, &c) in row.iter().enumerate() {
+                let bit = B::ONE << (col as u32 * (h + 1) + h - 1 - i as u32);
+                match c {
+                    'X' | 'x' => x |= bit,
+                    'O' | 'o' => o |= bit,
+                    '.' => {}
+                    _ => return Err(GridError::InvalidCell { row: i as u32 + 1, cell: c }),
+                }
+            }
+        }
+        let mask = x | o;
+        // 各列の石が下から隙間なく積まれていれば、mask + bottom は各列の石の 1 つ上だけになる
+        if (mask + self.bottom) & mask != B::ZERO {
+            let col = (0..w).find(|&col| {
+                let m = mask & self.column_mask(col);
+                (m + (B::ONE << (col * (h + 1)))) & m != B::ZERO
+            }).unwrap_or(0);
+            return Err(GridError::FloatingPiece { col: col + 1 });
+        }
+        let (xs, os) = (x.count_ones(), o.count_ones());
+        let x_to_move = match to_move.map(|c| c.to_ascii_uppercase()) {
+            _ if strict && xs != os && xs != os + 1 => return Err(GridError::PieceCount { x: xs, o: os }),
+            Some('X') => true,
+            Some('O') => false,
+            Some(c) => return Err(GridError::InvalidSideToMove(c)),
+            None if xs == os => true,
+            None if xs == os + 1 => false,
+            None => return Err(GridError::PieceCount { x: xs, o: os }),
+        };
+        let parity = ((xs + os).is_multiple_of(2) != x_to_move) as u8;
+        #[allow(unused_mut)]
+        let mut board = Self { position: if x_to_move { x } else { o }, mask, moves: xs + os, parity, ..self };
+        #[cfg(feature = "zobrist")]
+        board.reset_zobrist();
+        if strict && board.has_line(x) && board.has_line(o) { return Err(GridError::BothConnected); }
+        if board.has_line(board.position) {
+            return Err(GridError::SideToMoveConnected { side: if x_to_move { 'X' } else { 'O' } });
+        }
+        Ok(board)
+    }
+
+    // 1 始まりの列番号の並びを続けて打つ（空白は無視する）
+    pub fn play_moves(&mut self, moves: &str) -> Result<(), String> {
+        let start = self.moves;
+        for (i, c) in moves.chars().filter(|c| !c.is_whitespace()).enumerate() {
+            let n = start as usize + i + 1;
+            let col = match c.to_digit(10) {
+                Some(d) if (1..=self.width()).contains(&d) => d - 1,
+                _ => return Err(format!("invalid column '{}' at move {}", c, n)),
+            };
+            if self.is_win() { return Err(format!("game is already over before move {}", n)); }
+            if !self.can_play(col) { return Err(format!("column {} is full at move {}", col + 1, n)); }
+            self.play(col);
+        }
+        Ok(())
+    }
+    #[inline(always)]
+    pub fn width(&self) -> u32 { self.width as u32 }
+    #[inline(always)]
+    pub fn height(&self) -> u32 { self.height as u32 }
+    #[inline(always)]
+    pub fn connect(&self) -> u32 { self.connect as u32 }
+    #[inline(always)]
+    pub fn size(&self) -> u32 { self.width() * self.height() }
+    #[inline(always)]
+    pub fn moves(&self) -> u32 { self.moves }
+    #[inline(always)]
+    pub fn can_play(&self, col: u32) -> bool {
+        (self.mask & (B::ONE << ((col * (self.height() + 1)) + self.height() - 1))) == B::ZERO
+    }
+    #[inline(always)]
+    pub fn play(&mut self, col: u32) {
+        #[cfg(feature = "zobrist")]
+        {
+            let row = self.column_height(col);
+            let [k, m] = crate::zobrist::stone(self.moves % 2, col, row, self.width(), self.height());
+            self.zobrist[0] ^= k;
+            self.zobrist[1] ^= m;
+        }
+        self.position ^= self.mask;
+        self.mask |= self.mask + (B::ONE << (col * (self.height() + 1)));
+        self.moves += 1;
+    }
+    // 手番の側
+    #[inline(always)]
+    pub fn side_to_move(&self) -> Side {
+        if (self.moves + self.parity as u32).is_multiple_of(2) { Side::X } else { Side::O }
+    }
+
+    // 勝負がついたか、盤が埋まったか
+    #[inline(always)]
+    pub fn is_over(&self) -> bool {
+        self.is_win() || self.moves == self.size()
+    }
+
+    // 勝ちは直前に打った側（盤が埋まる最後の手で並べても勝ち）
+    pub fn state(&self) -> GameState {
+        if self.is_win() { GameState::WonBy(self.side_to_move().other()) }
+        else if self.moves == self.size() { GameState::Draw }
+        else { GameState::InProgress }
+    }
+
+    // play(col) を取り消す。col は直前に打った列であること（列の一番上の石を直前の手として取り除く）。
+    // 石の無い列に対して呼ぶと panic する。
+    #[inline(always)]
+    pub fn undo(&mut self, col: u32) {
+        let bottom = B::ONE << (col * (self.height() + 1));
+        let column = self.mask & self.column_mask(col);
+        assert!(column != B::ZERO, "undo on an empty column {}", col + 1);
+        // 石の 1 つ上のビットから 1 つ下げた位置が一番上の石
+        let top = (column + bottom) >> 1;
+        self.mask ^= top;
+        self.position ^= self.mask;
+        self.moves -= 1;
+        #[cfg(feature = "zobrist")]
+        {
+            let row = self.column_height(col);
+            let [k, m] = crate::zobrist::stone(self.moves % 2, col, row, self.width(), self.height());
+            self.zobrist[0] ^= k;
+            self.zobrist[1] ^= m;
+        }
+    }
+
+    // 列 col に積まれた石の数（height() は盤の高さ）
+    #[inline(always)]
+    pub fn column_height(&self, col: u32) -> u32 {
+        (self.mask & self.column_mask(col)).count_ones()
+    }
+
+    // どの列も一番上まで埋まっているか。PopOut では石を抜けるので、手数ではなく石で見る。
+    #[inline(always)]
+    pub fn is_full(&self) -> bool {
+        (self.bottom << (self.height() - 1)) & !self.mask == B::ZERO
+    }
+
+    // 列 col の下から row 段目（どちらも 0 始まり）の石。盤の外を指すと panic する。
+    pub fn piece_at(&self, col: u32, row: u32) -> Option<Side> {
+        assert!(col < self.width() && row < self.height(), "piece_at called outside the board");
+        let bit = B::ONE << (col * (self.height() + 1) + row);
+        if self.mask & bit == B::ZERO { None }
+        else if self.position & bit != B::ZERO { Some(self.side_to_move()) }
+        else { Some(self.side_to_move().other()) }
+    }
+
+    // 全てのマス。grid[row][col] が piece_at(col, row) で、段は下から（図にするときは逆順に並べる）。
+    pub fn to_grid(&self) -> Vec<Vec<Cell>> {
+        (0..self.height()).map(|row| (0..self.width()).map(|col| self.piece_at(col, row).map_or(Cell::Empty, Cell::from)).collect()).collect()
+    }
+
+    // 直前に打った側が並べ終えているか
+    #[inline(always)]
+    pub fn is_win(&self) -> bool {
+        self.has_line(self.position ^ self.mask)
+    }
+
+    // is_win のとき、直前に打った側が並べたマス (列, 段) を並びの端から順に。段は下から 0 始まりで、長さは connect()。
+    // 並びが複数あれば縦・横・斜め（右下がり）・斜め（右上がり）の順で最初に見つかったもの。
+    pub fn winning_cells(&self) -> Option<Vec<(u32, u32)>> {
+        let pos = self.position ^ self.mask;
+        let h = self.height();
+        [1, h + 1, h, h + 2].into_iter().find_map(|d| {
+            let starts = (1..self.connect()).fold(pos, |m, i| m & (pos >> (i * d)));
+            if starts == B::ZERO { return None; }
+            let start = starts.trailing_zeros();
+            Some((0..self.connect()).map(|i| ((start + i * d) / (h + 1), (start + i * d) % (h + 1))).collect())
+        })
+    }
+
+    // 手番側が並べ終えているか（PopOut で石を抜くと、抜いていない側の並びができることがある）
+    #[inline(always)]
+    pub fn is_win_for_next(&self) -> bool {
+        self.has_line(self.position)
+    }
+
+    // PopOut: 列 col の最下段に手番側の石があり、抜けるか
+    #[inline(always)]
+    pub fn can_pop(&self, col: u32) -> bool {
+        self.position & (B::ONE << (col * (self.height() + 1))) != B::ZERO
+    }
+
+    // PopOut: 列 col の最下段の石（手番側の石）を抜いて、上の石を 1 段ずつ落とす
+    pub fn pop(&mut self, col: u32) {
+        let column = self.column_mask(col);
+        let drop = |x: B| (x & !column) | ((x >> 1) & column);
+        self.position = drop(self.position) ^ drop(self.mask);
+        self.mask = drop(self.mask);
+        self.moves -= 1;
+        #[cfg(feature = "zobrist")]
+        self.reset_zobrist();
+    }
+
+    // 石の並びから Zobrist キーを計算し直す（1 手ずつの更新ができない操作の後に呼ぶ）
+    #[cfg(feature = "zobrist")]
+    fn reset_zobrist(&mut self) {
+        self.zobrist = crate::zobrist::full_key(self.position, self.mask, self.moves % 2, self.width(), self.height());
+    }
+
+    // 石の集合 pos に connect 個の並びがあるか
+    #[inline(always)]
+    pub(crate) fn has_line(&self, pos: B) -> bool {
+        let h = self.height();
+        let directions = [1, h, h + 1, h + 2];
+        if self.connect() != 4 {
+            return directions.iter().any(|&d| (1..self.connect()).fold(pos, |m, i| m & (pos >> (i * d))) != B::ZERO);
+        }
+        pos.has_four(directions)
+    }
+    #[inline(always)]
+    pub fn key(&self) -> B { self.position + self.mask }
+
+    // (手番側の石, 全ての石)
+    #[inline(always)]
+    pub(crate) fn stones(&self) -> (B, B) { (self.position, self.mask) }
+
+    // 中央に近い列から順に並べた列番号（探索で手を調べる順番）
+    #[inline(always)]
+    pub fn column_order(&self) -> impl Iterator<Item = u32> {
+        let w = self.width();
+        (0..w).map(move |i| if i % 2 == 0 { w / 2 + i / 2 } else { w / 2 - i.div_ceil(2) })
+    }
+
+    // 打てる列を column_order の順に。列が埋まっているかだけを見るので、勝負がついた局面でも空でない。
+    pub fn legal_moves(&self) -> MoveList {
+        let mut list = MoveList { cols: [0; MAX_WIDTH as usize], len: 0 };
+        for col in self.column_order() {
+            if self.can_play(col) { list.push(col); }
+        }
+        list
+    }
+
+    // self から plies 手を、打てる列から一様に選んで打った局面と、その手順。途中で勝負がついたらそこで止める。
+    // rng は splitmix64 の状態で、同じ値から始めれば同じ局面になる。
+    pub fn random(&self, plies: u32, rng: &mut u64) -> (Self, Vec<u32>) {
+        let mut board = *self;
+        let mut moves = Vec::new();
+        while moves.len() < plies as usize && !board.is_over() {
+            let cols = board.legal_moves();
+            let col = cols[(splitmix64(rng) % cols.len() as u64) as usize];
+            board.play(col);
+            moves.push(col);
+        }
+        (board, moves)
+    }
+
+    // random と同じだが、plies 手ちょうどで終局していない局面になるまで選び直す。
+    // RANDOM_ATTEMPTS 回で見つからなければ None（plies が残りのマスより多い、など）。
+    pub fn random_unfinished(&self, plies: u32, rng: &mut u64) -> Option<(Self, Vec<u32>)> {
+        (0..RANDOM_ATTEMPTS).map(|_| self.random(plies, rng))
+            .find(|(board, moves)| moves.len() == plies as usize && !board.is_over())
+    }
+
+    // 左右を反転した局面（評価値は元の局面と同じ）
+    pub fn mirror(&self) -> Self {
+        Self {
+            position: self.mirror_bits(self.position), mask: self.mirror_bits(self.mask),
+            #[cfg(feature = "zobrist")]
+            zobrist: [self.zobrist[1], self.zobrist[0]],
+            ..*self
+        }
+    }
+
+    // 左右反転した局面と共通のキー。反転側のキーを使った場合は true。
+    #[inline(always)]
+    pub fn canonical_key(&self) -> (B, bool) {
+        let key = self.key();
+        let mirrored = self.mirror_bits(key);
+        if mirrored < key { (mirrored, true) } else { (key, false) }
+    }
+
+    // TT のキーと、左右反転した側で正規化したか。既定では canonical_key を 64 ビットにしたもの、
+    // zobrist feature では 1 手ずつ更新した Zobrist キーの小さい方。
+    #[inline(always)]
+    pub fn table_key(&self) -> (u64, bool) {
+        #[cfg(feature = "zobrist")]
+        {
+            let [key, mirrored] = self.zobrist;
+            if mirrored < key { (mirrored, true) } else { (key, false) }
+        }
+        #[cfg(not(feature = "zobrist"))]
+        {
+            let (key, mirrored) = self.canonical_key();
+            (key.fold(), mirrored)
+        }
+    }
+
+    // 列 col の全マス
+    #[inline(always)]
+    pub fn column_mask(&self, col: u32) -> B {
+        ((B::ONE << self.height()) - B::ONE) << (col * (self.height() + 1))
+    }
+
+    // 下から row 段目 (0 始まり) の全マス
+    #[inline(always)]
+    pub(crate) fn row_mask(&self, row: u32) -> B {
+        self.bottom << row
+    }
+
+    // 盤面上の全マス（各列の番兵ビットを除く）
+    #[inline(always)]
+    pub(crate) fn board_mask(&self) -> B {
+        self.bottom * ((B::ONE << self.height()) - B::ONE)
+    }
+
+    // 次に石を置けるマス（各列の一番下の空きマス）
+    #[inline(always)]
+    pub fn possible(&self) -> B {
+        (self.mask + self.bottom) & self.board_mask()
+    }
+
+    // 手番側が置けば四目になる空きマス（まだ置けない高さのマスも含む）
+    #[inline(always)]
+    pub fn winning_position(&self) -> B {
+        self.compute_winning_position(self.position, self.mask)
+    }
+
+    // 相手が置けば四目になる空きマス
+    #[inline(always)]
+    pub fn opponent_winning_position(&self) -> B {
+        self.compute_winning_position(self.position ^ self.mask, self.mask)
+    }
+
+    // 手番側が次の一手で勝てるか
+    #[inline(always)]
+    pub fn can_win_next(&self) -> bool {
+        self.winning_position() & self.possible() != B::ZERO
+    }
+
+    // mv (possible() の 1 ビット) に打った後の手番側の勝ちマスの数。手の並べ替えに使う。
+    #[inline(always)]
+    pub fn move_score(&self, mv: B) -> u32 {
+        self.compute_winning_position(self.position | mv, self.mask | mv).count_ones()
+    }
+
+    // 今すぐ塞がないと相手に勝たれるマス（相手の勝ちマスのうち今置けるもの）
+    #[inline(always)]
+    pub fn forced_moves(&self) -> B {
+        self.possible() & self.opponent_winning_position()
+    }
+
+    // 相手の勝ちマスの真下に置く手（相手に即勝ちを与える手）を除いた着手可能マス。
+    // 0 なら何を打っても次に相手が勝つ。
+    #[inline(always)]
+    pub fn non_losing_moves(&self) -> B {
+        self.possible() & !(self.opponent_winning_position() >> 1)
+    }
+
+    // 列の並びを左右反転する（各列の番兵ビットも含めて列ごと入れ替える）
+    #[inline(always)]
+    fn mirror_bits(&self, x: B) -> B {
+        let (w, h) = (self.width(), self.height());
+        let column = (B::ONE << (h + 1)) - B::ONE;
+        let mut r = B::ZERO;
+        for col in 0..w {
+            r |= ((x >> (col * (h + 1))) & column) << ((w - 1 - col) * (h + 1));
+        }
+        r
+    }
+
+    // position の石に 1 つ足せば四目になる空きマスを 4 方向まとめて求める
+    #[inline(always)]
+    pub(crate) fn compute_winning_position(&self, position: B, mask: B) -> B {
+        let h = self.height();
+        if self.connect() != 4 { return self.compute_winning_position_k(position) & (self.board_mask() ^ mask); }
+        position.four_threats([1, h, h + 1, h + 2]) & (self.board_mask() ^ mask)
+    }
+
+    // connect が 4 以外のとき。空きマスが長さ k の並びの j 番目にある場合をすべて見る。
+    fn compute_winning_position_k(&self, position: B) -> B {
+        let (h, k) = (self.height(), self.connect());
+        let mut r = (1..k).fold(!B::ZERO, |m, i| m & (position << i));
+        for d in [h + 1, h, h + 2] {
+            for j in 0..k {
+                let mut m = !B::ZERO;
+                for i in 0..j { m &= position << ((j - i) * d); }
+                for i in j + 1..k { m &= position >> ((i - j) * d); }
+                r |= m;
+            }
+        }
+        r
+    }
+}
+
+// 汎用の探索 (Solver::solve_game) 用。キーと最善手の向きは Solver::solve と同じ（左右反転で正規化）なので、
+// 評価値の意味も同じ専用の探索と TT のエントリを共有できる。
+impl<B: Bits> Game for Board<B> {
+    type Move = u32;
+
+    // 中央寄りの列から、打った後の勝ちマスが多い順
+    fn moves(&self) -> Vec<u32> {
+        let mut cols = self.legal_moves().to_vec();
+        cols.sort_by_key(|&col| std::cmp::Reverse(self.move_score(self.possible() & self.column_mask(col))));
+        cols
+    }
+
+    #[inline(always)]
+    fn play(&mut self, col: u32) { Board::play(self, col) }
+
+    fn is_terminal(&self) -> Option<i8> {
+        if self.is_win() { Some(-((self.size() + 2 - self.moves()) as i8 / 2)) }
+        else if self.moves() == self.size() { Some(0) }
+        else { None }
+    }
+
+    fn key(&self) -> u64 { self.table_key().0 }
+    fn max_score(&self) -> i8 { (self.size() + 1 - self.moves()) as i8 / 2 }
+    fn remaining(&self) -> u32 { self.size() - self.moves() }
+    fn move_index(&self, col: u32) -> u32 {
+        if self.table_key().1 { self.width() - 1 - col } else { col }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // to_grid の図（上の段から）
+    fn grid<B: Bits>(board: &Board<B>) -> String {
+        board.to_grid().iter().rev().map(|row| row.iter().map(|c| c.symbol()).collect::<String>() + "\n").collect()
+    }
+
+    #[test]
+    fn grid_round_trip() {
+        for moves in ["", "4", "44", "4455", "112233", "4444443", "7172737"] {
+            let board = Board::from_moves(moves).unwrap();
+            let parsed = Board::<u64>::try_from_grid(&grid(&board), 4).unwrap();
+            assert!(parsed == board, "{}", moves);
+            assert_eq!(parsed.side_to_move(), board.side_to_move(), "{}", moves);
+        }
+        // 空白・| と # の行は無視する
+        let parsed = Board::<u64>::try_from_grid("# comment\n|. . .|\n|. . .|\n|X O .|\n", 3).unwrap();
+        let mut board = Board::<u64>::with_size(3, 3).unwrap().with_connect(3).unwrap();
+        board.play_moves("12").unwrap();
+        assert!(parsed == board);
+    }
+
+    #[test]
+    fn grid_side_round_trip() {
+        let top = ".......\n".repeat(4);
+        // 石の数の偶奇と違う手番を指定しても、色と手番はそのまま
+        for (g, side, next) in [("X......\nO......\n", 'O', Side::O), ("X......\nOX.....\n", 'X', Side::X), ("X......\nO......\n", 'X', Side::X)] {
+            let g = format!("{}{}", top, g);
+            let mut board = Board::<u64>::new().with_grid(&g, Some(side)).unwrap();
+            assert_eq!(grid(&board), g);
+            assert_eq!(board.side_to_move(), next);
+            board.play(6);
+            assert_eq!(board.piece_at(6, 0), Some(next));
+            assert_eq!(board.side_to_move(), next.other());
+            board.undo(6);
+            assert_eq!(grid(&board), g);
+        }
+    }
+
+    #[test]
+    fn grid_errors() {
+        let err = |grid: &str| Board::<u64>::try_from_grid(grid, 4).err().unwrap_or_else(|| panic!("{}", grid));
+        assert_eq!(err(""), GridError::Empty);
+        assert_eq!(err("....\n...\n"), GridError::RaggedRow { row: 2, cells: 3, expected: 4 });
+        assert_eq!(err("....\n..#.\n"), GridError::InvalidCell { row: 2, cell: '#' });
+        assert!(matches!(err("..\n"), GridError::Size(_)));
+        let top = ".......\n".repeat(4);
+        assert_eq!(err(&format!("{}.X.....\n.......\n", top)), GridError::FloatingPiece { col: 2 });
+        assert_eq!(err(&format!("{}.......\nXX.....\n", top)), GridError::PieceCount { x: 2, o: 0 });
+        // X の横と O の縦
+        assert_eq!(err(".......\n.......\n......O\n......O\nXX....O\nXXXXOOO\n"), GridError::BothConnected);
+        // 同数なので X の手番だが、X が既に並べている
+        assert_eq!(err(&format!("{}OO.....\nXXXXOO.\n", top)), GridError::SideToMoveConnected { side: 'X' });
+        // 相手（直前に打った側）が並べているのは終局した局面
+        assert!(Board::<u64>::try_from_grid(&format!("{}.......\nXXXXOOO\n", top), 4).unwrap().is_win());
+    }
+
+    #[test]
+    fn winning_cells() {
+        let cells = |moves: &str| Board::from_moves(moves).unwrap().winning_cells();
+        // 縦・横・斜め（右上がり）・斜め（右下がり）
+        assert_eq!(cells("1212121"), Some(vec![(0, 0), (0, 1), (0, 2), (0, 3)]));
+        assert_eq!(cells("1122334"), Some(vec![(0, 0), (1, 0), (2, 0), (3, 0)]));
+        assert_eq!(cells("12234334544"), Some(vec![(0, 0), (1, 1), (2, 2), (3, 3)]));
+        assert_eq!(cells("76654554344"), Some(vec![(3, 3), (4, 2), (5, 1), (6, 0)]));
+        // 5 目並べの横
+        let mut board = Board::<u64>::with_size(7, 6).unwrap().with_connect(5).unwrap();
+        board.play_moves("112233445").unwrap();
+        assert_eq!(board.winning_cells(), Some(vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)]));
+        assert_eq!(cells(""), None);
+        assert_eq!(cells("112233"), None);
+        assert_eq!(cells("4444443"), None);
+    }
+
+    #[test]
+    fn undo_restores_the_board() {
+        let mut rng = 0x2545_f491_4f6c_dd1d;
+        for (width, height) in [(7, 6), (4, 4), (8, 7)] {
+            let empty = Board::<u64>::with_size(width, height).unwrap();
+            for _ in 0..20 {
+                let (_, moves) = empty.random(width * height, &mut rng);
+                let mut boards = vec![empty];
+                let mut board = empty;
+                for &col in &moves {
+                    board.play(col);
+                    boards.push(board);
+                }
+                for &col in moves.iter().rev() {
+                    boards.pop();
+                    board.undo(col);
+                    assert!(board == *boards.last().unwrap(), "{:?}", moves);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "undo on an empty column 3")]
+    fn undo_on_an_empty_column() {
+        Board::<u64>::from_moves("44").unwrap().undo(2);
+    }
+}
@@ -0,0 +1,166 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::bits::Bits;
+use crate::board::{Board, Board128};
+use crate::perft::Layers;
+use crate::solver::Solver;
+
+const BOOK_MAGIC: &[u8; 4] = b"C4BK";
+const BOOK_VERSION: u32 = 3;
+const HEADER_BYTES: usize = 24;
+const RECORD_BYTES: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BookEntry {
+    pub key: u64,
+    pub score: i8,
+    pub best_col: u8,
+}
+
+// 序盤の全局面 (key → 評価値, 最善手) を key でソートして持つ定跡。
+// depth は収録されている最も深い局面の手数。key は盤の大きさごとに意味が違い、評価値は何目並べかで変わるので両方持つ。
+// 64 ビットを超える盤の key は Bits::fold で畳み込んだ値。
+pub struct Book {
+    width: u32,
+    height: u32,
+    connect: u32,
+    depth: u32,
+    weak: bool,
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    // root から depth 手先までに現れる未終局の全局面を解く（通常 root は初期局面）。
+    // 深い局面から順に解くことで、浅い局面の探索で TT が効くようにする。
+    pub fn build<B: Bits>(solver: &Solver, root: Board<B>, depth: u32, mut progress: impl FnMut(u32, usize)) -> Self {
+        // 左右反転した局面は get で引けるので片方だけ解く
+        let mut layers: Vec<Vec<Board<B>>> = Layers::new(root, true).up_to(depth)
+            .map(|layer| layer.into_iter().filter(|board| !board.is_over()).collect()).collect();
+        layers.resize(depth as usize + 1, Vec::new());
+
+        let mut entries = Vec::new();
+        for (ply, layer) in layers.iter().enumerate().rev() {
+            for board in layer {
+                let (col, score) = solver.best_move(board);
+                entries.push(BookEntry { key: board.key().fold(), score, best_col: col as u8 });
+            }
+            progress(root.moves() + ply as u32, layer.len());
+        }
+        entries.sort_unstable_by_key(|e| e.key);
+        Self { width: root.width(), height: root.height(), connect: root.connect(), depth: root.moves() + depth, weak: solver.is_weak(), entries }
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn connect(&self) -> u32 { self.connect }
+    pub fn depth(&self) -> u32 { self.depth }
+    pub fn is_weak(&self) -> bool { self.weak }
+    pub fn len(&self) -> usize { self.entries.len() }
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    // 定跡にある局面なら (最善手の列, 評価値) を返す。左右反転した局面で入っていれば列を反転して返す。
+    pub fn get<B: Bits>(&self, board: &Board<B>) -> Option<(u32, i8)> {
+        if (board.width(), board.height(), board.connect()) != (self.width, self.height, self.connect) { return None; }
+        let find = |key: u64| self.entries.binary_search_by_key(&key, |e| e.key).ok().map(|i| &self.entries[i]);
+        if let Some(e) = find(board.key().fold()) { return Some((e.best_col as u32, e.score)); }
+        let e = find(board.mirror().key().fold())?;
+        Some((board.width() - 1 - e.best_col as u32, e.score))
+    }
+
+    // ヘッダ (magic, version, width, height, connect, depth, weak, entries) の後に key, score, best_col を並べる
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        w.write_all(BOOK_MAGIC)?;
+        w.write_all(&BOOK_VERSION.to_le_bytes())?;
+        w.write_all(&[self.width as u8, self.height as u8, self.connect as u8])?;
+        w.write_all(&self.depth.to_le_bytes())?;
+        w.write_all(&[self.weak as u8])?;
+        w.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for e in &self.entries {
+            w.write_all(&e.key.to_le_bytes())?;
+            w.write_all(&[e.score as u8, e.best_col])?;
+        }
+        w.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut r = BufReader::new(File::open(path)?);
+        let mut header = [0u8; HEADER_BYTES];
+        r.read_exact(&mut header)?;
+        if &header[0..4] != BOOK_MAGIC { return Err(invalid("not an opening book file".into())); }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != BOOK_VERSION {
+            return Err(invalid(format!("unsupported book version {} (expected {})", version, BOOK_VERSION)));
+        }
+        let (width, height, connect) = (header[8] as u32, header[9] as u32, header[10] as u32);
+        Board128::with_size(width, height).and_then(|b| b.with_connect(connect)).map_err(invalid)?;
+        let depth = u32::from_le_bytes(header[11..15].try_into().unwrap());
+        let weak = header[15] != 0;
+        let count = u64::from_le_bytes(header[16..24].try_into().unwrap()) as usize;
+
+        let mut entries = Vec::with_capacity(count);
+        let mut buf = [0u8; RECORD_BYTES];
+        for _ in 0..count {
+            r.read_exact(&mut buf)?;
+            // get は鏡像の局面で width - 1 - best_col を返すので、盤の外の列はここで弾く
+            if buf[9] as u32 >= width {
+                return Err(invalid(format!("book entry has best move column {} on a board of width {}", buf[9], width)));
+            }
+            entries.push(BookEntry {
+                key: u64::from_le_bytes(buf[0..8].try_into().unwrap()),
+                score: buf[8] as i8,
+                best_col: buf[9],
+            });
+        }
+        if !entries.is_sorted_by_key(|e| e.key) { return Err(invalid("book entries are not sorted".into())); }
+        Ok(Self { width, height, connect, depth, weak, entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_save_load_round_trip() {
+        let solver = Solver::new(1 << 16);
+        let root = Board::<u64>::with_size(5, 4).unwrap();
+        let book = Book::build(&solver, root, 4, |_, _| {});
+        let path = std::env::temp_dir().join(format!("connect4_book_{}.bin", std::process::id()));
+        book.save(&path).unwrap();
+        let loaded = Book::load(&path);
+        let _ = std::fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+        assert_eq!((loaded.width(), loaded.height(), loaded.connect(), loaded.depth()), (5, 4, 4, 4));
+        assert!(!loaded.is_weak());
+        assert_eq!(loaded.entries, book.entries);
+
+        // 左右反転した局面も含め、4 手目までの未終局の全局面が引けて、最善手を打つと評価値どおりになる
+        let fresh = Solver::new(1 << 20);
+        for board in Layers::new(root, false).up_to(4).flatten().filter(|board| !board.is_over()) {
+            let (col, score) = loaded.get(&board).unwrap();
+            assert_eq!(score, fresh.solve(board));
+            let mut next = board;
+            next.play(col);
+            assert_eq!(-fresh.solve(next), score);
+        }
+        // 5 手目の局面と、大きさや何目並べかが違う盤は無い
+        let deeper = Layers::new(root, false).nth(5).unwrap().into_iter().find(|board| !board.is_over()).unwrap();
+        assert_eq!(loaded.get(&deeper), None);
+        assert_eq!(loaded.get(&Board::<u64>::new()), None);
+        assert_eq!(loaded.get(&root.with_connect(3).unwrap()), None);
+    }
+
+    #[test]
+    fn load_rejects_other_files() {
+        let path = std::env::temp_dir().join(format!("connect4_not_a_book_{}.bin", std::process::id()));
+        std::fs::write(&path, [0u8; HEADER_BYTES]).unwrap();
+        let err = Book::load(&path).err().unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+
+use connect4_solver::{Bits, Board, Solver, MAX_HEIGHT, MAX_WIDTH};
+
+// 'certificate' と 'verify': 局面の結果の証明書を書き出し、探索を使わずに確かめる。
+// 証明書は結果を保証する側の戦略で、その側の手番の局面ごとに打つ手を並べたもの。勝ちなら勝つ側の、
+// 引き分けなら両方の（それぞれ負けない）戦略を書く。相手の手は全て辿るので、戦略の手を打ち続ければ
+// どの応手に対しても主張した結果になることを、盤の規則だけで確かめられる。手数（評価値の大きさ）は主張しない。
+//
+//   # connect4 certificate
+//   width 7
+//   height 6
+//   connect 4
+//   root 44              <- 根の局面までの手順（1 始まりの列、空の盤なら -）
+//   result X             <- 勝つ側（X は先手）か draw
+//   strategy X
+//   - 4                  <- 根から手順 "-"（根そのもの）の局面で 4 を打つ
+//   43 3                 <- 根から 4, 3 と打った局面で 3 を打つ
+//   ...
+//
+// 局面は手順で書くが、verify は手順を打った盤で引くので、手順違いで同じ局面に着いても 1 行で足りる。
+pub const HEADER: &str = "# connect4 certificate";
+
+// board の結果の証明書を作る。board は終局していないこと。中断された場合の内容は当てにならない。
+// 戻り値は証明書と、戦略ごとの局面の数。
+pub fn build<B: Bits>(solver: &Solver, root_moves: &str, board: Board<B>) -> (String, Vec<(char, usize)>) {
+    let score = solver.solve(board);
+    let to_move = side_to_move(board.moves());
+    let other = if to_move == 'X' { 'O' } else { 'X' };
+    let (result, provers) = match score.signum() {
+        1 => (to_move.to_string(), vec![to_move]),
+        -1 => (other.to_string(), vec![other]),
+        _ => ("draw".to_string(), vec!['X', 'O']),
+    };
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", HEADER);
+    let _ = writeln!(out, "width {}\nheight {}\nconnect {}", board.width(), board.height(), board.connect());
+    let _ = writeln!(out, "root {}", if root_moves.is_empty() { "-" } else { root_moves });
+    let _ = writeln!(out, "result {}", result);
+    let mut sizes = Vec::new();
+    for prover in provers {
+        let _ = writeln!(out, "strategy {}", prover);
+        let mut seen = HashSet::new();
+        let mut entries = 0;
+        strategy(solver, board, prover, &mut String::new(), &mut seen, &mut out, &mut entries);
+        sizes.push((prover, entries));
+    }
+    (out, sizes)
+}
+
+// prover の手番では最善手を 1 つ書いて進み、相手の手番では全ての手を辿る
+fn strategy<B: Bits>(solver: &Solver, board: Board<B>, prover: char, line: &mut String, seen: &mut HashSet<B>,
+                     out: &mut String, entries: &mut usize) {
+    if board.is_over() || !seen.insert(board.key()) || solver.is_aborted() { return; }
+    let moves: Vec<u32> = if side_to_move(board.moves()) == prover {
+        let (col, _) = solver.best_move(&board);
+        let _ = writeln!(out, "{} {}", if line.is_empty() { "-" } else { line.as_str() }, col + 1);
+        *entries += 1;
+        vec![col]
+    } else {
+        board.legal_moves().to_vec()
+    };
+    for col in moves {
+        let mut next = board;
+        next.play(col);
+        line.push(char::from_digit(col + 1, 10).expect("columns are single digits"));
+        strategy(solver, next, prover, line, seen, out, entries);
+        line.pop();
+    }
+}
+
+fn side_to_move(moves: u32) -> char { if moves.is_multiple_of(2) { 'X' } else { 'O' } }
+
+// 確かめた結果
+pub struct Verified {
+    // 勝つ側（'X' か 'O'）か、引き分けなら None
+    pub winner: Option<char>,
+    // 辿った局面の数（戦略ごとの和）
+    pub positions: usize,
+    // 証明書の戦略の局面の数と、そのうち辿らなかったもの
+    pub entries: usize,
+    pub unused: usize,
+}
+
+// 証明書を確かめる。Board や Solver は使わず、下の Grid（素朴な盤）だけで手の正しさと勝ちを判定する。
+pub fn verify(text: &str) -> Result<Verified, String> {
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())).filter(|(_, line)| !line.is_empty());
+    if lines.next().map(|(_, line)| line) != Some(HEADER) { return Err("not a connect4 certificate".to_string()); }
+    let mut field = |name: &str| -> Result<String, String> {
+        let (no, line) = lines.next().ok_or_else(|| format!("missing '{}'", name))?;
+        line.strip_prefix(name).and_then(|v| v.strip_prefix(' '))
+            .map(|v| v.trim().to_string()).ok_or_else(|| format!("line {}: expected '{} ...'", no, name))
+    };
+    let mut number = |name: &str| -> Result<usize, String> {
+        let v = field(name)?;
+        v.parse().map_err(|_| format!("invalid {} '{}'", name, v))
+    };
+    let (width, height, connect) = (number("width")?, number("height")?, number("connect")?);
+    // 証明書は信用しない入力なので、盤を作る前に大きさを Board と同じ範囲に収める。これで check の再帰も
+    // 盤のマス数（最大 9x16）までの深さで済む。connect が長い方の辺より長い盤では誰も勝てないので弾く。
+    if !(1..=MAX_WIDTH as usize).contains(&width) { return Err(format!("width must be between 1 and {}", MAX_WIDTH)); }
+    if !(1..=MAX_HEIGHT as usize).contains(&height) { return Err(format!("height must be between 1 and {}", MAX_HEIGHT)); }
+    if !(2..=width.max(height)).contains(&connect) {
+        return Err(format!("connect must be between 2 and {} on a {}x{} board", width.max(height), width, height));
+    }
+    let root_moves = field("root")?;
+    let winner = match field("result")?.as_str() {
+        "X" => Some('X'),
+        "O" => Some('O'),
+        "draw" => None,
+        v => return Err(format!("invalid result '{}' (expected X, O or draw)", v)),
+    };
+    let mut root = Grid::new(width, height, connect);
+    if root_moves != "-" {
+        root = root.play_line(&root_moves).map_err(|e| format!("root: {}", e))?;
+    }
+    if root.over() { return Err("the root position is already over".to_string()); }
+
+    // 戦略ごとに、局面 → 打つ列
+    let mut strategies: Vec<(char, HashMap<Vec<u8>, usize>)> = Vec::new();
+    for (no, line) in lines {
+        if let Some(side) = line.strip_prefix("strategy ") {
+            let side = match side.trim() {
+                "X" => 'X',
+                "O" => 'O',
+                v => return Err(format!("line {}: invalid side '{}'", no, v)),
+            };
+            if strategies.iter().any(|(s, _)| *s == side) { return Err(format!("line {}: second strategy for {}", no, side)); }
+            strategies.push((side, HashMap::new()));
+            continue;
+        }
+        let (_, entries) = strategies.last_mut().ok_or_else(|| format!("line {}: move before 'strategy'", no))?;
+        let (seq, col) = line.split_once(' ').ok_or_else(|| format!("line {}: expected '<moves> <column>'", no))?;
+        let grid = if seq == "-" { root.clone() } else { root.play_line(seq).map_err(|e| format!("line {}: {}", no, e))? };
+        let col = col.trim().parse::<usize>().ok().filter(|&c| (1..=width).contains(&c))
+            .ok_or_else(|| format!("line {}: invalid column '{}'", no, col.trim()))?;
+        if entries.insert(grid.cells.clone(), col - 1).is_some() {
+            return Err(format!("line {}: second move for the position after '{}'", no, seq));
+        }
+    }
+
+    // 勝ちなら勝つ側の戦略が、引き分けなら両方の戦略が要る
+    let needed: Vec<char> = match winner { Some(side) => vec![side], None => vec!['X', 'O'] };
+    let mut verified = Verified { winner, positions: 0, entries: 0, unused: 0 };
+    for side in needed {
+        let entries = &strategies.iter().find(|(s, _)| *s == side).ok_or_else(|| format!("missing the strategy for {}", side))?.1;
+        let mut checker = Checker { side, must_win: winner.is_some(), entries, proven: HashSet::new(), used: HashSet::new() };
+        checker.check(&root, &mut String::new())?;
+        verified.positions += checker.proven.len();
+        verified.entries += entries.len();
+        verified.unused += entries.len() - checker.used.len();
+    }
+    Ok(verified)
+}
+
+struct Checker<'a> {
+    side: char,
+    // 勝たなければならないか（false なら引き分けでもよい）
+    must_win: bool,
+    entries: &'a HashMap<Vec<u8>, usize>,
+    // 確かめ終えた局面と、使った戦略の局面
+    proven: HashSet<Vec<u8>>,
+    used: HashSet<Vec<u8>>,
+}
+
+impl Checker<'_> {
+    // grid から戦略どおりに打てば主張どおりになるか。line は根からの手順（エラーの表示用）。
+    fn check(&mut self, grid: &Grid, line: &mut String) -> Result<(), String> {
+        if self.proven.contains(&grid.cells) { return Ok(()); }
+        let at = || if line.is_empty() { "the root".to_string() } else { format!("'{}'", line) };
+        if let Some(winner) = grid.winner {
+            if winner != self.side { return Err(format!("{} loses after {}", self.side, at())); }
+        } else if grid.full() {
+            if self.must_win { return Err(format!("the game is drawn after {}, but {} should win", at(), self.side)); }
+        } else if grid.to_move() == self.side {
+            let &col = self.entries.get(&grid.cells).ok_or_else(|| format!("the strategy for {} has no move after {}", self.side, at()))?;
+            let next = grid.play(col).ok_or_else(|| format!("the strategy for {} plays the full column {} after {}", self.side, col + 1, at()))?;
+            self.used.insert(grid.cells.clone());
+            line.push(char::from_digit(col as u32 + 1, 10).expect("columns are single digits"));
+            self.check(&next, line)?;
+            line.pop();
+        } else {
+            for col in 0..grid.width {
+                if let Some(next) = grid.play(col) {
+                    line.push(char::from_digit(col as u32 + 1, 10).expect("columns are single digits"));
+                    self.check(&next, line)?;
+                    line.pop();
+                }
+            }
+        }
+        self.proven.insert(grid.cells.clone());
+        Ok(())
+    }
+}
+
+// 検証用の素朴な盤。cells は列ごとに下から 0（空き）/ 1（X）/ 2（O）。
+#[derive(Clone)]
+struct Grid {
+    width: usize,
+    height: usize,
+    connect: usize,
+    cells: Vec<u8>,
+    moves: usize,
+    winner: Option<char>,
+}
+
+impl Grid {
+    fn new(width: usize, height: usize, connect: usize) -> Self {
+        Self { width, height, connect, cells: vec![0; width * height], moves: 0, winner: None }
+    }
+
+    fn to_move(&self) -> char { if self.moves.is_multiple_of(2) { 'X' } else { 'O' } }
+    fn full(&self) -> bool { self.moves == self.width * self.height }
+    fn over(&self) -> bool { self.winner.is_some() || self.full() }
+    fn at(&self, col: isize, row: isize) -> u8 {
+        if col < 0 || row < 0 || col >= self.width as isize || row >= self.height as isize { return 0; }
+        self.cells[col as usize * self.height + row as usize]
+    }
+
+    // col（0 始まり）に打った盤。終局後か列が埋まっていれば None。
+    fn play(&self, col: usize) -> Option<Self> {
+        if self.over() || col >= self.width { return None; }
+        let row = (0..self.height).find(|&row| self.cells[col * self.height + row] == 0)?;
+        let mut next = self.clone();
+        let stone = if self.to_move() == 'X' { 1 } else { 2 };
+        next.cells[col * self.height + row] = stone;
+        next.moves += 1;
+        // 打った石を通る縦・横・斜めの 4 方向に connect 個並んだか
+        for (dc, dr) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+            let count = |sign: isize| (1..).take_while(|&i| next.at(col as isize + sign * i * dc, row as isize + sign * i * dr) == stone).count();
+            if 1 + count(1) + count(-1) >= self.connect { next.winner = Some(self.to_move()); }
+        }
+        Some(next)
+    }
+
+    // 1 始まりの列の数字の並びを打つ
+    fn play_line(&self, line: &str) -> Result<Self, String> {
+        let mut grid = self.clone();
+        for c in line.chars() {
+            let col = c.to_digit(10).filter(|&d| d >= 1).ok_or_else(|| format!("invalid move '{}' in '{}'", c, line))?;
+            grid = grid.play(col as usize - 1).ok_or_else(|| format!("illegal move {} in '{}'", col, line))?;
+        }
+        Ok(grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn certificate(root: &str, result: &str, strategies: &str) -> String {
+        format!("{}\nwidth 7\nheight 6\nconnect 4\nroot {}\nresult {}\n{}", HEADER, root, result, strategies)
+    }
+
+    #[test]
+    fn build_then_verify() {
+        let roots = [
+            ("2252576253462244111563365343671351441", Board::from_moves("2252576253462244111563365343671351441").unwrap()),
+            ("7422341735647741166133573473242566", Board::from_moves("7422341735647741166133573473242566").unwrap()),
+            // 引き分け
+            ("", Board::with_size(4, 4).unwrap()),
+        ];
+        for (moves, board) in roots {
+            // TT は盤の大きさごとに分ける
+            let solver = Solver::new(1 << 16);
+            let (text, sizes) = build(&solver, moves, board);
+            let verified = verify(&text).unwrap_or_else(|e| panic!("{}: {}\n{}", moves, e, text));
+            let to_move = side_to_move(board.moves());
+            let expected = match solver.solve(board).signum() {
+                1 => Some(to_move),
+                -1 => Some(if to_move == 'X' { 'O' } else { 'X' }),
+                _ => None,
+            };
+            assert_eq!(verified.winner, expected, "{}", moves);
+            assert_eq!(sizes.len(), if expected.is_some() { 1 } else { 2 });
+            assert_eq!(verified.entries, sizes.iter().map(|&(_, n)| n).sum::<usize>());
+            assert_eq!(verified.unused, 0);
+        }
+    }
+
+    // 勝ちの証明書の根の手を勝てない手に替えると通らない
+    #[test]
+    fn rejects_a_losing_move() {
+        let solver = Solver::new(1 << 16);
+        let moves = "7422341735647741166133573473242566";
+        let board = Board::from_moves(moves).unwrap();
+        let (text, _) = build(&solver, moves, board);
+        let root = text.lines().find(|line| line.starts_with("- ")).unwrap();
+        let bad = board.legal_moves().iter().copied().find(|&col| {
+            let mut next = board;
+            next.play(col);
+            !next.is_win() && solver.solve(next) >= 0
+        }).unwrap();
+        let tampered = text.replace(root, &format!("- {}", bad + 1));
+        assert!(verify(&tampered).is_err());
+    }
+
+    #[test]
+    fn verify_checks_claims() {
+        // X は 3 か 7 で並べる
+        let winning = certificate("445566", "X", "strategy X\n- 7\n");
+        let verified = verify(&winning).unwrap();
+        assert_eq!((verified.winner, verified.positions, verified.entries, verified.unused), (Some('X'), 2, 1, 0));
+        assert!(verify(&certificate("445566", "X", "strategy X\n- 7\n4 1\n")).unwrap().unused == 1);
+        // 並べない手では O の応手が辿れない
+        assert!(verify(&certificate("445566", "X", "strategy X\n- 1\n")).err().unwrap().contains("has no move"));
+        // 勝つ側の戦略が無い、引き分けなら両方の戦略が要る
+        assert!(verify(&certificate("445566", "O", "strategy X\n- 7\n")).err().unwrap().contains("missing the strategy for O"));
+        assert!(verify(&certificate("445566", "draw", "strategy X\n- 7\n")).is_err());
+        // 埋まった列には打てない
+        assert!(verify(&certificate("444444", "X", "strategy X\n- 4\n")).err().unwrap().contains("full column"));
+        assert!(verify(&winning.replace("width 7", "width 12")).is_err());
+        assert!(verify("# something else\n").is_err());
+    }
+}
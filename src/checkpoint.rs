@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use connect4_solver::{Bits, Board, CheckpointHeader, CheckpointTask};
+
+// 初手の全探索の途中経過。1 行目に CheckpointHeader、解き終えたタスクごとに CheckpointTask を
+// 1 行の JSON で追記していき、--resume で読み直して解き終えたタスクを飛ばす。
+pub struct Checkpoint {
+    file: Mutex<File>,
+    done: HashMap<(u32, u32, u32), i8>,
+}
+
+impl Checkpoint {
+    // resume なら既存の記録を読み込んで追記し、そうでなければ新しく作り直す。
+    // 盤の大きさや何目並べか、弱解決かどうかが記録と違えば評価値の意味が変わるのでエラーにする。
+    pub fn open<B: Bits>(path: &str, board: &Board<B>, weak: bool, resume: bool) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let header = CheckpointHeader {
+            checkpoint: CheckpointHeader::VERSION, width: board.width(), height: board.height(), connect: board.connect(), weak,
+        };
+        let mut done = HashMap::new();
+        if resume {
+            let text = std::fs::read_to_string(path)?;
+            let mut lines = text.lines();
+            match lines.next().and_then(|line| serde_json::from_str::<CheckpointHeader>(line).ok()) {
+                Some(h) if h == header => {}
+                Some(h) if h.checkpoint != CheckpointHeader::VERSION => {
+                    return Err(invalid(format!("unsupported checkpoint version {} (expected {})", h.checkpoint, CheckpointHeader::VERSION)));
+                }
+                Some(_) => return Err(invalid("checkpoint was written with a different board or --weak setting".into())),
+                None => return Err(invalid("not a checkpoint file".into())),
+            }
+            // 書き込み途中で落ちた最後の行は読めないので捨てる
+            for task in lines.filter_map(|line| serde_json::from_str::<CheckpointTask>(line).ok()) {
+                let [c1, c2, c3] = task.task;
+                done.insert((c1, c2, c3), task.score);
+            }
+            let mut file = OpenOptions::new().append(true).open(path)?;
+            if !text.ends_with('\n') { writeln!(file)?; }
+            Ok(Self { file: Mutex::new(file), done })
+        } else {
+            let mut file = File::create(path)?;
+            writeln!(file, "{}", serde_json::to_string(&header)?)?;
+            file.sync_data()?;
+            Ok(Self { file: Mutex::new(file), done })
+        }
+    }
+
+    pub fn len(&self) -> usize { self.done.len() }
+
+    pub fn get(&self, col1: u32, col2: u32, col3: u32) -> Option<i8> {
+        self.done.get(&(col1, col2, col3)).copied()
+    }
+
+    pub fn record(&self, col1: u32, col2: u32, col3: u32, score: i8) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", serde_json::to_string(&CheckpointTask { task: [col1, col2, col3], score })?)?;
+        file.sync_data()
+    }
+}
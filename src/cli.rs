@@ -0,0 +1,968 @@
+use connect4_solver::{Algorithm, Bits, Level, Board, Board128, HashFunction, DEFAULT_EXPLORATION, DEFAULT_MCTS_SOLVER_DEPTH, HugePages, NumaPolicy, ScoreFormat, TableBackend, TraceFormat, DEFAULT_STACK_SIZE, DEFAULT_TABLE_ENTRIES, ENTRY_BYTES, CONNECT, HEIGHT, WIDTH};
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config;
+use crate::export::ImageFormat;
+use crate::logging::LogLevel;
+use crate::selfplay::DataFormat;
+use crate::tournament::Contestant;
+
+// serve で --max-time も --max-nodes も指定しないときの、1 リクエストの探索時間の上限
+pub const DEFAULT_REQUEST_TIME: Duration = Duration::from_secs(10);
+
+pub const USAGE: &str = "\
+Usage:
+  connect4_solver [options]                 solve all seven first moves from the empty board
+  connect4_solver [options] solve <moves>   solve the position reached by <moves> (e.g. 44453)
+  connect4_solver [options] solve --batch <path|->
+                                            solve one position per line (first field; the rest of
+                                            the line is ignored) and print
+                                            'position score bestmove nodes seconds' per line
+                                            (or write them to --parquet <path>)
+  connect4_solver [options] engine          speak a UCI-style protocol on stdin/stdout
+                                            (position/go/stop/bestmove; see src/engine.rs)
+  connect4_solver [options] serve [--port <n>] [--bind <addr>]
+                                            serve GET /solve?pos=<moves> and /analyze?pos=<moves>
+                                            as JSON over HTTP (default 127.0.0.1:8080), and stream
+                                            live analysis over a WebSocket at /ws. Both also take
+                                            grid=<rows top first, separated by />, e.g. ....../X.....
+                                            Each request gets --max-time/--max-nodes (default 10s)
+  connect4_solver [options] coordinator [--depth <k>] [--port <n>] [--bind <addr>] [<moves>]
+                                            solve the position reached by <moves> on several machines:
+                                            split it into the positions <k> plies deeper (default 3),
+                                            hand them out to workers over TCP (default 127.0.0.1:8080),
+                                            and combine their scores
+  connect4_solver [options] worker <host:port>
+                                            solve work units for a coordinator; use the same board
+                                            options (--width, --height, --connect, --weak) as it
+  connect4_solver [options] book build --depth <n> [--out <path>] [<moves>]
+                                            solve every position up to <n> plies (after <moves>,
+                                            if given) into an opening book (default book.bin)
+  connect4_solver [options] db build --depth <n> [--db <path>] [<moves>]
+                                            like 'book build', but append each solved position (key,
+                                            score, best move, nodes) to an SQLite database (default
+                                            positions.db) as it is solved; rerunning resumes
+  connect4_solver [options] db query <moves> [--db <path>]
+                                            look up a position in the database
+  connect4_solver [options] db dump [--db <path>]
+                                            print the records of the database, or write them to
+                                            --parquet <path> (the file can also be opened with
+                                            sqlite3: tables meta and positions)
+  connect4_solver [options] perft <depth> [<moves>]
+                                            count the move sequences and distinct positions at each
+                                            ply up to <depth> (after <moves>, if given), also counting
+                                            mirror images as one. Distinct positions are kept in
+                                            memory, so deep counts need a lot.
+  connect4_solver [options] hashstats [<samples>]
+                                            compare the --tt-hash functions on <samples> positions
+                                            from random games (default 1000000): bucket overflow
+                                            and check-bit collisions for the --tt-size table
+  connect4_solver [options] mcts [<moves>]  pick a move for the position reached by <moves> with Monte Carlo
+                                            tree search within --max-time (default 1s) and print the
+                                            visits and average result of each move. Positions with
+                                            few moves left are solved exactly (--mcts-solver-depth)
+  connect4_solver [options] hint <moves>    print the best move for the position reached by <moves> with
+                                            a short explanation derived from the threats it makes
+                                            or stops (cells as c4: column letter, row from the
+                                            bottom) and where the principal variation ends
+  connect4_solver [options] analyze <moves> solve every legal move of the position reached by <moves> and
+                                            print their scores, best first, marking the best moves,
+                                            the moves that lose ('??') and the other weaker moves ('?'),
+                                            each weaker move with the first 8 plies of the opponent's
+                                            refutation
+  connect4_solver [options] review <moves>  solve every position of the game <moves>, print each move's
+                                            score next to the best move's, and flag the moves that
+                                            changed the result (win -> draw, draw -> loss)
+  connect4_solver [options] selfplay --games <n> [--temperature <t>] [--format <f>] [--out <path>] [<moves>]
+                                            play <n> games against itself (from <moves>, if given) and
+                                            write every position with its score and the score of each
+                                            move, as csv (default) or binary, to <path> (default -,
+                                            stdout). Temperature 0 (default) plays a best move at
+                                            random; t > 0 picks moves with weights exp((s - best) / t).
+                                            See src/selfplay.rs for the formats
+  connect4_solver [options] match <a> <b> --games <n> [--opening-plies <k>] [<moves>]
+                                            play <n> games between two players (a level name,
+                                            depth:<n> for a <n>-ply static search or mcts:<ms> for
+                                            <ms> per move), swapping colours after each random
+                                            <k>-ply opening (default 2), and report a's wins, draws,
+                                            losses and Elo difference with a 95% confidence interval
+  connect4_solver [options] puzzles --count <n> [--win-in <k>]
+                                            find <n> positions from random games where exactly one
+                                            move wins (with the side to move's <k>-th move, if given)
+                                            and print each with its board and winning line
+  connect4_solver [options] export [--format <f>] [--scores] [--pv] [--out <path>] <moves>
+                                            draw the position reached by <moves> as svg (default) or
+                                            png to <path> (default -, stdout): with --scores, the
+                                            score of each column above the board; with --pv, the
+                                            principal variation as numbered discs joined by arrows
+  connect4_solver [options] proof-tree [--depth <n>] [--out <path>] [<moves>]
+                                            solve the position reached by <moves> and write the tree
+                                            that proves its score, <n> plies deep (default 6), as
+                                            Graphviz DOT to <path> (default -, stdout): one move for
+                                            the side the score favours (the side to move on a draw),
+                                            every reply that does not lose at once for the other side
+  connect4_solver [options] certificate [--out <path>] [<moves>]
+                                            solve the position reached by <moves> and write a
+                                            certificate of its result to <path> (default -, stdout):
+                                            the winner's move in every position its strategy reaches,
+                                            whatever the opponent replies (both sides' strategies
+                                            for a draw). See src/certificate.rs for the format
+  connect4_solver [options] verify <path>   check a certificate without searching: play every reply
+                                            against its strategies with a separate, plain board and
+                                            confirm that each game ends as claimed (exit status 1 if
+                                            not). The board size is read from the certificate
+  connect4_solver [options] play [<moves>]  play against the engine in the terminal (after <moves>, if given):
+                                            enter a column to move, 'u' to undo, 'h' for a hint.
+                                            The engine plays at --level (default perfect) and takes
+                                            at most --max-time per move or hint (default 5s); your
+                                            mistakes are listed when the game ends
+  connect4_solver [options] bench --set <name> [--bench-dir <dir>]
+                                            solve a Pascal Pons test set (endgame_easy, middle_easy,
+                                            middle_medium, begin_easy, begin_medium, begin_hard),
+                                            check the scores and report nodes, time and NPS.
+                                            The files (e.g. Test_L3_R1) are read from <dir>
+                                            (default bench); the table is kept between positions.
+
+Options:
+  --config <path>    read options from a TOML file of 'key = value' lines (threads, stack_size,
+                     tt_size, tt_fraction, tt_mmap, parallel_depth, book, output, score_format,
+                     color, log_level, log_format, algorithm). Default: connect4.toml in the current
+                     directory, if it exists. Options on the command line override the file.
+  --threads <n>      number of search threads (default: number of logical cores)
+  --stack-size <s>   stack size of each search thread, in bytes or with a K/M/G suffix
+                     (default 16M)
+  --tt-size <size>   transposition table size, as entries (e.g. 1048576) or bytes
+                     with a K/M/G/T suffix (e.g. 4G). Rounded down to a power of two.
+                     Default: 32G
+  --tt-size auto     size the table from available physical memory
+  --tt-fraction <f>  fraction of available memory used by --tt-size auto (default 0.5)
+  --tt-load <path>   warm-start the transposition table from a file written by --tt-save
+  --tt-save <path>   write the transposition table to a file when the run finishes
+  --tt-mmap          allocate the table with an anonymous mmap (instant startup)
+  --tt-file <path>   back the table with a memory-mapped file that persists between runs
+  --tt-populate      prefault mmap tables at startup (MAP_POPULATE)
+  --tt-hugepages <m> back an mmap table with huge pages: thp, 2m or 1g (implies --tt-mmap;
+                     not with --tt-file).
+                     Explicit 2m/1g pages fall back to thp, then to normal pages.
+  --numa <m>         spread an mmap table over NUMA nodes and pin the search threads to
+                     nodes in turn: interleave (pages round-robin over all nodes, one shared
+                     table) or partition (one slice per node; each node's threads probe only
+                     their own slice, so results are not shared across nodes; falls back to
+                     interleave unless the node count is a power of two).
+                     Implies --tt-mmap (not with --tt-file); ignored on single-node machines.
+  --tt-hash <name>   hash function for the table index and check bits: splitmix64 (default),
+                     xxhash or multiply-shift. Saved tables remember theirs (see hashstats);
+                     an existing --tt-file table keeps its own and rejects a different one
+  --tt-min-depth <n> do not probe or store positions with fewer than <n> moves left, so
+                     cheap endgame results do not evict expensive ones (default 0)
+  --knowledge-depth <n|off>
+                     before searching a position with at least <n> moves left, try to prove
+                     that the side to move cannot win with the claimeven and baseinverse
+                     rules (default 4)
+  --redis <host:port>
+                     share solved positions with other solver instances through a Redis server:
+                     positions missing from the table are looked up there, and results stored
+                     in the table are also written there (in batches, in the background).
+                     Keys are namespaced by board size. If the server goes away, lookups miss
+                     and writes are dropped until it is reached again (retried with backoff)
+  --redis-password <p>
+                     authenticate to the Redis server with AUTH
+  --redis-min-depth <n>
+                     only use Redis for positions with at least <n> moves left, since each
+                     lookup is a network round trip (default 28)
+  --algorithm <name> search used to solve positions: alphabeta (default) or pns
+                     (single-threaded depth-first proof-number search). Both share the table;
+                     pns gives half of --tt-size to its own proof-number table
+                     Standard rules only
+  --mcts-playouts <n>
+                     random playouts per MCTS leaf, run in parallel (default: number of threads)
+  --mcts-exploration <c>
+                     UCT exploration constant (default 1.414)
+  --mcts-solver-depth <n|off>
+                     solve MCTS leaves with at most <n> moves left instead of playing them out
+                     (default 16)
+  --mcts-model <path>
+                     evaluate MCTS leaves with an ONNX policy/value network and select children
+                     by PUCT instead of UCT. Input [1, 2, height, width] (side to move, then the
+                     opponent), outputs policy [1, width] and value [1, 1] (needs a build with
+                     --features onnx)
+  --level <name>     playing strength of the engine's 'go' and of 'play': random, easy
+                     (2-ply lookahead), medium (7-ply lookahead, sometimes the second-best
+                     move), hard (solved scores, sometimes the second-best move) or perfect
+  --seed <n>         seed for --level's random choices (default 0)
+  --human <x|o>      side you play in 'play' (default x, the first player)
+  --book <path>      probe an opening book written by 'book build' before searching
+  --parallel-depth <n|auto>
+                     search children in parallel down to <n> plies below the root.
+                     Default: auto (chosen from the number of threads)
+  --max-time <t>     stop searching after <t> (seconds, or with an ms/s/m/h suffix) and
+                     report the proven score range and the best move found so far. The limit
+                     applies to each position of 'solve --batch' and 'bench', each request of
+                     'serve' and each move of 'match'; other commands count the whole run
+  --max-nodes <n>    stop searching after <n> nodes, like --max-time
+  --checkpoint <path>
+                     record each solved root task so an interrupted first-move run can resume
+  --resume           skip the tasks already recorded in --checkpoint (and warm-start with
+                     --tt-load, if the table was saved with --tt-save)
+  --parquet <path>   write the results of 'solve --batch' or the records of 'db dump' as a
+                     Parquet file (columns key, key_high, moves, score, best_move, nodes;
+                     - for stdout)
+  --tui              show a full-screen dashboard while 'solve <moves>' runs: the board, the
+                     score of each column as it is solved, speed and node graphs, table fill
+                     and elapsed time with an estimate of the time left (needs a terminal)
+  --verify-distance  for 'solve <moves>' and the first-move run: play the principal variation
+                     out to the end and check that the game ends after as many moves as the
+                     score says (exit status 1 if not)
+  --trace <path>     write every searched position with at most --trace-depth discs to <path>
+                     when its score is settled: its table key, the search window, the score
+                     returned and the best move (not with --popout, --misere, --cube or
+                     --algorithm pns)
+  --trace-depth <n>  deepest position --trace writes, in discs on the board (default 12)
+  --trace-format <f> ndjson (default; one JSON object per line) or binary (16 bytes per
+                     position, see src/trace.rs)
+  --batch-parallel   solve batch positions in parallel (nodes are then reported as '-')
+  --color <when>     auto (default), always or never: draw boards in 'play', 'hint', 'review' and
+                     'puzzles' with coloured discs, the last move highlighted and the squares
+                     that win at once marked. auto colours when stdout is a terminal and
+                     NO_COLOR is not set.
+  --score-format <f> how scores are reported: pons (default; Pascal Pons's convention, e.g. 22 minus
+                     the winner's number of discs on 7x6, positive for the side to move's win),
+                     distance (plies to the deciding move, including it: positive for a win,
+                     negative for a loss, 0 for a draw) or wdl (1/0/-1). Bounds, books and
+                     databases keep the pons scores
+  --output <format>  text (default) or json: one JSON object per line on stdout, with
+                     1-based columns and scores for the side to move after the root move
+  --log-level <lvl>  off (default), error, warn, info, debug or trace: write diagnostic log
+                     lines to stderr (search start and finish, root move results, table
+                     statistics, and the messages, warnings and errors shown on the console;
+                     debug adds progress and per-position events). While warnings and errors
+                     are logged, they are not also printed as plain 'warning:' lines
+  --log-format <f>   text (default) or json: one log object per line on stderr
+  --width <n>        board width (default 7); width * (height + 1) must be at most 128.
+                     Boards over 64 bits (e.g. 8x8, 9x7) use a slower 128-bit board.
+  --height <n>       board height (default 6)
+  --connect <k>      number in a row needed to win (default 4)
+  --popout           PopOut rules for 'solve <moves>': a player may instead remove their own
+                     piece from the bottom of a column (written p<col>, e.g. 44p4).
+                     Repeated positions are draws; only win/draw/loss is determined.
+  --misere           misère rules for 'solve <moves>': completing a line loses
+  --cube             3D Connect Four (4x4x4, 76 lines) for 'solve <moves>': pegs are a-p,
+                     row by row (a b c d / e f g h / ...), e.g. fkgj
+  --start <path|->   start 'solve', 'book build' and 'perft' from a grid read from a file (one row per
+                     line, top row first, X/O/. cells); <moves> are played on top of it.
+                     The grid sets the board size, and may hold positions that cannot arise
+                     in play (handicaps, composed puzzles).
+  --to-move <x|o>    side to move in the --start grid (default: X if the counts are equal,
+                     O if X has one more piece)
+  --weak             only determine win/draw/loss (score 1/0/-1), not the distance";
+
+pub enum Command {
+    FirstMoves,
+    Solve(String),
+    SolveBatch(String),
+    Engine,
+    Serve,
+    Coordinator { depth: u32, root: String },
+    Worker(String),
+    BookBuild { depth: u32, out: String, root: String },
+    Bench { set: String, dir: String },
+    Perft { depth: u32, root: String },
+    DbBuild { depth: u32, path: String, root: String },
+    DbQuery { path: String, moves: String },
+    DbDump { path: String },
+    HashStats { samples: usize },
+    Mcts(String),
+    Hint(String),
+    Analyze(String),
+    Review(String),
+    SelfPlay { games: usize, out: String, root: String },
+    Match { a: Contestant, b: Contestant, games: u32, root: String },
+    Puzzles { count: usize, win_in: Option<u32> },
+    Export { moves: String, out: String },
+    ProofTree { moves: String, depth: u32, out: String },
+    Certificate { moves: String, out: String },
+    Verify(String),
+    Play(String),
+    Help,
+}
+
+pub enum TableSize {
+    Entries(usize),
+    Auto,
+}
+
+pub struct Options {
+    // None は論理コア数
+    pub threads: Option<usize>,
+    pub stack_size: usize,
+    pub tt_size: TableSize,
+    pub tt_fraction: f64,
+    pub weak: bool,
+    pub tt_load: Option<String>,
+    pub tt_save: Option<String>,
+    pub tt_mmap: bool,
+    pub tt_file: Option<String>,
+    pub tt_populate: bool,
+    pub tt_huge_pages: HugePages,
+    pub tt_min_depth: u32,
+    pub redis: Option<String>,
+    pub redis_password: Option<String>,
+    pub redis_min_depth: u32,
+    // None なら Solver の既定値
+    pub knowledge_depth: Option<u32>,
+    // None なら既定（--tt-file の既存の TT ならそのファイルの関数）
+    pub tt_hash: Option<HashFunction>,
+    pub algorithm: Algorithm,
+    pub level: Option<Level>,
+    pub seed: u64,
+    // play で人が先手 (X) を持つか
+    pub human_first: bool,
+    pub mcts_playouts: Option<usize>,
+    pub mcts_exploration: f64,
+    pub mcts_solver_depth: u32,
+    // --mcts-model: 葉を評価する ONNX モデル (PUCT)
+    pub mcts_model: Option<String>,
+    pub numa: NumaPolicy,
+    pub depth: Option<u32>,
+    pub games: Option<usize>,
+    pub opening_plies: u32,
+    pub count: Option<usize>,
+    pub win_in: Option<u32>,
+    pub temperature: f64,
+    pub format: DataFormat,
+    pub image_format: ImageFormat,
+    // export で列ごとの評価値と読み筋を描くか
+    pub scores: bool,
+    pub pv: bool,
+    pub out: Option<String>,
+    pub db: String,
+    pub book: Option<String>,
+    pub parallel_depth: Option<u32>,
+    pub max_time: Option<Duration>,
+    pub max_nodes: Option<usize>,
+    pub checkpoint: Option<String>,
+    pub resume: bool,
+    pub json: bool,
+    // None は auto（端末なら色付き）
+    pub color: Option<bool>,
+    pub log_level: LogLevel,
+    pub log_json: bool,
+    pub batch: Option<String>,
+    pub batch_parallel: bool,
+    pub parquet: Option<String>,
+    pub tui: bool,
+    pub verify_distance: bool,
+    pub score_format: ScoreFormat,
+    pub trace: Option<String>,
+    pub trace_depth: u32,
+    pub trace_format: TraceFormat,
+    pub port: u16,
+    pub bind: String,
+    pub width: u32,
+    pub height: u32,
+    pub connect: u32,
+    pub popout: bool,
+    pub misere: bool,
+    pub cube: bool,
+    pub start: Option<String>,
+    pub to_move: Option<char>,
+    pub set: Option<String>,
+    pub bench_dir: Option<String>,
+}
+
+impl Options {
+    // --threads。指定が無ければ論理コア数。
+    pub fn thread_count(&self) -> usize {
+        self.threads.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    // --width/--height/--connect の空の盤。parse は Board128 で検証するので、B が u64 なら
+    // Board::needs_wide が false の大きさでなければ Err になる。
+    pub fn empty_board<B: Bits>(&self) -> Result<Board<B>, String> {
+        Board::with_size(self.width, self.height).and_then(|b| b.with_connect(self.connect))
+    }
+
+    // --start の図の局面。無ければ空の盤。
+    pub fn start_board<B: Bits>(&self) -> Result<Board<B>, String> {
+        let board = self.empty_board()?;
+        match &self.start {
+            Some(grid) => board.with_grid(grid, self.to_move),
+            None => Ok(board),
+        }
+    }
+
+    pub fn table_backend(&self) -> TableBackend {
+        match &self.tt_file {
+            Some(path) => TableBackend::File { path: PathBuf::from(path), populate: self.tt_populate },
+            None if self.tt_mmap || self.tt_huge_pages != HugePages::Off || self.numa != NumaPolicy::Off => {
+                TableBackend::Mmap { populate: self.tt_populate, huge_pages: self.tt_huge_pages, numa: self.numa }
+            }
+            None => TableBackend::Heap,
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            threads: None,
+            stack_size: DEFAULT_STACK_SIZE,
+            tt_size: TableSize::Entries(DEFAULT_TABLE_ENTRIES),
+            tt_fraction: 0.5,
+            weak: false,
+            tt_load: None,
+            tt_save: None,
+            tt_mmap: false,
+            tt_file: None,
+            tt_populate: false,
+            tt_huge_pages: HugePages::Off,
+            tt_min_depth: 0,
+            redis: None,
+            redis_password: None,
+            redis_min_depth: 28,
+            knowledge_depth: None,
+            tt_hash: None,
+            algorithm: Algorithm::AlphaBeta,
+            level: None,
+            seed: 0,
+            human_first: true,
+            mcts_playouts: None,
+            mcts_exploration: DEFAULT_EXPLORATION,
+            mcts_solver_depth: DEFAULT_MCTS_SOLVER_DEPTH,
+            mcts_model: None,
+            numa: NumaPolicy::Off,
+            depth: None,
+            games: None,
+            opening_plies: 2,
+            count: None,
+            win_in: None,
+            temperature: 0.0,
+            format: DataFormat::Csv,
+            image_format: ImageFormat::Svg,
+            scores: false,
+            pv: false,
+            out: None,
+            db: "positions.db".to_string(),
+            book: None,
+            parallel_depth: None,
+            max_time: None,
+            max_nodes: None,
+            checkpoint: None,
+            resume: false,
+            json: false,
+            color: None,
+            log_level: LogLevel::Off,
+            log_json: false,
+            batch: None,
+            batch_parallel: false,
+            parquet: None,
+            tui: false,
+            verify_distance: false,
+            score_format: ScoreFormat::Pons,
+            trace: None,
+            trace_depth: 12,
+            trace_format: TraceFormat::Ndjson,
+            port: 8080,
+            bind: "127.0.0.1".to_string(),
+            width: WIDTH,
+            height: HEIGHT,
+            connect: CONNECT,
+            popout: false,
+            misere: false,
+            cube: false,
+            start: None,
+            to_move: None,
+            set: None,
+            bench_dir: None,
+        }
+    }
+}
+
+pub fn parse(args: &[String]) -> Result<(Command, Options), String> {
+    let mut opts = Options::default();
+    let mut positional = Vec::new();
+    // --format の意味はコマンドによる（selfplay はデータ、export は画像）ので、コマンドが決まってから解釈する
+    let mut format = None;
+
+    // 設定ファイルのオプションを先に並べ、コマンドラインで上書きできるようにする
+    let mut config = None;
+    let mut rest = Vec::new();
+    let mut args_it = args.iter();
+    while let Some(arg) = args_it.next() {
+        match arg.strip_prefix("--config") {
+            Some("") => config = Some(args_it.next().ok_or("option '--config' requires a value")?.clone()),
+            Some(v) if v.starts_with('=') => config = Some(v[1..].to_string()),
+            _ => rest.push(arg.clone()),
+        }
+    }
+    let mut args = match &config {
+        Some(path) => config::load(path, true)?,
+        None => config::load(config::DEFAULT_PATH, false)?,
+    };
+    args.extend(rest);
+
+    // "--name=value" と "--name value" の両方を受け付ける
+    let mut tokens = Vec::new();
+    for arg in &args {
+        match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                tokens.push(name.to_string());
+                tokens.push(value.to_string());
+            }
+            _ => tokens.push(arg.clone()),
+        }
+    }
+
+    let mut it = tokens.into_iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--tt-size" => opts.tt_size = match value(&mut it, &arg)?.as_str() {
+                "auto" => TableSize::Auto,
+                v => TableSize::Entries(parse_tt_size(v)?),
+            },
+            "--threads" => {
+                let v = value(&mut it, &arg)?;
+                opts.threads = match v.parse() {
+                    Ok(n) if n > 0 => Some(n),
+                    _ => return Err(format!("invalid thread count '{}'", v)),
+                };
+            }
+            "--stack-size" => {
+                let v = value(&mut it, &arg)?;
+                opts.stack_size = match parse_bytes(&v) {
+                    Some((n, unit)) if n.saturating_mul(unit.unwrap_or(1)) > 0 => n.saturating_mul(unit.unwrap_or(1)),
+                    _ => return Err(format!("invalid stack size '{}'", v)),
+                };
+            }
+            "--tt-fraction" => {
+                let v = value(&mut it, &arg)?;
+                opts.tt_fraction = match v.parse::<f64>() {
+                    Ok(f) if f > 0.0 && f <= 1.0 => f,
+                    _ => return Err(format!("invalid memory fraction '{}' (expected 0 < f <= 1)", v)),
+                };
+            }
+            "--weak" => opts.weak = true,
+            "--tt-load" => opts.tt_load = Some(value(&mut it, &arg)?),
+            "--tt-save" => opts.tt_save = Some(value(&mut it, &arg)?),
+            "--tt-mmap" => opts.tt_mmap = true,
+            "--tt-file" => opts.tt_file = Some(value(&mut it, &arg)?),
+            "--tt-populate" => opts.tt_populate = true,
+            "--tt-hugepages" => opts.tt_huge_pages = match value(&mut it, &arg)?.as_str() {
+                "off" => HugePages::Off,
+                "thp" => HugePages::Transparent,
+                "2m" | "2M" => HugePages::Explicit2M,
+                "1g" | "1G" => HugePages::Explicit1G,
+                v => return Err(format!("invalid huge page mode '{}' (expected off, thp, 2m or 1g)", v)),
+            },
+            "--tt-hash" => {
+                let v = value(&mut it, &arg)?;
+                opts.tt_hash = Some(HashFunction::from_name(&v).ok_or_else(|| {
+                    let names: Vec<&str> = HashFunction::ALL.iter().map(|hash| hash.name()).collect();
+                    format!("invalid hash function '{}' (expected {})", v, names.join(", "))
+                })?);
+            }
+            "--tt-min-depth" => {
+                let v = value(&mut it, &arg)?;
+                opts.tt_min_depth = v.parse().map_err(|_| format!("invalid depth '{}'", v))?;
+            }
+            "--redis" => opts.redis = Some(value(&mut it, &arg)?),
+            "--redis-password" => opts.redis_password = Some(value(&mut it, &arg)?),
+            "--redis-min-depth" => {
+                let v = value(&mut it, &arg)?;
+                opts.redis_min_depth = v.parse().map_err(|_| format!("invalid depth '{}'", v))?;
+            }
+            "--knowledge-depth" => {
+                let v = value(&mut it, &arg)?;
+                opts.knowledge_depth = Some(match v.as_str() {
+                    "off" => u32::MAX,
+                    _ => v.parse().map_err(|_| format!("invalid depth '{}'", v))?,
+                });
+            }
+            "--algorithm" => {
+                let v = value(&mut it, &arg)?;
+                opts.algorithm = Algorithm::from_name(&v).ok_or_else(|| {
+                    let names: Vec<&str> = Algorithm::ALL.iter().map(|algorithm| algorithm.name()).collect();
+                    format!("invalid algorithm '{}' (expected {})", v, names.join(", "))
+                })?;
+            }
+            "--level" => {
+                let v = value(&mut it, &arg)?;
+                opts.level = Some(Level::from_name(&v).ok_or_else(|| {
+                    let names: Vec<&str> = Level::ALL.iter().map(|level| level.name()).collect();
+                    format!("invalid level '{}' (expected {})", v, names.join(", "))
+                })?);
+            }
+            "--human" => opts.human_first = match value(&mut it, &arg)?.to_ascii_lowercase().as_str() {
+                "x" => true,
+                "o" => false,
+                v => return Err(format!("invalid side '{}' (expected x or o)", v)),
+            },
+            "--seed" => {
+                let v = value(&mut it, &arg)?;
+                opts.seed = v.parse().map_err(|_| format!("invalid seed '{}'", v))?;
+            }
+            "--mcts-playouts" => {
+                let v = value(&mut it, &arg)?;
+                opts.mcts_playouts = Some(v.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("invalid playout count '{}'", v))?);
+            }
+            "--mcts-exploration" => {
+                let v = value(&mut it, &arg)?;
+                opts.mcts_exploration = v.parse().ok().filter(|&c: &f64| c >= 0.0).ok_or_else(|| format!("invalid exploration constant '{}'", v))?;
+            }
+            "--mcts-model" => opts.mcts_model = Some(value(&mut it, &arg)?),
+            "--mcts-solver-depth" => {
+                let v = value(&mut it, &arg)?;
+                opts.mcts_solver_depth = match v.as_str() {
+                    "off" => 0,
+                    _ => v.parse().map_err(|_| format!("invalid depth '{}'", v))?,
+                };
+            }
+            "--numa" => opts.numa = match value(&mut it, &arg)?.as_str() {
+                "off" => NumaPolicy::Off,
+                "interleave" => NumaPolicy::Interleave,
+                "partition" => NumaPolicy::Partition,
+                v => return Err(format!("invalid NUMA mode '{}' (expected off, interleave or partition)", v)),
+            },
+            "--depth" => {
+                let v = value(&mut it, &arg)?;
+                opts.depth = Some(v.parse().map_err(|_| format!("invalid depth '{}'", v))?);
+            }
+            "--games" => {
+                let v = value(&mut it, &arg)?;
+                opts.games = Some(v.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("invalid game count '{}'", v))?);
+            }
+            "--count" => {
+                let v = value(&mut it, &arg)?;
+                opts.count = Some(v.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("invalid count '{}'", v))?);
+            }
+            "--win-in" => {
+                let v = value(&mut it, &arg)?;
+                opts.win_in = Some(v.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("invalid move count '{}'", v))?);
+            }
+            "--opening-plies" => {
+                let v = value(&mut it, &arg)?;
+                opts.opening_plies = v.parse().map_err(|_| format!("invalid opening length '{}'", v))?;
+            }
+            "--temperature" => {
+                let v = value(&mut it, &arg)?;
+                opts.temperature = v.parse().ok().filter(|&t: &f64| t >= 0.0 && t.is_finite())
+                    .ok_or_else(|| format!("invalid temperature '{}'", v))?;
+            }
+            "--format" => format = Some(value(&mut it, &arg)?),
+            "--scores" => opts.scores = true,
+            "--pv" => opts.pv = true,
+            "--db" => opts.db = value(&mut it, &arg)?,
+            "--out" => opts.out = Some(value(&mut it, &arg)?),
+            "--book" => opts.book = Some(value(&mut it, &arg)?),
+            "--parallel-depth" => opts.parallel_depth = match value(&mut it, &arg)?.as_str() {
+                "auto" => None,
+                v => Some(v.parse().map_err(|_| format!("invalid parallel depth '{}'", v))?),
+            },
+            "--max-time" => opts.max_time = Some(parse_duration(&value(&mut it, &arg)?)?),
+            "--max-nodes" => {
+                let v = value(&mut it, &arg)?;
+                opts.max_nodes = Some(v.parse().map_err(|_| format!("invalid node count '{}'", v))?);
+            }
+            "--checkpoint" => opts.checkpoint = Some(value(&mut it, &arg)?),
+            "--resume" => opts.resume = true,
+            "--batch" => opts.batch = Some(value(&mut it, &arg)?),
+            "--batch-parallel" => opts.batch_parallel = true,
+            "--parquet" => opts.parquet = Some(value(&mut it, &arg)?),
+            "--port" => {
+                let v = value(&mut it, &arg)?;
+                opts.port = v.parse().map_err(|_| format!("invalid port '{}'", v))?;
+            }
+            "--bind" => opts.bind = value(&mut it, &arg)?,
+            "--width" => {
+                let v = value(&mut it, &arg)?;
+                opts.width = v.parse().map_err(|_| format!("invalid width '{}'", v))?;
+            }
+            "--height" => {
+                let v = value(&mut it, &arg)?;
+                opts.height = v.parse().map_err(|_| format!("invalid height '{}'", v))?;
+            }
+            "--tui" => opts.tui = true,
+            "--verify-distance" => opts.verify_distance = true,
+            "--score-format" => {
+                let v = value(&mut it, &arg)?;
+                opts.score_format = ScoreFormat::from_name(&v).ok_or_else(|| {
+                    let names: Vec<&str> = ScoreFormat::ALL.iter().map(|format| format.name()).collect();
+                    format!("invalid score format '{}' (expected {})", v, names.join(", "))
+                })?;
+            }
+            "--trace" => opts.trace = Some(value(&mut it, &arg)?),
+            "--trace-depth" => {
+                let v = value(&mut it, &arg)?;
+                opts.trace_depth = v.parse().map_err(|_| format!("invalid trace depth '{}'", v))?;
+            }
+            "--trace-format" => {
+                let v = value(&mut it, &arg)?;
+                opts.trace_format = TraceFormat::from_name(&v).ok_or_else(|| {
+                    let names: Vec<&str> = TraceFormat::ALL.iter().map(|format| format.name()).collect();
+                    format!("invalid trace format '{}' (expected {})", v, names.join(", "))
+                })?;
+            }
+            "--popout" => opts.popout = true,
+            "--misere" => opts.misere = true,
+            "--cube" => opts.cube = true,
+            "--start" => opts.start = Some(read_grid(&value(&mut it, &arg)?)?),
+            "--to-move" => opts.to_move = match value(&mut it, &arg)?.as_str() {
+                "x" | "X" => Some('X'),
+                "o" | "O" => Some('O'),
+                v => return Err(format!("invalid side to move '{}' (expected x or o)", v)),
+            },
+            "--connect" => {
+                let v = value(&mut it, &arg)?;
+                opts.connect = v.parse().map_err(|_| format!("invalid connect length '{}'", v))?;
+            }
+            "--set" => opts.set = Some(value(&mut it, &arg)?),
+            "--bench-dir" => opts.bench_dir = Some(value(&mut it, &arg)?),
+            "--color" => opts.color = match value(&mut it, &arg)?.as_str() {
+                "auto" => None,
+                "always" => Some(true),
+                "never" => Some(false),
+                v => return Err(format!("invalid color mode '{}' (expected auto, always or never)", v)),
+            },
+            "--log-level" => {
+                let v = value(&mut it, &arg)?;
+                opts.log_level = LogLevel::from_name(&v)
+                    .ok_or_else(|| format!("invalid log level '{}' (expected off, error, warn, info, debug or trace)", v))?;
+            }
+            "--log-format" => opts.log_json = match value(&mut it, &arg)?.as_str() {
+                "text" => false,
+                "json" => true,
+                v => return Err(format!("invalid log format '{}' (expected text or json)", v)),
+            },
+            "--output" => opts.json = match value(&mut it, &arg)?.as_str() {
+                "text" => false,
+                "json" => true,
+                v => return Err(format!("invalid output format '{}' (expected text or json)", v)),
+            },
+            "-h" | "--help" => return Ok((Command::Help, opts)),
+            s if s.starts_with('-') => return Err(format!("unknown option '{}'", s)),
+            _ => positional.push(arg),
+        }
+    }
+
+    if let Some(grid) = &opts.start {
+        (opts.width, opts.height) = Board::grid_size(grid)?;
+    } else if opts.to_move.is_some() {
+        return Err("--to-move requires --start <path>".into());
+    }
+    let empty = Board128::with_size(opts.width, opts.height)?.with_connect(opts.connect)?;
+    if let Some(grid) = &opts.start { empty.with_grid(grid, opts.to_move)?; }
+    if opts.resume && opts.checkpoint.is_none() { return Err("--resume requires --checkpoint <path>".into()); }
+
+    let command = match positional.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => Command::FirstMoves,
+        ["solve"] if opts.batch.is_some() => Command::SolveBatch(opts.batch.clone().unwrap()),
+        ["solve", moves] if opts.batch.is_none() => Command::Solve(moves.to_string()),
+        ["solve"] if opts.start.is_some() => Command::Solve(String::new()),
+        ["book", "build", root @ ..] if root.len() <= 1 => Command::BookBuild {
+            depth: opts.depth.ok_or("book build requires --depth <n>")?,
+            out: opts.out.clone().unwrap_or_else(|| "book.bin".to_string()),
+            root: root.first().map_or(String::new(), |m| m.to_string()),
+        },
+        ["db", "build", root @ ..] if root.len() <= 1 => Command::DbBuild {
+            depth: opts.depth.ok_or("db build requires --depth <n>")?,
+            path: opts.db.clone(),
+            root: root.first().map_or(String::new(), |m| m.to_string()),
+        },
+        ["db", "query", moves] => Command::DbQuery { path: opts.db.clone(), moves: moves.to_string() },
+        ["db", "dump"] => Command::DbDump { path: opts.db.clone() },
+        ["bench"] => Command::Bench {
+            set: opts.set.clone().ok_or("bench requires --set <name>")?,
+            dir: opts.bench_dir.clone().unwrap_or_else(|| "bench".to_string()),
+        },
+        ["perft", depth, root @ ..] if root.len() <= 1 => Command::Perft {
+            depth: depth.parse().map_err(|_| format!("invalid depth '{}'", depth))?,
+            root: root.first().map_or(String::new(), |m| m.to_string()),
+        },
+        ["hashstats", samples @ ..] if samples.len() <= 1 => Command::HashStats {
+            samples: match samples.first() {
+                Some(n) => n.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("invalid sample count '{}'", n))?,
+                None => 1_000_000,
+            },
+        },
+        ["mcts", moves @ ..] if moves.len() <= 1 => Command::Mcts(moves.first().map_or(String::new(), |m| m.to_string())),
+        ["hint", moves] => Command::Hint(moves.to_string()),
+        ["hint"] if opts.start.is_some() => Command::Hint(String::new()),
+        ["analyze", moves] => Command::Analyze(moves.to_string()),
+        ["analyze"] if opts.start.is_some() => Command::Analyze(String::new()),
+        ["review", moves] => Command::Review(moves.to_string()),
+        ["selfplay", root @ ..] if root.len() <= 1 => Command::SelfPlay {
+            games: opts.games.ok_or("selfplay requires --games <n>")?,
+            out: opts.out.clone().unwrap_or_else(|| "-".to_string()),
+            root: root.first().map_or(String::new(), |m| m.to_string()),
+        },
+        ["match", a, b, root @ ..] if root.len() <= 1 => Command::Match {
+            a: Contestant::from_spec(a)?,
+            b: Contestant::from_spec(b)?,
+            games: opts.games.ok_or("match requires --games <n>")? as u32,
+            root: root.first().map_or(String::new(), |m| m.to_string()),
+        },
+        ["puzzles"] => Command::Puzzles {
+            count: opts.count.ok_or("puzzles requires --count <n>")?,
+            win_in: opts.win_in,
+        },
+        ["export", moves] => Command::Export { moves: moves.to_string(), out: opts.out.clone().unwrap_or_else(|| "-".to_string()) },
+        ["export"] if opts.start.is_some() => Command::Export { moves: String::new(), out: opts.out.clone().unwrap_or_else(|| "-".to_string()) },
+        ["proof-tree", moves @ ..] if moves.len() <= 1 => Command::ProofTree {
+            moves: moves.first().map_or(String::new(), |m| m.to_string()),
+            depth: opts.depth.unwrap_or(6),
+            out: opts.out.clone().unwrap_or_else(|| "-".to_string()),
+        },
+        ["certificate", moves @ ..] if moves.len() <= 1 => Command::Certificate {
+            moves: moves.first().map_or(String::new(), |m| m.to_string()),
+            out: opts.out.clone().unwrap_or_else(|| "-".to_string()),
+        },
+        ["verify", path] => Command::Verify(path.to_string()),
+        ["play", moves @ ..] if moves.len() <= 1 => Command::Play(moves.first().map_or(String::new(), |m| m.to_string())),
+        ["engine"] => Command::Engine,
+        ["serve"] => Command::Serve,
+        ["coordinator", root @ ..] if root.len() <= 1 => Command::Coordinator {
+            depth: match opts.depth {
+                Some(0) => return Err("coordinator requires --depth of at least 1".into()),
+                Some(depth) => depth,
+                None => 3,
+            },
+            root: root.first().map_or(String::new(), |m| m.to_string()),
+        },
+        ["worker", addr] => Command::Worker(addr.to_string()),
+        ["help"] => Command::Help,
+        _ => return Err(format!("unexpected arguments: {}", positional.join(" "))),
+    };
+    if let Some(v) = format {
+        if matches!(command, Command::Export { .. }) {
+            opts.image_format = ImageFormat::from_name(&v).ok_or_else(|| format!("invalid format '{}' (expected svg or png)", v))?;
+        } else {
+            opts.format = DataFormat::from_name(&v).ok_or_else(|| format!("invalid format '{}' (expected csv or binary)", v))?;
+        }
+    }
+    if (opts.scores || opts.pv) && !matches!(command, Command::Export { .. } | Command::Help) {
+        return Err("--scores and --pv are only supported by 'export'".into());
+    }
+    if opts.popout && !matches!(command, Command::Solve(_) | Command::Help) {
+        return Err("--popout is only supported by 'solve <moves>'".into());
+    }
+    if opts.misere && !matches!(command, Command::Solve(_) | Command::Help) {
+        return Err("--misere is only supported by 'solve <moves>'".into());
+    }
+    if opts.misere && opts.popout { return Err("--misere cannot be combined with --popout".into()); }
+    if opts.cube && !matches!(command, Command::Solve(_) | Command::Help) {
+        return Err("--cube is only supported by 'solve <moves>'".into());
+    }
+    if opts.start.is_some() && !matches!(command, Command::Solve(_) | Command::SolveBatch(_) | Command::BookBuild { .. } | Command::DbBuild { .. } | Command::DbQuery { .. } | Command::Perft { .. } | Command::HashStats { .. } | Command::Mcts(_) | Command::Hint(_) | Command::Analyze(_) | Command::Review(_) | Command::SelfPlay { .. } | Command::Match { .. } | Command::Export { .. } | Command::ProofTree { .. } | Command::Help) {
+        return Err("--start is only supported by 'solve', 'book build', 'db build', 'db query', 'perft', 'hashstats', 'mcts', 'hint', 'analyze', 'review', 'selfplay', 'match', 'export' and 'proof-tree'".into());
+    }
+    if opts.parquet.is_some() && !matches!(command, Command::SolveBatch(_) | Command::DbDump { .. } | Command::Help) {
+        return Err("--parquet is only supported by 'solve --batch' and 'db dump'".into());
+    }
+    if opts.tui && (!matches!(command, Command::Solve(_) | Command::Help) || opts.popout || opts.misere || opts.cube) {
+        return Err("--tui is only supported by 'solve <moves>'".into());
+    }
+    if opts.tui && opts.json { return Err("--tui cannot be combined with --output json".into()); }
+    // ファイルを写した TT はページキャッシュに載るので、huge pages も NUMA の配置も効かない
+    if opts.tt_file.is_some() && opts.tt_huge_pages != HugePages::Off {
+        return Err("--tt-hugepages cannot be combined with --tt-file".into());
+    }
+    if opts.tt_file.is_some() && opts.numa != NumaPolicy::Off { return Err("--numa cannot be combined with --tt-file".into()); }
+    if opts.verify_distance && (!matches!(command, Command::Solve(_) | Command::FirstMoves | Command::Help)
+        || opts.popout || opts.misere || opts.cube || opts.tui) {
+        return Err("--verify-distance is only supported by 'solve <moves>' and the first-move run".into());
+    }
+    // 弱解決の評価値には決着までの手数が無い
+    if opts.score_format != ScoreFormat::Pons && (opts.popout || opts.misere || opts.cube) {
+        return Err("--score-format is not supported with --popout, --misere or --cube".into());
+    }
+    if opts.score_format == ScoreFormat::Distance && opts.weak {
+        return Err("--score-format distance cannot be combined with --weak".into());
+    }
+    // トレースは αβ 探索（Solver::search）のノードだけを書く
+    if opts.trace.is_some() && (opts.popout || opts.misere || opts.cube || opts.algorithm != Algorithm::AlphaBeta) {
+        return Err("--trace is not supported with --popout, --misere, --cube or --algorithm pns".into());
+    }
+    if opts.verify_distance && opts.weak { return Err("--verify-distance cannot be combined with --weak".into()); }
+    // 勝ちまでの手数は弱解決では分からない
+    if opts.weak && matches!(command, Command::Puzzles { .. }) { return Err("puzzles cannot be combined with --weak".into()); }
+    // テストセットの評価値は標準の 7x6 の四目並べのもの
+    if matches!(command, Command::Bench { .. })
+        && ((opts.width, opts.height, opts.connect) != (WIDTH, HEIGHT, CONNECT) || opts.popout || opts.misere || opts.cube || opts.start.is_some()) {
+        return Err("bench only supports the standard 7x6 Connect Four".into());
+    }
+    // PopOut・misère・立体は Game の汎用の探索で解く
+    if opts.algorithm != Algorithm::AlphaBeta && (opts.popout || opts.misere || opts.cube) {
+        return Err("--algorithm cannot be combined with --popout, --misere or --cube".into());
+    }
+    if opts.cube && opts.start.is_some() { return Err("--cube cannot be combined with --start".into()); }
+    if opts.cube && (opts.popout || opts.misere) { return Err("--cube cannot be combined with --popout or --misere".into()); }
+    // 上限は同時に走る探索をまとめて数えるので、局面ごとに効かせるには 1 局面ずつ解く
+    if opts.batch_parallel && (opts.max_time.is_some() || opts.max_nodes.is_some()) {
+        return Err("--max-time and --max-nodes cannot be combined with --batch-parallel".into());
+    }
+    // serve は TT を共有する Solver で 1 つずつ解くので、1 つのリクエストに Solver を取られ続けないようにする
+    if matches!(command, Command::Serve) && opts.max_time.is_none() && opts.max_nodes.is_none() {
+        opts.max_time = Some(DEFAULT_REQUEST_TIME);
+    }
+    if opts.mcts_model.is_some() && !cfg!(feature = "onnx") {
+        return Err("--mcts-model needs a build with --features onnx".into());
+    }
+    // worker は作業単位を解き切った値しか coordinator に返せない
+    if matches!(command, Command::Worker(_)) && (opts.max_time.is_some() || opts.max_nodes.is_some()) {
+        return Err("--max-time and --max-nodes are not supported by 'worker'".into());
+    }
+    Ok((command, opts))
+}
+
+fn read_grid(path: &str) -> Result<String, String> {
+    let text = if path == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map(|_| text)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    text.map_err(|e| format!("failed to read {}: {}", path, e))
+}
+
+fn value(it: &mut impl Iterator<Item = String>, name: &str) -> Result<String, String> {
+    it.next().ok_or_else(|| format!("option '{}' requires a value", name))
+}
+
+// (数, K/M/G/T(B) の接尾辞のバイト数)。接尾辞がなければ None。
+fn parse_bytes(s: &str) -> Option<(usize, Option<usize>)> {
+    let upper = s.trim().to_ascii_uppercase();
+    let units = [
+        ("TB", 1usize << 40), ("T", 1 << 40), ("GB", 1 << 30), ("G", 1 << 30),
+        ("MB", 1 << 20), ("M", 1 << 20), ("KB", 1 << 10), ("K", 1 << 10), ("B", 1),
+    ];
+    let (num, unit) = units.iter()
+        .find_map(|&(suffix, unit)| upper.strip_suffix(suffix).map(|n| (n, Some(unit))))
+        .unwrap_or((&upper, None));
+    Some((num.parse().ok()?, unit))
+}
+
+// 接尾辞なしはエントリ数、K/M/G/T(B) 付きはバイト数として解釈する
+fn parse_tt_size(s: &str) -> Result<usize, String> {
+    let (n, unit) = parse_bytes(s).ok_or_else(|| format!("invalid table size '{}'", s))?;
+    let entries = match unit {
+        Some(unit) => n.saturating_mul(unit) / ENTRY_BYTES,
+        None => n,
+    };
+    if entries == 0 { return Err(format!("table size '{}' is too small", s)); }
+    Ok(entries)
+}
+
+// 接尾辞なしは秒。ms/s/m/h を付けられる。
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let t = s.trim();
+    let units = [("ms", 0.001), ("s", 1.0), ("m", 60.0), ("h", 3600.0)];
+    let (num, unit) = units.iter()
+        .find_map(|&(suffix, unit)| t.strip_suffix(suffix).map(|n| (n, unit)))
+        .unwrap_or((t, 1.0));
+    match num.parse::<f64>() {
+        Ok(n) if n > 0.0 && n.is_finite() => Ok(Duration::from_secs_f64(n * unit)),
+        _ => Err(format!("invalid time limit '{}'", s)),
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use connect4_solver::{Bits, Board, Solver};
+
+use crate::output::{self, info, warning, JsonObject};
+use crate::signal;
+
+// 複数のマシンで 1 つの局面を解く。coordinator は root から depth 手先の局面（重複は除く）を作業単位に分け、
+// 接続してきた worker に 1 つずつ配り、全部の評価値が揃ったら depth 手分を negamax でまとめる。
+//
+// 通信は 4 バイト (big endian) の長さ + UTF-8 のテキストのフレーム:
+//   coordinator -> worker  hello <幅> <高さ> <何目並べ> <弱解決 0|1>   接続直後。worker は自分の設定と照合する
+//   worker -> coordinator  ready                                      作業の要求
+//   coordinator -> worker  work <番号> <空の盤からの手順> | done
+//   worker -> coordinator  result <番号> <評価値> <ノード数>           次の作業の要求も兼ねる
+//
+// 配る順は未配布のものから。未配布が無くなったら、まだ解けていない作業単位のうち配った数の少ないものを
+// 空いた worker にも重ねて配る（遅い worker の分を他が奪う）。先に返ってきた結果を使い、後のものは捨てる。
+// 接続が切れた worker に配っていた作業単位は、配った数から外して配り直す。
+const MAX_FRAME: u32 = 1 << 20;
+
+fn write_frame(stream: &mut impl Write, text: &str) -> io::Result<()> {
+    stream.write_all(&(text.len() as u32).to_be_bytes())?;
+    stream.write_all(text.as_bytes())?;
+    stream.flush()
+}
+
+fn read_frame(stream: &mut impl Read) -> io::Result<String> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME { return Err(io::Error::new(io::ErrorKind::InvalidData, format!("frame too large ({} bytes)", len))); }
+    let mut text = vec![0u8; len as usize];
+    stream.read_exact(&mut text)?;
+    String::from_utf8(text).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "frame is not UTF-8"))
+}
+
+fn protocol_error(frame: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("unexpected message '{}'", frame))
+}
+
+fn format_moves(moves: &[u32]) -> String {
+    moves.iter().map(|c| (c + 1).to_string()).collect()
+}
+
+struct Work {
+    // 空の盤からの手順
+    units: Vec<String>,
+    scores: Vec<Option<i8>>,
+    // まだ解けていない作業単位を今いくつの worker に配っているか
+    handed: Vec<u32>,
+    remaining: usize,
+    nodes: usize,
+}
+
+impl Work {
+    // 配った数が最も少ない未解決の作業単位（同数なら番号の小さいもの）
+    fn next(&mut self) -> Option<usize> {
+        let id = (0..self.units.len()).filter(|&id| self.scores[id].is_none()).min_by_key(|&id| self.handed[id])?;
+        self.handed[id] += 1;
+        Some(id)
+    }
+}
+
+// root（空の盤から moves を打った局面）を depth 手先で分けて解き、root の手ごとの評価値を返す。
+// Ctrl-C で止められたら None。
+pub fn coordinate<B: Bits>(empty: Board<B>, moves: &str, weak: bool, depth: u32, bind: &str, port: u16)
+    -> io::Result<Option<Vec<Option<i8>>>> {
+    let mut root = empty;
+    root.play_moves(moves).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut prefix = Vec::new();
+    for c in moves.chars().filter(|c| !c.is_whitespace()) { prefix.push(c.to_digit(10).expect("validated by play_moves") - 1); }
+
+    // depth 手先の終局していない局面。同じ局面は 1 つにまとめる。
+    let mut layer = vec![(root, prefix)];
+    for _ in 0..depth {
+        let mut seen = HashMap::new();
+        let mut next_layer = Vec::new();
+        for (board, path) in &layer {
+            for col in board.legal_moves() {
+                let mut next = *board;
+                next.play(col);
+                if next.is_over() { continue; }
+                if seen.insert(next.key(), ()).is_none() {
+                    let mut path = path.clone();
+                    path.push(col);
+                    next_layer.push((next, path));
+                }
+            }
+        }
+        layer = next_layer;
+    }
+    let index: HashMap<B, usize> = layer.iter().enumerate().map(|(id, (board, _))| (board.key(), id)).collect();
+    let count = layer.len();
+    let work = Arc::new(Mutex::new(Work {
+        units: layer.iter().map(|(_, path)| format_moves(path)).collect(),
+        scores: vec![None; count],
+        handed: vec![0; count],
+        remaining: count,
+        nodes: 0,
+    }));
+
+    let listener = TcpListener::bind((bind, port))?;
+    listener.set_nonblocking(true)?;
+    info!("Coordinating {} work units ({} plies deep) on {}; start workers with 'worker <host:port>'.",
+        count, depth, listener.local_addr()?);
+    let hello = format!("hello {} {} {} {}", empty.width(), empty.height(), empty.connect(), weak as u8);
+    let start = Instant::now();
+    while work.lock().unwrap().remaining > 0 {
+        if signal::interrupted() {
+            let work = work.lock().unwrap();
+            info!("Interrupted with {} of {} work units solved.", count - work.remaining, count);
+            return Ok(None);
+        }
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                let work = Arc::clone(&work);
+                let hello = hello.clone();
+                thread::spawn(move || {
+                    info!("Worker {} connected.", addr);
+                    match serve_worker(&work, stream, addr, &hello, start) {
+                        Ok(()) => info!("Worker {} finished.", addr),
+                        Err(e) => warning!("worker {} disconnected: {}", addr, e),
+                    }
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(20)),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let work = work.lock().unwrap();
+    let scores: HashMap<B, i8> = index.iter().map(|(&key, &id)| (key, work.scores[id].expect("all units are solved"))).collect();
+    let moves_scores = (0..root.width()).map(|col| {
+        if !root.can_play(col) { return None; }
+        let mut next = root;
+        next.play(col);
+        Some(if next.is_win() { win_score(&root, weak) } else { -merge(&next, depth - 1, weak, &scores) })
+    }).collect();
+    info!("Solved {} work units in {:?} ({} nodes on the workers).", count, start.elapsed(), work.nodes);
+    Ok(Some(moves_scores))
+}
+
+// 手番側がこの局面で次の手で勝つときの評価値
+fn win_score<B: Bits>(board: &Board<B>, weak: bool) -> i8 {
+    if weak { 1 } else { (board.size() + 1 - board.moves()) as i8 / 2 }
+}
+
+// depth 手先の評価値（作業単位の結果）から negamax で board の評価値を求める
+fn merge<B: Bits>(board: &Board<B>, depth: u32, weak: bool, scores: &HashMap<B, i8>) -> i8 {
+    if board.moves() == board.size() { return 0; }
+    if depth == 0 { return scores[&board.key()]; }
+    let mut best = i8::MIN;
+    for col in board.legal_moves() {
+        let mut next = *board;
+        next.play(col);
+        if next.is_win() { return win_score(board, weak); }
+        best = best.max(-merge(&next, depth - 1, weak, scores));
+    }
+    best
+}
+
+fn serve_worker(work: &Mutex<Work>, stream: TcpStream, addr: SocketAddr, hello: &str, start: Instant) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut reader = BufReader::new(&stream);
+    let mut writer = &stream;
+    write_frame(&mut writer, hello)?;
+    let mut current = None;
+    let result = (|| loop {
+        let frame = read_frame(&mut reader)?;
+        let mut fields = frame.split(' ');
+        match fields.next() {
+            Some("ready") => {}
+            Some("result") => {
+                let mut number = || fields.next().and_then(|v| v.parse::<i64>().ok()).ok_or_else(|| protocol_error(&frame));
+                let (id, score, nodes) = (number()? as usize, number()? as i8, number()? as usize);
+                if current != Some(id) { return Err(protocol_error(&frame)); }
+                current = None;
+                let mut work = work.lock().unwrap();
+                work.handed[id] -= 1;
+                work.nodes += nodes;
+                if work.scores[id].is_none() {
+                    work.scores[id] = Some(score);
+                    work.remaining -= 1;
+                    let solved = work.units.len() - work.remaining;
+                    if output::is_json() {
+                        JsonObject::new("unit").num("unit", id + 1).str("position", &work.units[id]).num("score", score)
+                            .num("nodes", nodes).str("worker", &addr.to_string()).num("solved", solved)
+                            .num("total", work.units.len()).time("seconds", start.elapsed()).emit();
+                    } else {
+                        println!("Unit {} ({}): {:+} from {} | Solved: {}/{} | Time: {:?}",
+                            id + 1, work.units[id], score, addr, solved, work.units.len(), start.elapsed());
+                    }
+                }
+            }
+            _ => return Err(protocol_error(&frame)),
+        }
+        let mut work = work.lock().unwrap();
+        match work.next() {
+            Some(id) => {
+                current = Some(id);
+                let message = format!("work {} {}", id, work.units[id]);
+                drop(work);
+                write_frame(&mut writer, &message)?;
+            }
+            None => {
+                drop(work);
+                return write_frame(&mut writer, "done");
+            }
+        }
+    })();
+    if let Some(id) = current { work.lock().unwrap().handed[id] -= 1; }
+    result
+}
+
+// coordinator に接続して、done が来るか接続が切れるまで作業単位を解く
+pub fn work<B: Bits>(solver: &Solver, empty: Board<B>, weak: bool, addr: &str) -> io::Result<()> {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(&stream);
+    let mut writer = &stream;
+    let hello = read_frame(&mut reader)?;
+    let expected = format!("hello {} {} {} {}", empty.width(), empty.height(), empty.connect(), weak as u8);
+    if hello != expected {
+        let fields: Vec<&str> = hello.split(' ').collect();
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, match fields.as_slice() {
+            ["hello", w, h, c, weak] => format!("the coordinator solves a {}x{} connect-{} board{}; start the worker with the same options",
+                w, h, c, if *weak == "1" { " with --weak" } else { "" }),
+            _ => format!("unexpected greeting '{}'", hello),
+        }));
+    }
+    info!("Connected to coordinator at {}.", addr);
+    write_frame(&mut writer, "ready")?;
+    let mut solved = 0;
+    loop {
+        let frame = match read_frame(&mut reader) {
+            Ok(frame) => frame,
+            // 全部解き終えた coordinator は worker を待たずに終了する
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        let (id, moves) = match frame.split(' ').collect::<Vec<_>>().as_slice() {
+            ["done"] => break,
+            ["work", id, moves] => (id.to_string(), moves.to_string()),
+            _ => return Err(protocol_error(&frame)),
+        };
+        let mut board = empty;
+        board.play_moves(&moves).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let nodes = solver.nodes();
+        let start = Instant::now();
+        let score = solver.solve(board);
+        if solver.is_aborted() {
+            info!("Stopped while solving {}.", moves);
+            return Ok(());
+        }
+        solved += 1;
+        info!("Solved {}: {:+} ({} nodes, {:?})", moves, score, solver.nodes() - nodes, start.elapsed());
+        write_frame(&mut writer, &format!("result {} {} {}", id, score, solver.nodes() - nodes))?;
+    }
+    info!("Coordinator finished; {} work units solved here.", solved);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // depth 手先の終局していない局面を解いた評価値から merge した値が、root を直接解いた値と同じになること
+    #[test]
+    fn merge_matches_direct_solve() {
+        for weak in [false, true] {
+            let roots = [
+                (Board::from_moves("7422341735647741166133573473242566").unwrap(), 3),
+                (Board::from_moves("2252576253462244111563365343671351441").unwrap(), 2),
+                (Board::with_size(5, 4).unwrap(), 3),
+            ];
+            for (root, depth) in roots {
+                // TT は盤の大きさごとに分ける
+                let solver = Solver::new(1 << 16).with_weak(weak);
+                let mut layer = vec![root];
+                for _ in 0..depth {
+                    layer = layer.iter().flat_map(|board| board.legal_moves().iter().map(move |&col| {
+                        let mut next = *board;
+                        next.play(col);
+                        next
+                    }).collect::<Vec<_>>()).filter(|next| !next.is_over()).collect();
+                }
+                let scores: HashMap<u64, i8> = layer.iter().map(|&board| (board.key(), solver.solve(board))).collect();
+                assert_eq!(merge(&root, depth, weak, &scores), solver.solve(root), "{:?} weak {}", root.to_grid(), weak);
+            }
+        }
+    }
+
+    #[test]
+    fn work_hands_out_unsolved_units_evenly() {
+        let mut work = Work { units: vec![String::new(); 3], scores: vec![None; 3], handed: vec![0; 3], remaining: 3, nodes: 0 };
+        assert_eq!((work.next(), work.next(), work.next()), (Some(0), Some(1), Some(2)));
+        work.scores[0] = Some(1);
+        work.handed[0] -= 1;
+        work.handed[2] -= 1;
+        // 解けていない中で配った数の少ないもの
+        assert_eq!((work.next(), work.next(), work.next()), (Some(2), Some(1), Some(2)));
+        work.scores = vec![Some(0); 3];
+        assert_eq!(work.next(), None);
+    }
+}
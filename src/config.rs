@@ -0,0 +1,122 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+// 起動時に読む設定ファイル。--config <path> か、なければカレントディレクトリの connect4.toml。
+// toml で Config に読み、書かれたキーをコマンドラインの "--key 値" に直してオプションの前に置く。
+// 後に書いたものが勝つので、コマンドラインのオプションは設定ファイルの値を上書きする。
+// 値の型はキーごとに決まっていて、値を取るオプションに真偽値を書いたり、フラグに文字列を書いたりするとエラー。
+//
+//   # connect4.toml
+//   threads = 16
+//   stack_size = "16M"
+//   tt_size = "4G"
+//   parallel_depth = "auto"
+//   book = "book.bin"
+//   output = "json"
+pub const DEFAULT_PATH: &str = "connect4.toml";
+
+// 設定ファイルに書けるキー（対応するオプションは - 区切り）
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    threads: Option<u32>,
+    stack_size: Option<Value>,
+    tt_size: Option<Value>,
+    tt_fraction: Option<f64>,
+    // フラグ (--tt-mmap)。false なら付けない。
+    tt_mmap: Option<bool>,
+    parallel_depth: Option<Value>,
+    book: Option<String>,
+    output: Option<String>,
+    score_format: Option<String>,
+    color: Option<String>,
+    log_level: Option<String>,
+    log_format: Option<String>,
+    algorithm: Option<String>,
+}
+
+// "4G" や "auto" のような文字列か整数を取るキーの値
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "a string or an integer")]
+enum Value {
+    Integer(u64),
+    String(String),
+}
+
+impl Value {
+    fn arg(&self) -> String {
+        match self {
+            Value::Integer(n) => n.to_string(),
+            Value::String(s) => s.clone(),
+        }
+    }
+}
+
+impl Config {
+    fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut push = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                args.push(format!("--{}", key.replace('_', "-")));
+                args.push(value);
+            }
+        };
+        push("threads", self.threads.map(|n| n.to_string()));
+        push("stack_size", self.stack_size.as_ref().map(Value::arg));
+        push("tt_size", self.tt_size.as_ref().map(Value::arg));
+        push("tt_fraction", self.tt_fraction.map(|f| f.to_string()));
+        push("parallel_depth", self.parallel_depth.as_ref().map(Value::arg));
+        push("book", self.book.clone());
+        push("output", self.output.clone());
+        push("score_format", self.score_format.clone());
+        push("color", self.color.clone());
+        push("log_level", self.log_level.clone());
+        push("log_format", self.log_format.clone());
+        push("algorithm", self.algorithm.clone());
+        if self.tt_mmap == Some(true) { args.push("--tt-mmap".to_string()); }
+        args
+    }
+}
+
+// path を読み、オプションの並びにする。required でなければ、ファイルがないときは空を返す。
+pub fn load(path: &str, required: bool) -> Result<Vec<String>, String> {
+    match fs::read_to_string(Path::new(path)) {
+        Ok(text) => parse(&text).map_err(|(line, e)| format!("{}:{}: {}", path, line, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(Vec::new()),
+        Err(e) => Err(format!("failed to read config {}: {}", path, e)),
+    }
+}
+
+// エラーは (行番号, 理由)
+fn parse(text: &str) -> Result<Vec<String>, (usize, String)> {
+    let config: Config = toml::from_str(text).map_err(|e| {
+        let line = e.span().map_or(1, |span| text[..span.start].matches('\n').count() + 1);
+        (line, e.message().to_string())
+    })?;
+    Ok(config.args())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_become_options() {
+        let args = parse("threads = 16\ntt_size = \"4G\"\nparallel_depth = 3 # comment\ntt_mmap = true\ncolor = \"never\"\n").unwrap();
+        assert_eq!(args, ["--threads", "16", "--tt-size", "4G", "--parallel-depth", "3", "--color", "never", "--tt-mmap"]);
+        assert_eq!(parse("tt_mmap = false").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(parse("threads = 4\ncolor = true\n").unwrap_err().0, 2);
+        assert_eq!(parse("threads = 4\n\ntt_size = false\n").unwrap_err().0, 3);
+        assert_eq!(parse("tt_mmap = \"yes\"").unwrap_err().0, 1);
+        let (line, msg) = parse("threads = 4\nthread = 2\n").unwrap_err();
+        assert_eq!(line, 2);
+        assert!(msg.contains("unknown field"), "{}", msg);
+    }
+}
@@ -0,0 +1,132 @@
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// 探索ノード数や TT の引き当ての回数をスレッドごとに別のキャッシュラインで数え、読むときに合計する。
+// 全スレッドが 1 つの AtomicUsize に fetch_add すると、そのキャッシュラインをスレッド間で奪い合う。
+// rayon のワーカーは番号ごとの欄、それ以外のスレッド（メインスレッドなど）は欄 0 を共有する。
+
+// 隣接キャッシュラインのプリフェッチでも干渉しないように 128 バイトずつ離す。1 つの欄に N 個の数を持つ。
+#[repr(align(128))]
+struct Slot<const N: usize>([AtomicUsize; N]);
+
+fn slots<const N: usize>(threads: usize) -> Box<[Slot<N>]> {
+    (0..threads + 1).map(|_| Slot(std::array::from_fn(|_| AtomicUsize::new(0)))).collect()
+}
+
+pub(crate) struct NodeCounter {
+    slots: Box<[Slot<1>]>,
+}
+
+impl NodeCounter {
+    // threads は探索に使うプールのスレッド数
+    pub(crate) fn new(threads: usize) -> Self {
+        Self { slots: slots(threads) }
+    }
+
+    // 今のスレッドの欄を 1 つ増やし、その欄の値を返す
+    #[inline(always)]
+    pub(crate) fn increment(&self) -> usize {
+        tally_node();
+        let slot = &self.slots[thread_slot() % self.slots.len()];
+        slot.0[0].fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub(crate) fn total(&self) -> usize {
+        self.slots.iter().map(|slot| slot.0[0].load(Ordering::Relaxed)).sum()
+    }
+}
+
+// TT を引いた回数、見つかった回数、バケットに別の局面が入っていて見つからなかった回数
+pub(crate) struct ProbeCounter {
+    slots: Box<[Slot<3>]>,
+}
+
+impl ProbeCounter {
+    pub(crate) fn new(threads: usize) -> Self {
+        Self { slots: slots(threads) }
+    }
+
+    #[inline(always)]
+    pub(crate) fn record(&self, hit: bool, collision: bool) {
+        tally_probe(hit);
+        let slot = &self.slots[thread_slot() % self.slots.len()];
+        slot.0[0].fetch_add(1, Ordering::Relaxed);
+        if hit { slot.0[1].fetch_add(1, Ordering::Relaxed); }
+        if collision { slot.0[2].fetch_add(1, Ordering::Relaxed); }
+    }
+
+    // (引いた回数, 見つかった回数, 衝突の回数)
+    pub(crate) fn totals(&self) -> (usize, usize, usize) {
+        let sum = |i: usize| self.slots.iter().map(|slot| slot.0[i].load(Ordering::Relaxed)).sum();
+        (sum(0), sum(1), sum(2))
+    }
+}
+
+// 探索に使うスレッド数の既定（Solver::with_thread_pool で決め直す）
+#[cfg(feature = "parallel")]
+pub(crate) fn current_threads() -> usize { rayon::current_num_threads() }
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn current_threads() -> usize { 1 }
+
+#[cfg(feature = "parallel")]
+#[inline(always)]
+pub(crate) fn thread_slot() -> usize { rayon::current_thread_index().map_or(0, |i| i + 1) }
+#[cfg(not(feature = "parallel"))]
+#[inline(always)]
+pub(crate) fn thread_slot() -> usize { 0 }
+
+// 探索の一部（初手ごとのタスクなど）に使ったノード数と TT の引き当てを数える (Solver::measure)。
+// 各スレッドは今の集計先と、まだ足していない数をスレッドローカルに持ち、集計先を切り替えるときにまとめて足す。
+// 並列に分けた子の探索（map_children）と Solver::install は、呼んだスレッドの集計先を引き継ぐ。
+#[derive(Default)]
+pub(crate) struct Tally {
+    nodes: AtomicUsize,
+    probes: AtomicUsize,
+    hits: AtomicUsize,
+}
+
+impl Tally {
+    // (ノード数, TT を引いた回数, 見つかった回数)
+    pub(crate) fn totals(&self) -> (usize, usize, usize) {
+        (self.nodes.load(Ordering::Relaxed), self.probes.load(Ordering::Relaxed), self.hits.load(Ordering::Relaxed))
+    }
+}
+
+thread_local! {
+    static SCOPE: RefCell<Option<Arc<Tally>>> = const { RefCell::new(None) };
+    static PENDING_NODES: Cell<usize> = const { Cell::new(0) };
+    static PENDING_PROBES: Cell<usize> = const { Cell::new(0) };
+    static PENDING_HITS: Cell<usize> = const { Cell::new(0) };
+}
+
+#[inline(always)]
+pub(crate) fn tally_node() { PENDING_NODES.with(|n| n.set(n.get() + 1)); }
+
+#[inline(always)]
+fn tally_probe(hit: bool) {
+    PENDING_PROBES.with(|n| n.set(n.get() + 1));
+    if hit { PENDING_HITS.with(|n| n.set(n.get() + 1)); }
+}
+
+#[cfg(feature = "parallel")]
+pub(crate) fn current_scope() -> Option<Arc<Tally>> { SCOPE.with(|scope| scope.borrow().clone()) }
+
+// f の間、このスレッドの集計先を scope にする（None なら数えない）
+pub(crate) fn in_scope<R>(scope: Option<Arc<Tally>>, f: impl FnOnce() -> R) -> R {
+    flush();
+    let prev = SCOPE.with(|s| s.replace(scope));
+    let result = f();
+    flush();
+    SCOPE.with(|s| *s.borrow_mut() = prev);
+    result
+}
+
+fn flush() {
+    let counts = (PENDING_NODES.with(|n| n.take()), PENDING_PROBES.with(|n| n.take()), PENDING_HITS.with(|n| n.take()));
+    SCOPE.with(|scope| if let Some(tally) = &*scope.borrow() {
+        tally.nodes.fetch_add(counts.0, Ordering::Relaxed);
+        tally.probes.fetch_add(counts.1, Ordering::Relaxed);
+        tally.hits.fetch_add(counts.2, Ordering::Relaxed);
+    });
+}
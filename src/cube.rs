@@ -0,0 +1,172 @@
+use crate::game::Game;
+
+// 立体四目並べ (Score Four)。4x4 の 16 本の柱に下から石を積み、縦・横・斜め・立体の斜めの
+// 76 本の線のどれかに 4 つ並べた方の勝ち。64 マスなので盤は u64 にちょうど収まる。
+// マス (x, y, z) のビットは 4 * (4 * y + x) + z（柱ごとに 4 ビット、z が高さ）。
+pub const PEGS: u32 = 16;
+pub const CUBE_SIZE: u32 = 64;
+
+// 通常の盤の TT と共有しても同じキーにならないように混ぜる値
+const KEY_SALT: u64 = 0x6d1f_2b84_c07e_953a;
+
+const fn cell(x: i32, y: i32, z: i32) -> u32 { (4 * (4 * y + x) + z) as u32 }
+
+// 13 方向それぞれについて、4 マスが盤に収まる始点をすべて数えると 76 本になる
+const fn build_lines() -> [u64; 76] {
+    let mut lines = [0u64; 76];
+    let mut n = 0;
+    let mut d = 0;
+    while d < 27 {
+        let (dx, dy, dz) = (d % 3 - 1, d / 3 % 3 - 1, d / 9 - 1);
+        // 同じ線を逆向きに数えないように、最初の 0 でない成分が正の向きだけ使う
+        let first = if dx != 0 { dx } else if dy != 0 { dy } else { dz };
+        if first > 0 {
+            let mut s = 0;
+            while s < 64 {
+                let (x, y, z) = (s % 4, s / 4 % 4, s / 16);
+                let (ex, ey, ez) = (x + 3 * dx, y + 3 * dy, z + 3 * dz);
+                if ex >= 0 && ex < 4 && ey >= 0 && ey < 4 && ez >= 0 && ez < 4 {
+                    let mut line = 0u64;
+                    let mut i = 0;
+                    while i < 4 {
+                        line |= 1 << cell(x + i * dx, y + i * dy, z + i * dz);
+                        i += 1;
+                    }
+                    lines[n] = line;
+                    n += 1;
+                }
+                s += 1;
+            }
+        }
+        d += 1;
+    }
+    assert!(n == 76);
+    lines
+}
+
+const LINES: [u64; 76] = build_lines();
+
+// 各柱の最下段のビット
+const BOTTOM: u64 = 0x1111_1111_1111_1111;
+
+// position は手番側の石、mask は全ての石（Board と同じ持ち方）
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Cube {
+    position: u64,
+    mask: u64,
+    moves: u32,
+}
+
+impl Cube {
+    pub fn new() -> Self { Self::default() }
+
+    // 柱を a〜p（手前の列から左から右へ、a b c d / e f g h / ...）で書いた手順から局面を作る
+    pub fn from_moves(moves: &str) -> Result<Self, String> {
+        let mut cube = Self::new();
+        cube.play_moves(moves)?;
+        Ok(cube)
+    }
+
+    pub fn play_moves(&mut self, moves: &str) -> Result<(), String> {
+        let start = self.moves;
+        for (i, c) in moves.chars().filter(|c| !c.is_whitespace()).enumerate() {
+            let n = start as usize + i + 1;
+            let peg = match c.to_ascii_lowercase() {
+                c @ 'a'..='p' => c as u32 - 'a' as u32,
+                _ => return Err(format!("invalid peg '{}' at move {} (expected a-p)", c, n)),
+            };
+            if self.is_win() { return Err(format!("game is already over before move {}", n)); }
+            if !self.can_play(peg) { return Err(format!("peg {} is full at move {}", c, n)); }
+            Game::play(self, peg);
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn moves(&self) -> u32 { self.moves }
+    #[inline(always)]
+    pub fn can_play(&self, peg: u32) -> bool { self.mask & (1 << (4 * peg + 3)) == 0 }
+
+    // 直前に打った側が並べ終えているか
+    #[inline(always)]
+    pub fn is_win(&self) -> bool {
+        let pos = self.position ^ self.mask;
+        LINES.iter().any(|&line| line & !pos == 0)
+    }
+
+    // 柱 peg に次に置かれるマス
+    #[inline(always)]
+    fn next_cell(&self, peg: u32) -> u64 {
+        (self.mask + BOTTOM) & (0xf << (4 * peg))
+    }
+}
+
+impl Game for Cube {
+    type Move = u32;
+
+    // 置くマスを通る線のうち、相手の石が無い線が多い柱から
+    fn moves(&self) -> Vec<u32> {
+        let opponent = self.position ^ self.mask;
+        let mut scored: Vec<(u32, usize)> = (0..PEGS).filter(|&peg| self.can_play(peg)).map(|peg| {
+            let cell = self.next_cell(peg);
+            (peg, LINES.iter().filter(|&&line| line & cell != 0 && line & opponent == 0).count())
+        }).collect();
+        scored.sort_by_key(|&(_, open)| std::cmp::Reverse(open));
+        scored.into_iter().map(|(peg, _)| peg).collect()
+    }
+
+    #[inline(always)]
+    fn play(&mut self, peg: u32) {
+        self.position ^= self.mask;
+        self.mask |= self.mask + (1 << (4 * peg));
+        self.moves += 1;
+    }
+
+    fn is_terminal(&self) -> Option<i8> {
+        if self.is_win() { Some(-((CUBE_SIZE + 2 - self.moves) as i8 / 2)) }
+        else if self.moves == CUBE_SIZE { Some(0) }
+        else { None }
+    }
+
+    // 柱ごとの状態は 31 通りで 64 ビットに収まらないので混ぜ合わせる。単射ではない（tt.rs のエントリのレイアウトの説明）。
+    fn key(&self) -> u64 {
+        let h = self.mask.wrapping_mul(0x9e37_79b9_7f4a_7c15).rotate_left(29);
+        (h ^ self.position).wrapping_mul(0xbf58_476d_1ce4_e5b9) ^ KEY_SALT
+    }
+
+    fn max_score(&self) -> i8 { (CUBE_SIZE + 1 - self.moves) as i8 / 2 }
+    fn remaining(&self) -> u32 { CUBE_SIZE - self.moves }
+    fn move_index(&self, peg: u32) -> u32 { peg }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Solver;
+
+    #[test]
+    fn lines() {
+        // 角のマスは 7 本、中の 8 マスは 7 本、それ以外の 48 マスは 4 本の線が通る
+        let through = |c: u32| LINES.iter().filter(|&&line| line & (1 << c) != 0).count();
+        assert_eq!(through(cell(0, 0, 0)), 7);
+        assert_eq!(through(cell(1, 1, 1)), 7);
+        assert_eq!(through(cell(1, 0, 0)), 4);
+        assert_eq!((0..CUBE_SIZE).map(through).sum::<usize>(), 76 * 4);
+    }
+
+    #[test]
+    fn wins_in_one() {
+        let solver = Solver::new(1 << 16);
+        // 横 (a b c d)、縦 (a の柱)、立体の斜め (a の 1 段目から p の 4 段目) の最後の 1 マスが空いている
+        for (moves, peg) in [("aebfcg", 3), ("ababab", 0), ("affkbkkphpmp", 15)] {
+            let cube = Cube::from_moves(moves).unwrap();
+            assert!(!cube.is_win());
+            let mut next = cube;
+            Game::play(&mut next, peg);
+            assert!(next.is_win(), "{}", moves);
+            assert_eq!(solver.solve_game(cube), (CUBE_SIZE + 1 - cube.moves()) as i8 / 2, "{}", moves);
+            assert_eq!(solver.best_game_move(&cube).0, peg, "{}", moves);
+        }
+        assert!(Cube::from_moves("aebfcgdh").is_err());
+    }
+}
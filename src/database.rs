@@ -0,0 +1,211 @@
+use std::io;
+use std::path::Path;
+
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+
+use crate::bits::Bits;
+use crate::board::{Board, Board128};
+use crate::perft::Layers;
+use crate::solver::Solver;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DbRecord {
+    // position + mask そのもの（u64 の盤なら上位 64 ビットは 0）
+    pub key: u128,
+    pub score: i8,
+    pub best_col: u8,
+    // 解くのにかかったノード数（それまでに解いた局面の TT が効いた後の数）
+    pub nodes: u64,
+}
+
+// データベースの盤の大きさと何目並べ、弱解決か
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DbHeader {
+    pub width: u32,
+    pub height: u32,
+    pub connect: u32,
+    pub weak: bool,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (width INTEGER NOT NULL, height INTEGER NOT NULL, connect INTEGER NOT NULL, weak INTEGER NOT NULL);
+    CREATE TABLE IF NOT EXISTS positions (
+        key BLOB PRIMARY KEY, score INTEGER NOT NULL, best_move INTEGER NOT NULL, nodes INTEGER NOT NULL
+    ) WITHOUT ROWID;";
+
+fn sql_error(e: rusqlite::Error) -> io::Error { io::Error::other(e) }
+
+// key の BLOB は盤のビット列の幅 (u64 の盤なら 8 バイト、u128 の盤なら 16 バイト) のリトルエンディアン
+fn key_bytes<B: Bits>(key: B) -> Vec<u8> {
+    key.widen().to_le_bytes()[..B::BITS as usize / 8].to_vec()
+}
+
+fn key_from_bytes(bytes: &[u8]) -> io::Result<u128> {
+    if bytes.len() > 16 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("a key of {} bytes", bytes.len())));
+    }
+    let mut buf = [0u8; 16];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    Ok(u128::from_le_bytes(buf))
+}
+
+// 解いた局面 (key → 評価値, 最善手, ノード数) を SQLite のファイルに書き足していく局面データベース。
+// 1 局面ごとにコミットするので、途中で止めても解き終えた局面は残り、build をやり直すと続きから解く。
+// key は position + mask そのもので（Bits::fold しない）、引くときも全ビットで比べる。
+// best_move は 1 始まりの列番号で、sqlite3 で開いてそのまま読めるようにしている。
+pub struct PositionDb {
+    width: u32,
+    height: u32,
+    connect: u32,
+    weak: bool,
+    conn: Connection,
+}
+
+impl PositionDb {
+    // path のデータベースを開く。無ければ作る。既存のファイルの盤の大きさ・何目並べ・弱解決かが違えばエラー。
+    pub fn open(path: impl AsRef<Path>, width: u32, height: u32, connect: u32, weak: bool) -> io::Result<Self> {
+        let conn = Connection::open(path).map_err(sql_error)?;
+        // WAL なら 1 局面ごとのコミットでも待たされない
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(sql_error)?;
+        conn.pragma_update(None, "synchronous", "NORMAL").map_err(sql_error)?;
+        conn.execute_batch(SCHEMA).map_err(sql_error)?;
+        match read_header(&conn)? {
+            Some(header) if header != (DbHeader { width, height, connect, weak }) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("the database is for a {}x{} connect-{}{} board",
+                    header.width, header.height, header.connect, if header.weak { " weak" } else { "" })));
+            }
+            Some(_) => {}
+            None => {
+                conn.execute("INSERT INTO meta VALUES (?1, ?2, ?3, ?4)", params![width, height, connect, weak])
+                    .map_err(sql_error)?;
+            }
+        }
+        Ok(Self { width, height, connect, weak, conn })
+    }
+
+    // 書き込まずに、ヘッダと key の順の全レコードを読む
+    pub fn read_all(path: impl AsRef<Path>) -> io::Result<(DbHeader, Vec<DbRecord>)> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(sql_error)?;
+        let header = read_header(&conn)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a position database"))?;
+        let mut stmt = conn.prepare("SELECT key, score, best_move, nodes FROM positions ORDER BY key").map_err(sql_error)?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, Vec<u8>>(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .map_err(sql_error)?;
+        let records = rows.map(|row| {
+            let (key, score, best_move, nodes) = row.map_err(sql_error)?;
+            record(&key, score, best_move, nodes)
+        }).collect::<io::Result<Vec<_>>>()?;
+        Ok((header, records))
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn connect(&self) -> u32 { self.connect }
+    pub fn is_weak(&self) -> bool { self.weak }
+
+    pub fn len(&self) -> io::Result<usize> {
+        self.conn.query_row("SELECT COUNT(*) FROM positions", [], |row| row.get(0)).map_err(sql_error)
+    }
+
+    pub fn is_empty(&self) -> io::Result<bool> { Ok(self.len()? == 0) }
+
+    pub fn get<B: Bits>(&self, board: &Board<B>) -> io::Result<Option<DbRecord>> {
+        if (board.width(), board.height(), board.connect()) != (self.width, self.height, self.connect) { return Ok(None); }
+        let mut stmt = self.conn.prepare_cached("SELECT key, score, best_move, nodes FROM positions WHERE key = ?1")
+            .map_err(sql_error)?;
+        let row = stmt.query_row([key_bytes(board.key())], |row| Ok((row.get::<_, Vec<u8>>(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .optional().map_err(sql_error)?;
+        row.map(|(key, score, best_move, nodes)| record(&key, score, best_move, nodes)).transpose()
+    }
+
+    // 局面を書いてコミットする
+    pub fn insert<B: Bits>(&mut self, board: &Board<B>, score: i8, best_col: u8, nodes: u64) -> io::Result<DbRecord> {
+        let mut stmt = self.conn.prepare_cached("INSERT OR REPLACE INTO positions VALUES (?1, ?2, ?3, ?4)").map_err(sql_error)?;
+        // SQLite の INTEGER は符号付き 64 ビットなので、nodes はそのビット列を i64 として書く
+        stmt.execute(params![key_bytes(board.key()), score, best_col + 1, nodes as i64]).map_err(sql_error)?;
+        Ok(DbRecord { key: board.key().widen(), score, best_col, nodes })
+    }
+
+    // root から depth 手先までに現れる未終局の全局面のうち、まだ無いものを解いて書き足す。
+    // Book::build と同じく深い局面から解く。progress(手数, その手数の局面数, 新たに解いた数) を手数ごとに呼ぶ。
+    // solver が中断されたら、その局面は書かずに止める。
+    pub fn build<B: Bits>(&mut self, solver: &Solver, root: Board<B>, depth: u32, mut progress: impl FnMut(u32, usize, usize))
+        -> io::Result<()> {
+        let mut layers: Vec<Vec<Board<B>>> = Layers::new(root, false).up_to(depth)
+            .map(|layer| layer.into_iter().filter(|board| !board.is_over()).collect()).collect();
+        layers.resize(depth as usize + 1, Vec::new());
+
+        for (ply, layer) in layers.iter().enumerate().rev() {
+            let mut solved = 0;
+            for board in layer {
+                if self.get(board)?.is_some() { continue; }
+                let nodes = solver.nodes();
+                let (col, score) = solver.best_move(board);
+                if solver.is_aborted() { return Ok(()); }
+                self.insert(board, score, col as u8, (solver.nodes() - nodes) as u64)?;
+                solved += 1;
+            }
+            progress(root.moves() + ply as u32, layer.len(), solved);
+        }
+        Ok(())
+    }
+}
+
+fn read_header(conn: &Connection) -> io::Result<Option<DbHeader>> {
+    let header = conn.query_row("SELECT width, height, connect, weak FROM meta", [], |row| {
+        Ok(DbHeader { width: row.get(0)?, height: row.get(1)?, connect: row.get(2)?, weak: row.get(3)? })
+    }).optional().map_err(sql_error)?;
+    if let Some(h) = &header {
+        Board128::with_size(h.width, h.height).and_then(|b| b.with_connect(h.connect))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+    Ok(header)
+}
+
+fn record(key: &[u8], score: i8, best_move: u8, nodes: i64) -> io::Result<DbRecord> {
+    Ok(DbRecord { key: key_from_bytes(key)?, score, best_col: best_move.wrapping_sub(1), nodes: nodes as u64 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("connect4_db_{}_{}.db", name, std::process::id()))
+    }
+
+    fn remove(path: &Path) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    // u128 の盤のキーを上位 64 ビットまでそのまま持ち、全ビットで引くこと
+    #[test]
+    fn wide_keys_are_not_folded() {
+        let path = temp_path("wide");
+        let mut db = PositionDb::open(&path, 9, 7, 4, false).unwrap();
+        let board = |moves: &str| {
+            let mut b = Board128::with_size(9, 7).unwrap();
+            b.play_moves(moves).unwrap();
+            b
+        };
+        let (a, b) = (board("9"), board("1"));
+        assert_ne!(a.key() >> 64, 0);
+        db.insert(&a, 3, 4, 10).unwrap();
+        assert_eq!(db.get(&b).unwrap(), None);
+        db.insert(&b, -2, 0, 20).unwrap();
+        assert_eq!(db.get(&a).unwrap().map(|r| (r.key, r.score, r.best_col)), Some((a.key(), 3, 4)));
+        assert_eq!(db.get(&b).unwrap().map(|r| (r.key, r.score, r.best_col)), Some((b.key(), -2, 0)));
+        assert_eq!(db.len().unwrap(), 2);
+        drop(db);
+
+        let (header, records) = PositionDb::read_all(&path).unwrap();
+        assert_eq!(header, DbHeader { width: 9, height: 7, connect: 4, weak: false });
+        assert_eq!(records.len(), 2);
+        assert!(PositionDb::open(&path, 7, 6, 4, false).is_err());
+        remove(&path);
+    }
+}
@@ -0,0 +1,219 @@
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use connect4_solver::{heuristic, Bits, Board, Level, Mcts, Player, Solver};
+
+// GUI や対局管理ソフトから使うための UCI 風の標準入出力プロトコル。列番号は 1 始まり。
+//
+//   uci                           -> id name ... / uciok
+//   isready                       -> readyok
+//   ucinewgame                    局面を初期化する
+//   position [startpos] [moves] 4 4 5   (連続した "445" でもよい)
+//   go [movetime <ms>] [nodes <n>] [infinite]
+//                                 -> info bounds <min> <max> nodes <n> time <ms>   (範囲が狭まるたび)
+//                                 -> info nodes <n> nps <n> time <ms>              (1 秒ごと)
+//                                 -> info score <s> nodes <n> time <ms> pv <列...>
+//                                 -> bestmove <列>
+//   go mcts [movetime <ms>] [nodes <n>] [infinite]
+//                                 解く代わりにモンテカルロ木探索で手を選ぶ（nodes は葉を解く Solver のノード数）
+//                                 -> info playouts <n> visits <n> value <0..1> time <ms> pv <列...>
+//                                 -> bestmove <列>
+//   go depth <n> [movetime <ms>] [infinite]
+//                                 TT を使わずに、静的評価で深さ 1 から n まで（infinite なら止められるまで）読み深める
+//                                 -> info depth <d> score <s> nodes <n> time <ms> pv <列>  (深さごと)
+//                                 -> bestmove <列>
+//   --level を指定すると、mcts と depth の無い go はその強さで手を選ぶ（info string level <名前> の後に bestmove）
+//   stop                          探索を打ち切って、その時点の最善手を返す
+//   quit
+// new_mcts は go mcts で局面ごとの Mcts を作る。level があれば go ごとに seed を 1 ずつずらした Player で手を選ぶ。
+pub fn run<B: Bits>(solver: Arc<Solver>, empty: Board<B>, new_mcts: impl Fn(Board<B>) -> Mcts<B>, level: Option<Level>, seed: u64) {
+    let mut board = empty;
+    let mut searches: u64 = 0;
+    let mut search: Option<JoinHandle<()>> = None;
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if search.as_ref().is_some_and(|h| h.is_finished()) { let _ = search.take().unwrap().join(); }
+        match tokens.as_slice() {
+            [] => {}
+            ["uci"] => {
+                println!("id name connect4_solver {}", env!("CARGO_PKG_VERSION"));
+                println!("uciok");
+            }
+            ["isready"] => println!("readyok"),
+            ["ucinewgame"] => {
+                stop(&solver, &mut search);
+                board = empty;
+            }
+            ["position", rest @ ..] => match parse_position(empty, rest) {
+                Ok(b) => board = b,
+                Err(e) => println!("info string error: {}", e),
+            },
+            ["go", rest @ ..] => {
+                if search.is_some() { println!("info string error: search already running"); continue; }
+                match parse_limits(rest) {
+                    Ok((max_time, max_nodes, kind)) => {
+                        // 終局済みの局面の Mcts は作れない（go が game over を返す）
+                        let over = board.is_over();
+                        let mode = match (kind, level) {
+                            (SearchKind::Mcts, _) if !over => Mode::Mcts(new_mcts(board)),
+                            (SearchKind::Depth(depth), _) => Mode::Depth(depth),
+                            (SearchKind::Solve, Some(level)) => Mode::Level(Player::new(level, seed.wrapping_add(searches))),
+                            _ => Mode::Solve,
+                        };
+                        searches += 1;
+                        search = Some(go(&solver, board, max_time, max_nodes, mode));
+                    }
+                    Err(e) => println!("info string error: {}", e),
+                }
+            }
+            ["stop"] => stop(&solver, &mut search),
+            ["quit"] => break,
+            _ => println!("info string error: unknown command '{}'", line.trim()),
+        }
+    }
+    stop(&solver, &mut search);
+}
+
+fn stop(solver: &Solver, search: &mut Option<JoinHandle<()>>) {
+    if let Some(handle) = search.take() {
+        solver.abort();
+        let _ = handle.join();
+    }
+}
+
+fn parse_position<B: Bits>(empty: Board<B>, tokens: &[&str]) -> Result<Board<B>, String> {
+    let moves: String = tokens.iter().filter(|&&t| t != "startpos" && t != "moves").copied().collect();
+    let mut board = empty;
+    board.play_moves(&moves)?;
+    Ok(board)
+}
+
+// go で選ぶ探索
+enum SearchKind {
+    Solve,
+    Mcts,
+    // 静的評価で読む深さの上限 (infinite なら u32::MAX)
+    Depth(u32),
+}
+
+enum Mode<B: Bits> {
+    Solve,
+    Mcts(Mcts<B>),
+    Depth(u32),
+    Level(Player),
+}
+
+// (制限時間, ノード数, 探索)
+fn parse_limits(tokens: &[&str]) -> Result<(Option<Duration>, Option<usize>, SearchKind), String> {
+    let (mut max_time, mut max_nodes, mut kind) = (None, None, SearchKind::Solve);
+    let mut infinite = false;
+    let mut it = tokens.iter();
+    while let Some(&token) = it.next() {
+        let mut number = || -> Result<u64, String> {
+            let v = it.next().ok_or_else(|| format!("'{}' requires a value", token))?;
+            v.parse().map_err(|_| format!("invalid value '{}' for '{}'", v, token))
+        };
+        match token {
+            "movetime" => max_time = Some(Duration::from_millis(number()?)),
+            "nodes" => max_nodes = Some(number()? as usize),
+            "infinite" => infinite = true,
+            "mcts" => kind = SearchKind::Mcts,
+            "depth" => kind = SearchKind::Depth(number()? as u32),
+            _ => return Err(format!("unknown go option '{}'", token)),
+        }
+    }
+    if infinite && let SearchKind::Depth(_) = kind { kind = SearchKind::Depth(u32::MAX); }
+    Ok((max_time, max_nodes, kind))
+}
+
+// 探索スレッドと、制限時間・ノード数の監視と 1 秒ごとの info を受け持つスレッドを起動する。
+// 中断フラグは両方のスレッドが終わってから探索スレッドが下ろす。
+fn go<B: Bits>(solver: &Arc<Solver>, board: Board<B>, max_time: Option<Duration>, max_nodes: Option<usize>,
+    mode: Mode<B>) -> JoinHandle<()> {
+    let solver = Arc::clone(solver);
+    thread::spawn(move || {
+        if board.is_over() {
+            println!("info string game over");
+            println!("bestmove none");
+            return;
+        }
+        let (start, start_nodes) = (Instant::now(), solver.nodes());
+        let done = Arc::new(AtomicBool::new(false));
+        let watcher = {
+            let (solver, done) = (Arc::clone(&solver), Arc::clone(&done));
+            thread::spawn(move || {
+                let mut next_info = Duration::from_secs(1);
+                while !done.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(5));
+                    let (elapsed, nodes) = (start.elapsed(), solver.nodes() - start_nodes);
+                    if max_time.is_some_and(|t| elapsed >= t) || max_nodes.is_some_and(|n| nodes >= n) {
+                        solver.abort();
+                        break;
+                    }
+                    if elapsed >= next_info {
+                        println!("info nodes {} nps {} time {}", nodes,
+                            (nodes as f64 / elapsed.as_secs_f64()) as u64, elapsed.as_millis());
+                        next_info += Duration::from_secs(1);
+                    }
+                }
+            })
+        };
+
+        let best = match mode {
+            Mode::Mcts(mut mcts) => {
+                mcts.run(|_| !solver.is_aborted());
+                let best = mcts.best_move();
+                let visits = mcts.root_moves().into_iter().find(|m| m.col == best);
+                let pv: Vec<String> = mcts.principal_variation().iter().map(|c| (c + 1).to_string()).collect();
+                println!("info playouts {} visits {} value {:.4} time {} pv {}", mcts.playouts(), visits.map_or(0, |m| m.visits),
+                    visits.map_or(0.0, |m| m.value), start.elapsed().as_millis(), pv.join(" "));
+                best
+            }
+            Mode::Depth(max_depth) => deepen(&solver, &board, max_depth, start),
+            Mode::Level(mut player) => {
+                println!("info string level {}", player.level().name());
+                player.choose_move(&board, &solver)
+            }
+            Mode::Solve => solve(&solver, &board, start, start_nodes),
+        };
+        println!("bestmove {}", best + 1);
+
+        done.store(true, Ordering::Relaxed);
+        let _ = watcher.join();
+        solver.clear_abort();
+    })
+}
+
+// 静的評価の探索を深さ 1 から max_depth まで繰り返し、止められたら最後に読み終えた深さの手を返す。
+// 勝敗が読み切れたらそれ以上は深くしない。
+fn deepen<B: Bits>(solver: &Solver, board: &Board<B>, max_depth: u32, start: Instant) -> u32 {
+    let mut best = None;
+    let mut nodes = 0;
+    for depth in 1..=max_depth.min(board.size() - board.moves()) {
+        let Some(result) = heuristic::search(board, depth, || !solver.is_aborted()) else { break };
+        nodes += result.nodes;
+        println!("info depth {} score {} nodes {} time {} pv {}", depth, result.score, nodes, start.elapsed().as_millis(), result.col + 1);
+        best = Some(result.col);
+        if result.score.abs() > heuristic::WIN_SCORE / 2 || solver.is_aborted() { break; }
+    }
+    best.unwrap_or_else(|| solver.hint_move(board))
+}
+
+// 解き終えていれば最善手と読み筋を info に出し、中断されていれば TT の手を返す
+fn solve<B: Bits>(solver: &Solver, board: &Board<B>, start: Instant, start_nodes: usize) -> u32 {
+    solver.solve_with_bounds(*board, |lo, hi| {
+        println!("info bounds {} {} nodes {} time {}", lo, hi, solver.nodes() - start_nodes, start.elapsed().as_millis());
+    });
+    if solver.is_aborted() { return solver.hint_move(board); }
+    let (col, score) = solver.best_move(board);
+    let pv = solver.principal_variation(*board);
+    let pv: Vec<String> = pv.iter().map(|c| (c + 1).to_string()).collect();
+    println!("info score {} nodes {} time {} pv {}",
+        score, solver.nodes() - start_nodes, start.elapsed().as_millis(), pv.join(" "));
+    col
+}
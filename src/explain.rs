@@ -0,0 +1,118 @@
+use crate::bits::Bits;
+use crate::board::Board;
+
+// 手の説明。勝ちマス（置けば四目になる空きマス）のビットボードと読み筋から、
+// 「c4 で相手の勝ちを防ぐ」「1 段目に両取りを作る」のような短い文を作る。
+// マスは列を a から、段を下から 1 から数えて c4 のように書く。
+
+// マスの名前 (0 始まりの列と段)
+pub fn cell_name(col: u32, row: u32) -> String {
+    format!("{}{}", char::from(b'a' + col as u8), row + 1)
+}
+
+// bits の各マスの (列, 段)。列の順、列の中は下から。
+fn cells<B: Bits>(board: &Board<B>, mut bits: B) -> Vec<(u32, u32)> {
+    let h = board.height() + 1;
+    let mut out = Vec::new();
+    while bits != B::ZERO {
+        let i = bits.trailing_zeros();
+        out.push((i / h, i % h));
+        bits &= bits.wrapping_sub(B::ONE);
+    }
+    out
+}
+
+fn names(cells: &[(u32, u32)]) -> String {
+    let names: Vec<String> = cells.iter().map(|&(col, row)| cell_name(col, row)).collect();
+    match names.as_slice() {
+        [] => String::new(),
+        [one] => one.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+// 手番側が列 col (0 始まり) に打つ理由を、重要なものから順に返す。
+// pv は col から始まる読み筋で、最後に誰が勝つか（引き分けか）を説明に加える。空なら加えない。
+// col に打てない局面で呼ぶと panic する。
+pub fn explain<B: Bits>(board: &Board<B>, col: u32, pv: &[u32]) -> Vec<String> {
+    assert!(board.can_play(col), "explain called with an unplayable column");
+    let mv = board.possible() & board.column_mask(col);
+    let (_, row) = cells(board, mv)[0];
+    let here = cell_name(col, row);
+    let mut next = *board;
+    next.play(col);
+    if next.is_win() { return vec![format!("wins immediately at {}", here)]; }
+
+    let mut reasons = Vec::new();
+    if board.forced_moves() & mv != B::ZERO { reasons.push(format!("blocks the opponent's win at {}", here)); }
+
+    // 打った後の手番側の勝ちマス（next では相手側）
+    let threats = next.opponent_winning_position();
+    let playable = threats & next.possible();
+    let immediate = cells(board, playable);
+    if immediate.len() >= 2 {
+        let same_row = immediate.iter().all(|&(_, r)| r == immediate[0].1);
+        let on_row = if same_row { format!(" on row {}", immediate[0].1 + 1) } else { String::new() };
+        reasons.push(format!("creates a double threat at {}{}", names(&immediate), on_row));
+    } else if immediate.len() == 1 {
+        reasons.push(format!("threatens to win at {}", names(&immediate)));
+    }
+    // すぐ上にも勝ちマスがある置ける勝ちマス: 塞いでも上で勝たれる
+    let stacked = cells(board, playable & (threats >> 1));
+    if let Some(&(c, r)) = stacked.first() {
+        reasons.push(format!("creates stacked threats at {} and {}", cell_name(c, r), cell_name(c, r + 1)));
+    }
+    // まだ置けない新しい勝ちマス。先手は奇数段、後手は偶数段の勝ちマスが終盤のツークツワンクで効く。
+    let first_player = board.moves().is_multiple_of(2);
+    let new_threats = cells(board, threats & !board.winning_position() & !next.possible());
+    let (good, other): (Vec<_>, Vec<_>) = new_threats.into_iter().partition(|&(_, r)| r.is_multiple_of(2) == first_player);
+    if !good.is_empty() {
+        let parity = if first_player { "odd" } else { "even" };
+        reasons.push(format!("creates a threat at {} on an {} row, good for the {} player",
+            names(&good), parity, if first_player { "first" } else { "second" }));
+    }
+    if !other.is_empty() { reasons.push(format!("creates a threat at {}", names(&other))); }
+
+    // 相手がここに打っていれば作れた、すぐ置ける勝ちマス
+    let (position, mask) = board.stones();
+    let denied = board.compute_winning_position((position ^ mask) | mv, mask | mv) & !board.opponent_winning_position();
+    let denied = cells(board, denied & next.possible());
+    if denied.len() >= 2 {
+        reasons.push(format!("prevents the opponent's double threat at {}", names(&denied)));
+    } else if !denied.is_empty() {
+        reasons.push(format!("prevents the opponent's threat at {}", names(&denied)));
+    }
+
+    // 相手の勝ちマスの真下で、打つと相手に勝たれる列
+    let unsafe_cells = cells(board, board.possible() & (board.opponent_winning_position() >> 1) & !mv);
+    if !unsafe_cells.is_empty() && board.non_losing_moves() & mv != B::ZERO {
+        let above: Vec<(u32, u32)> = unsafe_cells.iter().map(|&(c, r)| (c, r + 1)).collect();
+        reasons.push(format!("avoids playing under the opponent's threat at {}", names(&above)));
+    }
+    if reasons.is_empty() && col == board.width() / 2 && !board.width().is_multiple_of(2) {
+        reasons.push("takes the center column".to_string());
+    }
+
+    if let Some(reason) = outcome(board, pv) { reasons.push(reason); }
+    reasons
+}
+
+// 読み筋を最後まで辿った結果。手番側の手の数で数える。
+fn outcome<B: Bits>(board: &Board<B>, pv: &[u32]) -> Option<String> {
+    if pv.is_empty() { return None; }
+    let mut b = *board;
+    for (i, &col) in pv.iter().enumerate() {
+        if !b.can_play(col) { return None; }
+        b.play(col);
+        if b.is_win() {
+            let moves = i / 2 + 1;
+            let plural = if moves == 1 { "" } else { "s" };
+            return Some(if i.is_multiple_of(2) {
+                format!("forces a win in {} move{}", moves, plural)
+            } else {
+                format!("loses in {} move{} against best play, the longest defence", moves, plural)
+            });
+        }
+    }
+    (b.moves() == b.size()).then(|| "holds the draw".to_string())
+}
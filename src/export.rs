@@ -0,0 +1,418 @@
+use std::fmt::Write as _;
+
+use connect4_solver::{Bits, Board, Cell, Side};
+
+// 'export': 局面の図を SVG か PNG で書き出す（ブログや論文にそのまま貼れるように）。
+// 列ごとの評価値（手番側から見た値）を盤の上に、読み筋を打つ順の番号付きの円と矢印で重ねられる。
+// PNG は外部のライブラリを使わずに描く。文字は数字と +, - だけの 3x5 のドットのフォントで、圧縮はしない（無圧縮の deflate）。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Svg,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "svg" => Some(ImageFormat::Svg),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+// 描く内容。段は下から 0 始まり。
+pub struct Figure {
+    width: u32,
+    height: u32,
+    cells: Vec<Vec<Cell>>,
+    last: Option<(u32, u32)>,
+    scores: Option<Vec<Option<i8>>>,
+    // 読み筋の手を打つ順に (列, 段, 先手の手か)
+    pv: Vec<(u32, u32, bool)>,
+}
+
+impl Figure {
+    // last は board に最後に打った列、pv は board からの読み筋
+    pub fn new<B: Bits>(board: &Board<B>, last: Option<u32>, scores: Option<Vec<Option<i8>>>, pv: &[u32]) -> Self {
+        let mut b = *board;
+        let pv = pv.iter().map(|&col| {
+            let cell = (col, b.column_height(col), b.side_to_move() == Side::X);
+            b.play(col);
+            cell
+        }).collect();
+        Self {
+            width: board.width(),
+            height: board.height(),
+            cells: board.to_grid(),
+            last: last.map(|col| (col, board.column_height(col) - 1)),
+            scores,
+            pv,
+        }
+    }
+}
+
+// 大きさ (px)
+const CELL: u32 = 60;
+const RADIUS: u32 = 24;
+const MARGIN: u32 = 20;
+// 盤の上の評価値の欄と、下の列番号の欄
+const HEADER: u32 = 30;
+const FOOTER: u32 = 26;
+
+const BACKGROUND: [u8; 3] = [0xff, 0xff, 0xff];
+const BOARD: [u8; 3] = [0x1f, 0x4e, 0xb4];
+const HOLE: [u8; 3] = [0xf4, 0xf6, 0xfa];
+const X_DISC: [u8; 3] = [0xd6, 0x28, 0x28];
+const O_DISC: [u8; 3] = [0xf2, 0xc2, 0x1b];
+const LAST_RING: [u8; 3] = [0x11, 0x11, 0x11];
+const PV_LINE: [u8; 3] = [0x2e, 0x9e, 0x4f];
+const TEXT: [u8; 3] = [0x22, 0x22, 0x22];
+
+fn size(f: &Figure) -> (u32, u32) {
+    (2 * MARGIN + f.width * CELL, 2 * MARGIN + HEADER + f.height * CELL + FOOTER)
+}
+
+// マスの中心
+fn center(f: &Figure, col: u32, row: u32) -> (f64, f64) {
+    let x = MARGIN + col * CELL + CELL / 2;
+    let y = MARGIN + HEADER + (f.height - 1 - row) * CELL + CELL / 2;
+    (x as f64, y as f64)
+}
+
+fn hex(c: [u8; 3]) -> String { format!("#{:02x}{:02x}{:02x}", c[0], c[1], c[2]) }
+
+// 読み筋の矢印は円の縁から縁へ
+fn arrow(f: &Figure, from: (u32, u32), to: (u32, u32)) -> ((f64, f64), (f64, f64)) {
+    let (a, b) = (center(f, from.0, from.1), center(f, to.0, to.1));
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len = (dx * dx + dy * dy).sqrt().max(1.0);
+    let r = RADIUS as f64 * 0.6;
+    ((a.0 + dx / len * r, a.1 + dy / len * r), (b.0 - dx / len * r, b.1 - dy / len * r))
+}
+
+pub fn svg(f: &Figure) -> String {
+    let (w, h) = size(f);
+    let mut out = String::new();
+    let _ = writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">"#);
+    let _ = writeln!(out, r#"<defs><marker id="arrow" viewBox="0 0 10 10" refX="8" refY="5" markerWidth="5" markerHeight="5" orient="auto"><path d="M0,0 L10,5 L0,10 z" fill="{}"/></marker></defs>"#, hex(PV_LINE));
+    let _ = writeln!(out, r#"<rect width="{w}" height="{h}" fill="{}"/>"#, hex(BACKGROUND));
+    let _ = writeln!(out, r#"<rect x="{}" y="{}" width="{}" height="{}" rx="10" fill="{}"/>"#,
+        MARGIN, MARGIN + HEADER, f.width * CELL, f.height * CELL, hex(BOARD));
+    for (row, cells) in f.cells.iter().enumerate() {
+        for (col, cell) in cells.iter().enumerate() {
+            let (x, y) = center(f, col as u32, row as u32);
+            let fill = match cell {
+                Cell::Empty => HOLE,
+                Cell::X => X_DISC,
+                Cell::O => O_DISC,
+            };
+            let _ = writeln!(out, r#"<circle cx="{x}" cy="{y}" r="{RADIUS}" fill="{}"/>"#, hex(fill));
+        }
+    }
+    if let Some((col, row)) = f.last {
+        let (x, y) = center(f, col, row);
+        let _ = writeln!(out, r#"<circle cx="{x}" cy="{y}" r="{}" fill="none" stroke="{}" stroke-width="3"/>"#, RADIUS - 6, hex(LAST_RING));
+    }
+    for (i, &(col, row, first)) in f.pv.iter().enumerate() {
+        let (x, y) = center(f, col, row);
+        let color = if first { X_DISC } else { O_DISC };
+        let _ = writeln!(out, r#"<circle cx="{x}" cy="{y}" r="{RADIUS}" fill="{}" fill-opacity="0.35" stroke="{}" stroke-width="3"/>"#, hex(color), hex(color));
+        let _ = writeln!(out, r#"<text x="{x}" y="{}" font-size="18" font-weight="bold" text-anchor="middle" fill="{}">{}</text>"#, y + 6.0, hex(TEXT), i + 1);
+    }
+    for pair in f.pv.windows(2) {
+        let ((x1, y1), (x2, y2)) = arrow(f, (pair[0].0, pair[0].1), (pair[1].0, pair[1].1));
+        let _ = writeln!(out, r#"<line x1="{x1:.1}" y1="{y1:.1}" x2="{x2:.1}" y2="{y2:.1}" stroke="{}" stroke-width="3" marker-end="url(#arrow)"/>"#, hex(PV_LINE));
+    }
+    if let Some(scores) = &f.scores {
+        for (col, score) in scores.iter().enumerate() {
+            let Some(score) = score else { continue };
+            let (x, _) = center(f, col as u32, 0);
+            let _ = writeln!(out, r#"<text x="{x}" y="{}" font-size="18" text-anchor="middle" fill="{}">{}</text>"#,
+                MARGIN + HEADER - 8, hex(TEXT), signed(*score));
+        }
+    }
+    for col in 0..f.width {
+        let (x, _) = center(f, col, 0);
+        let _ = writeln!(out, r#"<text x="{x}" y="{}" font-size="16" text-anchor="middle" fill="{}">{}</text>"#,
+            MARGIN + HEADER + f.height * CELL + FOOTER - 6, hex(TEXT), col + 1);
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn signed(score: i8) -> String { if score > 0 { format!("+{}", score) } else { score.to_string() } }
+
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+// 3x5 のドットの文字。行ごとに左から 3 ビット。
+fn glyph(c: char) -> Option<[u8; 5]> {
+    Some(match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        _ => return None,
+    })
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32, color: [u8; 3]) -> Self {
+        Self { width, height, pixels: color.repeat((width * height) as usize) }
+    }
+
+    fn set(&mut self, x: i64, y: i64, color: [u8; 3], alpha: f64) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 { return; }
+        let i = 3 * (y as usize * self.width as usize + x as usize);
+        for (k, &c) in color.iter().enumerate() {
+            let p = &mut self.pixels[i + k];
+            *p = (*p as f64 * (1.0 - alpha) + c as f64 * alpha).round() as u8;
+        }
+    }
+
+    // inside(x, y) が距離の符号（負なら内側）を返す図形を、境界を 1px でぼかして塗る
+    fn fill(&mut self, bounds: (f64, f64, f64, f64), color: [u8; 3], alpha: f64, inside: impl Fn(f64, f64) -> f64) {
+        let (x0, y0, x1, y1) = bounds;
+        for y in (y0.floor() as i64)..=(y1.ceil() as i64) {
+            for x in (x0.floor() as i64)..=(x1.ceil() as i64) {
+                let d = inside(x as f64 + 0.5, y as f64 + 0.5);
+                let coverage = (0.5 - d).clamp(0.0, 1.0);
+                if coverage > 0.0 { self.set(x, y, color, alpha * coverage); }
+            }
+        }
+    }
+
+    fn circle(&mut self, (cx, cy): (f64, f64), r: f64, color: [u8; 3], alpha: f64) {
+        self.fill((cx - r, cy - r, cx + r, cy + r), color, alpha, |x, y| ((x - cx).powi(2) + (y - cy).powi(2)).sqrt() - r);
+    }
+
+    fn ring(&mut self, (cx, cy): (f64, f64), r: f64, width: f64, color: [u8; 3]) {
+        self.fill((cx - r - width, cy - r - width, cx + r + width, cy + r + width), color, 1.0,
+            |x, y| (((x - cx).powi(2) + (y - cy).powi(2)).sqrt() - r).abs() - width / 2.0);
+    }
+
+    fn rect(&mut self, (x0, y0, x1, y1): (f64, f64, f64, f64), color: [u8; 3]) {
+        self.fill((x0, y0, x1, y1), color, 1.0, |x, y| (x0 - x).max(x - x1).max(y0 - y).max(y - y1));
+    }
+
+    fn line(&mut self, (ax, ay): (f64, f64), (bx, by): (f64, f64), width: f64, color: [u8; 3]) {
+        let (dx, dy) = (bx - ax, by - ay);
+        let len2 = (dx * dx + dy * dy).max(1e-9);
+        let pad = width;
+        self.fill((ax.min(bx) - pad, ay.min(by) - pad, ax.max(bx) + pad, ay.max(by) + pad), color, 1.0, |x, y| {
+            let t = (((x - ax) * dx + (y - ay) * dy) / len2).clamp(0.0, 1.0);
+            ((x - ax - t * dx).powi(2) + (y - ay - t * dy).powi(2)).sqrt() - width / 2.0
+        });
+    }
+
+    // 終点に三角の矢じりを付けた線
+    fn arrow(&mut self, a: (f64, f64), b: (f64, f64), width: f64, color: [u8; 3]) {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len = (dx * dx + dy * dy).sqrt().max(1.0);
+        let (ux, uy) = (dx / len, dy / len);
+        let head = 10.0;
+        let base = (b.0 - ux * head, b.1 - uy * head);
+        self.line(a, base, width, color);
+        let (l, r) = ((base.0 - uy * head * 0.6, base.1 + ux * head * 0.6), (base.0 + uy * head * 0.6, base.1 - ux * head * 0.6));
+        let edges = [(b, l), (l, r), (r, b)];
+        self.fill((b.0.min(l.0).min(r.0), b.1.min(l.1).min(r.1), b.0.max(l.0).max(r.0), b.1.max(l.1).max(r.1)), color, 1.0, |x, y| {
+            // 3 辺の外側への距離の最大（反時計回りでも時計回りでも同じになるように向きを合わせる）
+            let orient = ((l.0 - b.0) * (r.1 - b.1) - (l.1 - b.1) * (r.0 - b.0)).signum();
+            edges.iter().map(|&(p, q)| {
+                let (ex, ey) = (q.0 - p.0, q.1 - p.1);
+                let len = (ex * ex + ey * ey).sqrt().max(1e-9);
+                orient * ((x - p.0) * ey - (y - p.1) * ex) / len
+            }).fold(f64::MIN, f64::max)
+        });
+    }
+
+    // 中心 (cx, cy) に scale 倍の 3x5 の文字で書く
+    fn text(&mut self, (cx, cy): (f64, f64), text: &str, scale: u32, color: [u8; 3]) {
+        let glyphs: Vec<[u8; 5]> = text.chars().filter_map(glyph).collect();
+        let w = (glyphs.len() as u32 * 4).saturating_sub(1) * scale;
+        let (x0, y0) = (cx as i64 - w as i64 / 2, cy as i64 - (5 * scale) as i64 / 2);
+        for (i, g) in glyphs.iter().enumerate() {
+            for (row, bits) in g.iter().enumerate() {
+                for col in 0..3 {
+                    if bits >> (2 - col) & 1 == 0 { continue; }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let x = x0 + ((i as u32 * 4 + col) * scale + dx) as i64;
+                            self.set(x, y0 + (row as u32 * scale + dy) as i64, color, 1.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn png(f: &Figure) -> Vec<u8> {
+    let (w, h) = size(f);
+    let mut canvas = Canvas::new(w, h, BACKGROUND);
+    let (bx, by) = (MARGIN as f64, (MARGIN + HEADER) as f64);
+    canvas.rect((bx, by, bx + (f.width * CELL) as f64, by + (f.height * CELL) as f64), BOARD);
+    for (row, cells) in f.cells.iter().enumerate() {
+        for (col, cell) in cells.iter().enumerate() {
+            let fill = match cell {
+                Cell::Empty => HOLE,
+                Cell::X => X_DISC,
+                Cell::O => O_DISC,
+            };
+            canvas.circle(center(f, col as u32, row as u32), RADIUS as f64, fill, 1.0);
+        }
+    }
+    if let Some((col, row)) = f.last { canvas.ring(center(f, col, row), (RADIUS - 6) as f64, 3.0, LAST_RING); }
+    for (i, &(col, row, first)) in f.pv.iter().enumerate() {
+        let color = if first { X_DISC } else { O_DISC };
+        canvas.circle(center(f, col, row), RADIUS as f64, color, 0.35);
+        canvas.ring(center(f, col, row), RADIUS as f64 - 1.5, 3.0, color);
+        canvas.text(center(f, col, row), &(i + 1).to_string(), 3, TEXT);
+    }
+    for pair in f.pv.windows(2) {
+        let (a, b) = arrow(f, (pair[0].0, pair[0].1), (pair[1].0, pair[1].1));
+        canvas.arrow(a, b, 3.0, PV_LINE);
+    }
+    if let Some(scores) = &f.scores {
+        for (col, score) in scores.iter().enumerate() {
+            let Some(score) = score else { continue };
+            let (x, _) = center(f, col as u32, 0);
+            canvas.text((x, (MARGIN + HEADER / 2) as f64), &signed(*score), 3, TEXT);
+        }
+    }
+    for col in 0..f.width {
+        let (x, _) = center(f, col, 0);
+        canvas.text((x, (MARGIN + HEADER + f.height * CELL + FOOTER / 2) as f64), &(col + 1).to_string(), 3, TEXT);
+    }
+    encode_png(&canvas)
+}
+
+// RGB 8 ビット、フィルタなし、無圧縮の deflate で PNG にする
+fn encode_png(canvas: &Canvas) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(canvas.pixels.len() + canvas.height as usize);
+    for row in canvas.pixels.chunks(3 * canvas.width as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xffff).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push((i + 1 == blocks.len()) as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut ihdr = Vec::new();
+    ihdr.extend_from_slice(&canvas.width.to_be_bytes());
+    ihdr.extend_from_slice(&canvas.height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, data) in [(b"IHDR", &ihdr[..]), (b"IDAT", &zlib[..]), (b"IEND", &[][..])] {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let crc = crc32(&out[start..]);
+        out.extend_from_slice(&crc.to_be_bytes());
+    }
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 { crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 }; }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &d in data {
+        a = (a + d as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    // 無圧縮の PNG を読み、チャンクの CRC と zlib の Adler-32 を確かめて (幅, 高さ, RGB) を返す
+    fn decode(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let (mut pos, mut chunks) = (8, Vec::new());
+        while pos < png.len() {
+            let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let body = &png[pos + 4..pos + 8 + len];
+            assert_eq!(crc32(body).to_be_bytes(), png[pos + 8 + len..pos + 12 + len]);
+            chunks.push((body[..4].to_vec(), body[4..].to_vec()));
+            pos += 12 + len;
+        }
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| &kind[..]).collect();
+        assert_eq!(kinds, [&b"IHDR"[..], b"IDAT", b"IEND"]);
+        let ihdr = &chunks[0].1;
+        let (w, h) = (u32::from_be_bytes(ihdr[0..4].try_into().unwrap()), u32::from_be_bytes(ihdr[4..8].try_into().unwrap()));
+        assert_eq!(ihdr[8..], [8, 2, 0, 0, 0]);
+
+        let zlib = &chunks[1].1;
+        assert_eq!(zlib[..2], [0x78, 0x01]);
+        let (mut pos, mut raw) = (2, Vec::new());
+        loop {
+            let last = zlib[pos] == 1;
+            let len = u16::from_le_bytes([zlib[pos + 1], zlib[pos + 2]]);
+            assert_eq!(!len, u16::from_le_bytes([zlib[pos + 3], zlib[pos + 4]]));
+            raw.extend_from_slice(&zlib[pos + 5..pos + 5 + len as usize]);
+            pos += 5 + len as usize;
+            if last { break; }
+        }
+        assert_eq!(zlib[pos..], adler32(&raw).to_be_bytes());
+        let rows: Vec<&[u8]> = raw.chunks(1 + 3 * w as usize).collect();
+        assert_eq!(rows.len(), h as usize);
+        assert!(rows.iter().all(|row| row[0] == 0 && row.len() == 1 + 3 * w as usize));
+        (w, h, rows.iter().flat_map(|row| row[1..].to_vec()).collect())
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let board = Board::from_moves("4453").unwrap();
+        let f = Figure::new(&board, Some(2), Some(vec![Some(1), None, Some(-2), Some(0), None, None, None]), &[3, 3]);
+        let (w, h, pixels) = decode(&png(&f));
+        assert_eq!((w, h), size(&f));
+        // 幅 7 の盤の画像は 64KB を超えるので、deflate のブロックが複数になる
+        assert!(3 * (w * h) as usize > 0xffff);
+        let pixel = |(x, y): (f64, f64)| {
+            let i = 3 * (y as usize * w as usize + x as usize);
+            [pixels[i], pixels[i + 1], pixels[i + 2]]
+        };
+        assert_eq!(pixel((0.0, 0.0)), BACKGROUND);
+        assert_eq!(pixel(center(&f, 3, 0)), X_DISC);
+        assert_eq!(pixel(center(&f, 3, 1)), O_DISC);
+        assert_eq!(pixel(center(&f, 0, 0)), HOLE);
+        // 最後の手の石の輪
+        let (cx, cy) = center(&f, 2, 0);
+        assert_eq!(pixel((cx + (RADIUS - 6) as f64, cy)), LAST_RING);
+    }
+}
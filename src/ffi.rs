@@ -0,0 +1,57 @@
+// C から使うための API。宣言は include/connect4.h にある（関数を変えたらヘッダも合わせること）。
+// 列番号は 1 始まり、評価値は手番側から見た値。
+use std::ffi::{c_char, c_int, CStr};
+
+use crate::board::Board;
+use crate::solver::Solver;
+use crate::INVALID_POSITION;
+
+// moves が NULL、不正な手順、終局済みの局面なら None
+unsafe fn parse(moves: *const c_char) -> Option<Board> {
+    if moves.is_null() { return None; }
+    let moves = unsafe { CStr::from_ptr(moves) }.to_str().ok()?;
+    let board = Board::from_moves(moves).ok()?;
+    (!board.is_over()).then_some(board)
+}
+
+// table_entries 個のエントリ (1 つ 8 バイト) を持つ solver を作る。c4_free で解放する。
+#[unsafe(no_mangle)]
+pub extern "C" fn c4_new_solver(table_entries: usize) -> *mut Solver {
+    Box::into_raw(Box::new(Solver::new(table_entries)))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn c4_free(solver: *mut Solver) {
+    if !solver.is_null() { drop(unsafe { Box::from_raw(solver) }); }
+}
+
+// 手順 moves ("4453" のような NUL 終端文字列) の局面の評価値。不正なら C4_INVALID_POSITION。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn c4_solve(solver: *const Solver, moves: *const c_char) -> c_int {
+    let Some(solver) = (unsafe { solver.as_ref() }) else { return INVALID_POSITION };
+    match unsafe { parse(moves) } {
+        Some(board) => solver.solve(board) as c_int,
+        None => INVALID_POSITION,
+    }
+}
+
+// 最善手を返し、score が NULL でなければ評価値を書き込む。不正なら 0。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn c4_best_move(solver: *const Solver, moves: *const c_char, score: *mut c_int) -> c_int {
+    let Some(solver) = (unsafe { solver.as_ref() }) else { return 0 };
+    let Some(board) = (unsafe { parse(moves) }) else { return 0 };
+    let (col, s) = solver.best_move(&board);
+    if !score.is_null() { unsafe { *score = s as c_int; } }
+    col as c_int + 1
+}
+
+// 別スレッドから実行中の探索を止める。c4_clear_abort を呼ぶまで以降の探索もすぐに打ち切られる。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn c4_abort(solver: *const Solver) {
+    if let Some(solver) = unsafe { solver.as_ref() } { solver.abort(); }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn c4_clear_abort(solver: *const Solver) {
+    if let Some(solver) = unsafe { solver.as_ref() } { solver.clear_abort(); }
+}
@@ -0,0 +1,114 @@
+use crate::solver::{search_siblings, Solver};
+use crate::tt::Bound;
+
+// 同じ探索（negamax + TT + 並列探索）で解けるゲーム。評価値は手番側から見た値で、勝ちは正。
+// 標準の四目並べ (Board) は速さのために専用の探索 (Solver::solve) も持つが、TT のエントリは共有できる。
+pub trait Game: Copy + Send + Sync {
+    type Move: Copy + PartialEq + Send + Sync;
+
+    // 合法手を調べる順（良さそうな手から）に並べたもの。終局していない局面では空にならない。
+    fn moves(&self) -> Vec<Self::Move>;
+    fn play(&mut self, mv: Self::Move);
+    // 終局していれば手番側から見た評価値
+    fn is_terminal(&self) -> Option<i8>;
+    // TT のキー。評価値の意味が異なるゲーム（ルール）のキーと重ならないように値を混ぜておく。
+    fn key(&self) -> u64;
+    // この局面の評価値の上限（下限は -max_score）
+    fn max_score(&self) -> i8;
+    // 残りの手数の上限。TT の深さ優先の置き換えに使う。
+    fn remaining(&self) -> u32;
+    // TT の最善手の欄 (8 ビット) に入れる番号
+    fn move_index(&self, mv: Self::Move) -> u32;
+}
+
+impl Solver {
+    // 任意のゲームの局面を解く。手番側から見た評価値を返す（弱解決モードなら 1/0/-1）。
+    // null window 探索を繰り返して範囲を狭める。
+    pub fn solve_game<G: Game>(&self, game: G) -> i8 {
+        if let Some(score) = game.is_terminal() { return self.report(score); }
+        let _search = self.begin_search();
+        let (mut min, mut max) = if self.is_weak() { (-1, 1) } else { (-game.max_score(), game.max_score()) };
+        while min < max {
+            let med = min + (max - min) / 2;
+            let r = self.report(self.install(|| self.game_negamax(game, med, med + 1, 0)));
+            if self.is_aborted() { break; }
+            if r <= med { max = r; } else { min = r; }
+        }
+        min
+    }
+
+    // 手番側の最善手とその評価値。終局済みの局面に対して呼ぶと panic する。
+    pub fn best_game_move<G: Game>(&self, game: &G) -> (G::Move, i8) {
+        assert!(game.is_terminal().is_none(), "best_game_move called on a finished game");
+        let _search = self.begin_search();
+        let target = self.solve_game(*game);
+        let mut best: Option<(G::Move, i8)> = None;
+        for mv in game.moves() {
+            let mut next = *game;
+            next.play(mv);
+            let score = -self.solve_game(next);
+            if best.is_none_or(|(_, s)| score > s) { best = Some((mv, score)); }
+            if score == target { break; }
+        }
+        best.expect("unfinished game has a legal move")
+    }
+
+    // 終局していない局面の評価値
+    fn game_negamax<G: Game>(&self, game: G, mut alpha: i8, mut beta: i8, p_depth: u32) -> i8 {
+        if !self.count_node() { return 0; }
+        let moves = game.moves();
+        // 一手で勝てるならそれが最善
+        for &mv in &moves {
+            let mut next = game;
+            next.play(mv);
+            if let Some(score) = next.is_terminal() && score < 0 { return -score; }
+        }
+
+        let max = game.max_score();
+        if beta > max {
+            beta = max;
+            if alpha >= beta { return beta; }
+        }
+        if alpha < -max {
+            alpha = -max;
+            if alpha >= beta { return alpha; }
+        }
+
+        let key = game.key();
+        let mut order = moves;
+        let use_table = self.uses_table(game.remaining());
+        if use_table && let Some((score, bound, index)) = self.table().lookup(key) {
+            if let Some(score) = bound.narrow(score, &mut alpha, &mut beta) { return score; }
+            if let Some(pos) = order.iter().position(|&mv| game.move_index(mv) == index) { order[..=pos].rotate_right(1); }
+        }
+
+        let child = |mv: G::Move, alpha: i8, beta: i8| {
+            let mut next = game;
+            next.play(mv);
+            match next.is_terminal() {
+                Some(score) => -score,
+                None => -self.game_negamax(next, -beta, -alpha, p_depth + 1),
+            }
+        };
+        let (alpha_orig, beta_orig) = (alpha, beta);
+        let (mut best, mut best_mv) = (i8::MIN, order[0]);
+        // Solver::solve と同じく、並列に分けるノードでも最初の子だけは逐次に探索する (Young Brothers Wait)
+        let (first, rest) = if p_depth < self.parallel_depth() { order.split_at(1) } else { (&order[..], &[][..]) };
+        for &mv in first {
+            let score = child(mv, alpha, beta);
+            if score > best { best = score; best_mv = mv; }
+            if score > alpha { alpha = score; }
+            if alpha >= beta { break; }
+        }
+        if alpha < beta && !rest.is_empty() {
+            for (score, mv) in search_siblings(rest, alpha, beta, |mv, alpha| child(mv, alpha, beta)) {
+                if score > best { best = score; best_mv = mv; }
+                if score > alpha { alpha = score; }
+                if alpha >= beta { break; }
+            }
+        }
+        if self.is_aborted() { return 0; }
+        if use_table { self.table().store(key, best, Bound::of(best, alpha_orig, beta_orig), game.move_index(best_mv), game.remaining()); }
+        best
+    }
+}
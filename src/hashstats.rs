@@ -0,0 +1,128 @@
+use std::collections::{HashMap, HashSet};
+use std::hint::black_box;
+use std::time::Instant;
+
+use crate::bits::Bits;
+use crate::board::Board;
+use crate::tt::{HashFunction, BUCKET_ENTRIES, CHECK_SHIFT};
+
+// TT のハッシュ関数の比較用。探索に現れるような局面のキーを集め、ある大きさの TT のバケットに
+// どう散らばるかを、一様に散らばった場合の期待値と比べる。
+
+// sample_keys で集めたキー
+pub struct KeySample {
+    pub keys: Vec<u64>,
+    // 別の局面と同じ TT のキーになった局面の数（Bits::fold などが単射でないため。tt.rs の照合の説明を参照）
+    pub key_collisions: usize,
+}
+
+// 探索と同じく、相手に即勝ちを与えない手を優先するランダムな手順で局面を辿り、
+// 異なる TT のキー（左右対称をまとめたもの）を最大 samples 個集める。
+// 盤が小さくて局面が足りなければ、見つかった分だけ返す。
+pub fn sample_keys<B: Bits>(root: Board<B>, samples: usize, seed: u64) -> KeySample {
+    let mut rng = seed | 1;
+    let mut next_random = move || {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        rng
+    };
+    // TT のキー → 盤のキー
+    let mut seen: HashMap<u64, B> = HashMap::new();
+    let mut keys = Vec::with_capacity(samples);
+    let (mut misses, mut key_collisions) = (0, 0);
+    while keys.len() < samples && misses < samples.max(1000) {
+        let mut board = root;
+        while !board.is_over() {
+            let safe = board.non_losing_moves();
+            let playable = board.legal_moves();
+            let preferred: Vec<u32> = playable.iter().copied().filter(|&col| safe & board.column_mask(col) != B::ZERO).collect();
+            let cols = if preferred.is_empty() { &playable[..] } else { &preferred[..] };
+            board.play(cols[next_random() as usize % cols.len()]);
+            let (key, full) = (board.table_key().0, board.canonical_key().0);
+            match seen.get(&key) {
+                None => {
+                    seen.insert(key, full);
+                    keys.push(key);
+                    if keys.len() == samples { break; }
+                }
+                Some(&other) => {
+                    if other != full { key_collisions += 1; }
+                    misses += 1;
+                }
+            }
+        }
+    }
+    KeySample { keys, key_collisions }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HashQuality {
+    pub hash: HashFunction,
+    pub keys: usize,
+    pub buckets: usize,
+    // キーが 1 つ以上入ったバケットの数と、一様に散らばった場合の期待値
+    pub used_buckets: usize,
+    pub expected_used_buckets: f64,
+    // バケットに入りきらず、置き換えで追い出し合うキーの数と、一様な場合の期待値
+    pub overflow: usize,
+    pub expected_overflow: f64,
+    pub max_load: usize,
+    // バケットごとのキー数の χ² をバケット数で割った値。一様なら 1 に近い。
+    pub chi_square: f64,
+    // バケットも検証ビットも同じで、TT では区別できないキーの組の数
+    pub check_collisions: usize,
+    pub nanos_per_key: f64,
+}
+
+// entries エントリの TT に keys を入れたときのバケットの偏りを測る
+pub fn measure(keys: &[u64], entries: usize, hash: HashFunction) -> HashQuality {
+    let bucket_mask = (entries - 1) & !(BUCKET_ENTRIES - 1);
+    let buckets = entries / BUCKET_ENTRIES;
+
+    let start = Instant::now();
+    let folded = keys.iter().fold(0u64, |acc, &key| acc ^ hash.mix(black_box(key)));
+    black_box(folded);
+    let nanos_per_key = start.elapsed().as_nanos() as f64 / keys.len().max(1) as f64;
+
+    let mut loads: HashMap<usize, usize> = HashMap::new();
+    let mut checks = HashSet::new();
+    let mut check_collisions = 0;
+    for &key in keys {
+        let h = hash.mix(key);
+        let bucket = h as usize & bucket_mask;
+        *loads.entry(bucket).or_default() += 1;
+        if !checks.insert((bucket, h >> CHECK_SHIFT)) { check_collisions += 1; }
+    }
+
+    let lambda = keys.len() as f64 / buckets as f64;
+    let overflow = loads.values().map(|&n| n.saturating_sub(BUCKET_ENTRIES)).sum();
+    let chi_square = (loads.values().map(|&n| (n as f64 - lambda).powi(2)).sum::<f64>()
+        + (buckets - loads.len()) as f64 * lambda * lambda) / lambda / buckets as f64;
+    HashQuality {
+        hash,
+        keys: keys.len(),
+        buckets,
+        used_buckets: loads.len(),
+        expected_used_buckets: buckets as f64 * -(-lambda).exp_m1(),
+        overflow,
+        expected_overflow: buckets as f64 * expected_excess(lambda, BUCKET_ENTRIES),
+        max_load: loads.values().copied().max().unwrap_or(0),
+        chi_square,
+        check_collisions,
+        nanos_per_key,
+    }
+}
+
+// 平均 lambda のポアソン分布に従う K について E[max(K - capacity, 0)] = lambda - E[min(K, capacity)]
+fn expected_excess(lambda: f64, capacity: usize) -> f64 {
+    let mut p = (-lambda).exp();
+    let mut below = 0.0;
+    let mut at_most = 0.0;
+    for k in 0..capacity {
+        below += k as f64 * p;
+        at_most += p;
+        p *= lambda / (k + 1) as f64;
+    }
+    (lambda - below - capacity as f64 * (1.0 - at_most)).max(0.0)
+}
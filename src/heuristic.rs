@@ -0,0 +1,152 @@
+use crate::bits::Bits;
+use crate::board::{Board, MAX_WIDTH};
+
+// 深さを区切った探索と、その末端で使う静的評価。TT を使わないので、大きな TT を持てない環境でも
+// 数ミリ秒で手を返せる。評価値は手番側から見た値で、勝敗が読み切れた場合は ±(WIN_SCORE - 決着する手数)。
+
+pub const WIN_SCORE: i32 = 1_000_000;
+
+// 評価の重み。石 1 つの中央寄りの度合い（端の列 1、中央の列 width）あたり、
+// 勝ちマス（そこに置けば並ぶ空きマス）1 つあたり、そのうち手番の偶奇で自分が取れる段にあるもの 1 つあたり。
+const CENTER_WEIGHT: i32 = 3;
+const THREAT_WEIGHT: i32 = 20;
+const PARITY_THREAT_WEIGHT: i32 = 40;
+
+// 途中で keep_going を確かめる間隔（ノード数）
+const CHECK_INTERVAL: u64 = 4096;
+
+// 深さを区切った探索の結果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DepthResult {
+    // 0 始まりの列番号
+    pub col: u32,
+    pub score: i32,
+    pub nodes: u64,
+}
+
+// 手番側から見た静的評価。中央の列の石、勝ちマスの数、
+// 勝ちマスの段の偶奇（先手は下から奇数段、後手は偶数段の勝ちマスを最後に取れる）を見る。
+pub fn evaluate<B: Bits>(board: &Board<B>) -> i32 {
+    let (position, mask) = board.stones();
+    let opponent = position ^ mask;
+    let mut score = 0;
+    for col in 0..board.width() {
+        let center = (board.width() - (2 * col + 1).abs_diff(board.width())) as i32;
+        let column = board.column_mask(col);
+        score += CENTER_WEIGHT * center * ((position & column).count_ones() as i32 - (opponent & column).count_ones() as i32);
+    }
+
+    // 下から奇数段 (1, 3, 5, ...) のマス
+    let mut odd_rows = B::ZERO;
+    for row in (0..board.height()).step_by(2) { odd_rows |= board.row_mask(row); }
+    let even_rows = board.board_mask() & !odd_rows;
+    let first_to_move = board.moves().is_multiple_of(2);
+    let (own_rows, opponent_rows) = if first_to_move { (odd_rows, even_rows) } else { (even_rows, odd_rows) };
+    let own_threats = board.winning_position() & !mask;
+    let opponent_threats = board.opponent_winning_position() & !mask;
+    score += THREAT_WEIGHT * (own_threats.count_ones() as i32 - opponent_threats.count_ones() as i32);
+    score += PARITY_THREAT_WEIGHT * ((own_threats & own_rows).count_ones() as i32 - (opponent_threats & opponent_rows).count_ones() as i32);
+    score
+}
+
+// depth 手先まで読んで手番側の最善手を選ぶ。keep_going が false を返したら打ち切って None。
+// 終局済みの局面に対して呼ぶと panic する。
+pub fn search<B: Bits>(board: &Board<B>, depth: u32, mut keep_going: impl FnMut() -> bool) -> Option<DepthResult> {
+    assert!(!board.is_over(), "search called on a finished game");
+    let mut nodes = 1;
+    let candidates = match candidates(board) {
+        Ok(candidates) => candidates,
+        // 読まずに決まる局面: 勝てる手か、塞ぐ手か、中央寄りの手
+        Err(score) => {
+            let wins = board.winning_position() & board.possible();
+            let forced = board.forced_moves();
+            let preferred = if wins != B::ZERO { wins } else if forced != B::ZERO { forced } else { board.possible() };
+            let col = board.column_order().find(|&col| preferred & board.column_mask(col) != B::ZERO)
+                .expect("unfinished game has a legal move");
+            return Some(DepthResult { col, score, nodes });
+        }
+    };
+    let mut best: Option<(u32, i32)> = None;
+    let mut alpha = -WIN_SCORE;
+    let (order, n) = order(board, candidates);
+    for &col in &order[..n] {
+        let mut next = *board;
+        next.play(col);
+        let score = -negamax(&next, depth.saturating_sub(1), -WIN_SCORE, -alpha, &mut nodes, &mut keep_going)?;
+        if best.is_none_or(|(_, s)| score > s) { best = Some((col, score)); }
+        alpha = alpha.max(score);
+    }
+    let (col, score) = best.expect("candidates are not empty");
+    Some(DepthResult { col, score, nodes })
+}
+
+fn negamax<B: Bits>(board: &Board<B>, depth: u32, mut alpha: i32, beta: i32, nodes: &mut u64,
+                    keep_going: &mut impl FnMut() -> bool) -> Option<i32> {
+    *nodes += 1;
+    if (*nodes).is_multiple_of(CHECK_INTERVAL) && !keep_going() { return None; }
+    if board.moves() == board.size() { return Some(0); }
+    let candidates = match candidates(board) {
+        Ok(candidates) => candidates,
+        Err(score) => return Some(score),
+    };
+    if board.moves() + 1 == board.size() { return Some(0); }
+    if depth == 0 { return Some(evaluate(board)); }
+    let mut best = -WIN_SCORE;
+    let (order, n) = order(board, candidates);
+    for &col in &order[..n] {
+        let mut next = *board;
+        next.play(col);
+        let score = -negamax(&next, depth - 1, -beta, -alpha, nodes, keep_going)?;
+        best = best.max(score);
+        alpha = alpha.max(score);
+        if alpha >= beta { break; }
+    }
+    Some(best)
+}
+
+// 手番側が読む手。勝敗が読まずに決まる局面では Err(評価値)。
+fn candidates<B: Bits>(board: &Board<B>) -> Result<B, i32> {
+    if board.can_win_next() { return Err(WIN_SCORE - (board.moves() + 1) as i32); }
+    let loss = -(WIN_SCORE - (board.moves() + 2) as i32);
+    let forced = board.forced_moves();
+    if forced & forced.wrapping_sub(B::ONE) != B::ZERO { return Err(loss); }
+    let mut candidates = board.non_losing_moves();
+    if forced != B::ZERO { candidates &= forced; }
+    if candidates == B::ZERO { return Err(loss); }
+    Ok(candidates)
+}
+
+// 打った後の勝ちマスが多い手、同数なら中央寄りの手から並べた列とその数
+fn order<B: Bits>(board: &Board<B>, candidates: B) -> ([u32; MAX_WIDTH as usize], usize) {
+    let mut scored = [(0u32, 0u32); MAX_WIDTH as usize];
+    let mut n = 0;
+    for col in board.column_order() {
+        let mv = candidates & board.column_mask(col);
+        if mv != B::ZERO {
+            scored[n] = (col, board.move_score(mv));
+            n += 1;
+        }
+    }
+    scored[..n].sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    let mut order = [0u32; MAX_WIDTH as usize];
+    for (slot, &(col, _)) in order.iter_mut().zip(&scored[..n]) { *slot = col; }
+    (order, n)
+}
+
+// 手番側の着手可能な手それぞれを depth 手先まで読んだ評価値（列の小さい順）。
+// 終局済みの局面では空。
+pub fn score_moves<B: Bits>(board: &Board<B>, depth: u32) -> Vec<(u32, i32)> {
+    if board.is_win() { return Vec::new(); }
+    let mut nodes = 0;
+    board.legal_moves().into_iter().map(|col| {
+        let mut next = *board;
+        next.play(col);
+        let score = if next.is_win() {
+            WIN_SCORE - next.moves() as i32
+        } else {
+            -negamax(&next, depth.saturating_sub(1), -WIN_SCORE, WIN_SCORE, &mut nodes, &mut || true)
+                .expect("the search is never stopped")
+        };
+        (col, score)
+    }).collect()
+}
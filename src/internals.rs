@@ -0,0 +1,26 @@
+// ベンチマーク (benches/) から探索の内部を直接測るための入口。API としては公開しない。
+use crate::tt::{self, Bound, HashFunction, TableBackend, TranspositionTable};
+
+// TT の添字を求めるハッシュ
+#[inline(always)]
+pub fn hash_key(hash: HashFunction, key: u64, index_mask: usize) -> usize { tt::hash_key(hash, key, index_mask) }
+
+// ヒープに確保した TT
+pub struct Table(TranspositionTable);
+
+impl Table {
+    pub fn new(entries: usize) -> Self {
+        Self(TranspositionTable::new(entries, &TableBackend::Heap).expect("heap allocation does not fail"))
+    }
+
+    #[inline(always)]
+    pub fn store(&self, key: u64, score: i8, best_col: u32, depth: u32) {
+        self.0.store(key, score, Bound::Exact, best_col, depth)
+    }
+
+    // (評価値, 最善手の列)
+    #[inline(always)]
+    pub fn lookup(&self, key: u64) -> Option<(i8, u32)> {
+        self.0.lookup(key).map(|(score, _, col)| (score, col))
+    }
+}
@@ -0,0 +1,99 @@
+use crate::bits::Bits;
+use crate::board::Board;
+
+// Allis (VICTOR) の知識ルールによる静的な証明。探索せずに「手番でない側は負けない」ことを示す。
+//
+// 手番でない側 (follower) は次の戦略を取る。
+// - Claimeven: 空きマスが偶数の列では、相手が打った直後に同じ列の上に打つ。
+//   follower はその列の空きマスのうち下から 2, 4, ... 番目を、相手は 1, 3, ... 番目を得る。
+// - Baseinverse: 空きマスが奇数の列は 2 列ずつ組にし、相手が一方の列の一番下の空きマスに打ったら、
+//   もう一方の列の一番下の空きマスに打つ。その後は残りの空きマス（偶数個）で Claimeven を続ける。
+//   組の 2 マスはどちらを得るか分からないが、相手が両方を得ることはない。
+// 相手が得るかもしれないマス（と相手の石）だけで並べられる線が、どれも組の 2 マスを両方含むなら、
+// 相手は最後まで並べられず、follower は引き分け以上になる。
+
+// 組にする奇数列の数の上限。組み方は (n - 1)!! 通り、それぞれ 2^(n/2) 通りを調べる。
+const MAX_ODD_COLUMNS: usize = 4;
+
+// 手番でない側が Claimeven と Baseinverse で引き分け以上を確保できるか（手番側の評価値が 0 以下か）
+pub(crate) fn follower_holds<B: Bits>(board: &Board<B>) -> bool {
+    let (position, mask) = board.stones();
+    let empty = board.board_mask() & !mask;
+    let possible = board.possible();
+    // 手番側（follower の相手）が得るかもしれないマス
+    let mut reachable = position;
+    let mut odd_bases = [B::ZERO; MAX_ODD_COLUMNS];
+    let mut n_odd = 0;
+    for col in 0..board.width() {
+        let column = empty & board.column_mask(col);
+        if column == B::ZERO { continue; }
+        let base = possible & column;
+        // 一番下の空きマスから 1 つおきのマス（下から 1, 3, 5, ... 番目）
+        let mut alternate = B::ZERO;
+        let mut square = base;
+        while square & column != B::ZERO {
+            alternate |= square;
+            square = square << 2;
+        }
+        if column.count_ones().is_multiple_of(2) {
+            reachable |= alternate;
+        } else {
+            if n_odd == MAX_ODD_COLUMNS { return false; }
+            odd_bases[n_odd] = base;
+            n_odd += 1;
+            // 一番下は組の相手次第、その上は下から 2, 4, ... 番目を相手が得る
+            reachable |= base | (column & !alternate);
+        }
+    }
+    if !n_odd.is_multiple_of(2) { return false; }
+    any_pairing(board, reachable, &mut odd_bases[..n_odd], &mut [(B::ZERO, B::ZERO); MAX_ODD_COLUMNS / 2], 0)
+}
+
+// bases を 2 つずつ組にする組み方のどれかで、相手の線がすべて組の両方を含むか
+fn any_pairing<B: Bits>(board: &Board<B>, reachable: B, bases: &mut [B], pairs: &mut [(B, B); MAX_ODD_COLUMNS / 2], n_pairs: usize) -> bool {
+    if bases.is_empty() { return pairs_hold(board, reachable, &pairs[..n_pairs]); }
+    for i in 1..bases.len() {
+        bases.swap(1, i);
+        pairs[n_pairs] = (bases[0], bases[1]);
+        let holds = any_pairing(board, reachable, &mut bases[2..], pairs, n_pairs + 1);
+        bases.swap(1, i);
+        if holds { return true; }
+    }
+    false
+}
+
+// 組ごとにどちらのマスを相手が得ても、相手の線ができないか
+fn pairs_hold<B: Bits>(board: &Board<B>, reachable: B, pairs: &[(B, B)]) -> bool {
+    (0..1u32 << pairs.len()).all(|choice| {
+        let taken = pairs.iter().enumerate().fold(reachable, |squares, (k, &(a, b))| {
+            squares & !if choice >> k & 1 == 0 { a } else { b }
+        });
+        !board.has_line(taken)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Solver;
+
+    // 乱数で打った終盤の局面で、知識ルールを使っても使わなくても評価値が同じで、follower_holds なら 0 以下
+    #[test]
+    fn same_scores_with_and_without_knowledge() {
+        let with = Solver::new(1 << 16).with_knowledge_depth(0);
+        let without = Solver::new(1 << 16).with_knowledge_depth(u32::MAX);
+        let mut rng = 0x9e37_79b9_7f4a_7c15;
+        let mut held = 0;
+        for _ in 0..100 {
+            let (board, _) = Board::new().random_unfinished(30, &mut rng).unwrap();
+            if board.can_win_next() { continue; }
+            let score = without.solve(board);
+            assert_eq!(with.solve(board), score, "{:?}", board.to_grid());
+            if follower_holds(&board) {
+                assert!(score <= 0, "{:?}", board.to_grid());
+                held += 1;
+            }
+        }
+        assert!(held > 0);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::bits::Bits;
+use crate::board::{splitmix64, Board};
+use crate::heuristic;
+use crate::solver::Solver;
+
+// 対局相手としての強さ。同じ seed なら同じ局面で同じ手を選ぶ。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Level {
+    // 着手可能な手から一様に選ぶ
+    Random,
+    // 静的評価で 2 手先まで読む
+    Easy,
+    // 静的評価で 7 手先まで読み、ときどき（MEDIUM_MISTAKES）2 番目に良い手を打つ
+    Medium,
+    // 解いた評価値で選び、ときどき（HARD_MISTAKES）2 番目に良い手を打つ
+    Hard,
+    // 常に最善手
+    #[default]
+    Perfect,
+}
+
+const EASY_DEPTH: u32 = 2;
+const MEDIUM_DEPTH: u32 = 7;
+// 最善でない手を打つ確率
+const MEDIUM_MISTAKES: f64 = 0.25;
+const HARD_MISTAKES: f64 = 0.1;
+
+impl Level {
+    pub const ALL: [Level; 5] = [Level::Random, Level::Easy, Level::Medium, Level::Hard, Level::Perfect];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Random => "random",
+            Level::Easy => "easy",
+            Level::Medium => "medium",
+            Level::Hard => "hard",
+            Level::Perfect => "perfect",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.name() == name)
+    }
+
+    // 手を選ぶのに Solver で解くか（序盤では時間がかかる）
+    pub fn uses_solver(self) -> bool { matches!(self, Level::Hard | Level::Perfect) }
+}
+
+// Level に従って手を選ぶ対局相手
+#[derive(Clone, Debug)]
+pub struct Player {
+    level: Level,
+    rng: u64,
+}
+
+impl Player {
+    pub fn new(level: Level, seed: u64) -> Self {
+        Self { level, rng: seed }
+    }
+
+    pub fn level(&self) -> Level { self.level }
+
+    fn next_random(&mut self) -> u64 { splitmix64(&mut self.rng) }
+
+    // [0, 1) の一様乱数
+    fn next_unit(&mut self) -> f64 {
+        (self.next_random() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // 手番側の手（0 始まりの列番号）を選ぶ。Hard と Perfect は solver で解き、
+    // 解いている途中で solver が中断されたら TT に残っている手を返す。
+    // 終局済みの局面に対して呼ぶと panic する。
+    pub fn choose_move<B: Bits>(&mut self, board: &Board<B>, solver: &Solver) -> u32 {
+        assert!(!board.is_over(), "choose_move called on a finished game");
+        match self.level {
+            Level::Random => {
+                let cols = board.legal_moves();
+                cols[(self.next_random() % cols.len() as u64) as usize]
+            }
+            Level::Easy => self.pick(heuristic::score_moves(board, EASY_DEPTH), 0.0),
+            Level::Medium => self.pick(heuristic::score_moves(board, MEDIUM_DEPTH), MEDIUM_MISTAKES),
+            Level::Hard => {
+                let scores: Vec<(u32, i32)> = solver.move_scores(board).into_iter().enumerate()
+                    .filter_map(|(col, score)| score.map(|s| (col as u32, s as i32))).collect();
+                if solver.is_aborted() { return solver.hint_move(board); }
+                self.pick(scores, HARD_MISTAKES)
+            }
+            Level::Perfect => {
+                let (col, _) = solver.best_move(board);
+                if solver.is_aborted() { solver.hint_move(board) } else { col }
+            }
+        }
+    }
+
+    // 評価値の最も高い手（同点なら乱数で）を選ぶ。確率 mistakes で、最善より低い評価値のうち最も高い手を選ぶ。
+    fn pick(&mut self, scores: Vec<(u32, i32)>, mistakes: f64) -> u32 {
+        let best = scores.iter().map(|&(_, s)| s).max().expect("unfinished game has a legal move");
+        let mut target = best;
+        if mistakes > 0.0 && self.next_unit() < mistakes
+            && let Some(second) = scores.iter().map(|&(_, s)| s).filter(|&s| s < best).max() {
+            target = second;
+        }
+        let cols: Vec<u32> = scores.iter().filter(|&&(_, s)| s == target).map(|&(col, _)| col).collect();
+        cols[(self.next_random() % cols.len() as u64) as usize]
+    }
+}
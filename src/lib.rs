@@ -0,0 +1,76 @@
+mod bits;
+mod board;
+mod book;
+mod counter;
+#[cfg(feature = "database")]
+mod database;
+pub mod cube;
+pub mod explain;
+pub mod game;
+pub mod hashstats;
+pub mod heuristic;
+#[doc(hidden)]
+pub mod internals;
+mod knowledge;
+mod level;
+mod mcts;
+mod memory;
+mod misere;
+mod network;
+mod numa;
+mod ordering;
+mod outcome;
+pub mod perft;
+mod pns;
+pub mod popout;
+mod progress;
+#[cfg(feature = "redis")]
+mod redis_cache;
+mod remote;
+mod review;
+#[cfg(all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"))]
+mod simd;
+mod solver;
+mod trace;
+mod tt;
+#[cfg(feature = "serde")]
+mod wire;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "zobrist")]
+mod zobrist;
+
+pub use bits::Bits;
+pub use board::{splitmix64, Board, Board128, Cell, GameState, GridError, MoveList, Side, CONNECT, HEIGHT, MAX_CONNECT, MAX_HEIGHT, MAX_WIDTH, SIZE, WIDTH};
+pub use book::{Book, BookEntry};
+pub use cube::Cube;
+#[cfg(feature = "database")]
+pub use database::{DbHeader, DbRecord, PositionDb};
+pub use game::Game;
+pub use level::{Level, Player};
+pub use mcts::{Mcts, MoveStats, DEFAULT_EXPLORATION, DEFAULT_MCTS_SOLVER_DEPTH};
+pub use memory::{auto_table_entries, available_memory};
+pub use misere::Misere;
+pub use network::{encode, Evaluation, Evaluator, NetworkInput};
+#[cfg(feature = "onnx")]
+pub use network::OnnxEvaluator;
+pub use numa::{online_nodes, pin_worker, NumaPolicy};
+pub use outcome::{verify_outcome, Outcome, ScoreFormat};
+pub use progress::{Progress, ProgressMonitor, ProgressSink};
+#[cfg(feature = "redis")]
+pub use redis_cache::RedisCache;
+pub use remote::{RemoteCache, RemoteStats};
+pub use review::{analyze_moves, outcome_name, review_game, MoveReview, MoveScore, DEFAULT_REFUTATION_PLIES};
+pub use solver::{Algorithm, SearchScope, SearchStats, SolveResult, Solver, DEFAULT_STACK_SIZE};
+pub use trace::{SearchTrace, TraceFormat, TraceRecord, TRACE_MAGIC};
+pub use tt::{Bound, HashFunction, HugePages, TableBackend, TableStats, DEFAULT_TABLE_ENTRIES, ENTRY_BYTES};
+#[cfg(feature = "wasm")]
+pub use wasm::WASM_TABLE_ENTRIES;
+#[cfg(feature = "serde")]
+pub use wire::{CheckpointHeader, CheckpointTask, ServeMessage};
+
+// wasm と C の API で、不正な手順や終局済みの局面を渡されたときの戻り値
+#[cfg(any(feature = "wasm", feature = "ffi"))]
+pub const INVALID_POSITION: i32 = i32::MIN;
@@ -0,0 +1,126 @@
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+
+// 調べもの用のログ（--log-level, --log-format）。tracing に流し、tracing-subscriber の fmt か json の層で
+// 結果の出力（stdout）とは別に、いつも stderr に 1 行ずつ書く。既定は off で、何も書かない。
+// event! は名前をメッセージにした key=value の並び、span! は始まりと終わり（経過時間付き）を書き、
+// その間の event に span の並びを付ける。info! と warning! / error! (src/output.rs) も target "console" の event になる。
+//
+//   text: 2026-01-02T03:04:05.678912Z  INFO solve:root{column=4}: connect4_solver: root.result column=4 score=1
+//   json: {"timestamp":"2026-01-02T03:04:05.678912Z","level":"INFO","message":"root.result","column":4,"score":1,"spans":[..]}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 6] = [LogLevel::Off, LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug, LogLevel::Trace];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.name() == name)
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Off as u8);
+
+pub fn init(level: LogLevel, json: bool) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+    let filter = match level {
+        LogLevel::Off => return,
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    };
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(io::stderr).with_ansi(io::stderr().is_terminal()).with_span_events(FmtSpan::NEW | FmtSpan::CLOSE);
+    let layer = if json { layer.json().flatten_event(true).with_span_list(true).boxed() } else { layer.boxed() };
+    tracing_subscriber::registry().with(layer.with_filter(filter)).init();
+}
+
+pub fn enabled(level: LogLevel) -> bool { level != LogLevel::Off && level as u8 <= LEVEL.load(Ordering::Relaxed) }
+
+// ログの値を tracing に渡せる型にする。時間は秒の小数。
+pub trait ToField {
+    type Value: tracing::Value;
+    fn to_field(&self) -> Self::Value;
+}
+
+macro_rules! field_as {
+    ($as:ty: $($t:ty),*) => { $(impl ToField for $t {
+        type Value = $as;
+        fn to_field(&self) -> $as { *self as $as }
+    })* };
+}
+field_as!(i64: i8, i32, i64);
+field_as!(u64: u16, u32, u64, usize);
+field_as!(f64: f64);
+
+impl ToField for bool {
+    type Value = bool;
+    fn to_field(&self) -> bool { *self }
+}
+
+impl ToField for Duration {
+    type Value = f64;
+    fn to_field(&self) -> f64 { self.as_secs_f64() }
+}
+
+impl ToField for str {
+    type Value = String;
+    fn to_field(&self) -> String { self.to_string() }
+}
+
+impl ToField for String {
+    type Value = String;
+    fn to_field(&self) -> String { self.clone() }
+}
+
+impl<T: ToField + ?Sized> ToField for &T {
+    type Value = T::Value;
+    fn to_field(&self) -> T::Value { (**self).to_field() }
+}
+
+// None のフィールドは書かない
+impl<T: ToField> ToField for Option<T> {
+    type Value = Option<T::Value>;
+    fn to_field(&self) -> Self::Value { self.as_ref().map(ToField::to_field) }
+}
+
+// level は tracing::Level の定数名 (ERROR, WARN, INFO, DEBUG, TRACE)
+macro_rules! event {
+    ($level:ident, $name:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        ::tracing::event!(::tracing::Level::$level, $($key = $crate::logging::ToField::to_field(&$value),)* "{}", $name)
+    };
+}
+pub(crate) use event;
+
+// 始まりと drop での終わり（経過時間付き）を level で書く span に入る
+macro_rules! span {
+    ($level:ident, $name:literal $(, $key:ident = $value:expr)* $(,)?) => {
+        ::tracing::span!(::tracing::Level::$level, $name $(, $key = $crate::logging::ToField::to_field(&$value))*).entered()
+    };
+}
+pub(crate) use span;
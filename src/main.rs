@@ -1,207 +1,1479 @@
+use connect4_solver::{Algorithm, auto_table_entries, Evaluator, explain, hashstats, popout, Bits, Board, Book, Cube, Game, Mcts, Misere, HashFunction, HugePages, NumaPolicy, online_nodes, PositionDb, outcome_name, perft, review_game, analyze_moves, Outcome, MoveScore, DEFAULT_REFUTATION_PLIES, pin_worker, Progress, ProgressMonitor, RedisCache, SearchStats, SearchTrace, Solver, TableStats, TableBackend, ENTRY_BYTES};
 use rayon::prelude::*;
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::io::{IsTerminal, Read, Write};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Instant, Duration};
 use std::collections::HashMap;
 
-const WIDTH: u32 = 7;
-const HEIGHT: u32 = 6;
-const SIZE: u32 = WIDTH * HEIGHT;
-// エントリ数を 2^30 に設定。16byte * 2^30 = 16GB。
-// 64GB環境で余裕を持って 32GB 使うなら 2 * (1 << 30) = 2^31 ですが、
-// 安全のため 1 << 31 ではなく明示的な計算式にします。
-const TABLE_ENTRIES: usize = 2147483648; // これで物理32GB確保
-const INDEX_MASK: usize = TABLE_ENTRIES - 1;
+mod bench;
+mod certificate;
+mod checkpoint;
+mod cli;
+mod cluster;
+mod config;
+mod engine;
+mod export;
+mod logging;
+mod output;
+mod parquet;
+mod play;
+mod prooftree;
+mod puzzle;
+mod selfplay;
+mod server;
+mod signal;
+mod tournament;
+mod tui;
+mod websocket;
 
-struct Entry {
-    key: AtomicU64,
-    data: AtomicU64,
+use checkpoint::Checkpoint;
+use cli::{Command, Options, TableSize};
+use logging::{event, span};
+use output::{error, info, warning, JsonObject};
+use parquet::ResultRow;
+
+// --trace の書き出し先。Solver をいくつ作っても 1 つのファイルに書く。
+static TRACE: OnceLock<Arc<SearchTrace>> = OnceLock::new();
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let (command, opts) = match cli::parse(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // 探索は Solver ごとのプール（new_solver）で動く。グローバルプールは perft や MCTS のプレイアウト、
+    // TT の初期化などに使うので、スレッド数だけ揃えておく。
+    let _ = rayon::ThreadPoolBuilder::new().num_threads(opts.thread_count()).build_global();
+
+    output::set_json(opts.json);
+    logging::init(opts.log_level, opts.log_json);
+    output::set_color(!opts.json && opts.color.unwrap_or_else(|| {
+        std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+    }));
+    signal::install();
+    // 64 ビットに収まらない盤だけ u128 の盤で解く（標準の盤は u64 の方が速い）
+    // 立体四目並べは盤の大きさのオプションを使わない（parse で solve 以外は弾いている）
+    if opts.cube {
+        match command {
+            Command::Solve(moves) => solve_cube_position(&opts, &moves),
+            _ => println!("{}", cli::USAGE),
+        }
+    } else if Board::needs_wide(opts.width, opts.height, opts.connect) { run::<u128>(command, &opts) } else { run::<u64>(command, &opts) }
+    exit(if signal::interrupted() { signal::INTERRUPTED_EXIT_CODE } else { 0 });
+}
+
+// --trace のバッファを書き出してから終了する。parse の後の終了は全てここを通す（途中で抜けてもトレースが切れないように）。
+fn exit(code: i32) -> ! {
+    if let Some(trace) = TRACE.get() && let Err(e) = trace.finish() {
+        error!("failed to write trace: {}", e);
+        std::process::exit(1);
+    }
+    std::process::exit(code);
+}
+
+fn run<B: Bits>(command: Command, opts: &Options) {
+    match command {
+        Command::FirstMoves => solve_first_moves::<B>(opts),
+        Command::Solve(moves) if opts.popout => solve_popout_position::<B>(opts, &moves),
+        Command::Solve(moves) if opts.misere => solve_misere_position::<B>(opts, &moves),
+        Command::Solve(moves) if opts.tui => solve_position_tui::<B>(opts, &moves),
+        Command::Solve(moves) => solve_position::<B>(opts, &moves),
+        Command::SolveBatch(path) => solve_batch::<B>(opts, &path),
+        Command::Serve => {
+            if let Err(e) = server::run(new_solver(opts), empty_board::<B>(opts), &opts.bind, opts.port) {
+                error!("server failed: {}", e);
+                exit(1);
+            }
+        }
+        Command::Coordinator { depth, root } => coordinate::<B>(opts, depth, &root),
+        Command::Worker(addr) => {
+            let solver = new_solver(opts);
+            if let Err(e) = cluster::work(&solver, empty_board::<B>(opts), opts.weak, &addr) {
+                error!("worker failed: {}", e);
+                exit(1);
+            }
+        }
+        Command::Engine => {
+            output::info_to_stderr();
+            let solver = new_solver(opts);
+            engine::run(Arc::clone(&solver), empty_board::<B>(opts), mcts_factory(opts, solver), opts.level, opts.seed);
+        }
+        Command::BookBuild { depth, out, root } => build_book::<B>(opts, depth, &out, &root),
+        Command::Bench { set, dir } => run_bench::<B>(opts, &set, &dir),
+        Command::Perft { depth, root } => run_perft::<B>(opts, depth, &root),
+        Command::DbBuild { depth, path, root } => build_database::<B>(opts, depth, &path, &root),
+        Command::DbQuery { path, moves } => query_database::<B>(opts, &path, &moves),
+        Command::DbDump { path } => dump_database(opts, &path),
+        Command::HashStats { samples } => run_hashstats::<B>(opts, samples),
+        Command::Mcts(moves) => run_mcts::<B>(opts, &moves),
+        Command::Hint(moves) => hint_position::<B>(opts, &moves),
+        Command::Analyze(moves) => analyze_position::<B>(opts, &moves),
+        Command::Review(moves) => review_position::<B>(opts, &moves),
+        Command::SelfPlay { games, out, root } => run_selfplay::<B>(opts, games, &out, &root),
+        Command::Export { moves, out } => export_position::<B>(opts, &moves, &out),
+        Command::ProofTree { moves, depth, out } => write_proof_tree::<B>(opts, &moves, depth, &out),
+        Command::Certificate { moves, out } => write_certificate::<B>(opts, &moves, &out),
+        Command::Verify(path) => verify_certificate(&path),
+        Command::Puzzles { count, win_in } => {
+            let solver = new_solver(opts);
+            let _search = solver.begin_search();
+            puzzle::run(&solver, empty_board::<B>(opts), count, win_in, opts.seed);
+            save_table(&solver, opts);
+        }
+        Command::Match { a, b, games, root } => {
+            let root: Board<B> = parse_board(opts, &root);
+            let solver = new_solver(opts);
+            let tally = tournament::run(&solver, root, a, b, games, opts.opening_plies, opts.seed, mcts_factory(opts, Arc::clone(&solver)));
+            tournament::report(a, b, &tally);
+            save_table(&solver, opts);
+        }
+        Command::Play(moves) => {
+            output::info_to_stderr();
+            play::run(new_solver(opts), empty_board::<B>(opts), &moves, opts.human_first, opts.level.unwrap_or_default(), opts.seed,
+                opts.max_time.unwrap_or(play::DEFAULT_MOVE_TIME));
+        }
+        Command::Help => println!("{}", cli::USAGE),
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{}GB", b >> 30),
+        b if b >= 1 << 20 => format!("{}MB", b >> 20),
+        b => format!("{}KB", b >> 10),
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-struct Board {
-    position: u64,
-    mask: u64,
-    moves: u32,
+// --tt-size のエントリ数（丸める前）
+fn table_entries(opts: &Options) -> usize {
+    match opts.tt_size {
+        TableSize::Entries(n) => n,
+        TableSize::Auto => match auto_table_entries(opts.tt_fraction) {
+            Some(n) => n,
+            None => {
+                error!("could not determine available memory; use an explicit --tt-size");
+                exit(1);
+            }
+        },
+    }
 }
 
-impl Board {
-    fn new() -> Self { Self { position: 0, mask: 0, moves: 0 } }
-    #[inline(always)]
-    fn can_play(&self, col: u32) -> bool {
-        (self.mask & (1 << ((col * (HEIGHT + 1)) + HEIGHT - 1))) == 0
+fn new_solver(opts: &Options) -> Arc<Solver> {
+    let entries = table_entries(opts);
+    // 証明数探索では --tt-size の分を TT と (pn, dn) の表で半分ずつ使う
+    let pns = opts.algorithm == Algorithm::ProofNumber;
+    let entries = if pns { Solver::round_entries(entries) / 2 } else { entries };
+    let bytes = Solver::round_entries(entries) * ENTRY_BYTES;
+    let backend = opts.table_backend();
+    match &backend {
+        TableBackend::Heap => info!("Allocating and FORCE-INITIALIZING {} Table...", format_bytes(bytes)),
+        TableBackend::Mmap { .. } => info!("Mapping {} Table...", format_bytes(bytes)),
+        TableBackend::File { path, .. } => info!("Mapping {} Table from {}...", format_bytes(bytes), path.display()),
+    }
+    let start_init = Instant::now();
+
+    let mut solver = match Solver::with_backend(entries, &backend) {
+        Ok(solver) => {
+            let solver = solver.with_weak(opts.weak).with_node_limit(opts.max_nodes.unwrap_or(usize::MAX))
+                .with_time_limit(opts.max_time)
+                .with_min_store_depth(opts.tt_min_depth).with_algorithm(opts.algorithm);
+            let solver = if pns { solver.with_proof_table_bytes(bytes) } else { solver };
+            let solver = match opts.knowledge_depth {
+                Some(depth) => solver.with_knowledge_depth(depth),
+                None => solver,
+            };
+            match opts.parallel_depth {
+                Some(depth) => solver.with_parallel_depth(depth),
+                None => solver,
+            }
+        }
+        Err(e) => {
+            error!("failed to allocate table: {}", e);
+            exit(1);
+        }
+    };
+    // 立体四目並べは 4x4x4（16 本の棒に高さ 4）
+    let (width, height, connect) = if opts.cube { (connect4_solver::cube::PEGS, 4, 4) } else { (opts.width, opts.height, opts.connect) };
+    // --tt-file の既存の TT は、盤の大きさもハッシュ関数もファイルに合わせる
+    let checked = solver.with_geometry(width, height, connect).and_then(|solver| match opts.tt_hash {
+        Some(hash) => solver.with_hash_function(hash),
+        None => Ok(solver),
+    });
+    solver = match checked {
+        Ok(solver) => solver,
+        Err(e) => {
+            error!("{}: {}", opts.tt_file.as_deref().unwrap_or("table file"), e);
+            exit(1);
+        }
+    };
+    if let Some(path) = &opts.book {
+        let book = match Book::load(path) {
+            Ok(book) => book,
+            Err(e) => {
+                error!("failed to load book from {}: {}", path, e);
+                exit(1);
+            }
+        };
+        if book.is_weak() && !opts.weak {
+            error!("{} is a weak (win/draw/loss) book; pass --weak to use it", path);
+            exit(1);
+        }
+        info!("Book loaded from {}: {} positions up to depth {}.", path, book.len(), book.depth());
+        solver = solver.with_book(Arc::new(book));
+    }
+    if let Some(addr) = &opts.redis {
+        let cache = match RedisCache::connect(addr, opts.redis_password.as_deref(), opts.width, opts.height, opts.connect) {
+            Ok(cache) => cache,
+            Err(e) => {
+                error!("failed to connect to Redis at {}: {}", addr, e);
+                exit(1);
+            }
+        };
+        info!("Sharing positions with {} moves or more left through Redis at {} ({}).", opts.redis_min_depth, addr, cache.namespace());
+        solver = solver.with_remote_cache(Arc::new(cache), opts.redis_min_depth);
+    }
+    if let Some(path) = &opts.trace {
+        let trace = TRACE.get_or_init(|| {
+            match SearchTrace::create(path, opts.trace_format, opts.trace_depth, opts.width, opts.height) {
+                Ok(trace) => Arc::new(trace),
+                Err(e) => {
+                    error!("failed to create trace {}: {}", path, e);
+                    exit(1);
+                }
+            }
+        });
+        info!("Tracing positions with up to {} discs to {} ({}).", opts.trace_depth, path, opts.trace_format.name());
+        solver = solver.with_trace(Arc::clone(trace));
+    }
+    // 探索スレッドのプール。--numa ではワーカーをノードに順に固定する。
+    let numa = opts.numa;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(opts.thread_count())
+        .stack_size(opts.stack_size)
+        .start_handler(move |index| if numa != NumaPolicy::Off { let _ = pin_worker(index); })
+        .build();
+    let solver = match pool {
+        Ok(pool) => Arc::new(solver.with_thread_pool(Arc::new(pool))),
+        Err(e) => {
+            error!("failed to start {} search threads: {}", opts.thread_count(), e);
+            exit(1);
+        }
+    };
+
+    if pns {
+        info!("Table initialized in {:?} (+ {} proof-number table, allocated on first use). Memory should be occupied.",
+            start_init.elapsed(), format_bytes(solver.proof_table_bytes()));
+    } else {
+        info!("Table initialized in {:?}. Memory should be occupied.", start_init.elapsed());
     }
-    #[inline(always)]
-    fn play(&mut self, col: u32) {
-        self.position ^= self.mask;
-        self.mask |= self.mask + (1 << (col * (HEIGHT + 1)));
-        self.moves += 1;
+    info!("Parallel depth: {} ({} threads).", solver.parallel_depth(), solver.threads());
+    if let TableBackend::Mmap { huge_pages, .. } = backend
+        && huge_pages != HugePages::Off {
+        info!("Huge pages: requested {:?}, using {:?}.", huge_pages, solver.table_huge_pages());
     }
-    #[inline(always)]
-    fn is_win(&self) -> bool {
-        let pos = self.position ^ self.mask;
-        let directions = [1, 7, 8, 9];
-        for &d in &directions {
-            let m = pos & (pos >> d);
-            if (m & (m >> (2 * d))) != 0 { return true; }
+    if let TableBackend::Mmap { numa, .. } = backend
+        && numa != NumaPolicy::Off {
+        info!("NUMA: requested {:?}, using {:?} over {} node(s).", numa, solver.table_numa(), online_nodes().len());
+        if solver.table_numa() == NumaPolicy::Partition {
+            info!("Each node's threads probe only that node's 1/{} of the table.", online_nodes().len());
         }
-        false
     }
-    #[inline(always)]
-    fn key(&self) -> u64 { self.position + self.mask }
+
+    if let Some(path) = &opts.tt_load {
+        let start_load = Instant::now();
+        if let Err(e) = solver.load_table(path) {
+            error!("failed to load table from {}: {}", path, e);
+            exit(1);
+        }
+        info!("Table loaded from {} in {:?}.", path, start_load.elapsed());
+    }
+
+    // Ctrl-C では探索を中断するだけにして、途中経過の表示と TT の保存は通常の中断処理に任せる
+    signal::abort_on_interrupt(&solver);
+    solver
+}
+
+fn save_table(solver: &Solver, opts: &Options) {
+    if let Some(path) = &opts.tt_save {
+        let start_save = Instant::now();
+        if let Err(e) = solver.save_table(path) {
+            error!("failed to save table to {}: {}", path, e);
+            exit(1);
+        }
+        info!("Table saved to {} in {:?}.", path, start_save.elapsed());
+    }
+}
+
+// 30 秒ごとに探索の進み具合を表示する
+fn monitor_progress(solver: &Arc<Solver>) -> ProgressMonitor {
+    ProgressMonitor::spawn(Arc::clone(solver), Duration::from_secs(30), |p: &Progress| {
+        if output::is_json() {
+            JsonObject::new("stats").num("nodes", p.nodes).num("nps", p.nps.round()).opt_num("column", p.root_move.map(|c| c + 1))
+                .num("tt_fill", p.table.fill).num("tt_hit_rate", p.table.hit_rate())
+                .num("tt_collision_rate", p.table.collision_rate()).time("seconds", p.elapsed).emit();
+            return;
+        }
+        event!(DEBUG, "search.progress", nodes = p.nodes, nps = p.nps, column = p.root_move.map(|c| c + 1),
+            tt_fill = p.table.fill, seconds = p.elapsed);
+        let root = p.root_move.map_or(String::new(), |col| format!(" | Column: {}", col + 1));
+        println!("[Stats] Speed: {:6.2} MNPS | Total: {:11} M | {}{} | Time: {:?}",
+            p.nps / 1_000_000.0, p.nodes / 1_000_000, format_table_stats(&p.table), root, p.elapsed);
+    })
 }
 
-#[inline(always)]
-fn hash_key(mut x: u64) -> usize {
-    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
-    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
-    x = x ^ (x >> 31);
-    (x as usize) & INDEX_MASK
+fn table_stats_json(stats: &TableStats) -> JsonObject {
+    let mut obj = JsonObject::new("table");
+    obj.num("probes", stats.probes).num("hits", stats.hits).num("collisions", stats.collisions).num("fill", stats.fill);
+    obj
 }
 
-struct Solver {
-    table: Arc<Vec<Entry>>,
-    nodes: Arc<AtomicUsize>,
+fn log_table_stats(stats: &TableStats) {
+    event!(INFO, "tt.stats", probes = stats.probes, hits = stats.hits, collisions = stats.collisions, fill = stats.fill,
+        hit_rate = stats.hit_rate(), collision_rate = stats.collision_rate());
 }
 
-impl Solver {
-    fn store(&self, key: u64, score: i8, best_col: u32) {
-        let idx = hash_key(key);
-        let data = ((best_col as u64) << 24) | ((score as u8 as u64) << 16);
-        self.table[idx].key.store(key, Ordering::Relaxed);
-        self.table[idx].data.store(data, Ordering::Relaxed);
+fn format_table_stats(stats: &TableStats) -> String {
+    format!("TT: fill {:5.1}% hit {:5.1}% collision {:5.1}%",
+        stats.fill * 100.0, stats.hit_rate() * 100.0, stats.collision_rate() * 100.0)
+}
+
+// --width/--height/--connect の空の盤。作れなければ（B に収まらなければ）終了する。
+fn empty_board<B: Bits>(opts: &Options) -> Board<B> {
+    opts.empty_board().unwrap_or_else(|e| {
+        error!("{}", e);
+        exit(2);
+    })
+}
+
+fn start_board<B: Bits>(opts: &Options) -> Board<B> {
+    opts.start_board().unwrap_or_else(|e| {
+        error!("{}", e);
+        exit(2);
+    })
+}
+
+fn parse_board<B: Bits>(opts: &Options, moves: &str) -> Board<B> {
+    let mut board = start_board(opts);
+    match board.play_moves(moves) {
+        Ok(()) => board,
+        Err(e) => {
+            error!("{}", e);
+            exit(2);
+        }
     }
+}
 
-    fn lookup(&self, key: u64) -> (Option<i8>, Option<u32>) {
-        let idx = hash_key(key);
-        if self.table[idx].key.load(Ordering::Relaxed) == key {
-            let data = self.table[idx].data.load(Ordering::Relaxed);
-            return (Some((data >> 16) as u8 as i8), Some((data >> 24) as u32));
+fn solve_position<B: Bits>(opts: &Options, moves: &str) {
+    let board: Board<B> = parse_board(opts, moves);
+    if board.is_over() {
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).num("score", shown(opts, &board, board_score(&board))).bool("game_over", true).emit();
+        } else {
+            println!("Position: {} | Score: {} | Game over", moves, shown(opts, &board, board_score(&board)));
         }
-        (None, None)
+        return;
     }
 
-    fn solve(&self, board: Board, mut alpha: i8, mut beta: i8, p_depth: u32) -> i8 {
-        self.nodes.fetch_add(1, Ordering::Relaxed);
-        if board.moves == SIZE { return 0; }
-        let key = board.key();
-        let (cached_score, best_col) = self.lookup(key);
-        if let Some(score) = cached_score {
-            if alpha >= beta { return score; }
+    let solver = new_solver(opts);
+    let _progress = monitor_progress(&solver);
+    // --max-time と --max-nodes はコマンド全体を 1 回の探索として数える
+    let _search = solver.begin_search();
+    let _span = span!(INFO, "search", position = moves, weak = opts.weak);
+    let start = Instant::now();
+
+    let (lo, hi) = solver.solve_with_bounds(board, |lo, hi| {
+        event!(DEBUG, "search.bounds", min = lo, max = hi, seconds = start.elapsed());
+        if output::is_json() {
+            JsonObject::new("bounds").str("position", moves).num("min", lo).num("max", hi).time("seconds", start.elapsed()).emit();
+        } else if !opts.weak {
+            println!(">>> BOUNDS Position {}: score ∈ [{:+}, {:+}] (Time: {:?})", moves, lo, hi, start.elapsed());
         }
+    });
+    let stopped = || {
+        event!(WARN, "search.stopped", min = lo, max = hi, nodes = solver.nodes());
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).opt_num("score", None::<i8>).num("min", lo).num("max", hi)
+                .num("best_move", solver.hint_move(&board) + 1).num("nodes", solver.nodes())
+                .time("seconds", start.elapsed()).bool("stopped", true).emit();
+        } else {
+            println!("Position: {} | Score: [{:+}, {:+}] | Best move: {} | Nodes: {} | Time: {:?} (stopped)",
+                moves, lo, hi, solver.hint_move(&board) + 1, solver.nodes(), start.elapsed());
+        }
+        save_table(&solver, opts);
+    };
+    if solver.is_aborted() { return stopped(); }
+    // 評価値が決まった後も、最善手と読み筋を求める探索で上限に達しうる。そのときは途中で止まった結果として出す。
+    let (best_col, score) = solver.best_move(&board);
+    if solver.is_aborted() { return stopped(); }
+    let time = start.elapsed();
+    let pv = solver.principal_variation(board);
+    if solver.is_aborted() { return stopped(); }
+    let stats = solver.table_stats();
+    event!(INFO, "search.result", score = score, best_move = best_col + 1, nodes = solver.nodes(), seconds = time);
+    log_table_stats(&stats);
+    // --verify-distance: 評価値から求めた結果と、読み筋を終局まで打った結果を比べる
+    let verified = opts.verify_distance.then(|| check_distance(&board, score, &pv));
+    if output::is_json() {
+        let mut obj = JsonObject::new("result");
+        obj.str("position", moves).num("score", shown(opts, &board, score)).num("min", lo).num("max", hi)
+            .num("best_move", best_col + 1).num("nodes", solver.nodes()).time("seconds", time).bool("stopped", false)
+            .columns("pv", &pv);
+        if let Some((claimed, ok)) = verified {
+            obj.str("outcome", claimed.name()).opt_num("plies", claimed.plies()).bool("verified", ok);
+        }
+        obj.emit();
+        table_stats_json(&stats).emit();
+        if let Some(remote) = solver.remote_stats() {
+            JsonObject::new("redis").num("probes", remote.probes).num("hits", remote.hits).num("stores", remote.stores).emit();
+        }
+    } else {
+        println!("Position: {} | Score: {} | Best move: {} | Nodes: {} | Time: {:?}",
+            moves, shown(opts, &board, score), best_col + 1, solver.nodes(), time);
+        println!("PV: {}", format_pv(&pv));
+        if let Some((claimed, ok)) = verified {
+            println!("Outcome: {} ({})", claimed, if ok { "verified by playing out the PV" } else { "NOT verified" });
+        }
+        println!("{} | Probes: {}", format_table_stats(&stats), stats.probes);
+        if let Some(remote) = solver.remote_stats() {
+            println!("Redis: hit {:5.1}% | Probes: {} | Stores: {}", remote.hit_rate() * 100.0, remote.probes, remote.stores);
+        }
+    }
+    save_table(&solver, opts);
+    if verified.is_some_and(|(_, ok)| !ok) { exit(1); }
+}
 
-        let mut order = [3, 2, 4, 1, 5, 0, 6];
-        if let Some(bc) = best_col {
-            if bc < WIDTH {
-                if let Some(pos) = order.iter().position(|&x| x == bc) {
-                    order.swap(0, pos);
-                }
+// 評価値 score から求めた結果と、line を終局まで打った結果が一致するか。一致しなければ stderr に書く。
+fn check_distance<B: Bits>(board: &Board<B>, score: i8, line: &[u32]) -> (Outcome, bool) {
+    let claimed = Outcome::from_score(board, score);
+    let played = Outcome::of_line(board, line);
+    if played != Some(claimed) {
+        let played = played.map_or("does not end the game".to_string(), |p| format!("ends in {}", p));
+        error!("score {} claims {} but the principal variation {}", score, claimed, played);
+    }
+    (claimed, played == Some(claimed))
+}
+
+// --tui: ダッシュボードを出しながら手ごとに解き、終わったら元の画面に戻して結果を表示する
+fn solve_position_tui<B: Bits>(opts: &Options, moves: &str) {
+    if !std::io::stdout().is_terminal() {
+        error!("--tui needs a terminal on stdout");
+        exit(2);
+    }
+    let board: Board<B> = parse_board(opts, moves);
+    if board.is_over() {
+        println!("Position: {} | Score: {} | Game over", moves, shown(opts, &board, board_score(&board)));
+        return;
+    }
+    let solver = new_solver(opts);
+    let _search = solver.begin_search();
+    let start = Instant::now();
+    let mut scores = vec![None; board.width() as usize];
+    {
+        let dashboard = match tui::Dashboard::start(&solver, &board, moves) {
+            Ok(dashboard) => dashboard,
+            Err(e) => {
+                error!("failed to set up the terminal: {}", e);
+                exit(1);
+            }
+        };
+        for col in board.legal_moves() {
+            dashboard.set_column(col, tui::ColumnState::Solving);
+            let mut next = board;
+            next.play(col);
+            let score = if !next.is_win() { -solver.solve(next) }
+                else if opts.weak { 1 } else { (board.size() + 1 - board.moves()) as i8 / 2 };
+            if solver.is_aborted() {
+                dashboard.set_column(col, tui::ColumnState::Stopped);
+                break;
             }
+            scores[col as usize] = Some(score);
+            dashboard.set_column(col, tui::ColumnState::Score(shown(opts, &board, score)));
+        }
+    }
+    let cols: Vec<String> = scores.iter().map(|s| s.map_or("-".to_string(), |s| shown(opts, &board, s).to_string())).collect();
+    if solver.is_aborted() {
+        println!("Position: {} | Scores: {} | Nodes: {} | Time: {:?} (stopped)", moves, cols.join(" "), solver.nodes(), start.elapsed());
+        save_table(&solver, opts);
+        return;
+    }
+    let score = scores.iter().flatten().copied().max().expect("an unfinished game has a legal move");
+    let best_col = board.column_order().find(|&col| scores[col as usize] == Some(score)).expect("the best score has a column");
+    let pv = solver.principal_variation(board);
+    let time = start.elapsed();
+    if solver.is_aborted() {
+        println!("Position: {} | Scores: {} | Nodes: {} | Time: {:?} (stopped)", moves, cols.join(" "), solver.nodes(), time);
+        save_table(&solver, opts);
+        return;
+    }
+    println!("Position: {} | Score: {} | Best move: {} | Scores: {} | Nodes: {} | Time: {:?}",
+        moves, shown(opts, &board, score), best_col + 1, cols.join(" "), solver.nodes(), time);
+    println!("PV: {}", format_pv(&pv));
+    let stats = solver.table_stats();
+    println!("{} | Probes: {}", format_table_stats(&stats), stats.probes);
+    save_table(&solver, opts);
+}
+
+// 作業単位を worker に配って解き、手ごとの評価値と最善手を表示する
+fn coordinate<B: Bits>(opts: &Options, depth: u32, moves: &str) {
+    let board: Board<B> = parse_board(opts, moves);
+    if board.is_over() {
+        error!("the game is already over");
+        exit(2);
+    }
+    let start = Instant::now();
+    let scores = match cluster::coordinate(empty_board::<B>(opts), moves, opts.weak, depth, &opts.bind, opts.port) {
+        Ok(Some(scores)) => scores,
+        Ok(None) => exit(signal::INTERRUPTED_EXIT_CODE),
+        Err(e) => {
+            error!("coordinator failed: {}", e);
+            exit(1);
+        }
+    };
+    // 同点なら中央寄りの列
+    let score = scores.iter().flatten().copied().max().expect("an unfinished game has a legal move");
+    let best_col = board.column_order().find(|&col| scores[col as usize] == Some(score)).expect("the best score has a column");
+    if output::is_json() {
+        let shown_scores: Vec<Option<i8>> = scores.iter().map(|s| s.map(|s| shown(opts, &board, s))).collect();
+        JsonObject::new("result").str("position", moves).num("score", shown(opts, &board, score)).num("best_move", best_col + 1)
+            .opt_nums("scores", &shown_scores).time("seconds", start.elapsed()).emit();
+    } else {
+        let cols: Vec<String> = scores.iter().map(|s| s.map_or("-".to_string(), |s| shown(opts, &board, s).to_string())).collect();
+        println!("Position: {} | Score: {} | Best move: {} | Scores: {} | Time: {:?}",
+            moves, shown(opts, &board, score), best_col + 1, cols.join(" "), start.elapsed());
+    }
+}
+
+// 最善手と、その手を打つ理由（勝ちマスと読み筋から作った説明）を表示する
+fn hint_position<B: Bits>(opts: &Options, moves: &str) {
+    let board: Board<B> = parse_board(opts, moves);
+    if board.is_over() {
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).num("score", shown(opts, &board, board_score(&board))).bool("game_over", true).emit();
+        } else {
+            println!("Position: {} | Score: {} | Game over", moves, shown(opts, &board, board_score(&board)));
         }
+        return;
+    }
+
+    let solver = new_solver(opts);
+    let _progress = monitor_progress(&solver);
+    let _search = solver.begin_search();
+    let start = Instant::now();
+    let (col, score) = solver.best_move(&board);
+    let pv = if solver.is_aborted() { Vec::new() } else { solver.principal_variation(board) };
+    // 中断されたら TT の手を、読み筋を使わずに説明する
+    let (col, score) = if solver.is_aborted() { (solver.hint_move(&board), None) } else { (col, Some(shown(opts, &board, score))) };
+    let reasons = explain::explain(&board, col, &pv);
+    if output::is_json() {
+        JsonObject::new("hint").str("position", moves).num("best_move", col + 1).opt_num("score", score)
+            .strs("reasons", &reasons).columns("pv", &pv).time("seconds", start.elapsed()).bool("stopped", score.is_none()).emit();
+    } else {
+        if output::use_color() { println!("{}", play::render_board(&board, last_move(moves), true)); }
+        let score = score.map_or("?".to_string(), |s| s.to_string());
+        println!("Position: {} | Best move: {} | Score: {} | Time: {:?}{}", moves, col + 1, score, start.elapsed(),
+            if solver.is_aborted() { " (stopped)" } else { "" });
+        for reason in &reasons { println!("  - {}", reason); }
+        if !pv.is_empty() { println!("PV: {}", format_pv(&pv)); }
+    }
+    save_table(&solver, opts);
+}
+
+// 合法手をすべて解き、評価値の高い順に並べて表示する。最善手、負ける手 (??)、それ以外の劣る手 (?) に印を付け、
+// 劣る手には相手の反駁手順（その手の後の最善手順の最初の数手）を添える。
+fn analyze_position<B: Bits>(opts: &Options, moves: &str) {
+    let board: Board<B> = parse_board(opts, moves);
+    if board.is_over() {
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).num("score", shown(opts, &board, board_score(&board))).bool("game_over", true).emit();
+        } else {
+            println!("Position: {} | Score: {} | Game over", moves, shown(opts, &board, board_score(&board)));
+        }
+        return;
+    }
 
-        for &col in &order {
-            if board.can_play(col) {
-                let mut next = board;
-                next.play(col);
-                if next.is_win() { return (SIZE + 1 - board.moves) as i8 / 2; }
+    let solver = new_solver(opts);
+    let _progress = monitor_progress(&solver);
+    let _search = solver.begin_search();
+    let start = Instant::now();
+    let scores = analyze_moves(&solver, &board, DEFAULT_REFUTATION_PLIES);
+    let stopped = solver.is_aborted();
+    // 中断されたら解き終えた手の中での最善
+    let best = scores.first().map(|m| m.score);
+    let mark = |m: &MoveScore| match best {
+        Some(best) if m.score == best => "best",
+        Some(best) if m.is_losing() && best >= 0 => "??",
+        _ => "?",
+    };
+    if output::is_json() {
+        for m in &scores {
+            JsonObject::new("move").num("column", m.col + 1).num("score", shown(opts, &board, m.score)).str("outcome", outcome_name(m.score))
+                .bool("best", Some(m.score) == best).bool("losing", m.is_losing()).columns("refutation", &m.refutation).emit();
+        }
+        JsonObject::new("result").str("position", moves).opt_num("score", best.map(|s| shown(opts, &board, s)))
+            .opt_num("best_move", scores.first().map(|m| m.col + 1))
+            .num("nodes", solver.nodes()).time("seconds", start.elapsed()).bool("stopped", stopped).emit();
+    } else {
+        if output::use_color() { println!("{}", play::render_board(&board, last_move(moves), true)); }
+        let score = best.map_or("?".to_string(), |s| shown(opts, &board, s).to_string());
+        let best_move = scores.first().map_or("-".to_string(), |m| (m.col + 1).to_string());
+        println!("Position: {} | Best move: {} | Score: {} | Nodes: {} | Time: {:?}{}", moves, best_move, score,
+            solver.nodes(), start.elapsed(), if stopped { " (stopped)" } else { "" });
+        println!("  Column  Score  Result        Refutation");
+        for m in &scores {
+            let line = format!("  {:>6}  {:>+5}  {:<6}  {:<4}  {}", m.col + 1, shown(opts, &board, m.score), outcome_name(m.score), mark(m), format_pv(&m.refutation));
+            println!("{}", line.trim_end());
+        }
+        if stopped {
+            let solved: Vec<u32> = scores.iter().map(|m| m.col).collect();
+            let rest: Vec<String> = board.legal_moves().into_iter().filter(|c| !solved.contains(c)).map(|c| (c + 1).to_string()).collect();
+            println!("  not solved: {}", rest.join(" "));
+        }
+    }
+    save_table(&solver, opts);
+}
+
+// 対局の各手を解いて最善手と比べ、勝ち負けが変わった手（悪手）に印を付ける
+fn review_position<B: Bits>(opts: &Options, moves: &str) {
+    // 手順を打てるかは parse_board で確かめる
+    let end_board = parse_board::<B>(opts, moves);
+    let winning_line: Option<Vec<String>> = end_board.winning_cells()
+        .map(|cells| cells.iter().map(|&(col, row)| explain::cell_name(col, row)).collect());
+    let start_board: Board<B> = start_board(opts);
+    let cols: Vec<u32> = moves.chars().filter_map(|c| c.to_digit(10)).map(|d| d - 1).collect();
+    let solver = new_solver(opts);
+    let _progress = monitor_progress(&solver);
+    let _search = solver.begin_search();
+    let start = Instant::now();
+    let reviews = review_game(&solver, start_board, &cols);
+    let mut blunders = [0; 2];
+    // 評価値はその手を打つ前の局面から見た値
+    let mut board = start_board;
+    for r in &reviews {
+        let side = (start_board.moves() + r.ply) as usize % 2;
+        let n = start_board.moves() + r.ply + 1;
+        if r.is_blunder() { blunders[side] += 1; }
+        let (score, best_score) = (shown(opts, &board, r.score), shown(opts, &board, r.best_score));
+        board.play(r.col);
+        if output::is_json() {
+            JsonObject::new("move").num("move", n).str("side", ["X", "O"][side]).num("column", r.col + 1).num("score", score)
+                .num("best_move", r.best_move + 1).num("best_score", best_score).bool("mistake", r.is_mistake())
+                .bool("blunder", r.is_blunder()).emit();
+        } else {
+            let flag = if r.is_blunder() {
+                format!(" | BLUNDER: {} -> {}", outcome_name(r.best_score), outcome_name(r.score))
+            } else if r.is_mistake() {
+                " | inaccuracy".to_string()
+            } else {
+                String::new()
+            };
+            println!("Move {:2} ({}): {} | Score: {:+3} | Best: {} ({:+}){}",
+                n, ["X", "O"][side], r.col + 1, score, r.best_move + 1, best_score, flag);
+        }
+    }
+    let stopped = reviews.len() < cols.len();
+    if output::is_json() {
+        let mut obj = JsonObject::new("result");
+        obj.str("position", moves).num("moves", reviews.len()).num("blunders_x", blunders[0])
+            .num("blunders_o", blunders[1]).num("nodes", solver.nodes()).time("seconds", start.elapsed()).bool("stopped", stopped);
+        if let Some(line) = &winning_line { obj.strs("winning_line", line); }
+        obj.emit();
+    } else {
+        if output::use_color() { println!("{}", play::render_board(&end_board, last_move(moves), true)); }
+        println!("Review: {} of {} moves | Blunders: X {}, O {} | Nodes: {} | Time: {:?}{}", reviews.len(), cols.len(),
+            blunders[0], blunders[1], solver.nodes(), start.elapsed(), if stopped { " (stopped)" } else { "" });
+        if let Some(line) = &winning_line {
+            println!("Winning line: {} ({})", line.join(" "), end_board.side_to_move().other().symbol());
+        }
+    }
+    save_table(&solver, opts);
+}
+
+// 局面の図を out（- なら標準出力）に書き出す。--scores / --pv のときだけ解く。
+fn export_position<B: Bits>(opts: &Options, moves: &str, out: &str) {
+    if out == "-" { output::info_to_stderr(); }
+    let board: Board<B> = parse_board(opts, moves);
+    let (mut scores, mut pv) = (None, Vec::new());
+    if (opts.scores || opts.pv) && !board.is_over() {
+        let solver = new_solver(opts);
+        let _search = solver.begin_search();
+        if opts.scores { scores = Some(solver.move_scores(&board)); }
+        if opts.pv { pv = solver.principal_variation(board); }
+        if solver.is_aborted() { exit(signal::INTERRUPTED_EXIT_CODE); }
+        save_table(&solver, opts);
+    }
+    let figure = export::Figure::new(&board, last_move(moves), scores, &pv);
+    let bytes = match opts.image_format {
+        export::ImageFormat::Svg => export::svg(&figure).into_bytes(),
+        export::ImageFormat::Png => export::png(&figure),
+    };
+    let result = if out == "-" { std::io::stdout().lock().write_all(&bytes) } else { std::fs::write(out, &bytes) };
+    if let Err(e) = result {
+        error!("failed to write {}: {}", out, e);
+        exit(1);
+    }
+    if out != "-" { info!("Wrote {} ({} bytes).", out, bytes.len()); }
+}
+
+// 評価値を裏付ける木を DOT で out（- なら標準出力）に書き出す
+fn write_proof_tree<B: Bits>(opts: &Options, moves: &str, depth: u32, out: &str) {
+    if out == "-" { output::info_to_stderr(); }
+    let board: Board<B> = parse_board(opts, moves);
+    if board.is_over() {
+        error!("the game is already over");
+        exit(2);
+    }
+    let solver = new_solver(opts);
+    let _search = solver.begin_search();
+    let start = Instant::now();
+    let tree = prooftree::build(&solver, board, depth);
+    if solver.is_aborted() { exit(signal::INTERRUPTED_EXIT_CODE); }
+    save_table(&solver, opts);
+    let result = if out == "-" { std::io::stdout().lock().write_all(tree.dot.as_bytes()) } else { std::fs::write(out, &tree.dot) };
+    if let Err(e) = result {
+        error!("failed to write {}: {}", out, e);
+        exit(1);
+    }
+    info!("Wrote {} positions and {} moves, {} plies deep, to {} in {:?} ({} nodes).",
+        tree.nodes, tree.edges, depth, out, start.elapsed(), solver.nodes());
+}
+
+// 局面の結果の証明書を out（- なら標準出力）に書き出す
+fn write_certificate<B: Bits>(opts: &Options, moves: &str, out: &str) {
+    if out == "-" { output::info_to_stderr(); }
+    let board: Board<B> = parse_board(opts, moves);
+    if board.is_over() {
+        error!("the game is already over");
+        exit(2);
+    }
+    let solver = new_solver(opts);
+    let _search = solver.begin_search();
+    let start = Instant::now();
+    let (text, sizes) = certificate::build(&solver, moves, board);
+    if solver.is_aborted() { exit(signal::INTERRUPTED_EXIT_CODE); }
+    save_table(&solver, opts);
+    let result = if out == "-" { std::io::stdout().lock().write_all(text.as_bytes()) } else { std::fs::write(out, &text) };
+    if let Err(e) = result {
+        error!("failed to write {}: {}", out, e);
+        exit(1);
+    }
+    let sizes: Vec<String> = sizes.iter().map(|(side, n)| format!("{} positions for {}", n, side)).collect();
+    info!("Wrote {} ({}) in {:?} ({} nodes).", out, sizes.join(", "), start.elapsed(), solver.nodes());
+}
+
+// 証明書を探索を使わずに確かめる
+fn verify_certificate(path: &str) {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            error!("failed to read {}: {}", path, e);
+            exit(1);
+        }
+    };
+    let start = Instant::now();
+    match certificate::verify(&text) {
+        Ok(v) => {
+            let result = match v.winner {
+                Some(side) => format!("{} wins", side),
+                None => "draw".to_string(),
+            };
+            if output::is_json() {
+                JsonObject::new("verify").str("path", path).bool("ok", true).str("result", &result).num("positions", v.positions)
+                    .num("entries", v.entries).num("unused", v.unused).time("seconds", start.elapsed()).emit();
+            } else {
+                println!("Verified: {} | Positions: {} | Strategy moves: {} ({} unused) | Time: {:?}",
+                    result, v.positions, v.entries, v.unused, start.elapsed());
+            }
+        }
+        Err(e) => {
+            if output::is_json() {
+                JsonObject::new("verify").str("path", path).bool("ok", false).str("error", &e).emit();
+            } else {
+                println!("NOT verified: {}", e);
             }
+            exit(1);
         }
+    }
+}
 
-        let max_p = (SIZE - 1 - board.moves) as i8 / 2;
-        if beta > max_p {
-            beta = max_p;
-            if alpha >= beta { return beta; }
+// 自己対局のデータを out（- なら標準出力）に書き出す
+fn run_selfplay<B: Bits>(opts: &Options, games: usize, out: &str, root: &str) {
+    if out == "-" { output::info_to_stderr(); }
+    let root: Board<B> = parse_board(opts, root);
+    let solver = new_solver(opts);
+    let _search = solver.begin_search();
+    let start = Instant::now();
+    let result = if out == "-" {
+        selfplay::run(&solver, root, games, opts.temperature, opts.seed, opts.format, &mut std::io::stdout().lock())
+    } else {
+        std::fs::File::create(out).and_then(|file| {
+            let mut writer = std::io::BufWriter::new(file);
+            let written = selfplay::run(&solver, root, games, opts.temperature, opts.seed, opts.format, &mut writer)?;
+            writer.flush()?;
+            Ok(written)
+        })
+    };
+    match result {
+        Ok(written) => info!("Wrote {} games to {} in {:?} ({} nodes).", written, out, start.elapsed(), solver.nodes()),
+        Err(e) => {
+            error!("failed to write {}: {}", out, e);
+            exit(1);
         }
+    }
+    save_table(&solver, opts);
+}
 
-        let mut max_s = -22;
-        let mut current_best = order[0];
+fn solve_popout_position<B: Bits>(opts: &Options, moves: &str) {
+    let mut board: Board<B> = start_board(opts);
+    if let Err(e) = popout::play_moves(&mut board, moves) {
+        error!("{}", e);
+        exit(2);
+    }
+    if let Some(score) = popout::result(&board) {
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).num("score", score).bool("game_over", true).emit();
+        } else {
+            println!("Position: {} | Score: {} | Game over", moves, score);
+        }
+        return;
+    }
 
-        if p_depth < 4 {
-            let results: Vec<(i8, u32)> = order.par_iter().filter_map(|&col| {
-                if board.can_play(col) {
-                    let mut next = board;
-                    next.play(col);
-                    Some((-self.solve(next, -beta, -alpha, p_depth + 1), col))
-                } else {
-                    None
-                }
-            }).collect();
-
-            for (score, col) in results {
-                if score > max_s { max_s = score; current_best = col; }
-                if score > alpha { alpha = score; }
-                if alpha >= beta { break; }
-            }
-        } else {
-            for &col in &order {
-                if board.can_play(col) {
-                    let mut next = board;
-                    next.play(col);
-                    let score = -self.solve(next, -beta, -alpha, p_depth + 1);
-                    if score > max_s { max_s = score; current_best = col; }
-                    if score > alpha { alpha = score; }
-                    if alpha >= beta { break; }
-                }
+    let solver = new_solver(opts);
+    let _progress = monitor_progress(&solver);
+    let _search = solver.begin_search();
+    let start = Instant::now();
+    let (best, score) = solver.best_popout_move(&board);
+    let time = start.elapsed();
+    if solver.is_aborted() {
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).opt_num("score", None::<i8>).num("nodes", solver.nodes())
+                .time("seconds", time).bool("stopped", true).emit();
+        } else {
+            println!("Position: {} | Score: ? | Nodes: {} | Time: {:?} (stopped)", moves, solver.nodes(), time);
+        }
+        save_table(&solver, opts);
+        return;
+    }
+    if output::is_json() {
+        JsonObject::new("result").str("position", moves).num("score", score).str("best_move", &best.to_string())
+            .num("nodes", solver.nodes()).time("seconds", time).bool("stopped", false).emit();
+    } else {
+        println!("Position: {} | Score: {} | Best move: {} | Nodes: {} | Time: {:?}", moves, score, best, solver.nodes(), time);
+    }
+    save_table(&solver, opts);
+}
+
+// 並べた側が負けるので、終局済みの局面の評価値は通常ルールと符号が逆になる
+fn solve_misere_position<B: Bits>(opts: &Options, moves: &str) {
+    let board = Misere(parse_board::<B>(opts, moves));
+    if let Some(score) = board.is_terminal() {
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).num("score", score).bool("game_over", true).emit();
+        } else {
+            println!("Position: {} | Score: {} | Game over", moves, score);
+        }
+        return;
+    }
+
+    let solver = new_solver(opts);
+    let _progress = monitor_progress(&solver);
+    let _search = solver.begin_search();
+    let start = Instant::now();
+    let (best_col, score) = solver.best_game_move(&board);
+    let time = start.elapsed();
+    if solver.is_aborted() {
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).opt_num("score", None::<i8>).num("nodes", solver.nodes())
+                .time("seconds", time).bool("stopped", true).emit();
+        } else {
+            println!("Position: {} | Score: ? | Nodes: {} | Time: {:?} (stopped)", moves, solver.nodes(), time);
+        }
+        save_table(&solver, opts);
+        return;
+    }
+    if output::is_json() {
+        JsonObject::new("result").str("position", moves).num("score", score).num("best_move", best_col + 1)
+            .num("nodes", solver.nodes()).time("seconds", time).bool("stopped", false).emit();
+    } else {
+        println!("Position: {} | Score: {} | Best move: {} | Nodes: {} | Time: {:?}", moves, score, best_col + 1, solver.nodes(), time);
+    }
+    save_table(&solver, opts);
+}
+
+fn solve_cube_position(opts: &Options, moves: &str) {
+    let cube = match Cube::from_moves(moves) {
+        Ok(cube) => cube,
+        Err(e) => {
+            error!("{}", e);
+            exit(2);
+        }
+    };
+    if let Some(score) = cube.is_terminal() {
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).num("score", score).bool("game_over", true).emit();
+        } else {
+            println!("Position: {} | Score: {} | Game over", moves, score);
+        }
+        return;
+    }
+
+    let solver = new_solver(opts);
+    let _progress = monitor_progress(&solver);
+    let _search = solver.begin_search();
+    let start = Instant::now();
+    let (peg, score) = solver.best_game_move(&cube);
+    let best = char::from(b'a' + peg as u8).to_string();
+    let time = start.elapsed();
+    if solver.is_aborted() {
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).opt_num("score", None::<i8>).num("nodes", solver.nodes())
+                .time("seconds", time).bool("stopped", true).emit();
+        } else {
+            println!("Position: {} | Score: ? | Nodes: {} | Time: {:?} (stopped)", moves, solver.nodes(), time);
+        }
+        save_table(&solver, opts);
+        return;
+    }
+    if output::is_json() {
+        JsonObject::new("result").str("position", moves).num("score", score).str("best_move", &best)
+            .num("nodes", solver.nodes()).time("seconds", time).bool("stopped", false).emit();
+    } else {
+        println!("Position: {} | Score: {} | Best move: {} | Nodes: {} | Time: {:?}", moves, score, best, solver.nodes(), time);
+    }
+    save_table(&solver, opts);
+}
+
+// 1 行に 1 局面（先頭のフィールドが手順、残りは無視）。空行と # で始まる行は飛ばす。
+fn read_batch(path: &str) -> Vec<String> {
+    let text = if path == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map(|_| text)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    let text = match text {
+        Ok(text) => text,
+        Err(e) => {
+            error!("failed to read {}: {}", path, e);
+            exit(1);
+        }
+    };
+    text.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|moves| !moves.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+// (key, 最善手, 評価値, ノード数, 時間)。終局済みなら最善手は None、中断されたら全体が None。
+type BatchResult = Result<Option<(u128, Option<u32>, i8, usize, Duration)>, String>;
+
+fn solve_batch<B: Bits>(opts: &Options, path: &str) {
+    if opts.parquet.as_deref() == Some("-") { output::info_to_stderr(); }
+    let positions = read_batch(path);
+    let solver = new_solver(opts);
+    let _progress = monitor_progress(&solver);
+    let _span = span!(INFO, "batch", path = path, positions = positions.len(), parallel = opts.batch_parallel);
+    let start = Instant::now();
+
+    let solve_one = |moves: &String| -> BatchResult {
+        let mut board: Board<B> = start_board(opts);
+        board.play_moves(moves)?;
+        if board.is_over() {
+            return Ok(Some((board.key().widen(), None, shown(opts, &board, board_score(&board)), 0, Duration::ZERO)));
+        }
+        let (start, nodes) = (Instant::now(), solver.nodes());
+        let (best_col, score) = solver.best_move(&board);
+        if solver.is_stopped() { return Ok(None); }
+        if solver.is_aborted() { return Err("not solved within --max-time/--max-nodes".to_string()); }
+        Ok(Some((board.key().widen(), Some(best_col), shown(opts, &board, score), solver.nodes() - nodes, start.elapsed())))
+    };
+    let mut rows = Vec::new();
+    let mut print = |moves: &String, result: BatchResult| {
+        let (key, best_col, score, nodes, time) = match result {
+            Ok(Some(r)) => r,
+            Ok(None) => return,
+            Err(e) => {
+                warning!("skipping '{}': {}", moves, e);
+                return;
             }
+        };
+        event!(DEBUG, "batch.result", position = moves, score = score, best_move = best_col.map(|c| c + 1),
+            nodes = (!opts.batch_parallel).then_some(nodes), seconds = time);
+        if opts.parquet.is_some() {
+            rows.push(ResultRow {
+                key, moves: Some(moves.clone()), score: Some(score), best_move: best_col,
+                nodes: (!opts.batch_parallel).then_some(nodes as u64),
+            });
+        } else if output::is_json() {
+            let mut obj = JsonObject::new("result");
+            obj.str("position", moves).num("score", score).opt_num("best_move", best_col.map(|c| c + 1));
+            if !opts.batch_parallel { obj.num("nodes", nodes); }
+            obj.time("seconds", time).emit();
+        } else {
+            let nodes = if opts.batch_parallel { "-".to_string() } else { nodes.to_string() };
+            let best = best_col.map_or("-".to_string(), |c| (c + 1).to_string());
+            println!("{} {} {} {} {:.6}", moves, score, best, nodes, time.as_secs_f64());
+        }
+    };
+
+    // 並列に解く場合もノード数は全局面で共有されるので、局面ごとの値は出さない
+    if opts.batch_parallel {
+        let results: Vec<_> = solver.install(|| positions.par_iter().map(solve_one).collect());
+        for (moves, result) in positions.iter().zip(results) { print(moves, result); }
+    } else {
+        for moves in &positions {
+            print(moves, solve_one(moves));
+            if solver.is_stopped() { break; }
         }
-        self.store(key, max_s, current_best);
-        max_s
     }
+    log_table_stats(&solver.table_stats());
+    info!("Solved {} positions in {:?} ({} nodes){}.", positions.len(), start.elapsed(), solver.nodes(),
+        if solver.is_stopped() { " (stopped)" } else { "" });
+    if let Some(path) = &opts.parquet { write_parquet(path, &rows); }
+    save_table(&solver, opts);
 }
 
-fn main() {
-    // スレッドプールを最初に一回だけ設定（エラー回避）
-    let _ = rayon::ThreadPoolBuilder::new()
-        .num_threads(32)
-        .stack_size(16 * 1024 * 1024)
-        .build_global();
+fn build_book<B: Bits>(opts: &Options, depth: u32, out: &str, root: &str) {
+    let root: Board<B> = parse_board(opts, root);
+    let solver = new_solver(opts);
+    let _progress = monitor_progress(&solver);
+    let _search = solver.begin_search();
+    let start = Instant::now();
 
-    println!("Allocating and FORCE-INITIALIZING 32GB Table...");
-    let start_init = Instant::now();
+    let book = Book::build(&solver, root, depth, |ply, positions| {
+        if output::is_json() {
+            JsonObject::new("book_layer").num("ply", ply).num("positions", positions).time("seconds", start.elapsed()).emit();
+        } else {
+            println!(">>> BOOK ply {:2}: {:9} positions solved (Total Time: {:?})", ply, positions, start.elapsed());
+        }
+    });
+    if solver.is_aborted() {
+        error!("book build was stopped; no book written");
+        save_table(&solver, opts);
+        exit(if signal::interrupted() { signal::INTERRUPTED_EXIT_CODE } else { 1 });
+    }
+    if let Err(e) = book.save(out) {
+        error!("failed to write book to {}: {}", out, e);
+        exit(1);
+    }
+    if output::is_json() {
+        JsonObject::new("book").num("positions", book.len()).num("depth", book.depth()).str("path", out)
+            .time("seconds", start.elapsed()).emit();
+    } else {
+        println!("Book of {} positions (depth {}) written to {}.", book.len(), book.depth(), out);
+    }
+    save_table(&solver, opts);
+}
 
-    // Vec::with_capacity ではなく、実際に中身を埋めて確保する
-    let mut table_vec = Vec::new();
-    table_vec.reserve_exact(TABLE_ENTRIES);
-    
-    // 物理メモリへの割り当てを確実にするため、全要素を逐次的に生成。
-    // 7950Xならシングルスレッドでも数十秒で終わります。
-    for _ in 0..TABLE_ENTRIES {
-        table_vec.push(Entry { key: AtomicU64::new(0), data: AtomicU64::new(0) });
+fn open_database(opts: &Options, path: &str) -> PositionDb {
+    match PositionDb::open(path, opts.width, opts.height, opts.connect, opts.weak) {
+        Ok(db) => db,
+        Err(e) => {
+            error!("failed to open database {}: {}", path, e);
+            exit(1);
+        }
     }
+}
+
+fn database_len(db: &PositionDb, path: &str) -> usize {
+    db.len().unwrap_or_else(|e| {
+        error!("failed to read database {}: {}", path, e);
+        exit(1);
+    })
+}
+
+fn build_database<B: Bits>(opts: &Options, depth: u32, path: &str, root: &str) {
+    let root: Board<B> = parse_board(opts, root);
+    let mut db = open_database(opts, path);
+    let existing = database_len(&db, path);
+    let solver = new_solver(opts);
+    let _progress = monitor_progress(&solver);
+    let _search = solver.begin_search();
+    let start = Instant::now();
 
-    let solver = Arc::new(Solver {
-        table: Arc::new(table_vec),
-        nodes: Arc::new(AtomicUsize::new(0)),
+    let result = db.build(&solver, root, depth, |ply, positions, solved| {
+        if output::is_json() {
+            JsonObject::new("db_layer").num("ply", ply).num("positions", positions).num("solved", solved)
+                .time("seconds", start.elapsed()).emit();
+        } else {
+            println!(">>> DB ply {:2}: {:9} positions, {:9} newly solved (Total Time: {:?})", ply, positions, solved, start.elapsed());
+        }
     });
+    if let Err(e) = result {
+        error!("failed to write database {}: {}", path, e);
+        exit(1);
+    }
+    let len = database_len(&db, path);
+    if output::is_json() {
+        JsonObject::new("db").num("positions", len).num("added", len - existing).str("path", path)
+            .time("seconds", start.elapsed()).bool("stopped", solver.is_aborted()).emit();
+    } else {
+        println!("Database {}: {} positions ({} added){}.", path, len, len - existing,
+            if solver.is_aborted() { "; stopped, rerun to continue" } else { "" });
+    }
+    save_table(&solver, opts);
+}
+
+fn query_database<B: Bits>(opts: &Options, path: &str, moves: &str) {
+    let board: Board<B> = parse_board(opts, moves);
+    let db = open_database(opts, path);
+    let record = match db.get(&board) {
+        Ok(record) => record,
+        Err(e) => {
+            error!("failed to read database {}: {}", path, e);
+            exit(1);
+        }
+    };
+    if output::is_json() {
+        let mut obj = JsonObject::new("result");
+        obj.str("position", moves).bool("found", record.is_some());
+        if let Some(r) = &record { obj.num("score", shown(opts, &board, r.score)).num("best_move", r.best_col + 1).num("nodes", r.nodes); }
+        obj.emit();
+    } else {
+        match record {
+            Some(r) => println!("Position: {} | Score: {} | Best move: {} | Nodes: {}", moves, shown(opts, &board, r.score), r.best_col + 1, r.nodes),
+            None => println!("Position: {} | not in {}", moves, path),
+        }
+    }
+}
+
+// Parquet を path（- なら標準出力）に書く
+fn write_parquet(path: &str, rows: &[ResultRow]) {
+    let result = if path == "-" {
+        parquet::write(std::io::BufWriter::new(std::io::stdout()), rows)
+    } else {
+        std::fs::File::create(path).and_then(|file| parquet::write(std::io::BufWriter::new(file), rows))
+    };
+    match result {
+        Ok(()) => info!("Wrote {} rows to {}.", rows.len(), path),
+        Err(e) => {
+            error!("failed to write {}: {}", path, e);
+            exit(1);
+        }
+    }
+}
 
-    println!("Table initialized in {:?}. Memory should be occupied.", start_init.elapsed());
+fn dump_database(opts: &Options, path: &str) {
+    if opts.parquet.as_deref() == Some("-") { output::info_to_stderr(); }
+    let (header, records) = match PositionDb::read_all(path) {
+        Ok(db) => db,
+        Err(e) => {
+            error!("failed to read database {}: {}", path, e);
+            exit(1);
+        }
+    };
+    if let Some(out) = &opts.parquet {
+        // 局面の手順は持っていないので moves は null
+        let rows: Vec<ResultRow> = records.iter().map(|r| ResultRow {
+            key: r.key, moves: None, score: Some(r.score), best_move: Some(r.best_col as u32), nodes: Some(r.nodes),
+        }).collect();
+        write_parquet(out, &rows);
+        return;
+    }
+    if output::is_json() {
+        JsonObject::new("db").num("width", header.width).num("height", header.height).num("connect", header.connect)
+            .bool("weak", header.weak).num("positions", records.len()).str("path", path).emit();
+        for r in &records {
+            JsonObject::new("record").str("key", &format!("{:x}", r.key)).num("score", r.score)
+                .num("best_move", r.best_col + 1).num("nodes", r.nodes).emit();
+        }
+        return;
+    }
+    println!("Database {}: {}x{} connect-{}{}, {} positions", path, header.width, header.height, header.connect,
+        if header.weak { " weak" } else { "" }, records.len());
+    for r in &records {
+        println!("Key: {:x} | Score: {} | Best move: {} | Nodes: {}", r.key, r.score, r.best_col + 1, r.nodes);
+    }
+}
 
-    let nodes_counter = Arc::clone(&solver.nodes);
-    std::thread::spawn(move || {
+// テストセットの各局面を解いて期待される評価値と比べ、1 局面あたりの平均の時間とノード数、NPS を出す
+fn run_bench<B: Bits>(opts: &Options, set: &str, dir: &str) {
+    let positions = match bench::read_set(dir, set) {
+        Ok(positions) => positions,
+        Err(e) => {
+            error!("{}", e);
+            exit(1);
+        }
+    };
+    let solver = new_solver(opts);
+    let (mut solved, mut mismatches, mut limited, mut time) = (0usize, 0usize, 0usize, Duration::ZERO);
+    for (moves, expected) in &positions {
+        let board: Board<B> = parse_board(opts, moves);
         let start = Instant::now();
-        let mut last_nodes = 0;
-        loop {
-            std::thread::sleep(Duration::from_secs(30));
-            let current_nodes = nodes_counter.load(Ordering::Relaxed);
-            let nps = (current_nodes - last_nodes) / 30;
-            println!("[Stats] Speed: {:6.2} MNPS | Total: {:11} M | Time: {:?}", 
-                nps as f64 / 1_000_000.0, current_nodes / 1_000_000, start.elapsed());
-            last_nodes = current_nodes;
+        let score = solver.solve(board);
+        if solver.is_stopped() { break; }
+        time += start.elapsed();
+        if solver.is_aborted() {
+            limited += 1;
+            warning!("{}: not solved within --max-time/--max-nodes", moves);
+            continue;
+        }
+        solved += 1;
+        let expected = if opts.weak { expected.signum() } else { *expected };
+        if score != expected {
+            mismatches += 1;
+            warning!("{}: expected {}, got {}", moves, expected, score);
+        }
+    }
+    let n = solved.max(1);
+    let nps = solver.nodes() as f64 / time.as_secs_f64().max(f64::MIN_POSITIVE);
+    if output::is_json() {
+        JsonObject::new("bench").str("set", set).num("positions", solved).num("mismatches", mismatches)
+            .num("nodes", solver.nodes()).time("seconds", time).num("mean_nodes", solver.nodes() / n)
+            .time("mean_seconds", time / n as u32).num("nps", nps.round()).num("unsolved", limited)
+            .bool("stopped", solver.is_stopped()).emit();
+    } else {
+        println!("Set: {} | Positions: {} | Mismatches: {} | Unsolved: {} | Mean time: {:?} | Mean nodes: {} | {:.2} MNPS{}",
+            set, solved, mismatches, limited, time / n as u32, solver.nodes() / n, nps / 1_000_000.0,
+            if solver.is_stopped() { " (stopped)" } else { "" });
+    }
+    save_table(&solver, opts);
+    if mismatches > 0 { exit(1); }
+}
+
+fn run_perft<B: Bits>(opts: &Options, depth: u32, root: &str) {
+    let root: Board<B> = parse_board(opts, root);
+    let start = Instant::now();
+    perft::count_positions(root, depth, |ply, positions, symmetric| {
+        let sequences = perft::perft(root, ply);
+        if output::is_json() {
+            JsonObject::new("perft").num("ply", ply).num("sequences", sequences).num("positions", positions)
+                .num("mirror_distinct", symmetric).time("seconds", start.elapsed()).emit();
+        } else {
+            println!("Ply {:2} | Sequences: {:15} | Positions: {:12} | Up to mirror: {:12} | Time: {:?}",
+                ply, sequences, positions, symmetric, start.elapsed());
         }
     });
+}
 
-    let first_moves = [3, 2, 4, 1, 5, 0, 6];
+fn run_hashstats<B: Bits>(opts: &Options, samples: usize) {
+    let entries = Solver::round_entries(table_entries(opts));
+    let sample = hashstats::sample_keys(start_board::<B>(opts), samples, 0x2545_f491_4f6c_dd1d);
+    let keys = sample.keys;
+    info!("Sampled {} positions for a table of {} entries ({}).", keys.len(), entries, format_bytes(entries * ENTRY_BYTES));
+    if output::is_json() {
+        JsonObject::new("key_collisions").num("keys", keys.len()).num("collisions", sample.key_collisions).emit();
+    } else {
+        println!("Key collisions: {} (distinct positions sharing a table key)", sample.key_collisions);
+    }
+    for hash in HashFunction::ALL {
+        let q = hashstats::measure(&keys, entries, hash);
+        if output::is_json() {
+            JsonObject::new("hashstats").str("hash", hash.name()).num("keys", q.keys).num("buckets", q.buckets)
+                .num("used_buckets", q.used_buckets).num("expected_used_buckets", format!("{:.1}", q.expected_used_buckets))
+                .num("overflow", q.overflow).num("expected_overflow", format!("{:.1}", q.expected_overflow))
+                .num("max_load", q.max_load).num("chi_square", format!("{:.4}", q.chi_square))
+                .num("check_collisions", q.check_collisions).num("nanos_per_key", format!("{:.2}", q.nanos_per_key)).emit();
+        } else {
+            println!("{:14} | Buckets used: {:10} (expected {:12.1}) | Overflow: {:9} (expected {:11.1}) | Max load: {:3} | Chi2/bucket: {:.4} | Check collisions: {} | {:.2} ns/key",
+                hash.name(), q.used_buckets, q.expected_used_buckets, q.overflow, q.expected_overflow, q.max_load, q.chi_square,
+                q.check_collisions, q.nanos_per_key);
+        }
+    }
+}
+
+// --mcts-* の設定で局面ごとの Mcts を作る関数（葉は solver で解く）
+fn mcts_factory<B: Bits>(opts: &Options, solver: Arc<Solver>) -> impl Fn(Board<B>) -> Mcts<B> {
+    let (exploration, depth, playouts) = (opts.mcts_exploration, opts.mcts_solver_depth, opts.mcts_playouts);
+    let evaluator = opts.mcts_model.as_deref().map(|path| load_model(opts, path));
+    move |board| {
+        let mcts = Mcts::new(board).with_exploration(exploration).with_solver(Arc::clone(&solver), depth);
+        let mcts = match &evaluator {
+            Some(evaluator) => mcts.with_evaluator(Arc::clone(evaluator)),
+            None => mcts,
+        };
+        match playouts {
+            Some(n) => mcts.with_playouts(n),
+            None => mcts,
+        }
+    }
+}
+
+// --mcts-model の ONNX モデル。盤の大きさに合わせて読み込む。
+#[cfg(feature = "onnx")]
+fn load_model(opts: &Options, path: &str) -> Arc<dyn Evaluator> {
+    match connect4_solver::OnnxEvaluator::load(path, opts.width, opts.height) {
+        Ok(evaluator) => {
+            info!("Evaluating MCTS leaves with {}.", path);
+            Arc::new(evaluator)
+        }
+        Err(e) => {
+            error!("failed to load model {}: {}", path, e);
+            exit(1);
+        }
+    }
+}
+// parse で弾いているので呼ばれない
+#[cfg(not(feature = "onnx"))]
+fn load_model(_opts: &Options, path: &str) -> Arc<dyn Evaluator> {
+    unreachable!("--mcts-model {} needs the onnx feature", path)
+}
+
+// 1 秒（--max-time）でモンテカルロ木探索をして、根の手ごとの結果と選んだ手を表示する
+fn run_mcts<B: Bits>(opts: &Options, moves: &str) {
+    let board: Board<B> = parse_board(opts, moves);
+    if board.is_over() {
+        if output::is_json() {
+            JsonObject::new("result").str("position", moves).num("score", shown(opts, &board, board_score(&board))).bool("game_over", true).emit();
+        } else {
+            println!("Position: {} | Score: {} | Game over", moves, shown(opts, &board, board_score(&board)));
+        }
+        return;
+    }
+
+    let solver = new_solver(opts);
+    let limit = opts.max_time.unwrap_or(Duration::from_secs(1));
+    let start = Instant::now();
+    let mut mcts = mcts_factory(opts, Arc::clone(&solver))(board);
+    mcts.run(|_| !solver.is_aborted() && start.elapsed() < limit);
+    let time = start.elapsed();
+    let mut stats = mcts.root_moves();
+    stats.sort_by_key(|m| std::cmp::Reverse(m.visits));
+    for m in &stats {
+        if output::is_json() {
+            JsonObject::new("move").num("column", m.col + 1).num("visits", m.visits).num("value", format!("{:.4}", m.value))
+                .bool("proven", m.proven).emit();
+        } else {
+            println!("Move {} | Visits: {:9} | Value: {:.4}{}", m.col + 1, m.visits, m.value, if m.proven { " (proven)" } else { "" });
+        }
+    }
+    let pv = mcts.principal_variation();
+    if output::is_json() {
+        JsonObject::new("result").str("position", moves).num("best_move", mcts.best_move() + 1).num("playouts", mcts.playouts())
+            .num("tree_nodes", mcts.tree_size()).num("solver_nodes", solver.nodes()).time("seconds", time).columns("pv", &pv).emit();
+    } else {
+        println!("Position: {} | Best move: {} | Playouts: {} | Tree: {} nodes | Solver nodes: {} | Time: {:?}",
+            moves, mcts.best_move() + 1, mcts.playouts(), mcts.tree_size(), solver.nodes(), time);
+        println!("PV: {}", format_pv(&pv));
+    }
+}
+
+fn format_pv(pv: &[u32]) -> String {
+    pv.iter().map(|col| (col + 1).to_string()).collect::<Vec<_>>().join(" ")
+}
+
+// 局面 board の手番側から見た評価値 score を --score-format で表す
+fn shown<B: Bits>(opts: &Options, board: &Board<B>, score: i8) -> i8 { opts.score_format.convert(board, score) }
+
+// 先手から見た評価値の勝敗
+fn outcome(score: i8) -> &'static str {
+    match score.signum() {
+        1 => "先手勝ち",
+        -1 => "後手勝ち",
+        _ => "引き分け",
+    }
+}
+
+// 手順の最後の手の列（0 始まり）
+fn last_move(moves: &str) -> Option<u32> {
+    moves.chars().rev().find_map(|c| c.to_digit(10)).map(|d| d - 1)
+}
+
+fn board_score<B: Bits>(board: &Board<B>) -> i8 {
+    if board.is_win() { -((board.size() + 2 - board.moves()) as i8 / 2) } else { 0 }
+}
+
+// タスク (2 手目, 3 手目) ごとの範囲から、2 手目が最大化・3 手目が最小化する値の範囲を求める
+fn column_bounds(task_bounds: &HashMap<(u32, u32), (i8, i8)>) -> (i8, i8) {
+    let mut per_c2: HashMap<u32, (i8, i8)> = HashMap::new();
+    for (&(c2, _), &(lo, hi)) in task_bounds {
+        let entry = per_c2.entry(c2).or_insert((lo, hi));
+        *entry = (entry.0.min(lo), entry.1.min(hi));
+    }
+    per_c2.values().fold((i8::MIN, i8::MIN), |acc, &(lo, hi)| (acc.0.max(lo), acc.1.max(hi)))
+}
+
+// 初手ごとに表示する重いタスクの数
+const COSTLY_TASKS: usize = 3;
+
+// タスク (2 手目, 3 手目)。2 手目で勝負がつくタスクは 3 手目がない。
+fn format_task(c2: u32, c3: u32, width: u32) -> String {
+    if c3 < width { format!("{}-{}", c2 + 1, c3 + 1) } else { format!("{}", c2 + 1) }
+}
+
+// depth は測った探索の根の局面の残り手数（実効分岐数に使う）
+fn format_search_stats(stats: &SearchStats, depth: u32) -> String {
+    format!("Nodes: {} | Time: {:?} | TT hits: {:.1}% | EBF: {:.3}",
+        stats.nodes, stats.time, stats.hit_rate() * 100.0, stats.branching_factor(depth))
+}
+
+fn task_stats_json(col1: u32, c2: u32, c3: Option<u32>, stats: &SearchStats, depth: u32) -> JsonObject {
+    let mut obj = JsonObject::new("task_stats");
+    obj.num("column", col1 + 1).num("reply", c2 + 1).opt_num("third", c3.map(|c| c + 1)).num("nodes", stats.nodes)
+        .time("seconds", stats.time).num("tt_probes", stats.tt_probes).num("tt_hits", stats.tt_hits)
+        .num("ebf", format!("{:.4}", stats.branching_factor(depth)));
+    obj
+}
+
+// 初手の後の (2 手目, 3 手目, 2 手目の手番から見た評価値) から、2 手目ごとに最も悪い 3 手目を選び、
+// その中で最も良い (2 手目, 評価値, 3 手目) を返す
+fn best_reply(tasks: impl IntoIterator<Item = (u32, u32, i8)>) -> (u32, i8, u32) {
+    let mut min_scores: HashMap<u32, (i8, u32)> = HashMap::new();
+    for (c2, c3, score) in tasks {
+        let entry = min_scores.entry(c2).or_insert((i8::MAX, c3));
+        if score < entry.0 { *entry = (score, c3); }
+    }
+    let (&best_c2, &(score, best_c3)) = min_scores.iter()
+        .max_by_key(|&(_, &(score, _))| score)
+        .expect("first move has replies");
+    (best_c2, score, best_c3)
+}
+
+fn solve_first_moves<B: Bits>(opts: &Options) {
+    let solver = new_solver(opts);
+    let progress = monitor_progress(&solver);
+    let _search = solver.begin_search();
+
+    let empty: Board<B> = empty_board(opts);
+    let (width, size) = (empty.width(), empty.size());
     let start_total = Instant::now();
+    let checkpoint = opts.checkpoint.as_ref().map(|path| {
+        match Checkpoint::open(path, &empty, opts.weak, opts.resume) {
+            Ok(cp) => {
+                if opts.resume { info!("Resuming from {}: {} tasks already solved.", path, cp.len()); }
+                cp
+            }
+            Err(e) => {
+                error!("failed to open checkpoint {}: {}", path, e);
+                exit(1);
+            }
+        }
+    });
+    // 解いた初手の局面 → (列, 結果, PV)。左右反転した初手は解き直さずに結果を流用する。
+    let mut solved: HashMap<B, (u32, i8, String, Vec<u32>)> = HashMap::new();
+    // --verify-distance で読み筋と手数が合わなかった初手の数
+    let mut distance_errors = 0;
+    // 解いた初手ごとの探索の量（左右反転で流用したものは含まない）
+    let mut root_costs: Vec<(u32, SearchStats)> = Vec::new();
 
-    for &col1 in &first_moves {
-        let mut b1 = Board::new();
+    for col1 in empty.column_order() {
+        let mut b1 = empty;
         b1.play(col1);
+        progress.set_root_move(Some(col1));
+        if let Some((col, score, res, pv)) = solved.get(&b1.mirror().key()) {
+            let pv: Vec<u32> = pv.iter().map(|c| width - 1 - c).collect();
+            event!(INFO, "root.result", column = col1 + 1, score = *score, mirror_of = col + 1);
+            if output::is_json() {
+                JsonObject::new("result").num("column", col1 + 1).num("score", shown(opts, &b1, *score)).num("mirror_of", col + 1)
+                    .time("seconds", start_total.elapsed()).columns("pv", &pv).emit();
+            } else {
+                println!(">>> RESULT Column {}: {} (mirror of Column {}) (Total Time: {:?})",
+                    col1 + 1, res, col + 1, start_total.elapsed());
+                println!("    PV: {}", format_pv(&pv));
+            }
+            continue;
+        }
         let mut tasks = Vec::new();
-        for col2 in 0..WIDTH {
+        for col2 in 0..width {
             if b1.can_play(col2) {
                 let mut b2 = b1; b2.play(col2);
-                if b2.is_win() { tasks.push((col2, 999, 21)); continue; }
-                for col3 in 0..WIDTH {
+                if b2.is_win() { tasks.push((col2, 999, (size - 1) as i8 / 2)); continue; }
+                for col3 in 0..width {
                     if b2.can_play(col3) {
                         let mut b3 = b2; b3.play(col3);
                         tasks.push((col2, col3, 0));
@@ -209,22 +1481,151 @@ fn main() {
                 }
             }
         }
-        let results: Vec<(u32, i8)> = tasks.into_par_iter().map(|(c2, c3, pre_score)| {
-            let score = if pre_score != 0 { pre_score } else {
-                let mut b3 = Board::new(); b3.play(col1); b3.play(c2); b3.play(c3);
-                if b3.is_win() { 21 } else { solver.solve(b3, -22, 22, 0) }
+        let _span = span!(INFO, "root", column = col1 + 1, tasks = tasks.len());
+        // 各タスクの証明済みの範囲から、この初手の評価値の範囲を求めて変化するたびに表示する
+        let full = if opts.weak { (-1, 1) } else { (-((size - 3) as i8) / 2, (size - 2) as i8 / 2) };
+        let bounds = Mutex::new((
+            tasks.iter().map(|&(c2, c3, _)| ((c2, c3), full)).collect::<HashMap<_, _>>(),
+            full,
+        ));
+        let update_bounds = |c2: u32, c3: u32, lo: i8, hi: i8| {
+            let mut guard = bounds.lock().unwrap();
+            let (task_bounds, printed) = &mut *guard;
+            task_bounds.insert((c2, c3), (lo, hi));
+            let current = column_bounds(task_bounds);
+            if current != *printed {
+                *printed = current;
+                if output::is_json() {
+                    JsonObject::new("bounds").num("column", col1 + 1).num("min", current.0).num("max", current.1)
+                        .time("seconds", start_total.elapsed()).emit();
+                } else {
+                    println!(">>> BOUNDS Column {}: score ∈ [{:+}, {:+}] (Total Time: {:?})",
+                        col1 + 1, current.0, current.1, start_total.elapsed());
+                }
+            }
+        };
+        // タスクごとのノード数・時間・TT の引き当て。探索しなかったタスク（即勝ち・チェックポイント）は 0。
+        let root_start = Instant::now();
+        let results: Vec<(u32, u32, i8, SearchStats)> = solver.install(|| tasks.into_par_iter().map(|(c2, c3, pre_score)| {
+            let ((lo, hi), stats) = if pre_score != 0 { ((pre_score.min(full.1), pre_score.min(full.1)), SearchStats::default()) } else {
+                let mut b3 = b1; b3.play(c2); b3.play(c3);
+                // 3 手目で先手が勝つ（k が 3 以下の盤）なら、後手から見て 3 つ目の石での負け
+                if b3.is_win() { let loss = -((size - 1) as i8 / 2); ((loss, loss), SearchStats::default()) }
+                else if let Some(score) = checkpoint.as_ref().and_then(|cp| cp.get(col1, c2, c3)) { ((score, score), SearchStats::default()) }
+                else {
+                    let ((lo, hi), stats) = solver.measure(|| solver.solve_with_bounds(b3, |lo, hi| update_bounds(c2, c3, lo, hi)));
+                    if lo == hi && let Some(cp) = &checkpoint && let Err(e) = cp.record(col1, c2, c3, lo) {
+                        warning!("failed to write checkpoint: {}", e);
+                    }
+                    ((lo, hi), stats)
+                }
             };
-            (c2, score)
-        }).collect();
-        let mut min_scores = HashMap::new();
-        for (c2, score) in results {
-            let entry = min_scores.entry(c2).or_insert(22);
-            if score < *entry { *entry = score; }
-        }
-        let final_score = *min_scores.values().max().unwrap_or(&0);
-        let res = if final_score > 0 { format!("先手勝ち (あと {:2} 手)", final_score * 2 - 1) } 
-                  else if final_score < 0 { format!("後手勝ち (あと {:2} 手)", final_score.abs() * 2) } 
-                  else { "引き分け".to_string() };
-        println!(">>> RESULT Column {}: {} (Total Time: {:?})", col1 + 1, res, start_total.elapsed());
+            event!(TRACE, "task.result", column = col1 + 1, reply = c2 + 1, third = (c3 < width).then_some(c3 + 1), min = lo, max = hi);
+            event!(DEBUG, "task.stats", column = col1 + 1, reply = c2 + 1, third = (c3 < width).then_some(c3 + 1),
+                nodes = stats.nodes, seconds = stats.time, tt_hit_rate = stats.hit_rate(), ebf = stats.branching_factor(size - 3));
+            update_bounds(c2, c3, lo, hi);
+            (c2, c3, lo, stats)
+        }).collect());
+        // 初手全体の時間はタスクの時間の和ではなく経過時間（タスクは並列に動く）
+        let root_stats = results.iter().fold(SearchStats { time: root_start.elapsed(), ..SearchStats::default() }, |acc, t| SearchStats {
+            nodes: acc.nodes + t.3.nodes,
+            tt_probes: acc.tt_probes + t.3.tt_probes,
+            tt_hits: acc.tt_hits + t.3.tt_hits,
+            ..acc
+        });
+        let stopped = || {
+            let (lo, hi) = bounds.lock().unwrap().1;
+            event!(WARN, "root.stopped", column = col1 + 1, min = lo, max = hi);
+            if output::is_json() {
+                JsonObject::new("result").num("column", col1 + 1).opt_num("score", None::<i8>).num("min", lo).num("max", hi)
+                    .time("seconds", start_total.elapsed()).bool("stopped", true).emit();
+            } else {
+                println!(">>> STOPPED Column {}: score ∈ [{:+}, {:+}] (Total Time: {:?})", col1 + 1, lo, hi, start_total.elapsed());
+            }
+        };
+        if solver.is_aborted() {
+            stopped();
+            break;
+        }
+        let (best_c2, final_score, best_c3) = best_reply(results.iter().map(|&(c2, c3, score, _)| (c2, c3, score)));
+        let mut pv = vec![col1, best_c2];
+        if best_c3 < width {
+            let mut b3 = b1; b3.play(best_c2); b3.play(best_c3);
+            pv.push(best_c3);
+            pv.extend(solver.principal_variation(b3));
+        }
+        // 読み筋を求める探索で上限に達したら、評価値は分かっていても止まった初手として出す
+        if solver.is_aborted() {
+            stopped();
+            break;
+        }
+        // final_score は初手の後の手番（後手）から見た値。手数は初手の後から決着の手まで。
+        let res = if opts.weak { outcome(-final_score).to_string() } else {
+            match Outcome::from_score(&b1, final_score) {
+                Outcome::WinIn(n) => format!("後手勝ち (あと {:2} 手)", n),
+                Outcome::LossIn(n) => format!("先手勝ち (あと {:2} 手)", n),
+                Outcome::Draw => "引き分け".to_string(),
+            }
+        };
+        if opts.verify_distance && !check_distance(&b1, final_score, &pv[1..]).1 { distance_errors += 1; }
+        event!(INFO, "root.result", column = col1 + 1, score = final_score, nodes = solver.nodes(), seconds = start_total.elapsed());
+        event!(INFO, "root.stats", column = col1 + 1, nodes = root_stats.nodes, seconds = root_stats.time,
+            tt_hit_rate = root_stats.hit_rate(), ebf = root_stats.branching_factor(size - 1));
+        log_table_stats(&solver.table_stats());
+        if output::is_json() {
+            for &(c2, c3, _, stats) in &results {
+                task_stats_json(col1, c2, (c3 < width).then_some(c3), &stats, size - 3).emit();
+            }
+            JsonObject::new("result").num("column", col1 + 1).num("score", shown(opts, &b1, final_score)).num("nodes", solver.nodes())
+                .time("seconds", start_total.elapsed()).bool("stopped", false).columns("pv", &pv).emit();
+            JsonObject::new("root_stats").num("column", col1 + 1).num("nodes", root_stats.nodes).time("seconds", root_stats.time)
+                .num("tt_probes", root_stats.tt_probes).num("tt_hits", root_stats.tt_hits)
+                .num("ebf", format!("{:.4}", root_stats.branching_factor(size - 1))).emit();
+            table_stats_json(&solver.table_stats()).emit();
+        } else {
+            println!(">>> RESULT Column {}: {} (Total Time: {:?})", col1 + 1, res, start_total.elapsed());
+            println!("    PV: {}", format_pv(&pv));
+            println!("    {}", format_search_stats(&root_stats, size - 1));
+            // 重いタスクの上位。並列に分ける深さを決める目安にする。
+            let mut costly: Vec<_> = results.iter().filter(|t| t.3.nodes > 0).collect();
+            costly.sort_by_key(|t| std::cmp::Reverse(t.3.nodes));
+            for &&(c2, c3, _, stats) in costly.iter().take(COSTLY_TASKS) {
+                let share = stats.nodes as f64 / root_stats.nodes.max(1) as f64 * 100.0;
+                println!("    Task {}: {} ({:.1}% of nodes)", format_task(c2, c3, width), format_search_stats(&stats, size - 3), share);
+            }
+            println!("    {}", format_table_stats(&solver.table_stats()));
+        }
+        root_costs.push((col1, root_stats));
+        solved.insert(b1.key(), (col1, final_score, res, pv));
+        // 長時間の実行では初手ごとに TT も書き出しておく（--tt-save があれば）
+        if checkpoint.is_some() { save_table(&solver, opts); }
+    }
+    save_table(&solver, opts);
+    // 初手ごとの重さの一覧（解いた順ではなく、ノード数の多い順）
+    if !output::is_json() && root_costs.len() > 1 {
+        root_costs.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.nodes));
+        let total = root_costs.iter().map(|(_, stats)| stats.nodes).sum::<usize>().max(1);
+        println!("Cost per first move:");
+        for (col1, stats) in &root_costs {
+            println!("  Column {}: {} ({:.1}% of nodes)", col1 + 1, format_search_stats(stats, size - 1), stats.nodes as f64 / total as f64 * 100.0);
+        }
+    }
+    if opts.verify_distance {
+        info!("Distances verified: {} of {} first moves.", solved.len() - distance_errors, solved.len());
+        if distance_errors > 0 { exit(1); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 8x7 の盤では 3 手目のタスクの評価値が (56 - 2) / 2 = 27 まで取りうる
+    #[test]
+    fn best_reply_on_large_boards() {
+        let tasks = (0..8).flat_map(|c2| (0..8).map(move |c3| (c2, c3, 27 - ((c2 + c3) % 5) as i8)));
+        let (c2, score, c3) = best_reply(tasks.filter(|&(c2, _, _)| c2 != 3).chain((0..8).map(|c3| (3, c3, 27 - (c3 == 6) as i8))));
+        assert_eq!((c2, score, c3), (3, 26, 6));
+        assert_eq!(best_reply([(0, 999, 27)]), (0, 27, 999));
     }
 }
@@ -0,0 +1,406 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::sync::Arc;
+
+use crate::bits::Bits;
+use crate::board::{splitmix64, Board};
+use crate::network::{encode, Evaluator};
+use crate::solver::Solver;
+
+// 対局用のモンテカルロ木探索 (UCT)。評価値の証明はしないが、短い持ち時間でもそれなりに強い手を返す。
+// 木は Vec に並べた節点（アリーナ）で、節点を展開するときに子をまとめて連続した位置に追加する。
+// 葉ごとに終局までのランダムなプレイアウトを playouts 回（parallel feature では rayon で並列に）行う。
+// 手の選び方は Solver と同じく、即勝ちと相手に即勝ちを与える手・塞がないと負ける手を見分ける。
+// Solver を持たせると、残りの手数が少ない葉ではプレイアウトの代わりに Solver で勝敗を求める。
+// Evaluator（方策・価値ネットワーク）を持たせると AlphaZero と同じく、葉をプレイアウトの代わりに
+// ネットワークの評価値で評価し、方策を事前確率として子を選ぶ (PUCT)。
+
+// UCT の探索項の係数の既定値 (√2)
+pub const DEFAULT_EXPLORATION: f64 = std::f64::consts::SQRT_2;
+// Solver で解く葉の残り手数の既定値。7x6 ではこのくらいまでなら 1 局面を数ミリ秒で解ける。
+pub const DEFAULT_MCTS_SOLVER_DEPTH: u32 = 16;
+
+#[derive(Clone, Copy)]
+struct Node {
+    // 親からこの節点に入る手
+    col: u32,
+    parent: u32,
+    // 子は nodes[first_child..first_child + children]。未展開なら children == 0。
+    first_child: u32,
+    children: u32,
+    visits: u64,
+    // この節点に入る手を打った側から見た得点の合計（勝ち 1、引き分け 0.5、負け 0）
+    reward: f64,
+    // 勝敗の決まった節点の、打った側から見た得点
+    terminal: Option<f64>,
+    // Evaluator の方策による、この節点に入る手の事前確率（Evaluator が無ければ 0）
+    prior: f32,
+}
+
+// 根の手ごとの集計
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoveStats {
+    pub col: u32,
+    pub visits: u64,
+    // 手番側から見た平均得点 (0..=1)
+    pub value: f64,
+    // 勝敗が確定していれば true（value は 0, 0.5, 1 のどれか）
+    pub proven: bool,
+}
+
+pub struct Mcts<B: Bits> {
+    root: Board<B>,
+    nodes: Vec<Node>,
+    exploration: f64,
+    playouts: usize,
+    solver: Option<(Arc<Solver>, u32)>,
+    evaluator: Option<Arc<dyn Evaluator>>,
+    seed: u64,
+    total_playouts: u64,
+}
+
+impl<B: Bits> Mcts<B> {
+    // 終局済みの局面に対して作ると panic する
+    pub fn new(root: Board<B>) -> Self {
+        assert!(!root.is_over(), "Mcts::new called on a finished game");
+        let node = Node { col: 0, parent: 0, first_child: 0, children: 0, visits: 0, reward: 0.0, terminal: None, prior: 1.0 };
+        Self {
+            root,
+            nodes: vec![node],
+            exploration: DEFAULT_EXPLORATION,
+            playouts: default_playouts(),
+            solver: None,
+            evaluator: None,
+            seed: 0x2545_f491_4f6c_dd1d,
+            total_playouts: 0,
+        }
+    }
+
+    pub fn with_exploration(mut self, c: f64) -> Self {
+        self.exploration = c;
+        self
+    }
+
+    // 葉 1 つあたりのプレイアウトの回数。既定は rayon のスレッド数（parallel feature が無ければ 1）。
+    pub fn with_playouts(mut self, playouts: usize) -> Self {
+        self.playouts = playouts.max(1);
+        self
+    }
+
+    // 残りの手数が depth 以下の葉は solver で解いて勝敗を確定させる
+    pub fn with_solver(mut self, solver: Arc<Solver>, depth: u32) -> Self {
+        self.solver = Some((solver, depth));
+        self
+    }
+
+    // 葉をプレイアウトの代わりに evaluator で評価し、子を PUCT で選ぶ。
+    // exploration は PUCT の係数になる（AlphaZero では 1 から 2 程度）。
+    pub fn with_evaluator(mut self, evaluator: Arc<dyn Evaluator>) -> Self {
+        self.evaluator = Some(evaluator);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn playouts(&self) -> u64 { self.total_playouts }
+    pub fn tree_size(&self) -> usize { self.nodes.len() }
+
+    // keep_going(これまでのプレイアウト数) が false を返すか、根の勝敗が確定するまで探索を繰り返す
+    pub fn run(&mut self, mut keep_going: impl FnMut(u64) -> bool) {
+        while self.nodes[0].terminal.is_none() && keep_going(self.total_playouts) {
+            if !self.iterate() { break; }
+        }
+    }
+
+    // 選択・展開・プレイアウト・逆伝播を 1 回行う。Solver が中断されて結果を捨てたら false。
+    fn iterate(&mut self) -> bool {
+        if self.evaluator.is_some() { return self.iterate_with_evaluator(); }
+        let (mut index, mut board) = self.select();
+        if self.nodes[index].terminal.is_none() {
+            if self.nodes[index].children == 0 { self.expand(index, &board); }
+            if self.nodes[index].terminal.is_none() {
+                index = self.nodes[index].first_child as usize;
+                board.play(self.nodes[index].col);
+                self.classify(index, &board);
+            }
+        }
+        let (reward, count) = match self.nodes[index].terminal {
+            Some(terminal) => (terminal, 1),
+            None => match self.simulate(index, &board) {
+                Some(result) => result,
+                None => return false,
+            },
+        };
+        self.total_playouts += count;
+        self.backpropagate(index, reward, count);
+        true
+    }
+
+    // 葉を展開して、ネットワークの方策を子の事前確率に、評価値を葉の得点にする。
+    // 勝敗が手を読まずに決まる葉と、Solver で解ける葉はネットワークを使わない。
+    fn iterate_with_evaluator(&mut self) -> bool {
+        let (index, board) = self.select();
+        if self.nodes[index].terminal.is_none() { self.classify(index, &board); }
+        let reward = match self.nodes[index].terminal {
+            Some(terminal) => terminal,
+            None if self.solver.as_ref().is_some_and(|(_, depth)| board.size() - board.moves() <= *depth) => {
+                match self.simulate(index, &board) {
+                    Some((reward, _)) => reward,
+                    None => return false,
+                }
+            }
+            None => {
+                let evaluator = self.evaluator.as_ref().expect("called with an evaluator");
+                let evaluation = evaluator.evaluate(&encode(&board));
+                self.expand(index, &board);
+                let node = self.nodes[index];
+                let children = node.first_child as usize..(node.first_child + node.children) as usize;
+                let priors: Vec<f32> = children.clone()
+                    .map(|i| evaluation.policy.get(self.nodes[i].col as usize).copied().filter(|p| *p > 0.0).unwrap_or(0.0))
+                    .collect();
+                let sum: f32 = priors.iter().sum();
+                for (i, p) in children.zip(priors) {
+                    self.nodes[i].prior = if sum > 0.0 { p / sum } else { 1.0 / node.children as f32 };
+                }
+                // 手番側の評価値を、この節点に入る手を打った側の得点 (0..=1) にする
+                (1.0 - evaluation.value.clamp(-1.0, 1.0) as f64) / 2.0
+            }
+        };
+        self.total_playouts += 1;
+        self.backpropagate(index, reward, 1);
+        true
+    }
+
+    // UCT 値（Evaluator があれば PUCT 値）の最も大きい子を、未展開の節点か勝敗の決まった節点まで辿る
+    fn select(&self) -> (usize, Board<B>) {
+        let mut index = 0;
+        let mut board = self.root;
+        loop {
+            let node = &self.nodes[index];
+            if node.terminal.is_some() || node.children == 0 { return (index, board); }
+            let children = node.first_child as usize..(node.first_child + node.children) as usize;
+            index = if self.evaluator.is_some() {
+                let sqrt_visits = (node.visits as f64).sqrt();
+                children.max_by(|&a, &b| self.puct(a, sqrt_visits).total_cmp(&self.puct(b, sqrt_visits)))
+            } else {
+                let log_visits = (node.visits.max(1) as f64).ln();
+                children.max_by(|&a, &b| self.uct(a, log_visits).total_cmp(&self.uct(b, log_visits)))
+            }.expect("expanded nodes have children");
+            board.play(self.nodes[index].col);
+        }
+    }
+
+    #[inline]
+    fn uct(&self, index: usize, log_parent_visits: f64) -> f64 {
+        let node = &self.nodes[index];
+        match node.terminal {
+            // 勝ちが確定した子は必ず選び、負けが確定した子は選ばない
+            Some(1.0) => f64::INFINITY,
+            Some(0.0) => f64::NEG_INFINITY,
+            _ if node.visits == 0 => f64::MAX,
+            _ => node.reward / node.visits as f64 + self.exploration * (log_parent_visits / node.visits as f64).sqrt(),
+        }
+    }
+
+    // 平均得点 + exploration * 事前確率 * √親の訪問回数 / (1 + 訪問回数)。未訪問の子の平均得点は 0.5 とみなす。
+    #[inline]
+    fn puct(&self, index: usize, sqrt_parent_visits: f64) -> f64 {
+        let node = &self.nodes[index];
+        match node.terminal {
+            Some(1.0) => f64::INFINITY,
+            Some(0.0) => f64::NEG_INFINITY,
+            _ => {
+                let value = if node.visits == 0 { 0.5 } else { node.reward / node.visits as f64 };
+                value + self.exploration * node.prior as f64 * sqrt_parent_visits / (1 + node.visits) as f64
+            }
+        }
+    }
+
+    // 子を追加する。手番側の勝敗が手を読まずに決まる局面なら、子を作らずに節点の勝敗を確定させる。
+    fn expand(&mut self, index: usize, board: &Board<B>) {
+        let candidates = match candidates(board) {
+            Ok(candidates) => candidates,
+            Err(reward) => {
+                self.nodes[index].terminal = Some(1.0 - reward);
+                return;
+            }
+        };
+        let first_child = self.nodes.len() as u32;
+        for col in board.column_order() {
+            if candidates & board.column_mask(col) == B::ZERO { continue; }
+            self.nodes.push(Node { col, parent: index as u32, first_child: 0, children: 0, visits: 0, reward: 0.0, terminal: None, prior: 0.0 });
+        }
+        let children = self.nodes.len() as u32 - first_child;
+        let node = &mut self.nodes[index];
+        node.first_child = first_child;
+        node.children = children;
+    }
+
+    // 新しい節点の勝敗が手を読まずに決まるなら確定させる（子は展開するときに作る）
+    fn classify(&mut self, index: usize, board: &Board<B>) {
+        if board.moves() == board.size() {
+            self.nodes[index].terminal = Some(0.5);
+        } else if let Err(reward) = candidates(board) {
+            self.nodes[index].terminal = Some(1.0 - reward);
+        }
+    }
+
+    // index の局面から playouts 回のプレイアウトをした (得点の合計, 回数)（節点に入る手を打った側から見た得点）。
+    // 残り手数が少なければ Solver で解いて節点の勝敗を確定させ、その得点を 1 回分として返す。
+    // Solver が中断されたら None。
+    fn simulate(&mut self, index: usize, board: &Board<B>) -> Option<(f64, u64)> {
+        if let Some((solver, depth)) = &self.solver
+            && board.size() - board.moves() <= *depth {
+            let score = solver.solve(*board);
+            if solver.is_aborted() { return None; }
+            let reward = match score.signum() { 1 => 0.0, 0 => 0.5, _ => 1.0 };
+            self.nodes[index].terminal = Some(reward);
+            return Some((reward, 1));
+        }
+        let seed = self.seed ^ self.total_playouts.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let board = *board;
+        let total = sum_playouts(self.playouts, |i| 1.0 - playout(board, seed.wrapping_add(i as u64)));
+        Some((total, self.playouts as u64))
+    }
+
+    // 葉の得点 reward（count 回分の合計）を根まで、手番を入れ替えながら足していく
+    fn backpropagate(&mut self, mut index: usize, mut reward: f64, count: u64) {
+        loop {
+            let node = &mut self.nodes[index];
+            node.visits += count;
+            node.reward += reward;
+            if index == 0 { break; }
+            reward = count as f64 - reward;
+            index = node.parent as usize;
+            self.settle(index);
+        }
+    }
+
+    // 子の勝敗がすべて決まったか、勝ちの子があれば、節点の勝敗も確定させる
+    fn settle(&mut self, index: usize) {
+        let node = self.nodes[index];
+        if node.terminal.is_some() || node.children == 0 { return; }
+        let children = &self.nodes[node.first_child as usize..(node.first_child + node.children) as usize];
+        if children.iter().any(|c| c.terminal == Some(1.0)) {
+            self.nodes[index].terminal = Some(0.0);
+        } else if let Some(best) = children.iter().map(|c| c.terminal).try_fold(0.0f64, |best, t| t.map(|t| best.max(t))) {
+            self.nodes[index].terminal = Some(1.0 - best);
+        }
+    }
+
+    // 根の手ごとの訪問回数と得点
+    pub fn root_moves(&self) -> Vec<MoveStats> {
+        let root = &self.nodes[0];
+        self.nodes[root.first_child as usize..(root.first_child + root.children) as usize].iter().map(|c| MoveStats {
+            col: c.col,
+            visits: c.visits,
+            value: c.terminal.unwrap_or(c.reward / c.visits.max(1) as f64),
+            proven: c.terminal.is_some(),
+        }).collect()
+    }
+
+    // 訪問回数の最も多い手（勝ちが確定した手があればそれ、負けが確定した手は最後の手段）。
+    // 根が手を読まずに決まる局面（即勝ちや、どう打っても負け）なら Solver と同じ選び方をする。
+    pub fn best_move(&self) -> u32 {
+        let rank = |m: &MoveStats| (m.proven && m.value == 1.0, !(m.proven && m.value == 0.0), m.visits);
+        match self.root_moves().iter().max_by_key(|m| rank(m)) {
+            Some(m) => m.col,
+            None => fallback_move(&self.root),
+        }
+    }
+
+    // 訪問回数の最も多い子を辿った手順
+    pub fn principal_variation(&self) -> Vec<u32> {
+        let mut pv = vec![self.best_move()];
+        let mut index = self.nodes[0].first_child as usize
+            + self.root_moves().iter().position(|m| m.col == pv[0]).unwrap_or(0);
+        while self.nodes[index].children > 0 {
+            let node = &self.nodes[index];
+            let children = node.first_child as usize..(node.first_child + node.children) as usize;
+            index = children.max_by_key(|&i| self.nodes[i].visits).expect("expanded nodes have children");
+            if self.nodes[index].visits == 0 { break; }
+            pv.push(self.nodes[index].col);
+        }
+        pv
+    }
+}
+
+// 手番側が探索する手。勝敗が手を読まずに決まる局面では Err(手番側から見た得点)。
+fn candidates<B: Bits>(board: &Board<B>) -> Result<B, f64> {
+    if board.can_win_next() { return Err(1.0); }
+    let forced = board.forced_moves();
+    if forced & forced.wrapping_sub(B::ONE) != B::ZERO { return Err(0.0); }
+    let mut candidates = board.non_losing_moves();
+    if forced != B::ZERO { candidates &= forced; }
+    if candidates == B::ZERO { return Err(0.0); }
+    Ok(candidates)
+}
+
+// 子の無い根での手: 即勝ちの手、無ければ塞ぐ手か中央寄りの手
+fn fallback_move<B: Bits>(board: &Board<B>) -> u32 {
+    let wins = board.winning_position() & board.possible();
+    let forced = board.forced_moves();
+    let preferred = if wins != B::ZERO { wins } else if forced != B::ZERO { forced } else { board.possible() };
+    board.column_order().find(|&col| preferred & board.column_mask(col) != B::ZERO)
+        .expect("unfinished game has a legal move")
+}
+
+// board から終局まで、候補手から一様に選んで打ち進めたときの手番側の得点
+fn playout<B: Bits>(mut board: Board<B>, seed: u64) -> f64 {
+    let mut rng = seed;
+    let mut next_random = move || splitmix64(&mut rng);
+    let mover = board.moves() % 2;
+    loop {
+        if board.moves() == board.size() { return 0.5; }
+        let candidates = match candidates(&board) {
+            Ok(candidates) => candidates,
+            Err(reward) => return if board.moves() % 2 == mover { reward } else { 1.0 - reward },
+        };
+        let n = candidates.count_ones();
+        let mut pick = (next_random() % n as u64) as u32;
+        for col in 0..board.width() {
+            if candidates & board.column_mask(col) == B::ZERO { continue; }
+            if pick == 0 { board.play(col); break; }
+            pick -= 1;
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn default_playouts() -> usize { rayon::current_num_threads() }
+#[cfg(not(feature = "parallel"))]
+fn default_playouts() -> usize { 1 }
+
+#[cfg(feature = "parallel")]
+fn sum_playouts(n: usize, f: impl Fn(usize) -> f64 + Sync + Send) -> f64 {
+    (0..n).into_par_iter().map(f).sum()
+}
+#[cfg(not(feature = "parallel"))]
+fn sum_playouts(n: usize, f: impl Fn(usize) -> f64) -> f64 {
+    (0..n).map(f).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn best(moves: &str) -> u32 {
+        let mut mcts = Mcts::<u64>::new(Board::from_moves(moves).unwrap()).with_playouts(1).with_seed(1);
+        mcts.run(|n| n < 500);
+        mcts.best_move()
+    }
+
+    #[test]
+    fn takes_the_win_or_blocks() {
+        // X が 1 列目に 3 つ: X の手番なら並べ、O の手番なら塞ぐ
+        assert_eq!(best("121212"), 0);
+        assert_eq!(best("12121"), 0);
+        // 即勝ちがあれば塞ぐより優先する（O は 2 列目に 3 つ）
+        assert_eq!(best("1212123"), 1);
+        // 横: X が 3〜5 列目に並べていて、両端のどちらでも勝てる
+        assert!([1, 5].contains(&best("334455")));
+    }
+}
@@ -0,0 +1,21 @@
+use crate::solver::Solver;
+use crate::tt::ENTRY_BYTES;
+
+// 利用可能な物理メモリ（バイト）。/proc/meminfo の MemAvailable、なければ MemTotal を使う。
+pub fn available_memory() -> Option<usize> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| {
+        meminfo.lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|rest| rest.trim().trim_end_matches("kB").trim().parse::<usize>().ok())
+            .map(|kb| kb * 1024)
+    };
+    field("MemAvailable:").or_else(|| field("MemTotal:"))
+}
+
+// 利用可能メモリの fraction 倍に収まる最大の 2 のべき乗エントリ数
+pub fn auto_table_entries(fraction: f64) -> Option<usize> {
+    let budget = (available_memory()? as f64 * fraction) as usize / ENTRY_BYTES;
+    if budget == 0 { return None; }
+    Some(Solver::round_entries(budget))
+}
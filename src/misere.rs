@@ -0,0 +1,87 @@
+use crate::bits::Bits;
+use crate::board::Board;
+use crate::game::Game;
+
+// Misère: 4 つ（connect 個）並べた側の負け。盤が埋まれば引き分け。
+// 評価値は通常ルールと同じ向きで、手番側が勝つなら正。早く勝つほど大きい。
+// 並べてしまう手しか残っていない側は、その手を指して負ける。
+// 探索は汎用の Solver::solve_game / best_game_move を使う。
+
+// 通常ルールの探索と TT を共有しても同じキーにならないように混ぜる値
+const KEY_SALT: u64 = 0x3b71_d5c2_96e8_0fa4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Misere<B: Bits = u64>(pub Board<B>);
+
+// 手番側が m 手目（0 始まり）に並べて負けたときの、その手番側から見た評価値
+#[inline(always)]
+fn loss_at(size: u32, m: u32) -> i8 {
+    -((size + 1 - m) as i8 / 2)
+}
+
+impl<B: Bits> Game for Misere<B> {
+    type Move = u32;
+
+    // 並べずに指せる手があればそれだけ（並べる手は即負けなので調べない）。無ければどれを指しても負け。
+    fn moves(&self) -> Vec<u32> {
+        let board = &self.0;
+        let safe = board.possible() & !board.winning_position();
+        let cols = board.legal_moves().into_iter();
+        if safe == B::ZERO { return cols.collect(); }
+        cols.filter(|&col| safe & board.column_mask(col) != B::ZERO).collect()
+    }
+
+    #[inline(always)]
+    fn play(&mut self, col: u32) { self.0.play(col) }
+
+    // 直前に指した側が並べていれば手番側の勝ち
+    fn is_terminal(&self) -> Option<i8> {
+        let board = &self.0;
+        if board.is_win() { Some(-loss_at(board.size(), board.moves() - 1)) }
+        else if board.moves() == board.size() { Some(0) }
+        else { None }
+    }
+
+    fn key(&self) -> u64 { self.0.table_key().0 ^ KEY_SALT }
+
+    // 負けるのは最速でこの手。勝つのは最速で相手の次の手なので、絶対値はこちらが大きい。
+    fn max_score(&self) -> i8 { -loss_at(self.0.size(), self.0.moves()) }
+    fn remaining(&self) -> u32 { self.0.size() - self.0.moves() }
+    fn move_index(&self, col: u32) -> u32 { self.0.move_index(col) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Solver;
+
+    #[test]
+    fn forced_loss_on_a_tiny_board() {
+        // 2x2 で 2 つ並べたら負け: 3 つ目の石はどこに置いても先手の石と縦・横・斜めに並ぶ
+        let empty = Board::<u64>::with_size(2, 2).unwrap().with_connect(2).unwrap();
+        let solver = Solver::new(1 << 10);
+        assert_eq!(solver.solve_game(Misere(empty)), loss_at(4, 2));
+        let mut board = empty;
+        board.play(0);
+        assert_eq!(solver.solve_game(Misere(board)), -loss_at(4, 2));
+        // 通常ルールなら同じ 3 つ目の石で先手の勝ち
+        assert_eq!(solver.solve(empty), -loss_at(4, 2));
+    }
+
+    #[test]
+    fn avoids_and_rewards_completed_lines() {
+        // X が 1 列目に 3 つ。X は 1 列目に打たず、O は並べた X に勝つ
+        let game = Misere(Board::from_moves("121212").unwrap());
+        assert!(!game.moves().contains(&0));
+        assert_eq!(game.is_terminal(), None);
+        assert_eq!(Misere(Board::from_moves("1212121").unwrap()).is_terminal(), Some(-loss_at(42, 6)));
+        // 1 段の盤で 2 つ並べたら負け。X . O . から X は自分の石の隣を避けて 4 列目に打ち、
+        // O は残った 2 列目に打って自分の石と並べるしかない
+        let mut board = Board::<u64>::with_size(4, 1).unwrap().with_connect(2).unwrap();
+        board.play_moves("13").unwrap();
+        assert_eq!(Misere(board).moves(), [3]);
+        board.play(3);
+        assert_eq!(Misere(board).moves(), [1]);
+        assert_eq!(Solver::new(1 << 10).solve_game(Misere(board)), loss_at(4, 3));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::bits::Bits;
+use crate::board::Board;
+
+// 方策・価値ネットワーク（AlphaZero 風）を Mcts から使うためのインターフェース。
+// Evaluator を実装した型を Mcts::with_evaluator で渡す。onnx feature では ONNX のモデルを tract で動かす OnnxEvaluator を使える。
+// 入力は encode で作る 2 x 高さ x 幅 の平面で、ネットワークはこの並びで学習してあること。
+
+// ネットワークへの入力。planes[(plane * height + row) * width + col] は
+// plane 0 が手番側の石、plane 1 が相手の石のマスで 1.0（row は下から 0 始まり）。
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkInput {
+    pub width: u32,
+    pub height: u32,
+    pub planes: Vec<f32>,
+}
+
+// 推論の結果。どちらも手番側から見たもの。
+#[derive(Clone, Debug, PartialEq)]
+pub struct Evaluation {
+    // 列ごとの事前確率（幅の長さ）。打てない列や負の値は無視し、残りを合計 1 に正規化して使う。
+    pub policy: Vec<f32>,
+    // 評価値。1 が勝ち、0 が引き分け、-1 が負け。
+    pub value: f32,
+}
+
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, input: &NetworkInput) -> Evaluation;
+}
+
+// ONNX の方策・価値ネットワーク。モデルは次の形であること:
+//   入力 0: float32 [1, 2, 高さ, 幅]（encode の planes の並び）
+//   出力 0: float32 [1, 幅] の方策（softmax 済みの確率）
+//   出力 1: float32 [1, 1] か [1] の価値 (-1..=1)
+// 読み込むときに盤の大きさで形を固定して最適化するので、別の大きさの盤には使えない。
+#[cfg(feature = "onnx")]
+pub struct OnnxEvaluator {
+    model: tract_onnx::prelude::TypedRunnableModel<tract_onnx::prelude::TypedModel>,
+    width: u32,
+    height: u32,
+}
+
+#[cfg(feature = "onnx")]
+impl OnnxEvaluator {
+    pub fn load(path: impl AsRef<std::path::Path>, width: u32, height: u32) -> Result<Self, String> {
+        use tract_onnx::prelude::*;
+        let shape = tvec!(1, 2, height as usize, width as usize);
+        let model = tract_onnx::onnx().model_for_path(path)
+            .and_then(|model| model.with_input_fact(0, InferenceFact::dt_shape(f32::datum_type(), shape)))
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| format!("{:#}", e))?;
+        let evaluator = Self { model, width, height };
+        // 出力の形はここで一度動かして確かめる（評価のたびに調べなくてよいように）
+        let empty = NetworkInput { width, height, planes: vec![0.0; (2 * width * height) as usize] };
+        let (policy, values) = evaluator.run(&empty)?;
+        if policy.len() != width as usize { return Err(format!("policy output has {} values, expected {}", policy.len(), width)); }
+        if values.is_empty() { return Err("value output is empty".to_string()); }
+        Ok(evaluator)
+    }
+
+    // (方策, 価値) の出力をそのまま返す
+    fn run(&self, input: &NetworkInput) -> Result<(Vec<f32>, Vec<f32>), String> {
+        use tract_onnx::prelude::*;
+        let shape = [1, 2, self.height as usize, self.width as usize];
+        let tensor = Tensor::from_shape(&shape, &input.planes).map_err(|e| format!("{:#}", e))?;
+        let outputs = self.model.run(tvec!(tensor.into())).map_err(|e| format!("{:#}", e))?;
+        if outputs.len() < 2 { return Err(format!("model has {} outputs, expected policy and value", outputs.len())); }
+        let values = |i: usize| outputs[i].as_slice::<f32>().map(<[f32]>::to_vec).map_err(|e| format!("output {}: {:#}", i, e));
+        Ok((values(0)?, values(1)?))
+    }
+}
+
+#[cfg(feature = "onnx")]
+impl Evaluator for OnnxEvaluator {
+    fn evaluate(&self, input: &NetworkInput) -> Evaluation {
+        assert_eq!((input.width, input.height), (self.width, self.height), "board size differs from the loaded model");
+        // 形は load で確かめてあるので、ここで失敗するのはランタイムの不具合
+        let (policy, values) = self.run(input).expect("ONNX inference failed");
+        Evaluation { policy, value: values[0].clamp(-1.0, 1.0) }
+    }
+}
+
+pub fn encode<B: Bits>(board: &Board<B>) -> NetworkInput {
+    let (w, h) = (board.width(), board.height());
+    let (position, mask) = board.stones();
+    let mut planes = vec![0.0; (2 * w * h) as usize];
+    for col in 0..w {
+        for row in 0..h {
+            let bit = B::ONE << (col * (h + 1) + row);
+            if mask & bit == B::ZERO { continue; }
+            let plane = if position & bit != B::ZERO { 0 } else { 1 };
+            planes[((plane * h + row) * w + col) as usize] = 1.0;
+        }
+    }
+    NetworkInput { width: w, height: h, planes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::Mcts;
+    use std::sync::Arc;
+
+    // PUCT の経路を動かすための Evaluator。方策は中央の列ほど高く、価値は常に 0。
+    struct CenterEvaluator;
+
+    impl Evaluator for CenterEvaluator {
+        fn evaluate(&self, input: &NetworkInput) -> Evaluation {
+            let half = input.width as f32 / 2.0;
+            let policy = (0..input.width).map(|col| half - (col as f32 + 0.5 - half).abs()).collect();
+            Evaluation { policy, value: 0.0 }
+        }
+    }
+
+    #[test]
+    fn encode_puts_side_to_move_on_plane_zero() {
+        let mut board: Board<u64> = Board::new();
+        board.play_moves("45").unwrap();
+        let input = encode(&board);
+        let (w, h) = (input.width as usize, input.height as usize);
+        // X (手番) は列 4 (0 始まり 3)、O は列 5 の最下段
+        assert_eq!(input.planes[3], 1.0);
+        assert_eq!(input.planes[h * w + 4], 1.0);
+        assert_eq!(input.planes.iter().sum::<f32>(), 2.0);
+    }
+
+    // planes -> Flatten -> Gemm -> Softmax を方策、Gemm -> Tanh を価値とする ONNX モデルを書き出す。
+    // 方策は bias で中央の列を高くし、価値は手番側の石を +0.1、相手の石を -0.1 として数える。
+    #[cfg(feature = "onnx")]
+    fn write_model(path: &std::path::Path, width: usize, height: usize) {
+        use prost::Message;
+        use tract_onnx::pb::{type_proto, GraphProto, ModelProto, NodeProto, OperatorSetIdProto, TensorProto, TypeProto, ValueInfoProto};
+        let cells = 2 * width * height;
+        let tensor = |name: &str, dims: Vec<i64>, data: Vec<f32>| TensorProto {
+            name: name.to_string(), dims, data_type: 1, float_data: data, ..Default::default()
+        };
+        let node = |op: &str, inputs: &[&str], output: &str| NodeProto {
+            op_type: op.to_string(), input: inputs.iter().map(|s| s.to_string()).collect(), output: vec![output.to_string()],
+            ..Default::default()
+        };
+        let float = TypeProto {
+            value: Some(type_proto::Value::TensorType(type_proto::Tensor { elem_type: 1, shape: None })),
+            ..Default::default()
+        };
+        let value = |name: &str| ValueInfoProto { name: name.to_string(), r#type: Some(float.clone()), ..Default::default() };
+        let center = (width as f32 - 1.0) / 2.0;
+        let graph = GraphProto {
+            node: vec![
+                node("Flatten", &["planes"], "flat"),
+                node("Gemm", &["flat", "policy_w", "policy_b"], "logits"),
+                node("Softmax", &["logits"], "policy"),
+                node("Gemm", &["flat", "value_w", "value_b"], "value_raw"),
+                node("Tanh", &["value_raw"], "value"),
+            ],
+            initializer: vec![
+                tensor("policy_w", vec![cells as i64, width as i64], vec![0.0; cells * width]),
+                tensor("policy_b", vec![width as i64], (0..width).map(|c| -(c as f32 - center).abs()).collect()),
+                tensor("value_w", vec![cells as i64, 1], (0..cells).map(|i| if i < cells / 2 { 0.1 } else { -0.1 }).collect()),
+                tensor("value_b", vec![1], vec![0.0]),
+            ],
+            input: vec![value("planes")],
+            output: vec![value("policy"), value("value")],
+            ..Default::default()
+        };
+        let model = ModelProto {
+            ir_version: 7,
+            opset_import: vec![OperatorSetIdProto { domain: String::new(), version: 13 }],
+            graph: Some(graph),
+            ..Default::default()
+        };
+        std::fs::write(path, model.encode_to_vec()).unwrap();
+    }
+
+    #[cfg(feature = "onnx")]
+    #[test]
+    fn onnx_model_evaluates_positions() {
+        let path = std::env::temp_dir().join(format!("connect4-test-{}.onnx", std::process::id()));
+        write_model(&path, 7, 6);
+        let evaluator = OnnxEvaluator::load(&path, 7, 6).unwrap();
+        // 別の大きさの盤では出力の形が合わない
+        assert!(OnnxEvaluator::load(&path, 6, 5).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let mut board: Board<u64> = Board::new();
+        board.play_moves("441").unwrap();
+        let evaluation = evaluator.evaluate(&encode(&board));
+        assert_eq!(evaluation.policy.len(), 7);
+        assert!((evaluation.policy.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        let best = (0..7).max_by(|&a, &b| evaluation.policy[a].total_cmp(&evaluation.policy[b])).unwrap();
+        assert_eq!(best, 3);
+        // 手番 (O) の石 1 つ、相手 (X) の石 2 つ
+        assert!((evaluation.value - (-0.1f32).tanh()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn puct_finds_the_immediate_win() {
+        let mut board: Board<u64> = Board::new();
+        board.play_moves("445566").unwrap();
+        let mut mcts = Mcts::new(board).with_evaluator(Arc::new(CenterEvaluator));
+        mcts.run(|playouts| playouts < 200);
+        assert!([2, 6].contains(&mcts.best_move()));
+    }
+}
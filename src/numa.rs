@@ -0,0 +1,140 @@
+use std::io;
+
+// mbind の範囲はページ境界に揃える
+const PAGE_BYTES: usize = 4096;
+
+// 2 ソケットやチップレットのマシンでの TT の置き方。TT へのアクセスは完全にランダムなので、
+// 一つのノードにまとめて置くと、他のノードのスレッドからのリモートアクセスとそのノードのメモリ帯域が NPS を抑える。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumaPolicy {
+    #[default]
+    Off,
+    // ページ単位で全ノードに順に割り振る (MPOL_INTERLEAVE)。TT は全スレッドで共有したまま、帯域を全ノードに散らす。
+    Interleave,
+    // TT をノード数に等分してそれぞれを一つのノードに置き (MPOL_BIND)、pin_worker で固定したワーカーは
+    // 自分のノードの部分だけを引いて書く。アクセスは全てノード内になるが、ほかのノードのワーカーが解いた局面は見えない。
+    // ノード数が 2 のべき乗でなければ（TT を等分できないので）Interleave にする。
+    Partition,
+}
+
+// オンラインの NUMA ノード番号。分からなければノード 0 だけとみなす。
+pub fn online_nodes() -> Vec<usize> {
+    std::fs::read_to_string("/sys/devices/system/node/online").ok()
+        .and_then(|list| parse_list(&list))
+        .filter(|nodes| !nodes.is_empty())
+        .unwrap_or_else(|| vec![0])
+}
+
+// ノードに属する CPU の番号
+pub fn node_cpus(node: usize) -> Vec<usize> {
+    std::fs::read_to_string(format!("/sys/devices/system/node/node{}/cpulist", node)).ok()
+        .and_then(|list| parse_list(&list))
+        .unwrap_or_default()
+}
+
+// sysfs の "0-3,8-11" 形式の一覧
+fn parse_list(list: &str) -> Option<Vec<usize>> {
+    let mut items = Vec::new();
+    for part in list.trim().split(',').filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => items.extend(first.parse::<usize>().ok()?..=last.parse().ok()?),
+            None => items.push(part.parse().ok()?),
+        }
+    }
+    Some(items)
+}
+
+// rayon のワーカー index 番をノードに順に割り振り、そのノードの CPU だけで動くように固定する。
+// ThreadPoolBuilder::start_handler から呼ぶ。
+pub fn pin_worker(index: usize) -> io::Result<()> {
+    let nodes = online_nodes();
+    let cpus = node_cpus(nodes[index % nodes.len()]);
+    if cpus.is_empty() { return Err(io::Error::new(io::ErrorKind::NotFound, "node has no CPUs")); }
+    sys::set_affinity(&cpus)
+}
+
+// [base, base + bytes) のページの置き場所を決める。ページに触れる前に呼ぶこと。
+// 実際に使った置き方と、TT を分けた数（Partition のノード数、ほかは 1）を返す。
+pub(crate) fn bind(base: *mut u8, bytes: usize, policy: NumaPolicy) -> io::Result<(NumaPolicy, usize)> {
+    let nodes = online_nodes();
+    match policy {
+        NumaPolicy::Off => Ok((NumaPolicy::Off, 1)),
+        NumaPolicy::Interleave => sys::mbind(base, bytes, sys::MPOL_INTERLEAVE, &nodes).map(|()| (NumaPolicy::Interleave, 1)),
+        NumaPolicy::Partition if !nodes.len().is_power_of_two() => bind(base, bytes, NumaPolicy::Interleave),
+        NumaPolicy::Partition => {
+            // ノードごとの部分は i 番目のワーカー (pin_worker で nodes[i % nodes.len()] に固定) が使う
+            let slice = bytes / nodes.len();
+            if !slice.is_multiple_of(PAGE_BYTES) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "table is too small to partition"));
+            }
+            for (i, &node) in nodes.iter().enumerate() {
+                sys::mbind(unsafe { base.add(i * slice) }, slice, sys::MPOL_BIND, &[node])?;
+            }
+            Ok((NumaPolicy::Partition, nodes.len()))
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod sys {
+    use std::ffi::{c_int, c_long, c_ulong, c_void};
+    use std::io;
+
+    pub const MPOL_BIND: c_int = 2;
+    pub const MPOL_INTERLEAVE: c_int = 3;
+    // glibc の cpu_set_t と同じ 1024 ビット。ノードのマスクにも同じ大きさを使う。
+    const MASK_WORDS: usize = 16;
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_MBIND: c_long = 237;
+    #[cfg(target_arch = "aarch64")]
+    const SYS_MBIND: c_long = 235;
+
+    unsafe extern "C" {
+        // mbind は glibc に無い (libnuma にある) のでシステムコールを直接呼ぶ
+        fn syscall(number: c_long, ...) -> c_long;
+        fn sched_setaffinity(pid: c_int, size: usize, mask: *const c_ulong) -> c_int;
+    }
+
+    fn mask(bits: &[usize]) -> io::Result<[c_ulong; MASK_WORDS]> {
+        let mut mask = [0 as c_ulong; MASK_WORDS];
+        for &bit in bits {
+            let word = mask.get_mut(bit / 64).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "too many CPUs or nodes"))?;
+            *word |= 1 << (bit % 64);
+        }
+        Ok(mask)
+    }
+
+    pub fn mbind(addr: *mut u8, len: usize, mode: c_int, nodes: &[usize]) -> io::Result<()> {
+        let mask = mask(nodes)?;
+        // maxnode はマスクのビット数 + 1 を渡す（カーネルが 1 を引いて使う）
+        let r = unsafe {
+            syscall(SYS_MBIND, addr.cast::<c_void>(), len, mode, mask.as_ptr(), MASK_WORDS * 64 + 1, 0 as c_ulong)
+        };
+        if r != 0 { return Err(io::Error::last_os_error()); }
+        Ok(())
+    }
+
+    pub fn set_affinity(cpus: &[usize]) -> io::Result<()> {
+        let mask = mask(cpus)?;
+        if unsafe { sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+mod sys {
+    use std::io;
+
+    pub const MPOL_BIND: i32 = 2;
+    pub const MPOL_INTERLEAVE: i32 = 3;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "NUMA placement is only supported on Linux (x86_64, aarch64)")
+    }
+
+    pub fn mbind(_addr: *mut u8, _len: usize, _mode: i32, _nodes: &[usize]) -> io::Result<()> { Err(unsupported()) }
+    pub fn set_affinity(_cpus: &[usize]) -> io::Result<()> { Err(unsupported()) }
+}
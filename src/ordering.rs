@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+
+use crate::bits::Bits;
+use crate::board::MAX_BITS;
+
+const NO_MOVE: u8 = u8::MAX;
+
+// βカットを起こした手を覚えておき、TT の最善手が無い手の並べ替えに使う。
+// killer は手数ごとに直近 2 手、history は手番・マスごとのカット回数 (残り手数の 2 乗で重み付け)。
+// 並列探索から共有されるので中身はすべて atomic（多少の競合は並べ替えの質が落ちるだけ）。
+pub(crate) struct MoveHistory {
+    killers: Vec<[AtomicU8; 2]>,
+    history: Vec<AtomicU64>,
+}
+
+impl MoveHistory {
+    pub fn new() -> Self {
+        Self {
+            killers: (0..=MAX_BITS).map(|_| [AtomicU8::new(NO_MOVE), AtomicU8::new(NO_MOVE)]).collect(),
+            history: (0..2 * MAX_BITS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    #[inline]
+    fn square(moves: u32, mv: impl Bits) -> usize {
+        ((moves & 1) * MAX_BITS + mv.trailing_zeros()) as usize
+    }
+
+    // col が moves 手目の killer なら 2 (1 番目) か 1 (2 番目)、違えば 0
+    #[inline]
+    pub fn killer_rank(&self, moves: u32, col: u32) -> u8 {
+        let [k0, k1] = &self.killers[moves as usize];
+        if k0.load(Ordering::Relaxed) == col as u8 { 2 }
+        else if k1.load(Ordering::Relaxed) == col as u8 { 1 }
+        else { 0 }
+    }
+
+    #[inline]
+    pub fn score(&self, moves: u32, mv: impl Bits) -> u64 {
+        self.history[Self::square(moves, mv)].load(Ordering::Relaxed)
+    }
+
+    // 盤のマス数が size の局面の moves 手目で、列 col (着手位置 mv) がβカットを起こした
+    pub fn record_cutoff(&self, moves: u32, size: u32, col: u32, mv: impl Bits) {
+        let [k0, k1] = &self.killers[moves as usize];
+        let prev = k0.swap(col as u8, Ordering::Relaxed);
+        if prev != col as u8 { k1.store(prev, Ordering::Relaxed); }
+        let depth = (size - moves) as u64;
+        self.history[Self::square(moves, mv)].fetch_add(depth * depth, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+
+use crate::bits::Bits;
+use crate::board::Board;
+use crate::solver::Solver;
+
+// 局面の結果を、手番側から見た勝ち負けと、決着までの手数（決着の手を含む）で表す
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Outcome {
+    WinIn(u32),
+    LossIn(u32),
+    Draw,
+}
+
+impl Outcome {
+    // 評価値（手番側から見た値、弱解決でないもの）から結果を求める。
+    // 評価値は勝った側が置いた勝ちの石の番号 n（1 始まり）から (size + 2 - n) / 2（切り捨て）なので、
+    // n は size + 2 - 2|score| か size + 1 - 2|score| で、勝った側の手番の偶奇で 1 つに決まる。
+    pub fn from_score<B: Bits>(board: &Board<B>, score: i8) -> Self {
+        let (size, moves) = (board.size() as i32, board.moves() as i32);
+        if score == 0 { return Outcome::Draw; }
+        // 勝ちの石の番号（1 始まり）は、手番側が勝つなら moves + 1 と、負けるなら moves と偶奇が同じ
+        let s = score.unsigned_abs() as i32;
+        let parity = if score > 0 { moves + 1 } else { moves };
+        let n = if (size + 2 - 2 * s - parity) % 2 == 0 { size + 2 - 2 * s } else { size + 1 - 2 * s };
+        let plies = (n - moves) as u32;
+        if score > 0 { Outcome::WinIn(plies) } else { Outcome::LossIn(plies) }
+    }
+
+    // board から line（0 始まりの列番号）を打って実際に決着を見る。
+    // 打てない手があるか、line の最後で終局していなければ None。
+    pub fn of_line<B: Bits>(board: &Board<B>, line: &[u32]) -> Option<Self> {
+        let mut b = *board;
+        for &col in line {
+            if b.is_over() || !b.can_play(col) { return None; }
+            b.play(col);
+        }
+        if !b.is_over() { return None; }
+        let plies = line.len() as u32;
+        Some(if !b.is_win() { Outcome::Draw } else if plies % 2 == 1 { Outcome::WinIn(plies) } else { Outcome::LossIn(plies) })
+    }
+
+    // 決着までの手数。引き分けなら None。
+    pub fn plies(self) -> Option<u32> {
+        match self {
+            Outcome::WinIn(n) | Outcome::LossIn(n) => Some(n),
+            Outcome::Draw => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::WinIn(_) => "win",
+            Outcome::LossIn(_) => "loss",
+            Outcome::Draw => "draw",
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::WinIn(n) => write!(f, "win in {}", n),
+            Outcome::LossIn(n) => write!(f, "loss in {}", n),
+            Outcome::Draw => write!(f, "draw"),
+        }
+    }
+}
+
+// 局面を解き、評価値から求めた結果を、最善手順を終局まで打って確かめる。
+// 確かめた結果と最善手順を返す。一致しなければ（評価値からの換算か探索が誤っていれば）Err。
+// 弱解決の Solver では手数が分からないので Err。終局済みの局面なら手順は空。
+pub fn verify_outcome<B: Bits>(solver: &Solver, board: &Board<B>) -> Result<(Outcome, Vec<u32>), String> {
+    if solver.is_weak() { return Err("a weak solver does not know the distance to the end".to_string()); }
+    let claimed = Outcome::from_score(board, solver.solve(*board));
+    let pv = solver.principal_variation(*board);
+    if solver.is_aborted() { return Err("the search was stopped".to_string()); }
+    let played = Outcome::of_line(board, &pv).ok_or("the principal variation does not end the game")?;
+    if played != claimed {
+        return Err(format!("the score claims {} but the principal variation ends in {}", claimed, played));
+    }
+    Ok((played, pv))
+}
+
+// 評価値の表し方（--score-format）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScoreFormat {
+    // 探索の評価値そのもの。Pascal Pons の solver と同じで、7x6 なら 22 - 勝った側の石の数（勝ちの石を含む）。
+    #[default]
+    Pons,
+    // 決着までの手数（決着の手を含む）。勝ちなら正、負けなら負、引き分けは 0。
+    Distance,
+    // 勝ち / 引き分け / 負けを 1 / 0 / -1 で
+    Wdl,
+}
+
+impl ScoreFormat {
+    pub const ALL: [ScoreFormat; 3] = [ScoreFormat::Pons, ScoreFormat::Distance, ScoreFormat::Wdl];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScoreFormat::Pons => "pons",
+            ScoreFormat::Distance => "distance",
+            ScoreFormat::Wdl => "wdl",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+
+    // board の手番側から見た評価値 score をこの表し方に直す。Distance は弱解決の評価値には使えない。
+    pub fn convert<B: Bits>(self, board: &Board<B>, score: i8) -> i8 {
+        match self {
+            ScoreFormat::Pons => score,
+            ScoreFormat::Distance => match Outcome::from_score(board, score) {
+                Outcome::WinIn(n) => n as i8,
+                Outcome::LossIn(n) => -(n as i8),
+                Outcome::Draw => 0,
+            },
+            ScoreFormat::Wdl => score.signum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pascal Pons のテストセットの endgame_easy と middle_easy の先頭の行
+    const KNOWN: [(&str, i8, Outcome); 2] = [
+        ("2252576253462244111563365343671351441", -1, Outcome::LossIn(4)),
+        ("7422341735647741166133573473242566", 1, Outcome::WinIn(7)),
+    ];
+
+    #[test]
+    fn from_score_matches_principal_variation() {
+        let solver = Solver::new(1 << 20);
+        for (moves, score, outcome) in KNOWN {
+            let board = Board::from_moves(moves).unwrap();
+            assert_eq!(Outcome::from_score(&board, score), outcome, "{}", moves);
+            assert_eq!(verify_outcome(&solver, &board).unwrap().0, outcome, "{}", moves);
+        }
+        // 乱数で打った終盤の局面。7x6 以外の盤でも、評価値からの換算と最善手順の決着が一致すること
+        let mut rng = 0x2545_f491_4f6c_dd1d;
+        for (width, height, plies) in [(7, 6, 28), (6, 5, 16), (5, 4, 8)] {
+            let solver = Solver::new(1 << 16);
+            let empty = Board::<u64>::with_size(width, height).unwrap();
+            for _ in 0..20 {
+                let (board, _) = empty.random_unfinished(plies, &mut rng).unwrap();
+                verify_outcome(&solver, &board).unwrap_or_else(|e| panic!("{}x{} {:?}: {}", width, height, board.to_grid(), e));
+            }
+        }
+    }
+
+    #[test]
+    fn score_formats() {
+        for (moves, score, outcome) in KNOWN {
+            let board = Board::from_moves(moves).unwrap();
+            let distance = match outcome { Outcome::WinIn(n) => n as i8, Outcome::LossIn(n) => -(n as i8), Outcome::Draw => 0 };
+            assert_eq!(ScoreFormat::Pons.convert(&board, score), score);
+            assert_eq!(ScoreFormat::Distance.convert(&board, score), distance);
+            assert_eq!(ScoreFormat::Wdl.convert(&board, score), score.signum());
+        }
+        // 空の 7x6 で評価値 1 は 41 手目で勝ち、18 は最短の 7 手目で勝ち、-18 は 8 手目で負け
+        let board = Board::new();
+        assert_eq!(ScoreFormat::Distance.convert(&board, 1), 41);
+        assert_eq!(ScoreFormat::Distance.convert(&board, 18), 7);
+        assert_eq!(ScoreFormat::Distance.convert(&board, -18), -8);
+        assert_eq!(ScoreFormat::Distance.convert(&board, 0), 0);
+        assert_eq!(ScoreFormat::Wdl.convert(&board, -18), -1);
+        for format in ScoreFormat::ALL {
+            assert_eq!(ScoreFormat::from_name(format.name()), Some(format));
+        }
+        assert_eq!(ScoreFormat::from_name("elo"), None);
+    }
+}
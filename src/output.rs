@@ -0,0 +1,156 @@
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static INFO_TO_STDERR: AtomicBool = AtomicBool::new(false);
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+    if json { INFO_TO_STDERR.store(true, Ordering::Relaxed); }
+}
+pub fn is_json() -> bool { JSON.load(Ordering::Relaxed) }
+
+// 盤の図を色付きの ●/○ で描くか（--color）
+pub fn set_color(color: bool) { COLOR.store(color, Ordering::Relaxed); }
+pub fn use_color() -> bool { COLOR.load(Ordering::Relaxed) }
+
+// stdout を JSON やエンジンのプロトコルだけに使うときは、info! を stderr に回す
+pub fn info_to_stderr() { INFO_TO_STDERR.store(true, Ordering::Relaxed); }
+pub fn is_info_to_stderr() -> bool { INFO_TO_STDERR.load(Ordering::Relaxed) }
+
+// 人が読むための経過報告。ログ（src/logging.rs）にも target "console" の INFO の event として流す。
+macro_rules! info {
+    ($($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        ::tracing::info!(target: "console", "{}", message);
+        if $crate::output::is_info_to_stderr() { eprintln!("{}", message) } else { println!("{}", message) }
+    }};
+}
+pub(crate) use info;
+
+// 続けられる問題。ログの WARN の event にし、そのレベルのログを書いていなければ "warning: ..." を stderr に書く。
+macro_rules! warning {
+    ($($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        ::tracing::warn!(target: "console", "{}", message);
+        if !$crate::logging::enabled($crate::logging::LogLevel::Warn) { eprintln!("warning: {}", message) }
+    }};
+}
+pub(crate) use warning;
+
+// 続けられない問題（呼び出し側で終了する）。warning! と同じく ERROR の event にするか "error: ..." を書く。
+macro_rules! error {
+    ($($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        ::tracing::error!(target: "console", "{}", message);
+        if !$crate::logging::enabled($crate::logging::LogLevel::Error) { eprintln!("error: {}", message) }
+    }};
+}
+pub(crate) use error;
+
+// --output json で 1 行に 1 つ出力する JSON オブジェクト
+pub struct JsonObject(String);
+
+impl JsonObject {
+    pub fn new(kind: &str) -> Self {
+        let mut obj = Self(String::from("{"));
+        obj.str("type", kind);
+        obj
+    }
+
+    fn key(&mut self, key: &str) -> &mut String {
+        if self.0.len() > 1 { self.0.push(','); }
+        write_str(&mut self.0, key);
+        self.0.push(':');
+        &mut self.0
+    }
+
+    pub fn str(&mut self, key: &str, value: &str) -> &mut Self {
+        write_str(self.key(key), value);
+        self
+    }
+
+    pub fn num(&mut self, key: &str, value: impl Display) -> &mut Self {
+        let _ = write!(self.key(key), "{}", value);
+        self
+    }
+
+    pub fn opt_num(&mut self, key: &str, value: Option<impl Display>) -> &mut Self {
+        match value {
+            Some(v) => self.num(key, v),
+            None => { self.key(key).push_str("null"); self }
+        }
+    }
+
+    pub fn bool(&mut self, key: &str, value: bool) -> &mut Self {
+        self.num(key, value)
+    }
+
+    pub fn time(&mut self, key: &str, value: Duration) -> &mut Self {
+        self.num(key, value.as_secs_f64())
+    }
+
+    // 列番号の並び（1 始まりで出力する）
+    pub fn columns(&mut self, key: &str, cols: &[u32]) -> &mut Self {
+        let out = self.key(key);
+        out.push('[');
+        for (i, col) in cols.iter().enumerate() {
+            if i > 0 { out.push(','); }
+            let _ = write!(out, "{}", col + 1);
+        }
+        out.push(']');
+        self
+    }
+
+    // 文字列の並び
+    pub fn strs(&mut self, key: &str, values: &[String]) -> &mut Self {
+        self.key(key).push('[');
+        for (i, v) in values.iter().enumerate() {
+            if i > 0 { self.0.push(','); }
+            write_str(&mut self.0, v);
+        }
+        self.0.push(']');
+        self
+    }
+
+    // 整数の並び。None は null にする。
+    pub fn opt_nums(&mut self, key: &str, values: &[Option<i8>]) -> &mut Self {
+        let out = self.key(key);
+        out.push('[');
+        for (i, v) in values.iter().enumerate() {
+            if i > 0 { out.push(','); }
+            match v {
+                Some(v) => { let _ = write!(out, "{}", v); }
+                None => out.push_str("null"),
+            }
+        }
+        out.push(']');
+        self
+    }
+
+    pub fn build(&mut self) -> String {
+        let mut out = std::mem::take(&mut self.0);
+        out.push('}');
+        out
+    }
+
+    pub fn emit(&mut self) {
+        println!("{}", self.build());
+    }
+}
+
+fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
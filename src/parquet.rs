@@ -0,0 +1,116 @@
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+
+// 解いた結果を Parquet で書き出す（pandas / Polars / DuckDB などでそのまま読める）。
+// 列は key (uint64), key_high (uint64), moves (string), score (int32), best_move (int32, 1 始まり), nodes (int64) で、
+// key と key_high 以外は null になりうる。position + mask の下位 64 ビットが key、上位が key_high
+// （64 ビットに収まる標準の盤では key_high は 0）。圧縮はしない。
+pub struct ResultRow {
+    pub key: u128,
+    pub moves: Option<String>,
+    pub score: Option<i8>,
+    pub best_move: Option<u32>,
+    pub nodes: Option<u64>,
+}
+
+const SCHEMA: &str = "message result {
+    REQUIRED INT64 key (INTEGER(64, false));
+    REQUIRED INT64 key_high (INTEGER(64, false));
+    OPTIONAL BYTE_ARRAY moves (UTF8);
+    OPTIONAL INT32 score;
+    OPTIONAL INT32 best_move;
+    OPTIONAL INT64 nodes;
+}";
+const ROW_GROUP_ROWS: usize = 1 << 20;
+
+fn to_io(e: ParquetError) -> io::Error { io::Error::other(e) }
+
+// null になりうる列の値と definition level（1 なら値あり、0 なら null）
+fn optional<T, U>(rows: &[ResultRow], f: impl Fn(&ResultRow) -> Option<T>, map: impl Fn(T) -> U) -> (Vec<U>, Vec<i16>) {
+    let mut values = Vec::new();
+    let levels = rows.iter().map(|row| match f(row) {
+        Some(v) => {
+            values.push(map(v));
+            1
+        }
+        None => 0,
+    }).collect();
+    (values, levels)
+}
+
+pub fn write(out: impl Write + Send, rows: &[ResultRow]) -> io::Result<()> {
+    let schema = Arc::new(parse_message_type(SCHEMA).map_err(to_io)?);
+    let props = Arc::new(WriterProperties::builder().set_compression(Compression::UNCOMPRESSED).build());
+    let mut writer = SerializedFileWriter::new(out, schema, props).map_err(to_io)?;
+    for chunk in rows.chunks(ROW_GROUP_ROWS) {
+        let mut group = writer.next_row_group().map_err(to_io)?;
+        let mut index = 0;
+        while let Some(mut column) = group.next_column().map_err(to_io)? {
+            match index {
+                0 => {
+                    // uint64 は INT64 の物理型にビット列のまま入れる
+                    let keys: Vec<i64> = chunk.iter().map(|r| r.key as u64 as i64).collect();
+                    column.typed::<Int64Type>().write_batch(&keys, None, None)
+                }
+                1 => {
+                    let keys: Vec<i64> = chunk.iter().map(|r| (r.key >> 64) as u64 as i64).collect();
+                    column.typed::<Int64Type>().write_batch(&keys, None, None)
+                }
+                2 => {
+                    let (values, levels) = optional(chunk, |r| r.moves.clone(), |m| ByteArray::from(m.into_bytes()));
+                    column.typed::<ByteArrayType>().write_batch(&values, Some(&levels), None)
+                }
+                3 => {
+                    let (values, levels) = optional(chunk, |r| r.score, |s| s as i32);
+                    column.typed::<Int32Type>().write_batch(&values, Some(&levels), None)
+                }
+                4 => {
+                    let (values, levels) = optional(chunk, |r| r.best_move, |c| c as i32 + 1);
+                    column.typed::<Int32Type>().write_batch(&values, Some(&levels), None)
+                }
+                _ => {
+                    let (values, levels) = optional(chunk, |r| r.nodes, |n| n as i64);
+                    column.typed::<Int64Type>().write_batch(&values, Some(&levels), None)
+                }
+            }.map_err(to_io)?;
+            column.close().map_err(to_io)?;
+            index += 1;
+        }
+        group.close().map_err(to_io)?;
+    }
+    writer.close().map_err(to_io)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::Field;
+
+    #[test]
+    fn rows_round_trip() {
+        let rows = vec![
+            ResultRow { key: (5 << 64) | u64::MAX as u128, moves: Some("4453".into()), score: Some(-3), best_move: Some(0), nodes: Some(1234) },
+            ResultRow { key: 7, moves: None, score: None, best_move: None, nodes: None },
+        ];
+        let path = std::env::temp_dir().join(format!("connect4_parquet_{}.parquet", std::process::id()));
+        write(std::fs::File::create(&path).unwrap(), &rows).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let read: Vec<Vec<Field>> = reader.get_row_iter(None).unwrap()
+            .map(|row| row.unwrap().get_column_iter().map(|(_, field)| field.clone()).collect())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read, vec![
+            vec![Field::ULong(u64::MAX), Field::ULong(5), Field::Str("4453".into()), Field::Int(-3), Field::Int(1), Field::Long(1234)],
+            vec![Field::ULong(7), Field::ULong(0), Field::Null, Field::Null, Field::Null, Field::Null],
+        ]);
+    }
+}
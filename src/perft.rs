@@ -0,0 +1,123 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::HashSet;
+
+use crate::bits::Bits;
+use crate::board::Board;
+
+// 手の生成の検算用。勝負がついた局面からは先に進まない。
+
+// 局面の子（勝負がついていれば無し）
+fn children<B: Bits>(board: &Board<B>) -> impl Iterator<Item = Board<B>> + '_ {
+    let over = board.is_win();
+    board.legal_moves().into_iter().filter(move |_| !over).map(move |col| {
+        let mut next = *board;
+        next.play(col);
+        next
+    })
+}
+
+// board から depth 手進めた手順の数
+pub fn perft<B: Bits>(board: Board<B>, depth: u32) -> u64 {
+    match depth {
+        0 => 1,
+        // 最後の 1 手は子を数えるだけにする
+        1 => children(&board).count() as u64,
+        _ => sum_children(&board, |next| perft(next, depth - 1)),
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn sum_children<B: Bits>(board: &Board<B>, f: impl Fn(Board<B>) -> u64 + Sync + Send) -> u64 {
+    children(board).collect::<Vec<_>>().into_par_iter().map(f).sum()
+}
+#[cfg(not(feature = "parallel"))]
+fn sum_children<B: Bits>(board: &Board<B>, f: impl Fn(Board<B>) -> u64) -> u64 {
+    children(board).map(f).sum()
+}
+
+// board から 0..=depth 手進めた、手数ごとの異なる局面の数と、そのうち左右反転を同じとみなした数。
+// 手数ごとに全局面を持つので、深い手数ではメモリを多く使う。
+pub fn count_positions<B: Bits>(board: Board<B>, depth: u32, mut on_layer: impl FnMut(u32, usize, usize)) -> Vec<(usize, usize)> {
+    let mut layer: HashSet<Board<B>> = HashSet::from([board]);
+    let mut counts = vec![(1, 1)];
+    on_layer(0, 1, 1);
+    for ply in 1..=depth {
+        layer = next_layer(&layer);
+        let symmetric = layer.iter().map(|b| b.canonical_key().0).collect::<HashSet<_>>().len();
+        counts.push((layer.len(), symmetric));
+        on_layer(ply, layer.len(), symmetric);
+    }
+    counts
+}
+
+// root から手数ごとの異なる局面。最初は root だけの手数 0 で、以降は 1 手ずつ進めた局面を、初めて現れた順に並べる。
+// symmetric なら左右反転で同じになる局面は先に現れた方だけにする。勝負がついた局面も含むが、その先には進まない。
+// 手数を 1 つ進めるたびにその手数の全局面を持つので、深い手数ではメモリを多く使う。
+pub struct Layers<B: Bits> {
+    layer: Vec<Board<B>>,
+    symmetric: bool,
+    // 返した層の子を次の層として先に求めておくので、最後に要る層の先は求めないように残りの層数で止める
+    remaining: u32,
+}
+
+impl<B: Bits> Layers<B> {
+    pub fn new(root: Board<B>, symmetric: bool) -> Self {
+        Self { layer: vec![root], symmetric, remaining: u32::MAX }
+    }
+
+    // 手数 plies の層で終える
+    pub fn up_to(self, plies: u32) -> Self {
+        Self { remaining: plies, ..self }
+    }
+}
+
+impl<B: Bits> Iterator for Layers<B> {
+    type Item = Vec<Board<B>>;
+
+    fn next(&mut self) -> Option<Vec<Board<B>>> {
+        if self.layer.is_empty() { return None; }
+        let next = if self.remaining == 0 { Vec::new() } else {
+            self.remaining -= 1;
+            let mut seen = HashSet::new();
+            self.layer.iter().flat_map(|board| children(board))
+                .filter(|next| seen.insert(if self.symmetric { next.canonical_key().0 } else { next.key() }))
+                .collect()
+        };
+        Some(std::mem::replace(&mut self.layer, next))
+    }
+}
+
+// root からちょうど plies 手で現れる異なる局面（Layers の plies 手目）
+pub fn positions_at<B: Bits>(root: Board<B>, plies: u32, symmetric: bool) -> std::vec::IntoIter<Board<B>> {
+    Layers::new(root, symmetric).up_to(plies).nth(plies as usize).unwrap_or_default().into_iter()
+}
+
+#[cfg(feature = "parallel")]
+fn next_layer<B: Bits>(layer: &HashSet<Board<B>>) -> HashSet<Board<B>> {
+    layer.par_iter().flat_map_iter(|board| children(board)).collect()
+}
+#[cfg(not(feature = "parallel"))]
+fn next_layer<B: Bits>(layer: &HashSet<Board<B>>) -> HashSet<Board<B>> {
+    layer.iter().flat_map(|board| children(board)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_board_counts() {
+        let board = Board::new();
+        assert_eq!((1..=4).map(|depth| perft(board, depth)).collect::<Vec<_>>(), [7, 49, 343, 2401]);
+        // 異なる局面の数 (OEIS A212693)
+        let counts = count_positions(board, 4, |_, _, _| {});
+        assert_eq!(counts.iter().map(|&(all, _)| all).collect::<Vec<_>>(), [1, 7, 49, 238, 1120]);
+        for symmetric in [false, true] {
+            let layers: Vec<usize> = Layers::new(board, symmetric).up_to(4).map(|layer| layer.len()).collect();
+            let expected: Vec<usize> = counts.iter().map(|&(all, sym)| if symmetric { sym } else { all }).collect();
+            assert_eq!(layers, expected);
+            assert_eq!(positions_at(board, 4, symmetric).count(), expected[4]);
+        }
+    }
+}
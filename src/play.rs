@@ -0,0 +1,184 @@
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+use connect4_solver::{explain, review_game, Bits, Board, Cell, Level, Player, Progress, ProgressMonitor, Side, Solver};
+
+use crate::output::{self, error};
+
+// 端末で人と対局する。列番号は 1 始まり。
+//
+//   <列>        その列に打つ
+//   u, undo     自分の最後の手と、それに対するエンジンの手を取り消す
+//   h, hint     最善手と評価値とその理由を表示する（解くので序盤は時間がかかる）
+//   q, quit     対局をやめる
+//
+// エンジンの手は level の Player で選ぶ。終局したら、自分の手のうち評価値を下げた手（悪手）を解いて一覧にする。
+// moves は空の盤から打っておく手で、X は常に先手。human_first なら人が X を持つ。
+// エンジンの手とヒントは 1 回 limit まで解き、読み切れなければ TT に残った手（hint_move）で答える。
+pub fn run<B: Bits>(solver: Arc<Solver>, empty: Board<B>, moves: &str, human_first: bool, level: Level, seed: u64, limit: Duration) {
+    let mut board = empty;
+    if let Err(e) = board.play_moves(moves) {
+        error!("{}", e);
+        crate::exit(2);
+    }
+    let mut history: Vec<u32> = moves.chars().filter_map(|c| c.to_digit(10)).map(|d| d - 1).collect();
+    let opening = history.len();
+    let human = if human_first { 0 } else { 1 };
+    let mut player = Player::new(level, seed);
+    println!("You are {} against the {} engine. Enter a column (1-{}), 'u' to undo, 'h' for a hint or 'q' to quit.",
+        if human_first { "X" } else { "O" }, level.name(), board.width());
+
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        let over = board.is_over();
+        if !over && board.moves() % 2 != human {
+            let col = timed(&solver, limit, || player.choose_move(&board, &solver));
+            solver.clear_abort();
+            println!("Engine plays {}", col + 1);
+            board.play(col);
+            history.push(col);
+            continue;
+        }
+        println!("{}", render(&empty, &history));
+        if over { break; }
+
+        print!("{}> ", board.side_to_move().symbol());
+        let _ = io::stdout().flush();
+        let Some(Ok(line)) = lines.next() else { println!(); return };
+        match line.trim() {
+            "" => {}
+            "q" | "quit" => return,
+            "h" | "hint" => {
+                let ((col, score), pv) = timed(&solver, limit, || (solver.best_move(&board), solver.principal_variation(board)));
+                if solver.is_aborted() {
+                    println!("Hint: play {} (not solved within {:?})", solver.hint_move(&board) + 1, limit);
+                } else {
+                    println!("Hint: play {} ({})", col + 1, describe(score));
+                    for reason in explain::explain(&board, col, &pv) { println!("  - {}", reason); }
+                }
+                solver.clear_abort();
+            }
+            "u" | "undo" => {
+                // 最後の人の手の直前まで戻す（最初に打っておいた手は取り消さない）
+                let Some(last) = (opening..history.len()).rev().find(|&i| i as u32 % 2 == human) else {
+                    println!("Nothing to undo");
+                    continue;
+                };
+                while history.len() > last { board.undo(history.pop().expect("history is longer than last")); }
+            }
+            s => match s.parse::<u32>() {
+                Ok(col) if (1..=board.width()).contains(&col) && board.can_play(col - 1) => {
+                    board.play(col - 1);
+                    history.push(col - 1);
+                }
+                Ok(col) if (1..=board.width()).contains(&col) => println!("Column {} is full", col),
+                _ => println!("Unknown input '{}' (enter a column 1-{}, u, h or q)", s, board.width()),
+            },
+        }
+    }
+
+    if let Some(cells) = board.winning_cells() {
+        let line: Vec<String> = cells.iter().map(|&(col, row)| explain::cell_name(col, row)).collect();
+        println!("{} ({})", if (board.moves() - 1) % 2 == human { "You win!" } else { "The engine wins." }, line.join(" "));
+    } else {
+        println!("Draw.");
+    }
+    summarize_mistakes(&solver, empty, &history, opening, human, limit);
+}
+
+// --max-time が無いときの 1 回の持ち時間
+pub const DEFAULT_MOVE_TIME: Duration = Duration::from_secs(5);
+
+// f を持ち時間 limit で実行する。時間が来たら監視スレッドが solver を中断する（戻った後の clear_abort は呼び出し側）。
+fn timed<R>(solver: &Arc<Solver>, limit: Duration, f: impl FnOnce() -> R) -> R {
+    // new_solver の --max-time のタイマーは起動から数えるので、先に中断されていても持ち時間は改めて数える
+    solver.clear_abort();
+    let timer = Arc::clone(solver);
+    let _monitor = ProgressMonitor::spawn(Arc::clone(solver), limit, move |_: &Progress| timer.abort());
+    f()
+}
+
+// 盤の図。history は empty から打った手順で、最後の手を強調する（render_board）。
+pub fn render<B: Bits>(empty: &Board<B>, history: &[u32]) -> String {
+    let mut board = *empty;
+    for &col in history { board.play(col); }
+    render_board(&board, history.last().copied(), output::use_color())
+}
+
+const RESET: &str = "\x1b[0m";
+// X は赤、O は黄、両方がすぐ勝てるマスは紫
+const X_COLOR: &str = "\x1b[1;31m";
+const O_COLOR: &str = "\x1b[1;33m";
+const BOTH_COLOR: &str = "\x1b[1;35m";
+const LAST_MOVE: &str = "\x1b[7m";
+
+// 盤の図。上の段から、最後の行に列番号を付ける。
+// color でなければ X (先手) と O (後手) と . (空き) だけで描く。
+// color なら ● (X) / ○ (O) を色付きで描き、last の列の一番上の石（直前の手）を反転表示し、
+// 次に打てるマスのうちどちらかがそこに打てば勝つマスを ◦ (その側の色) で示す。最後に凡例を付ける。
+pub fn render_board<B: Bits>(board: &Board<B>, last: Option<u32>, color: bool) -> String {
+    let w = board.width();
+    let mut out = String::new();
+    for (row, cells) in board.to_grid().iter().enumerate().rev() {
+        let row = row as u32;
+        let line: Vec<String> = cells.iter().enumerate().map(|(col, cell)| {
+            let col = col as u32;
+            if !color { return cell.symbol().to_string(); }
+            match cell {
+                Cell::X | Cell::O => {
+                    let (paint, disc) = if *cell == Cell::X { (X_COLOR, '●') } else { (O_COLOR, '○') };
+                    let last = if last == Some(col) && board.column_height(col) == row + 1 { LAST_MOVE } else { "" };
+                    format!("{}{}{}{}", paint, last, disc, RESET)
+                }
+                Cell::Empty if board.can_play(col) && board.column_height(col) == row && !board.is_over() => {
+                    let mv = board.possible() & board.column_mask(col);
+                    let mine = board.winning_position() & mv != B::ZERO;
+                    let theirs = board.opponent_winning_position() & mv != B::ZERO;
+                    let (x, o) = if board.side_to_move() == Side::X { (mine, theirs) } else { (theirs, mine) };
+                    match (x, o) {
+                        (true, true) => format!("{}◦{}", BOTH_COLOR, RESET),
+                        (true, false) => format!("{}◦{}", X_COLOR, RESET),
+                        (false, true) => format!("{}◦{}", O_COLOR, RESET),
+                        (false, false) => "·".to_string(),
+                    }
+                }
+                Cell::Empty => "·".to_string(),
+            }
+        }).collect();
+        out.push_str(&line.join(" "));
+        out.push('\n');
+    }
+    out.push_str(&(1..=w).map(|col| (col % 10).to_string()).collect::<Vec<_>>().join(" "));
+    if color {
+        out.push_str(&format!("\n{}●{} X  {}○{} O  ◦ wins at once", X_COLOR, RESET, O_COLOR, RESET));
+    }
+    out
+}
+
+// 手番側から見た評価値の説明
+fn describe(score: i8) -> String {
+    match score.signum() {
+        1 => format!("score {:+}: you can win", score),
+        -1 => format!("score {:+}: you lose against best play", score),
+        _ => "score 0: draw with best play".to_string(),
+    }
+}
+
+// 人の手ごとに、その手の評価値と最善の評価値を比べ、下げた手を一覧にする。
+// 勝ち負けが変わった手は blunder、手数だけ変わった手は inaccuracy と呼ぶ。持ち時間は 1 手あたり limit。
+fn summarize_mistakes<B: Bits>(solver: &Arc<Solver>, empty: Board<B>, history: &[u32], opening: usize, human: u32, limit: Duration) {
+    let mut board = empty;
+    for &col in &history[..opening] { board.play(col); }
+    let played = history.len() - opening;
+    let reviews = timed(solver, limit * played as u32, || review_game(solver, board, &history[opening..]));
+    if solver.is_aborted() { println!("Review stopped at the time limit after {} of {} moves.", reviews.len(), played); }
+    let mut mistakes = 0;
+    for r in reviews.iter().filter(|r| (opening as u32 + r.ply) % 2 == human && r.is_mistake()) {
+        mistakes += 1;
+        let kind = if r.is_blunder() { "blunder" } else { "inaccuracy" };
+        println!("Move {}: played {} ({:+}), best was {} ({:+}) - {}", opening as u32 + r.ply + 1, r.col + 1, r.score,
+            r.best_move + 1, r.best_score, kind);
+    }
+    if mistakes == 0 && !solver.is_aborted() { println!("No mistakes: every move kept the best score."); }
+}
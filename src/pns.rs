@@ -0,0 +1,208 @@
+use crate::bits::Bits;
+use crate::board::{Board, MAX_WIDTH};
+use crate::knowledge;
+use crate::solver::Solver;
+use crate::tt::{hash_key, Bound, HashFunction};
+
+// 証明数探索 (df-pn)。「手番側の評価値が target 以上か」を証明 / 反証する。
+// negamax と同じく手番側から見た形で書く: 局面 (target) は、評価値が -target 以下の子があれば、
+// つまり子 (1 - target) のどれかが反証されれば証明される。
+// pn は証明に、dn は反証に必要な（未展開の）局面の数の見積もりで、
+// pn = 子の dn の最小値、dn = 子の pn の和。
+// 探索はしきい値を持たせた深さ優先で、しきい値を超えたらその局面の (pn, dn) を表に残して親に戻る。
+// 証明 / 反証した結果は下限 / 上限として TT にも書くので、alpha-beta の探索とそのまま共有できる。
+// 並列化はしていない。
+
+const INFINITY: u32 = u32::MAX;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Numbers {
+    pn: u32,
+    dn: u32,
+}
+
+const PROVEN: Numbers = Numbers { pn: 0, dn: INFINITY };
+const DISPROVEN: Numbers = Numbers { pn: INFINITY, dn: 0 };
+// 初めて見る局面
+const UNKNOWN: Numbers = Numbers { pn: 1, dn: 1 };
+
+// 途中の (pn, dn) を持つ表。キーには target も混ぜるので、target を変えても消さずに使い回す。
+// 4 エントリのバケットで、同じ局面か空きが無ければ pn + dn の小さい（探索の浅い）エントリを置き換える。
+const PROOF_BUCKET_ENTRIES: usize = 4;
+
+#[derive(Clone, Copy, Default)]
+struct ProofEntry {
+    check: u64,
+    pn: u32,
+    dn: u32,
+}
+
+pub(crate) struct ProofTable {
+    entries: Vec<ProofEntry>,
+    hash: HashFunction,
+}
+
+impl ProofTable {
+    // bytes に収まるエントリ数を 2 のべき乗に切り下げる
+    pub(crate) fn with_bytes(bytes: usize, hash: HashFunction) -> Self {
+        Self { entries: vec![ProofEntry::default(); Self::entries_for(bytes)], hash }
+    }
+
+    fn entries_for(bytes: usize) -> usize {
+        1 << (bytes / std::mem::size_of::<ProofEntry>()).max(PROOF_BUCKET_ENTRIES).ilog2()
+    }
+
+    // with_bytes(bytes) で確保する大きさ
+    pub(crate) fn rounded_bytes(bytes: usize) -> usize {
+        Self::entries_for(bytes) * std::mem::size_of::<ProofEntry>()
+    }
+
+    // 0 は空きの印なので、検証用の値は 0 にならないようにする
+    #[inline(always)]
+    fn check(key: u64, target: i8) -> u64 {
+        (key ^ (target as u8 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)) | 1
+    }
+
+    #[inline(always)]
+    fn bucket(&mut self, check: u64) -> &mut [ProofEntry] {
+        let start = hash_key(self.hash, check, (self.entries.len() - 1) & !(PROOF_BUCKET_ENTRIES - 1));
+        &mut self.entries[start..start + PROOF_BUCKET_ENTRIES]
+    }
+
+    fn get(&mut self, key: u64, target: i8) -> Option<Numbers> {
+        let check = Self::check(key, target);
+        self.bucket(check).iter().find(|e| e.check == check).map(|e| Numbers { pn: e.pn, dn: e.dn })
+    }
+
+    fn put(&mut self, key: u64, target: i8, numbers: Numbers) {
+        let check = Self::check(key, target);
+        let bucket = self.bucket(check);
+        let slot = match bucket.iter().position(|e| e.check == check || e.check == 0) {
+            Some(i) => i,
+            None => (0..PROOF_BUCKET_ENTRIES).min_by_key(|&i| bucket[i].pn.saturating_add(bucket[i].dn)).unwrap_or(0),
+        };
+        bucket[slot] = ProofEntry { check, pn: numbers.pn, dn: numbers.dn };
+    }
+}
+
+impl Solver {
+    // 手番側の評価値が target 以上か。中断された場合の結果は当てにならない。
+    pub(crate) fn prove<B: Bits>(&self, board: Board<B>, target: i8) -> bool {
+        let mut table = self.proof_table().lock().unwrap_or_else(|e| e.into_inner());
+        self.mid(&mut table, board, board.table_key(), target, INFINITY, INFINITY).pn == 0
+    }
+
+    // pn >= pn_limit か dn >= dn_limit になるまで、dn の最も小さい子（証明に一番近い子）を掘り下げる
+    fn mid<B: Bits>(&self, table: &mut ProofTable, board: Board<B>, (key, mirrored): (u64, bool), target: i8,
+                    pn_limit: u32, dn_limit: u32) -> Numbers {
+        if !self.count_node() { return UNKNOWN; }
+        if let Some(proven) = self.evaluate(&board, key, target) {
+            return if proven { PROVEN } else { DISPROVEN };
+        }
+
+        // 相手に即勝ちを与えない手だけを調べる (evaluate で候補が無い局面は除いてある)
+        let forced = board.forced_moves();
+        let mut candidates = board.non_losing_moves();
+        if forced != B::ZERO { candidates &= forced; }
+        let mut children = [(0u32, board, (0u64, false), UNKNOWN); MAX_WIDTH as usize];
+        let mut n = 0;
+        for col in board.column_order() {
+            if candidates & board.column_mask(col) == B::ZERO { continue; }
+            let mut next = board;
+            next.play(col);
+            let key = next.table_key();
+            let numbers = table.get(key.0, 1 - target)
+                .or_else(|| self.evaluate(&next, key.0, 1 - target).map(|proven| if proven { PROVEN } else { DISPROVEN }))
+                .unwrap_or(UNKNOWN);
+            children[n] = (col, next, key, numbers);
+            n += 1;
+        }
+        let children = &mut children[..n];
+
+        loop {
+            let pn = children.iter().map(|c| c.3.dn).min().unwrap_or(INFINITY);
+            let dn = children.iter().fold(0u32, |sum, c| sum.saturating_add(c.3.pn));
+            let numbers = Numbers { pn, dn };
+            if pn == 0 || dn == 0 {
+                // 証明なら反証された子が最善手。反証ならどの手でも届かないので、調べた順の最初の手を入れておく。
+                let col = children.iter().find(|c| c.3.dn == 0).map_or(children[0].0, |c| c.0);
+                let (score, bound) = if pn == 0 { (target, Bound::Lower) } else { (target - 1, Bound::Upper) };
+                if self.uses_table(board.size() - board.moves()) {
+                    let best = if mirrored { board.width() - 1 - col } else { col };
+                    self.table().store(key, score, bound, best, board.size() - board.moves());
+                }
+                table.put(key, target, numbers);
+                return numbers;
+            }
+            if pn >= pn_limit || dn >= dn_limit || self.is_aborted() {
+                table.put(key, target, numbers);
+                return numbers;
+            }
+
+            // dn が最小の子と、2 番目に小さい dn
+            let mut best = 0;
+            let mut second = INFINITY;
+            for (i, c) in children.iter().enumerate().skip(1) {
+                if c.3.dn < children[best].3.dn { second = children[best].3.dn; best = i; }
+                else if c.3.dn < second { second = c.3.dn; }
+            }
+            // 子の dn が 2 番目を超えるか、他の子と合わせた pn の和が dn_limit に届いたら戻ってくる
+            let child = children[best];
+            let child_pn_limit = if dn_limit == INFINITY { INFINITY } else { dn_limit - (dn - child.3.pn) };
+            let child_dn_limit = pn_limit.min(second.saturating_add(1));
+            children[best].3 = self.mid(table, child.1, child.2, 1 - target, child_pn_limit, child_dn_limit);
+        }
+    }
+
+    // 探索せずに分かれば、手番側の評価値が target 以上か
+    fn evaluate<B: Bits>(&self, board: &Board<B>, key: u64, target: i8) -> Option<bool> {
+        let size = board.size();
+        let moves = board.moves();
+        if moves == size { return Some(0 >= target); }
+        if let Some((_, score)) = self.probe_book(board) { return Some(score >= target); }
+        if board.can_win_next() { return Some((size + 1 - moves) as i8 / 2 >= target); }
+
+        let loss = -((size - moves) as i8) / 2;
+        let forced = board.forced_moves();
+        if forced & forced.wrapping_sub(B::ONE) != B::ZERO { return Some(loss >= target); }
+        let mut candidates = board.non_losing_moves();
+        if forced != B::ZERO { candidates &= forced; }
+        if candidates == B::ZERO { return Some(loss >= target); }
+        if moves >= size - 2 { return Some(0 >= target); }
+
+        if -((size - 2 - moves) as i8) / 2 >= target { return Some(true); }
+        if ((size - 1 - moves) as i8 / 2) < target { return Some(false); }
+        if self.uses_table(size - moves) && let Some((score, bound, _)) = self.table().lookup(key) {
+            match bound {
+                Bound::Exact => return Some(score >= target),
+                Bound::Lower if score >= target => return Some(true),
+                Bound::Upper if score < target => return Some(false),
+                _ => {}
+            }
+        }
+        if target > 0 && size - moves >= self.knowledge_depth() && knowledge::follower_holds(board) { return Some(false); }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Algorithm;
+
+    // Pascal Pons のテストセットの、勝ち (1) と負け (-1) が分かっている局面
+    #[test]
+    fn proves_known_results() {
+        let solver = Solver::new(1 << 20).with_algorithm(Algorithm::ProofNumber);
+        for (moves, score) in [("7422341735647741166133573473242566", 1), ("2252576253462244111563365343671351441", -1)] {
+            let board = Board::from_moves(moves).unwrap();
+            {
+                let _search = solver.begin_search();
+                // 勝ちなら「1 以上」が証明でき、負けなら「0 以上」が反証される
+                assert_eq!(solver.prove(board, 1), score > 0, "{}", moves);
+                assert_eq!(solver.prove(board, 0), score >= 0, "{}", moves);
+            }
+            assert_eq!(solver.solve(board), score, "{}", moves);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::bits::Bits;
+use crate::board::Board;
+use crate::solver::Solver;
+use crate::tt::Bound;
+
+// PopOut: 石を落とす代わりに、最下段にある自分の石を抜いてもよい（上の石は 1 段ずつ落ちる）。
+// 抜いた結果両者に並びができたら抜いた側の勝ち。盤が埋まっても終局せず、手を指せなければ引き分け。
+// 同じ局面が探索経路上で繰り返されたら引き分けとする。評価値は勝敗だけ (1/0/-1)。
+// 抜く手で局面が戻れるので経路はいくらでも長くなりうる。盤のマス数の MAX_PATH_FACTOR 倍の深さで打ち切り、
+// そこは繰り返しと同じく（経路に依存した）引き分けとみなす。
+const MAX_PATH_FACTOR: usize = 4;
+
+// 通常ルールの探索と TT を共有しても同じキーにならないように混ぜる値
+const KEY_SALT: u64 = 0xa5c3_9e1f_0b7d_4263;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PopOutMove {
+    Drop(u32),
+    Pop(u32),
+}
+
+// 落とす手は "4"、抜く手は "p4"（1 始まりの列番号）
+impl fmt::Display for PopOutMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PopOutMove::Drop(col) => write!(f, "{}", col + 1),
+            PopOutMove::Pop(col) => write!(f, "p{}", col + 1),
+        }
+    }
+}
+
+impl PopOutMove {
+    // TT の最善手の欄に入れる番号。抜く手は width 以降。
+    fn index(self, width: u32) -> u32 {
+        match self {
+            PopOutMove::Drop(col) => col,
+            PopOutMove::Pop(col) => width + col,
+        }
+    }
+}
+
+// 中央に近い列から、落とす手、抜く手の順
+pub fn legal_moves<B: Bits>(board: &Board<B>) -> Vec<PopOutMove> {
+    let drops = board.legal_moves().into_iter().map(PopOutMove::Drop);
+    let pops = board.column_order().filter(|&col| board.can_pop(col)).map(PopOutMove::Pop);
+    drops.chain(pops).collect()
+}
+
+pub fn play<B: Bits>(board: &mut Board<B>, mv: PopOutMove) {
+    match mv {
+        PopOutMove::Drop(col) => board.play(col),
+        PopOutMove::Pop(col) => board.pop(col),
+    }
+}
+
+// 終局していれば手番側から見た勝敗。両者に並びがあるときは直前に指した側の勝ち。
+pub fn result<B: Bits>(board: &Board<B>) -> Option<i8> {
+    if board.is_win() { Some(-1) }
+    else if board.is_win_for_next() { Some(1) }
+    else if legal_moves(board).is_empty() { Some(0) }
+    else { None }
+}
+
+// "44p4" のような手順を続けて指す。p の次の列番号は抜く手。
+pub fn play_moves<B: Bits>(board: &mut Board<B>, moves: &str) -> Result<(), String> {
+    let mut chars = moves.chars().filter(|c| !c.is_whitespace());
+    let mut n = 0;
+    while let Some(c) = chars.next() {
+        n += 1;
+        let (pop, c) = if c == 'p' || c == 'P' { (true, chars.next().unwrap_or(c)) } else { (false, c) };
+        let col = match c.to_digit(10) {
+            Some(d) if (1..=board.width()).contains(&d) => d - 1,
+            _ => return Err(format!("invalid column '{}' at move {}", c, n)),
+        };
+        if result(board).is_some() { return Err(format!("game is already over before move {}", n)); }
+        let mv = if pop { PopOutMove::Pop(col) } else { PopOutMove::Drop(col) };
+        if !legal_moves(board).contains(&mv) { return Err(format!("illegal move {} at move {}", mv, n)); }
+        play(board, mv);
+    }
+    Ok(())
+}
+
+impl Solver {
+    // PopOut の局面を解く。手番側から見た勝敗 (1/0/-1) を返す。
+    pub fn solve_popout<B: Bits>(&self, board: Board<B>) -> i8 {
+        if let Some(score) = result(&board) { return score; }
+        let _search = self.begin_search();
+        self.popout_negamax(board, -1, 1, &mut HashSet::new()).0
+    }
+
+    // 手番側の最善手とその評価値。終局済みの局面に対して呼ぶと panic する。
+    pub fn best_popout_move<B: Bits>(&self, board: &Board<B>) -> (PopOutMove, i8) {
+        assert!(result(board).is_none(), "best_popout_move called on a finished game");
+        let _search = self.begin_search();
+        let target = self.solve_popout(*board);
+        let mut best: Option<(PopOutMove, i8)> = None;
+        for mv in legal_moves(board) {
+            let mut next = *board;
+            play(&mut next, mv);
+            let score = -self.solve_popout(next);
+            if best.is_none_or(|(_, s)| score > s) { best = Some((mv, score)); }
+            if score == target { break; }
+        }
+        best.expect("unfinished game has a legal move")
+    }
+
+    // 終局していない局面の評価値と、経路上の繰り返しに依存した値か。
+    // 繰り返しで引き分けにした値は経路によって変わるので TT に書かない。
+    fn popout_negamax<B: Bits>(&self, board: Board<B>, mut alpha: i8, mut beta: i8, path: &mut HashSet<u64>) -> (i8, bool) {
+        if !self.count_node() { return (0, false); }
+        let key = board.key().fold() ^ KEY_SALT;
+        if path.contains(&key) || path.len() >= board.size() as usize * MAX_PATH_FACTOR { return (0, true); }
+        let use_table = self.uses_table(board.size() - board.moves());
+        if use_table && let Some((score, bound, _)) = self.table().lookup(key)
+            && let Some(score) = bound.narrow(score, &mut alpha, &mut beta) { return (score, false); }
+
+        let moves = legal_moves(&board);
+        for &mv in &moves {
+            let mut next = board;
+            play(&mut next, mv);
+            if next.is_win() { return (1, false); }
+        }
+
+        let alpha_orig = alpha;
+        let (mut best, mut best_mv, mut history) = (i8::MIN, moves[0], false);
+        path.insert(key);
+        for &mv in &moves {
+            let mut next = board;
+            play(&mut next, mv);
+            // 相手の石だけが並ぶ抜き方は負け。手が無ければ引き分け。
+            let score = if next.is_win_for_next() { -1 }
+                else if legal_moves(&next).is_empty() { 0 }
+                else {
+                    let (score, h) = self.popout_negamax(next, -beta, -alpha, path);
+                    history |= h;
+                    -score
+                };
+            if score > best { best = score; best_mv = mv; }
+            if score > alpha { alpha = score; }
+            if alpha >= beta { break; }
+        }
+        path.remove(&key);
+        if self.is_aborted() { return (0, false); }
+        if !history && use_table {
+            self.table().store(key, best, Bound::of(best, alpha_orig, beta), best_mv.index(board.width()), board.size() - board.moves());
+        }
+        (best, history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pops_can_repeat_a_position() {
+        let mut board: Board = Board::new();
+        play_moves(&mut board, "12p1").unwrap();
+        assert_eq!(board.moves(), 1);
+        // 列 1 は空になり、列 2 の最下段は O（手番）
+        let mut copy = board;
+        assert!(play_moves(&mut copy, "p1").is_err());
+        assert!(legal_moves(&board).contains(&PopOutMove::Pop(1)));
+        play_moves(&mut board, "p2").unwrap();
+        // 抜き合って空の盤の X の手番に戻る
+        assert!(board == Board::new());
+        assert_eq!(result(&board), None);
+    }
+
+    #[test]
+    fn pop_results() {
+        let grid = |rows: &str| Board::<u64>::try_from_grid(&format!("{}{}", ".......\n".repeat(6 - rows.lines().count()), rows), 4).unwrap();
+        // 抜くと相手の石だけが並ぶ: 相手（次の手番）の勝ち
+        let mut board = grid("O....X.\nXOOO.XX\n");
+        play(&mut board, PopOutMove::Pop(0));
+        assert_eq!(result(&board), Some(1));
+        // 両者に並びができれば抜いた側の勝ち
+        let mut board = grid("X......\nOXXX...\nXOOO..O\n");
+        play(&mut board, PopOutMove::Pop(0));
+        assert_eq!(result(&board), Some(-1));
+        // 盤が埋まっても抜ければ終局しない
+        let mut board = Board::<u64>::with_size(2, 2).unwrap().with_connect(3).unwrap();
+        play_moves(&mut board, "1122").unwrap();
+        assert_eq!(result(&board), None);
+        let moves = legal_moves(&board);
+        assert!(moves.len() == 2 && moves.contains(&PopOutMove::Pop(0)) && moves.contains(&PopOutMove::Pop(1)));
+    }
+
+    // 経路上の繰り返しを引き分けにするだけの素朴な探索
+    fn reference<B: Bits>(board: Board<B>, path: &mut Vec<B>) -> i8 {
+        if path.contains(&board.key()) || path.len() >= board.size() as usize * MAX_PATH_FACTOR { return 0; }
+        path.push(board.key());
+        let mut best = -1;
+        for mv in legal_moves(&board) {
+            let mut next = board;
+            play(&mut next, mv);
+            best = best.max(match result(&next) { Some(score) => -score, None => -reference(next, path) });
+            if best == 1 { break; }
+        }
+        path.pop();
+        best
+    }
+
+    // 繰り返しに依存した値を TT に書かないので、経路を全て辿る探索と同じ値になる
+    #[test]
+    fn matches_reference_on_small_boards() {
+        for (width, height, connect) in [(2, 2, 2), (4, 1, 3), (2, 2, 3), (3, 2, 3)] {
+            let board = Board::<u64>::with_size(width, height).unwrap().with_connect(connect).unwrap();
+            let solver = Solver::new(1 << 12);
+            assert_eq!(solver.solve_popout(board), reference(board, &mut Vec::new()), "{}x{} connect {}", width, height, connect);
+        }
+    }
+}
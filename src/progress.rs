@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::solver::Solver;
+use crate::tt::TableStats;
+
+const NO_ROOT_MOVE: u32 = u32::MAX;
+
+// 定期的に通知される探索の進み具合
+#[derive(Clone, Debug)]
+pub struct Progress {
+    pub elapsed: Duration,
+    pub nodes: usize,
+    // 直前の通知からのノード毎秒
+    pub nps: f64,
+    pub table: TableStats,
+    // いま調べている根の手（0 始まりの列番号）。呼び出し側が set_root_move で設定したもの。
+    pub root_move: Option<u32>,
+}
+
+// 進み具合の受け取り先（標準出力、JSON Lines、GUI のチャンネルなど）。監視スレッドから呼ばれる。
+pub trait ProgressSink: Send + 'static {
+    fn progress(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress) + Send + 'static> ProgressSink for F {
+    fn progress(&mut self, progress: &Progress) { self(progress) }
+}
+
+// solver を interval ごとに調べて sink に通知するスレッド。drop すると止まる。
+pub struct ProgressMonitor {
+    root_move: Arc<AtomicU32>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ProgressMonitor {
+    pub fn spawn(solver: Arc<Solver>, interval: Duration, mut sink: impl ProgressSink) -> Self {
+        let root_move = Arc::new(AtomicU32::new(NO_ROOT_MOVE));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (thread_root, thread_stop) = (Arc::clone(&root_move), Arc::clone(&stop));
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            let (mut last_nodes, mut last_time) = (solver.nodes(), start);
+            loop {
+                let deadline = last_time + interval;
+                while !thread_stop.load(Ordering::Relaxed) && Instant::now() < deadline {
+                    thread::park_timeout(deadline.saturating_duration_since(Instant::now()));
+                }
+                if thread_stop.load(Ordering::Relaxed) { break; }
+                let (now, nodes) = (Instant::now(), solver.nodes());
+                let root = thread_root.load(Ordering::Relaxed);
+                sink.progress(&Progress {
+                    elapsed: now - start,
+                    nodes,
+                    nps: (nodes - last_nodes) as f64 / (now - last_time).as_secs_f64(),
+                    table: solver.table_stats(),
+                    root_move: (root != NO_ROOT_MOVE).then_some(root),
+                });
+                (last_nodes, last_time) = (nodes, now);
+            }
+        });
+        Self { root_move, stop, handle: Some(handle) }
+    }
+
+    pub fn set_root_move(&self, col: Option<u32>) {
+        self.root_move.store(col.unwrap_or(NO_ROOT_MOVE), Ordering::Relaxed);
+    }
+}
+
+impl Drop for ProgressMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
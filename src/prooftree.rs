@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use connect4_solver::{outcome_name, Bits, Board, Outcome, Solver};
+
+// 'proof-tree': 局面を解いた後、その評価値を裏付ける木を depth 手先まで Graphviz の DOT で書き出す。
+// 評価値を保証する側（勝ちなら勝つ側、引き分けなら根の手番側で、負けないことを示す）の局面では最善手を 1 つだけ、
+// 相手の局面では全ての手を辿る。相手の手のうち次の手で即負けになるものは辿らずに数だけ書く。
+// 手順違いで同じ局面に着いたら 1 つのノードにまとめるので、木というより DAG になる。
+//
+//   box: 保証する側の手番。その手の辺は太線。
+//   ellipse: 相手の手番。即負けにならない手が 1 つしかない（強制の）局面とその辺は赤。
+//   点線: depth で打ち切った局面
+pub struct ProofTree {
+    pub nodes: usize,
+    pub edges: usize,
+    pub dot: String,
+}
+
+struct Builder<'a, B: Bits> {
+    solver: &'a Solver,
+    root: Board<B>,
+    depth: u32,
+    // 根の手番側が保証する側か
+    root_proves: bool,
+    // 局面 → ノード番号
+    ids: HashMap<B, usize>,
+    body: String,
+    edges: usize,
+}
+
+// root は終局していないこと。中断された場合の内容は当てにならない。
+pub fn build<B: Bits>(solver: &Solver, root: Board<B>, depth: u32) -> ProofTree {
+    let root_proves = solver.solve(root) >= 0;
+    let mut builder = Builder { solver, root, depth, root_proves, ids: HashMap::new(), body: String::new(), edges: 0 };
+    builder.visit(root, &[]);
+    let mut dot = String::from("digraph proof {\n  node [fontname=\"Helvetica\", fontsize=10];\n  edge [fontname=\"Helvetica\", fontsize=10];\n");
+    dot.push_str(&builder.body);
+    dot.push_str("}\n");
+    ProofTree { nodes: builder.ids.len(), edges: builder.edges, dot }
+}
+
+impl<B: Bits> Builder<'_, B> {
+    // board のノードを書いて番号を返す。line は根からの手順（ラベル用）。
+    fn visit(&mut self, board: Board<B>, line: &[u32]) -> usize {
+        if let Some(&id) = self.ids.get(&board.key()) { return id; }
+        let id = self.ids.len();
+        self.ids.insert(board.key(), id);
+        let side = board.side_to_move().symbol();
+        let moves = if line.is_empty() { "root".to_string() } else { line.iter().map(|col| (col + 1).to_string()).collect::<Vec<_>>().join(" ") };
+
+        if board.is_over() {
+            let result = if board.is_win() { format!("{} wins", if side == 'X' { 'O' } else { 'X' }) } else { "draw".to_string() };
+            let _ = writeln!(self.body, "  n{} [label=\"{}\\n{}\", shape=plaintext];", id, moves, result);
+            return id;
+        }
+        let score = self.solver.solve(board);
+        let result = if self.solver.is_weak() { outcome_name(score).to_string() } else { Outcome::from_score(&board, score).to_string() };
+        let label = format!("{}\\n{} to move: {}", moves, side, result);
+        let proving = (board.moves() % 2 == self.root.moves() % 2) == self.root_proves;
+        let shape = if proving { "box" } else { "ellipse" };
+        if line.len() as u32 >= self.depth {
+            let _ = writeln!(self.body, "  n{} [label=\"{}\", shape={}, style=dotted];", id, label, shape);
+            return id;
+        }
+
+        if proving {
+            let (col, _) = self.solver.best_move(&board);
+            let _ = writeln!(self.body, "  n{} [label=\"{}\", shape={}];", id, label, shape);
+            self.edge(board, line, id, col, "penwidth=2.5");
+            return id;
+        }
+        // 相手の手。次の手で即負けになる手は辿らない。
+        let replies: Vec<u32> = board.legal_moves().iter().copied().filter(|&col| {
+            let mut next = board;
+            next.play(col);
+            next.is_over() || !next.can_win_next()
+        }).collect();
+        let losing = board.legal_moves().len() - replies.len();
+        let note = if losing > 0 { format!("\\n(+{} losing at once)", losing) } else { String::new() };
+        let color = if replies.len() == 1 { ", color=red" } else { "" };
+        let _ = writeln!(self.body, "  n{} [label=\"{}{}\", shape={}{}];", id, label, note, shape, color);
+        for col in replies {
+            self.edge(board, line, id, col, if color.is_empty() { "" } else { "color=red" });
+        }
+        id
+    }
+
+    fn edge(&mut self, board: Board<B>, line: &[u32], from: usize, col: u32, style: &str) {
+        let mut next = board;
+        next.play(col);
+        let line = [line, &[col]].concat();
+        let to = self.visit(next, &line);
+        let sep = if style.is_empty() { "" } else { ", " };
+        let _ = writeln!(self.body, "  n{} -> n{} [label=\"{}\"{}{}];", from, to, col + 1, sep, style);
+        self.edges += 1;
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+
+use connect4_solver::{splitmix64, Bits, Board, Solver};
+
+use crate::output::{self, info, JsonObject};
+use crate::play::render;
+
+// 「勝ちを見つけよ」問題を作る。空の盤 root からランダムな対局（相手に即勝ちを与えない手から一様に選ぶ）を終局まで打ち、
+// 盤が半分以上埋まった局面を終局の側から調べて、勝てる手がただ 1 つで、その手で win_in 手目に勝てる局面を集める。
+// win_in が None なら手数は問わない。
+//
+// text: 問題ごとに次の形で出力し、空行で区切る。
+//   # puzzle <番号>: <X|O> to move, win in <手数>
+//   moves: <手順>
+//   <盤の図: 上の段から X / O / .、最後の行に列番号>
+//   solution: <勝つまでの最善手順>
+pub fn run<B: Bits>(solver: &Solver, root: Board<B>, count: usize, win_in: Option<u32>, seed: u64) -> usize {
+    let mut rng = seed;
+    let mut seen = HashSet::new();
+    let mut found = 0;
+    let mut games = 0;
+    while found < count && !solver.is_aborted() {
+        games += 1;
+        let moves = random_game(root, &mut rng);
+        let mut board = root;
+        let positions: Vec<Board<B>> = moves.iter().map(|&col| { let b = board; board.play(col); b }).collect();
+        for (ply, board) in positions.iter().enumerate().rev() {
+            if found == count || solver.is_aborted() { break; }
+            if board.moves() * 2 < board.size() { break; }
+            if !seen.insert(board.table_key().0) { continue; }
+            let Some((col, k)) = unique_win(solver, board) else { continue };
+            if win_in.is_some_and(|n| n != k) { continue; }
+            let mut next = *board;
+            next.play(col);
+            let mut solution = vec![col];
+            if !next.is_win() { solution.extend(solver.principal_variation(next)); }
+            if solver.is_aborted() { break; }
+            found += 1;
+            print_puzzle(found, &moves[..ply], board, k, &solution, root);
+        }
+    }
+    info!("Found {} puzzles in {} random games.", found, games);
+    found
+}
+
+// 勝てる手がただ 1 つなら (その手, 何手目で勝てるか)
+fn unique_win<B: Bits>(solver: &Solver, board: &Board<B>) -> Option<(u32, u32)> {
+    let scores = solver.move_scores(board);
+    if solver.is_aborted() { return None; }
+    let mut wins = scores.iter().enumerate().filter_map(|(col, s)| s.filter(|&s| s > 0).map(|s| (col as u32, s)));
+    let (col, score) = wins.next()?;
+    if wins.next().is_some() { return None; }
+    // 手番側の k 手目で勝つと評価値は (size + 1 - moves) / 2 - (k - 1)
+    let k = ((board.size() + 1 - board.moves()) / 2) as i32 - score as i32 + 1;
+    Some((col, k as u32))
+}
+
+// root から終局まで、相手に即勝ちを与えない手（無ければ打てる手）から一様に選んで打った手順
+fn random_game<B: Bits>(root: Board<B>, rng: &mut u64) -> Vec<u32> {
+    let mut board = root;
+    let mut moves = Vec::new();
+    while !board.is_over() {
+        let safe = board.non_losing_moves();
+        let playable = board.legal_moves();
+        let preferred: Vec<u32> = playable.iter().copied().filter(|&col| safe & board.column_mask(col) != B::ZERO).collect();
+        let cols = if preferred.is_empty() { &playable[..] } else { &preferred[..] };
+        let col = cols[(splitmix64(rng) % cols.len() as u64) as usize];
+        board.play(col);
+        moves.push(col);
+    }
+    moves
+}
+
+fn print_puzzle<B: Bits>(number: usize, history: &[u32], board: &Board<B>, win_in: u32, solution: &[u32], root: Board<B>) {
+    let to_move = board.side_to_move().symbol().to_string();
+    let moves: String = history.iter().map(|c| (c + 1).to_string()).collect();
+    if output::is_json() {
+        JsonObject::new("puzzle").num("number", number).str("moves", &moves).str("to_move", &to_move).num("win_in", win_in)
+            .columns("solution", solution).emit();
+        return;
+    }
+    println!("# puzzle {}: {} to move, win in {}", number, to_move, win_in);
+    println!("moves: {}", moves);
+    println!("{}", render(&root, history));
+    println!("solution: {}", solution.iter().map(|c| (c + 1).to_string()).collect::<Vec<_>>().join(" "));
+    println!();
+}
@@ -0,0 +1,309 @@
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use redis::{Client, Connection, ConnectionAddr, ConnectionInfo, RedisConnectionInfo, RedisResult};
+
+use crate::remote::RemoteCache;
+use crate::tt::Bound;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+// 繋がらなかったときに次に繋ぎ直すまでの待ち時間。失敗するたびに倍にする。
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+// 書き込み待ちの SET の上限。溢れた分は捨てる（探索を待たせないため）。
+const QUEUE_LEN: usize = 1 << 16;
+// 1 回のパイプラインで送る SET の数
+const BATCH: usize = 1024;
+
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> { m.lock().unwrap_or_else(|e| e.into_inner()) }
+
+// 探索スレッドと書き込みスレッドで共有する接続のプール
+struct Link {
+    client: Client,
+    idle: Mutex<Vec<Connection>>,
+    // (これより前には繋ぎ直さない時刻, 次に失敗したときの待ち時間)
+    retry: Mutex<(Option<Instant>, Duration)>,
+    errors: AtomicUsize,
+}
+
+impl Link {
+    fn open(&self) -> RedisResult<Connection> {
+        let conn = self.client.get_connection_with_timeout(TIMEOUT)?;
+        conn.set_read_timeout(Some(TIMEOUT))?;
+        conn.set_write_timeout(Some(TIMEOUT))?;
+        Ok(conn)
+    }
+
+    // 空いている接続を借りる。無ければ新しく繋ぐが、待ち時間の間は繋がずに None を返す。
+    fn take(&self) -> Option<Connection> {
+        if let Some(conn) = lock(&self.idle).pop() { return Some(conn); }
+        if lock(&self.retry).0.is_some_and(|until| Instant::now() < until) { return None; }
+        match self.open() {
+            Ok(conn) => {
+                *lock(&self.retry) = (None, MIN_BACKOFF);
+                Some(conn)
+            }
+            Err(_) => {
+                self.failed();
+                None
+            }
+        }
+    }
+
+    // サーバが落ちたなら他の接続も使えないので、空いている接続も捨てて待ち時間を延ばす
+    fn failed(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        lock(&self.idle).clear();
+        let mut retry = lock(&self.retry);
+        retry.0 = Some(Instant::now() + retry.1);
+        retry.1 = (retry.1 * 2).min(MAX_BACKOFF);
+    }
+
+    // 接続を 1 本借りて f を実行し、成功したら返す。失敗した接続は捨てる。
+    fn run<T>(&self, f: impl FnOnce(&mut Connection) -> RedisResult<T>) -> Option<T> {
+        let mut conn = self.take()?;
+        match f(&mut conn) {
+            Ok(value) => {
+                lock(&self.idle).push(conn);
+                Some(value)
+            }
+            Err(_) => {
+                self.failed();
+                None
+            }
+        }
+    }
+}
+
+// Redis を使う RemoteCache。GET は探索スレッドがプールから借りた接続で 1 往復ずつ送り、
+// SET はキューに積んで書き込みスレッドがまとめてパイプラインで送る。
+// キーは "<namespace>:<key の 16 進>"、値は "<評価値> <E|L|U> <最善手の列>"。
+// namespace には盤の大きさ・何目並べ・キーの作り方を入れるので、違う設定の Solver が同じサーバを使っても混ざらない。
+// 通信に失敗したら接続を捨て、待ち時間を倍にしながら繋ぎ直す。繋がらない間の GET は見つからなかったのと同じで、SET は捨てる。
+pub struct RedisCache {
+    namespace: String,
+    link: Arc<Link>,
+    queue: Option<SyncSender<(String, String)>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl RedisCache {
+    // addr は host:port。password があれば AUTH する。最初の接続に失敗したらエラー。
+    pub fn connect(addr: &str, password: Option<&str>, width: u32, height: u32, connect: u32) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("expected host:port, got '{}'", addr));
+        let (host, port) = addr.rsplit_once(':').ok_or_else(invalid)?;
+        let port = port.parse().map_err(|_| invalid())?;
+        let info = ConnectionInfo {
+            addr: ConnectionAddr::Tcp(host.to_string(), port),
+            redis: RedisConnectionInfo { password: password.map(str::to_string), ..Default::default() },
+        };
+        let link = Link {
+            client: Client::open(info).map_err(io::Error::other)?,
+            idle: Mutex::new(Vec::new()),
+            retry: Mutex::new((None, MIN_BACKOFF)),
+            errors: AtomicUsize::new(0),
+        };
+        let mut conn = link.open().map_err(io::Error::other)?;
+        let pong: String = redis::cmd("PING").query(&mut conn).map_err(io::Error::other)?;
+        if pong != "PONG" { return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected reply to PING")); }
+        lock(&link.idle).push(conn);
+
+        let link = Arc::new(link);
+        let (queue, pending) = mpsc::sync_channel(QUEUE_LEN);
+        let writer = thread::Builder::new().name("redis-writer".into()).spawn({
+            let link = Arc::clone(&link);
+            move || write_batches(&link, pending)
+        })?;
+        let scheme = if cfg!(feature = "zobrist") { "z" } else { "p" };
+        Ok(Self {
+            namespace: format!("c4:{}x{}x{}:{}", width, height, connect, scheme),
+            link,
+            queue: Some(queue),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn namespace(&self) -> &str { &self.namespace }
+
+    // 通信に失敗した回数（繋ぎ直しに失敗した回数も含む）
+    pub fn errors(&self) -> usize { self.link.errors.load(Ordering::Relaxed) }
+}
+
+// キューが閉じるまで、溜まった SET を BATCH 個ずつパイプラインで送る
+fn write_batches(link: &Link, pending: Receiver<(String, String)>) {
+    while let Ok(first) = pending.recv() {
+        let mut pipe = redis::pipe();
+        for (name, value) in std::iter::once(first).chain(pending.try_iter().take(BATCH - 1)) {
+            pipe.cmd("SET").arg(name).arg(value).ignore();
+        }
+        link.run(|conn| pipe.query::<()>(conn));
+    }
+}
+
+impl Drop for RedisCache {
+    // キューを閉じ、積んであった SET を送り終えるのを待つ
+    fn drop(&mut self) {
+        self.queue.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl RemoteCache for RedisCache {
+    fn get(&self, key: u64) -> Option<(i8, Bound, u32)> {
+        let name = format!("{}:{:016x}", self.namespace, key);
+        let value: String = self.link.run(|conn| redis::cmd("GET").arg(&name).query::<Option<String>>(conn))??;
+        let mut fields = value.split(' ');
+        let score = fields.next()?.parse().ok()?;
+        let bound = match fields.next()? {
+            "E" => Bound::Exact,
+            "L" => Bound::Lower,
+            "U" => Bound::Upper,
+            _ => return None,
+        };
+        let best_col = fields.next()?.parse().ok()?;
+        Some((score, bound, best_col))
+    }
+
+    fn put(&self, key: u64, score: i8, bound: Bound, best_col: u32) {
+        let name = format!("{}:{:016x}", self.namespace, key);
+        let bound = match bound {
+            Bound::Exact => "E",
+            Bound::Lower => "L",
+            Bound::Upper => "U",
+        };
+        if let Some(queue) = &self.queue {
+            let _ = queue.try_send((name, format!("{} {} {}", score, bound, best_col)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{Shutdown, TcpListener, TcpStream};
+    use std::sync::atomic::AtomicBool;
+
+    // テスト用の小さな RESP サーバ。GET / SET / PING / AUTH だけを覚え、他のコマンドには +OK を返す。
+    // stop() で全ての接続を切って待ち受けをやめる。
+    struct LocalServer {
+        port: u16,
+        stop: Arc<AtomicBool>,
+        streams: Arc<Mutex<Vec<TcpStream>>>,
+        acceptor: Option<JoinHandle<()>>,
+    }
+
+    impl LocalServer {
+        fn start(port: u16, data: Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>) -> Self {
+            let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+            listener.set_nonblocking(true).unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let stop = Arc::new(AtomicBool::new(false));
+            let streams = Arc::new(Mutex::new(Vec::new()));
+            let acceptor = thread::spawn({
+                let (stop, streams) = (Arc::clone(&stop), Arc::clone(&streams));
+                move || while !stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            stream.set_nonblocking(false).unwrap();
+                            lock(&streams).push(stream.try_clone().unwrap());
+                            let data = Arc::clone(&data);
+                            thread::spawn(move || serve(stream, data));
+                        }
+                        Err(_) => thread::sleep(Duration::from_millis(5)),
+                    }
+                }
+            });
+            Self { port, stop, streams, acceptor: Some(acceptor) }
+        }
+
+        fn stop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+            self.acceptor.take().unwrap().join().unwrap();
+            for stream in lock(&self.streams).drain(..) {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+
+    fn serve(stream: TcpStream, data: Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>) {
+        let mut out = stream.try_clone().unwrap();
+        let mut r = BufReader::new(stream);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if r.read_line(&mut line).unwrap_or(0) == 0 { return; }
+            let argc: usize = line.trim_end()[1..].parse().unwrap();
+            let mut args = Vec::new();
+            for _ in 0..argc {
+                line.clear();
+                r.read_line(&mut line).unwrap();
+                let len: usize = line.trim_end()[1..].parse().unwrap();
+                let mut arg = vec![0u8; len + 2];
+                r.read_exact(&mut arg).unwrap();
+                arg.truncate(len);
+                args.push(arg);
+            }
+            let reply = match args[0].to_ascii_uppercase().as_slice() {
+                b"PING" => b"+PONG\r\n".to_vec(),
+                b"GET" => match lock(&data).get(&args[1]) {
+                    Some(v) => [format!("${}\r\n", v.len()).as_bytes(), v, b"\r\n"].concat(),
+                    None => b"$-1\r\n".to_vec(),
+                },
+                b"SET" => {
+                    lock(&data).insert(args[1].clone(), args[2].clone());
+                    b"+OK\r\n".to_vec()
+                }
+                _ => b"+OK\r\n".to_vec(),
+            };
+            if out.write_all(&reply).is_err() { return; }
+        }
+    }
+
+    fn wait_for(mut f: impl FnMut() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(10) {
+            if f() { return true; }
+            thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn stores_and_reconnects() {
+        let data = Arc::new(Mutex::new(HashMap::new()));
+        let mut server = LocalServer::start(0, Arc::clone(&data));
+        let port = server.port;
+        let cache = RedisCache::connect(&format!("127.0.0.1:{}", port), None, 7, 6, 4).unwrap();
+        cache.put(42, 3, Bound::Lower, 2);
+        assert!(wait_for(|| cache.get(42).is_some()));
+        assert_eq!(cache.get(42), Some((3, Bound::Lower, 2)));
+        assert_eq!(cache.get(43), None);
+
+        // サーバが落ちている間は見つからないだけで、立ち上がり直せば繋ぎ直す
+        server.stop();
+        assert_eq!(cache.get(42), None);
+        assert!(cache.errors() > 0);
+        let _server = LocalServer::start(port, Arc::clone(&data));
+        assert!(wait_for(|| cache.get(42) == Some((3, Bound::Lower, 2))));
+        cache.put(7, -1, Bound::Exact, 0);
+        drop(cache);
+        assert!(lock(&data).keys().any(|k| k.ends_with(b":0000000000000007")));
+    }
+
+    // 本物の Redis で確かめる: redis-server を 127.0.0.1:6379 で動かして cargo test -- --ignored
+    #[test]
+    #[ignore]
+    fn stores_in_redis_server() {
+        let cache = RedisCache::connect("127.0.0.1:6379", None, 7, 6, 4).unwrap();
+        cache.put(0x1234, -5, Bound::Upper, 6);
+        assert!(wait_for(|| cache.get(0x1234) == Some((-5, Bound::Upper, 6))));
+    }
+}
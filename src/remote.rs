@@ -0,0 +1,22 @@
+use crate::tt::Bound;
+
+// 複数の Solver（別のマシンでもよい）で解いた結果を共有する、TT の外側のキャッシュ。
+// Solver は TT に無かった局面だけを引き、引けたものは TT にも入れる。TT に書くときは同じものをここにも書く。
+// key は Board::table_key の値（左右対称な局面で共通、best_col は正規化した向き）。
+// 失敗しても探索は続けられるので、get は見つからなかったのと同じ None を返し、put のエラーは捨ててよい。
+pub trait RemoteCache: Send + Sync {
+    fn get(&self, key: u64) -> Option<(i8, Bound, u32)>;
+    fn put(&self, key: u64, score: i8, bound: Bound, best_col: u32);
+}
+
+// Solver が数えるリモートキャッシュの利用状況
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RemoteStats {
+    pub probes: usize,
+    pub hits: usize,
+    pub stores: usize,
+}
+
+impl RemoteStats {
+    pub fn hit_rate(&self) -> f64 { self.hits as f64 / self.probes.max(1) as f64 }
+}
@@ -0,0 +1,97 @@
+use crate::bits::Bits;
+use crate::board::Board;
+use crate::solver::Solver;
+
+// 打たれた手の検討結果。評価値はどちらもその手を打った側から見たもの。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveReview {
+    // 開始局面からの手の番号（0 始まり）
+    pub ply: u32,
+    pub col: u32,
+    // 打った手の評価値
+    pub score: i8,
+    // 最善手とその評価値
+    pub best_move: u32,
+    pub best_score: i8,
+}
+
+impl MoveReview {
+    // 最善より評価値が低い手
+    pub fn is_mistake(&self) -> bool { self.score < self.best_score }
+
+    // 勝ち負けが変わった手（勝ち→引き分け、引き分け→負けなど）
+    pub fn is_blunder(&self) -> bool { self.score.signum() < self.best_score.signum() }
+}
+
+// analyze_moves が最善でない手に付ける反駁手順の既定の長さ
+pub const DEFAULT_REFUTATION_PLIES: usize = 8;
+
+// 局面の合法手の 1 つと、その手を打った側から見た評価値
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveScore {
+    pub col: u32,
+    pub score: i8,
+    // 最善でない手なら、その手の後の相手の応手から始まる最善手順（どう負けるか、勝ちを逃すか）。最善手なら空。
+    pub refutation: Vec<u32>,
+}
+
+impl MoveScore {
+    // 打つと負ける手
+    pub fn is_losing(&self) -> bool { self.score < 0 }
+}
+
+// 局面の合法手をすべて解き、評価値の高い順（同点なら中央寄りの順）に並べて返す。
+// 最善でない手には、解いた後の TT から最初の refutation_plies 手の反駁手順を復元して付ける。
+// 途中で solver が中断されたら、それまでに解いた手だけを返す。終局済みの局面なら空。
+pub fn analyze_moves<B: Bits>(solver: &Solver, board: &Board<B>, refutation_plies: usize) -> Vec<MoveScore> {
+    if board.is_over() { return Vec::new(); }
+    let mut scores = Vec::with_capacity(board.width() as usize);
+    for col in board.legal_moves() {
+        let mut next = *board;
+        next.play(col);
+        let score = if next.is_win() { solver.report((board.size() + 1 - board.moves()) as i8 / 2) } else { -solver.solve(next) };
+        if solver.is_aborted() { break; }
+        scores.push(MoveScore { col, score, refutation: Vec::new() });
+    }
+    // legal_moves は中央寄りの順なので、安定ソートで同点の並びが保たれる
+    scores.sort_by_key(|m| std::cmp::Reverse(m.score));
+    let Some(best) = scores.first().map(|m| m.score) else { return scores };
+    for m in scores.iter_mut().filter(|m| m.score < best) {
+        let mut next = *board;
+        next.play(m.col);
+        let refutation = solver.principal_variation_plies(next, refutation_plies);
+        if solver.is_aborted() { break; }
+        m.refutation = refutation;
+    }
+    scores
+}
+
+// 勝ち / 引き分け / 負け
+pub fn outcome_name(score: i8) -> &'static str {
+    match score.signum() {
+        1 => "win",
+        -1 => "loss",
+        _ => "draw",
+    }
+}
+
+// start から moves（0 始まりの列番号）を打った対局の各手を解いて、最善手と比べる。
+// 途中で solver が中断されたら、それまでに調べた手だけを返す。
+// moves は start から打てる手順であること（打てない手があると panic する）。
+pub fn review_game<B: Bits>(solver: &Solver, start: Board<B>, moves: &[u32]) -> Vec<MoveReview> {
+    let mut board = start;
+    let mut reviews = Vec::with_capacity(moves.len());
+    for (ply, &col) in moves.iter().enumerate() {
+        assert!(!board.is_win() && board.can_play(col), "review_game called with an illegal move");
+        let (best_move, best_score) = solver.best_move(&board);
+        let mut next = board;
+        next.play(col);
+        let score = if next.is_win() { solver.report((board.size() + 1 - board.moves()) as i8 / 2) } else { -solver.solve(next) };
+        if solver.is_aborted() { break; }
+        reviews.push(MoveReview { ply: ply as u32, col, score, best_move, best_score });
+        board = next;
+    }
+    reviews
+}
@@ -0,0 +1,123 @@
+use std::io::{self, Write};
+
+use connect4_solver::{splitmix64, Bits, Board, Solver};
+
+use crate::output::info;
+
+// 自己対局で機械学習用のデータを作る。対局中の各局面（終局した局面は除く）について、
+// 解いた評価値と、列ごとに打った後の評価値（最善手の分布はこれが最大の列に一様）を書き出す。
+//
+// csv:    game,ply,moves,score,best,s1,...,s<幅>
+//         moves は開始局面からの手順（1 始まりの列番号）、best は評価値が最大の列を並べたもの、
+//         s<列> はその列に打った場合の評価値（打てない列は空）
+// binary: 先頭に "C4SP" と幅・高さ・並べる数の 3 バイト。以降は局面ごとに
+//         [手数 n: u8][手順: 0 始まりの列番号 n バイト][評価値: i8][列ごとの評価値: i8 x 幅 (打てない列は -128)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    Csv,
+    Binary,
+}
+
+impl DataFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(DataFormat::Csv),
+            "binary" => Some(DataFormat::Binary),
+            _ => None,
+        }
+    }
+}
+
+const BINARY_MAGIC: &[u8; 4] = b"C4SP";
+const NO_SCORE: i8 = i8::MIN;
+
+// splitmix64 の状態
+struct Random(u64);
+
+impl Random {
+    // [0, 1) の一様乱数
+    fn unit(&mut self) -> f64 {
+        (splitmix64(&mut self.0) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// 評価値から手を選ぶ。temperature が 0 なら最善手から一様に、正なら exp((評価値 - 最大) / temperature) に比例して選ぶ。
+fn choose(scores: &[Option<i8>], temperature: f64, rng: &mut Random) -> u32 {
+    let best = scores.iter().flatten().copied().max().expect("unfinished game has a legal move");
+    let weights: Vec<f64> = scores.iter().map(|s| match *s {
+        Some(s) if temperature > 0.0 => ((s - best) as f64 / temperature).exp(),
+        Some(s) if s == best => 1.0,
+        _ => 0.0,
+    }).collect();
+    let mut r = rng.unit() * weights.iter().sum::<f64>();
+    for (col, &w) in weights.iter().enumerate() {
+        if w > 0.0 && r < w { return col as u32; }
+        r -= w;
+    }
+    // 丸め誤差で最後まで来たら、最後の打てる列
+    weights.iter().rposition(|&w| w > 0.0).unwrap_or(0) as u32
+}
+
+// root から games 局の自己対局をして out に書き出し、書き終えた対局の数を返す。
+// solver が中断されたら、途中の対局は書かずに止める。
+pub fn run<B: Bits>(solver: &Solver, root: Board<B>, games: usize, temperature: f64, seed: u64,
+    format: DataFormat, out: &mut impl Write) -> io::Result<usize> {
+    let width = root.width() as usize;
+    match format {
+        DataFormat::Csv => {
+            let columns: Vec<String> = (1..=width).map(|col| format!("s{}", col)).collect();
+            writeln!(out, "game,ply,moves,score,best,{}", columns.join(","))?;
+        }
+        DataFormat::Binary => {
+            out.write_all(BINARY_MAGIC)?;
+            out.write_all(&[root.width() as u8, root.height() as u8, root.connect() as u8])?;
+        }
+    }
+
+    let mut rng = Random(seed);
+    let mut positions = 0;
+    for game in 0..games {
+        let mut board = root;
+        let mut moves: Vec<u32> = Vec::new();
+        let mut records = Vec::new();
+        while !board.is_over() {
+            let scores = solver.move_scores(&board);
+            if solver.is_aborted() { break; }
+            records.push((moves.clone(), scores.clone()));
+            let col = choose(&scores, temperature, &mut rng);
+            board.play(col);
+            moves.push(col);
+        }
+        if solver.is_aborted() {
+            info!("Stopped during game {}; {} games written.", game + 1, game);
+            return Ok(game);
+        }
+        for (ply, (moves, scores)) in records.iter().enumerate() {
+            write_record(out, format, game, ply, moves, scores)?;
+        }
+        positions += records.len();
+        info!("Game {}/{}: {} ({} positions, {} total)", game + 1, games,
+            moves.iter().map(|c| (c + 1).to_string()).collect::<String>(), records.len(), positions);
+    }
+    Ok(games)
+}
+
+fn write_record(out: &mut impl Write, format: DataFormat, game: usize, ply: usize, moves: &[u32], scores: &[Option<i8>]) -> io::Result<()> {
+    let best = scores.iter().flatten().copied().max().expect("recorded positions have a legal move");
+    match format {
+        DataFormat::Csv => {
+            let path: String = moves.iter().map(|c| (c + 1).to_string()).collect();
+            let best_cols: String = (0..scores.len()).filter(|&col| scores[col] == Some(best)).map(|col| (col + 1).to_string()).collect();
+            let cols: Vec<String> = scores.iter().map(|s| s.map_or(String::new(), |s| s.to_string())).collect();
+            writeln!(out, "{},{},{},{},{},{}", game, ply, path, best, best_cols, cols.join(","))
+        }
+        DataFormat::Binary => {
+            let mut record = Vec::with_capacity(2 + moves.len() + scores.len());
+            record.push(moves.len() as u8);
+            record.extend(moves.iter().map(|&c| c as u8));
+            record.push(best as u8);
+            record.extend(scores.iter().map(|s| s.unwrap_or(NO_SCORE) as u8));
+            out.write_all(&record)
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use connect4_solver::{Bits, Board, ServeMessage, Solver};
+
+use crate::logging::{event, span};
+use crate::output::{info, warning};
+use crate::signal;
+use crate::websocket;
+
+// JSON を返す小さな HTTP サーバ。TT は全リクエストで共有するので、使うほど速くなる。
+// 応答の形式は ServeMessage (src/wire.rs) で、serde_json で書く。
+//
+//   GET /solve?pos=4453    -> {"type":"solve","position":"4453","score":..,"best_move":..,"pv":[..],..}
+//   GET /analyze?pos=4453  -> {"type":"analyze","position":"4453","scores":[..]}  列ごとの評価値 (打てない列は null)
+//   /solve と /analyze は grid=<上の段から / 区切りの X/O/. の図> でも局面を受け付ける（pos はその上に打つ）。
+//   図は Board::try_from_grid で検証し、実戦で現れない局面は 400 にする。
+//   GET /ws[?pos=4453]     WebSocket。pos か、テキストメッセージで送られた局面を解きながら
+//                          bounds (範囲が狭まるたび) と progress (0.5 秒ごと) を流し、最後に result を送る。
+//
+// 列番号は 1 始まり、評価値は pos の局面で手番の側から見た値。
+//
+// 探索は 1 つずつ行う（1 回の探索で Solver のスレッドを全て使うので、並べても速くはならない）。
+// --max-time と --max-nodes はリクエストごとの上限になり、解き切れなければ 503 を返す。
+//
+// 接続ごとにスレッドを立てるので、同時に扱う接続は MAX_CONNECTIONS までにして、それを超えた接続には 503 を返す。
+// 遅いクライアントにスレッドを取られ続けないように、読み書きには時間の上限を付ける。
+
+// 同時に扱う接続の上限
+const MAX_CONNECTIONS: usize = 64;
+// リクエストの行とヘッダを合わせた大きさの上限。超えたら 431 を返して閉じる。
+const MAX_HEADER_BYTES: u64 = 8 * 1024;
+// リクエストの行とヘッダを読み終えるまで、およびレスポンスを書くときの待ち時間の上限
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+// WebSocket で次のメッセージを待つ時間の上限。これを過ぎたら接続を閉じる。
+const WEBSOCKET_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub fn run<B: Bits>(solver: Arc<Solver>, empty: Board<B>, bind: &str, port: u16) -> io::Result<()> {
+    let server = Arc::new(Server { solver, searching: Mutex::new(()) });
+    let listener = TcpListener::bind((bind, port))?;
+    // Ctrl-C で止められるように accept はブロックさせずに回す
+    listener.set_nonblocking(true)?;
+    info!("Listening on http://{}", listener.local_addr()?);
+    let active = Arc::new(AtomicUsize::new(0));
+    while !signal::interrupted() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if active.load(Ordering::Relaxed) >= MAX_CONNECTIONS {
+                    event!(WARN, "request.rejected", active = MAX_CONNECTIONS);
+                    let _ = reject(&stream);
+                    continue;
+                }
+                let (server, connection) = (Arc::clone(&server), Connection::open(&active));
+                thread::spawn(move || {
+                    let _connection = connection;
+                    if let Err(e) = handle(&server, empty, stream) {
+                        warning!("request failed: {}", e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(20)),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+struct Server {
+    solver: Arc<Solver>,
+    // 探索中は持っておく。Solver の上限は実行中の探索をまとめて数えるので、リクエストを重ねると上限が混ざる。
+    searching: Mutex<()>,
+}
+
+impl Server {
+    // 1 回の探索として f を実行する（上限はここから数える）
+    fn search<R>(&self, f: impl FnOnce(&Solver) -> R) -> R {
+        let _searching = self.searching.lock().unwrap_or_else(|e| e.into_inner());
+        let _search = self.solver.begin_search();
+        f(&self.solver)
+    }
+}
+
+// 扱っている接続の数。スレッドが panic しても drop で数を戻す。
+struct Connection(Arc<AtomicUsize>);
+
+impl Connection {
+    fn open(active: &Arc<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::Relaxed);
+        Self(Arc::clone(active))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) { self.0.fetch_sub(1, Ordering::Relaxed); }
+}
+
+// 接続の上限を超えたときは、リクエストを読まずに 503 を返して閉じる（accept のループで呼ぶので待ち時間は短くする）
+fn reject(stream: &TcpStream) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+    respond(stream, 503, &error("too many connections"))
+}
+
+fn handle<B: Bits>(server: &Server, empty: Board<B>, stream: TcpStream) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    // ヘッダは MAX_HEADER_BYTES までしか読まない。改行の来ない行を送り続けられてもバッファが伸び続けないように。
+    let mut reader = BufReader::new(&stream).take(MAX_HEADER_BYTES);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // WebSocket のハンドシェイクに使う Sec-WebSocket-Key 以外のヘッダは読み飛ばす
+    let mut ws_key = None;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        if let Some((name, value)) = header.split_once(':')
+            && name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+            ws_key = Some(value.trim().to_string());
+        }
+        header.clear();
+    }
+    // 空行まで届かずに上限で読むのを止めた
+    if header.is_empty() && reader.limit() == 0 {
+        event!(WARN, "request.rejected", bytes = MAX_HEADER_BYTES);
+        return respond(&stream, 431, &error("request header too large"));
+    }
+    let reader = reader.into_inner();
+    if let ["GET", target, ..] = request_line.split_whitespace().collect::<Vec<_>>().as_slice()
+        && let (path, query) = target.split_once('?').unwrap_or((target, ""))
+        && path == "/ws"
+        && let Some(key) = ws_key {
+        return websocket(server, empty, reader, &key, query_pos(query));
+    }
+
+    let _span = span!(INFO, "request", line = request_line.trim());
+    let (status, body) = match request_line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["GET", target, ..] => route(server, empty, target),
+        _ => (405, error("only GET is supported")),
+    };
+    event!(INFO, "request.response", status = status, bytes = body.len());
+    respond(&stream, status, &body)
+}
+
+fn respond(mut stream: &TcpStream, status: u16, body: &str) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        431 => "Request Header Fields Too Large",
+        _ => "Service Unavailable",
+    };
+    write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+        Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}", status, reason, body.len(), body)?;
+    stream.flush()
+}
+
+fn route<B: Bits>(server: &Server, empty: Board<B>, target: &str) -> (u16, String) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let pos = query_pos(query);
+    let grid = query.split('&').find_map(|kv| kv.strip_prefix("grid="));
+    let board = match (path, parse_position(empty, grid, pos)) {
+        ("/solve" | "/analyze", Ok(board)) => board,
+        ("/solve" | "/analyze", Err(e)) => return (400, error(&e)),
+        _ => return (404, error("not found")),
+    };
+    if board.is_over() { return (400, error("the game is already over")); }
+    server.search(|solver| solve_request(solver, board, path, pos))
+}
+
+fn solve_request<B: Bits>(solver: &Solver, board: Board<B>, path: &str, pos: &str) -> (u16, String) {
+    let (start, searched) = (Instant::now(), solver.nodes());
+    let used = || ((solver.nodes() - searched) as u64, start.elapsed().as_secs_f64());
+    let position = pos.to_string();
+    let message = if path == "/solve" {
+        let (col, score) = solver.best_move(&board);
+        let pv = columns(&solver.principal_variation(board));
+        let (nodes, seconds) = used();
+        ServeMessage::Solve { position, score, best_move: col + 1, pv, nodes, seconds }
+    } else {
+        let scores: Vec<Option<i8>> = (0..board.width()).map(|col| {
+            if !board.can_play(col) { return None; }
+            let mut next = board;
+            next.play(col);
+            Some(if next.is_win() { (board.size() + 1 - board.moves()) as i8 / 2 } else { -solver.solve(next) })
+        }).collect();
+        let (nodes, seconds) = used();
+        ServeMessage::Analyze { position, scores, nodes, seconds }
+    };
+    if solver.is_stopped() { return (503, error("the server is shutting down")); }
+    if solver.is_aborted() { return (503, error("the position was not solved within the search limit")); }
+    (200, json(&message))
+}
+
+fn parse_position<B: Bits>(empty: Board<B>, grid: Option<&str>, pos: &str) -> Result<Board<B>, String> {
+    let mut board = match grid {
+        Some(grid) => {
+            let board: Board<B> = Board::try_from_grid(&grid.replace('/', "\n"), empty.connect()).map_err(|e| e.to_string())?;
+            if (board.width(), board.height()) != (empty.width(), empty.height()) {
+                return Err(format!("the grid must have {} rows of {} cells", empty.height(), empty.width()));
+            }
+            board
+        }
+        None => empty,
+    };
+    board.play_moves(pos)?;
+    Ok(board)
+}
+
+fn query_pos(query: &str) -> &str {
+    query.split('&').find_map(|kv| kv.strip_prefix("pos=")).unwrap_or("")
+}
+
+fn websocket<B: Bits>(server: &Server, empty: Board<B>, mut reader: BufReader<&TcpStream>, key: &str, pos: &str) -> io::Result<()> {
+    let _span = span!(INFO, "websocket", position = pos);
+    let mut stream = *reader.get_ref();
+    write!(stream, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+        Sec-WebSocket-Accept: {}\r\n\r\n", websocket::accept_key(key))?;
+    // 解いている間はメッセージを読まないので、待ち時間の上限は次のメッセージを待つ間だけ効く
+    stream.set_read_timeout(Some(WEBSOCKET_IDLE_TIMEOUT))?;
+    let writer = Mutex::new(stream);
+    let send = |text: String| websocket::write_frame(&mut *writer.lock().unwrap(), websocket::OP_TEXT, text.as_bytes());
+
+    if !pos.is_empty() { server.search(|solver| stream_analysis(solver, empty, pos, &send))?; }
+    loop {
+        let (opcode, payload) = match websocket::read_frame(&mut reader) {
+            Ok(frame) => frame,
+            Err(e) if matches!(e.kind(), io::ErrorKind::UnexpectedEof | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        match opcode {
+            websocket::OP_TEXT => server.search(|solver| stream_analysis(solver, empty, String::from_utf8_lossy(&payload).trim(), &send))?,
+            websocket::OP_PING => websocket::write_frame(&mut *writer.lock().unwrap(), websocket::OP_PONG, &payload)?,
+            websocket::OP_CLOSE => return websocket::write_frame(&mut *writer.lock().unwrap(), websocket::OP_CLOSE, &payload),
+            _ => {}
+        }
+    }
+}
+
+// 局面を解きながら途中経過を send で送る。best_move はその時点の TT の最善手。
+fn stream_analysis<B: Bits>(solver: &Solver, empty: Board<B>, pos: &str, send: &(impl Fn(String) -> io::Result<()> + Sync)) -> io::Result<()> {
+    let board = match parse_position(empty, None, pos) {
+        Ok(board) if board.is_over() => return send(error("the game is already over")),
+        Ok(board) => board,
+        Err(e) => return send(error(&e)),
+    };
+    let (start, searched) = (Instant::now(), solver.nodes());
+    let used = || ((solver.nodes() - searched) as u64, start.elapsed().as_secs_f64());
+    let done = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let progress = scope.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                thread::park_timeout(Duration::from_millis(500));
+                if done.load(Ordering::Relaxed) { break; }
+                let (nodes, seconds) = used();
+                let _ = send(json(&ServeMessage::Progress {
+                    position: pos.to_string(), nodes, best_move: solver.hint_move(&board) + 1, seconds,
+                }));
+            }
+        });
+        solver.solve_with_bounds(board, |min, max| {
+            let (nodes, seconds) = used();
+            let _ = send(json(&ServeMessage::Bounds {
+                position: pos.to_string(), min, max, nodes, best_move: solver.hint_move(&board) + 1, seconds,
+            }));
+        });
+        done.store(true, Ordering::Relaxed);
+        progress.thread().unpark();
+    });
+    if solver.is_stopped() { return send(error("the server is shutting down")); }
+    if solver.is_aborted() { return send(error("the position was not solved within the search limit")); }
+    let (col, score) = solver.best_move(&board);
+    let pv = columns(&solver.principal_variation(board));
+    let (nodes, seconds) = used();
+    send(json(&ServeMessage::Result { position: pos.to_string(), score, best_move: col + 1, pv, nodes, seconds }))
+}
+
+fn json(message: &ServeMessage) -> String {
+    serde_json::to_string(message).expect("ServeMessage always serializes")
+}
+
+// 0 始まりの列番号を応答の 1 始まりにする
+fn columns(cols: &[u32]) -> Vec<u32> { cols.iter().map(|col| col + 1).collect() }
+
+fn error(msg: &str) -> String {
+    json(&ServeMessage::Error { error: msg.to_string() })
+}
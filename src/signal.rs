@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Once, Weak};
+use std::thread;
+use std::time::Duration;
+
+use connect4_solver::Solver;
+
+// Ctrl-C で探索を止めて途中経過を出してから終了するときの終了コード (128 + SIGINT)
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+pub fn interrupted() -> bool { INTERRUPTED.load(Ordering::Relaxed) }
+
+// Ctrl-C で止める solver。監視するスレッドはプロセスに 1 つで、割り込まれたら生きている solver を全て abort する。
+static SOLVERS: Mutex<Vec<Weak<Solver>>> = Mutex::new(Vec::new());
+static WATCHER: Once = Once::new();
+
+// solver を Ctrl-C で止まるようにする。既に割り込まれていればすぐに止める。
+pub fn abort_on_interrupt(solver: &Arc<Solver>) {
+    {
+        let mut solvers = SOLVERS.lock().unwrap();
+        solvers.retain(|s| s.strong_count() > 0);
+        solvers.push(Arc::downgrade(solver));
+    }
+    if interrupted() { solver.abort(); }
+    WATCHER.call_once(|| {
+        thread::spawn(|| {
+            while !interrupted() { thread::sleep(Duration::from_millis(50)); }
+            eprintln!("Interrupted; stopping the search (press Ctrl-C again to exit immediately)...");
+            for solver in SOLVERS.lock().unwrap().iter().filter_map(Weak::upgrade) { solver.abort(); }
+        });
+    });
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::c_int;
+
+    pub const SIGINT: c_int = 2;
+
+    unsafe extern "C" {
+        pub fn signal(signum: c_int, handler: usize) -> usize;
+        pub fn _exit(status: c_int) -> !;
+    }
+}
+
+// 1 回目の SIGINT ではフラグを立てるだけで、2 回目はその場で終了する
+#[cfg(unix)]
+extern "C" fn on_sigint(_: std::ffi::c_int) {
+    if INTERRUPTED.swap(true, Ordering::Relaxed) {
+        unsafe { sys::_exit(INTERRUPTED_EXIT_CODE) }
+    }
+}
+
+#[cfg(unix)]
+pub fn install() {
+    let handler: extern "C" fn(std::ffi::c_int) = on_sigint;
+    unsafe { sys::signal(sys::SIGINT, handler as usize); }
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
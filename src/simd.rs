@@ -0,0 +1,38 @@
+// u64 の盤の四目の判定と勝ちマスの計算を、4 方向を 256 ビットレジスタの 4 レーンに載せて
+// まとめて行う (AVX2 の可変シフト vpsrlvq / vpsllvq)。結果は Bits の既定の実装と同じ。
+use std::arch::x86_64::*;
+
+#[inline(always)]
+fn lanes(dirs: [u32; 4], k: u32) -> __m256i {
+    let [a, b, c, d] = dirs.map(|x| (x * k) as i64);
+    unsafe { _mm256_set_epi64x(d, c, b, a) }
+}
+
+#[inline(always)]
+pub fn has_four(pos: u64, dirs: [u32; 4]) -> bool {
+    unsafe {
+        let p = _mm256_set1_epi64x(pos as i64);
+        let m = _mm256_and_si256(p, _mm256_srlv_epi64(p, lanes(dirs, 1)));
+        let r = _mm256_and_si256(m, _mm256_srlv_epi64(m, lanes(dirs, 2)));
+        _mm256_testz_si256(r, r) == 0
+    }
+}
+
+// 縦のレーンも横・斜めと同じ式で計算する。縦の並びの途中や下端の空きマスは、重力があるので
+// 必ず埋まっているか、列の番兵ビットを挟んで並びが切れているので、空きマスで絞れば消える。
+#[inline(always)]
+pub fn four_threats(pos: u64, dirs: [u32; 4]) -> u64 {
+    unsafe {
+        let p = _mm256_set1_epi64x(pos as i64);
+        let (d1, d2, d3) = (lanes(dirs, 1), lanes(dirs, 2), lanes(dirs, 3));
+        let up = _mm256_and_si256(_mm256_sllv_epi64(p, d1), _mm256_sllv_epi64(p, d2));
+        let down = _mm256_and_si256(_mm256_srlv_epi64(p, d1), _mm256_srlv_epi64(p, d2));
+        let r = _mm256_or_si256(
+            _mm256_and_si256(up, _mm256_or_si256(_mm256_sllv_epi64(p, d3), _mm256_srlv_epi64(p, d1))),
+            _mm256_and_si256(down, _mm256_or_si256(_mm256_sllv_epi64(p, d1), _mm256_srlv_epi64(p, d3))),
+        );
+        let mut out = [0u64; 4];
+        _mm256_storeu_si256(out.as_mut_ptr().cast(), r);
+        out[0] | out[1] | out[2] | out[3]
+    }
+}
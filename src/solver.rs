@@ -0,0 +1,891 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicI8, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::bits::Bits;
+use crate::board::{Board, MAX_WIDTH};
+use crate::book::Book;
+use crate::counter::{self, current_threads, NodeCounter, Tally};
+use crate::knowledge;
+use crate::numa::NumaPolicy;
+use crate::ordering::MoveHistory;
+use crate::pns::ProofTable;
+use crate::remote::{RemoteCache, RemoteStats};
+use crate::trace::{SearchTrace, TraceRecord};
+use crate::tt::{Bound, HashFunction, HugePages, TableBackend, TableStats, TranspositionTable, DEFAULT_TABLE_ENTRIES, ENTRY_BYTES};
+
+const NODE_LIMIT_INTERVAL: usize = 1024;
+// with_threads で作るスレッドのスタックの大きさ。並列に分ける浅いノードは再帰で探索するので大きめにする。
+pub const DEFAULT_STACK_SIZE: usize = 16 * 1024 * 1024;
+// 知識ルールを調べる残り手数の下限の既定値
+const KNOWLEDGE_DEPTH: u32 = 4;
+
+pub struct Solver {
+    table: TranspositionTable,
+    nodes: NodeCounter,
+    weak: bool,
+    book: Option<Arc<Book>>,
+    history: MoveHistory,
+    parallel_depth: u32,
+    // with_parallel_depth で決めていなければ、スレッド数に合わせて決め直す
+    auto_parallel_depth: bool,
+    // 並列探索に使うスレッドプール。None なら rayon のグローバルプールを使う。
+    #[cfg(feature = "parallel")]
+    pool: Option<Arc<rayon::ThreadPool>>,
+    stop: AtomicBool,
+    // 外側の探索ごとのノード数と時間の上限。上限で止めたときは stop ではなく limit_stop を立てる。
+    node_limit: usize,
+    time_limit: Option<Duration>,
+    limit_stop: AtomicBool,
+    // 実行中の外側の探索を始めたときの、created からの経過時間 (ns) とノード数
+    created: Instant,
+    search_start: AtomicU64,
+    search_base_nodes: AtomicUsize,
+    min_store_depth: u32,
+    knowledge_depth: u32,
+    algorithm: Algorithm,
+    // 証明数探索の (pn, dn) の表。初めて使うときに proof_table_bytes（既定は TT の 1/4）で確保する。
+    proof_table: OnceLock<Mutex<ProofTable>>,
+    proof_table_bytes: Option<usize>,
+    remote: Option<Remote>,
+    trace: Option<Arc<SearchTrace>>,
+    // 実行中の外側の探索の数（begin_search）
+    searches: AtomicUsize,
+}
+
+// Solver::begin_search の間、実行中の探索として数える
+pub struct SearchScope<'a>(&'a AtomicUsize);
+
+impl Drop for SearchScope<'_> {
+    fn drop(&mut self) { self.0.fetch_sub(1, Ordering::Relaxed); }
+}
+
+// TT の外側のキャッシュと、それを使う残り手数の下限・利用回数
+struct Remote {
+    cache: Arc<dyn RemoteCache>,
+    min_depth: u32,
+    probes: AtomicUsize,
+    hits: AtomicUsize,
+    stores: AtomicUsize,
+}
+
+// 局面を解いた結果。評価値は手番側から見たもので、列番号は 0 始まり。
+// 終局済みの局面では best_move は None、pv は空。
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SolveResult {
+    pub score: i8,
+    pub best_move: Option<u32>,
+    pub pv: Vec<u32>,
+    // この局面を解くのに使ったノード数
+    pub nodes: usize,
+}
+
+// Solver::measure で測った、探索の一部に使ったノード数・時間・TT の引き当て
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    pub nodes: usize,
+    pub time: Duration,
+    pub tt_probes: usize,
+    pub tt_hits: usize,
+}
+
+impl SearchStats {
+    pub fn hit_rate(&self) -> f64 { self.tt_hits as f64 / self.tt_probes.max(1) as f64 }
+
+    // 実効分岐数。残り depth 手の局面を nodes ノードで解いたとき、nodes = b^depth となる b。
+    pub fn branching_factor(&self, depth: u32) -> f64 {
+        if depth == 0 || self.nodes <= 1 { return 1.0; }
+        (self.nodes as f64).powf(1.0 / depth as f64)
+    }
+}
+
+// solve の null window 探索（「評価値が t 以上か」）に使う探索
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    AlphaBeta,
+    // df-pn (pns.rs)。並列化はしていない。
+    ProofNumber,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 2] = [Algorithm::AlphaBeta, Algorithm::ProofNumber];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::AlphaBeta => "alphabeta",
+            Algorithm::ProofNumber => "pns",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|algorithm| algorithm.name() == name)
+    }
+}
+
+impl Default for Solver {
+    fn default() -> Self { Self::new(DEFAULT_TABLE_ENTRIES) }
+}
+
+impl Solver {
+    // エントリ数は 2 のべき乗に切り下げる（バケット 1 つ分の最低 8）
+    pub fn round_entries(table_entries: usize) -> usize {
+        TranspositionTable::round_entries(table_entries)
+    }
+
+    pub fn new(table_entries: usize) -> Self {
+        Self::from_table(TranspositionTable::new(table_entries, &TableBackend::Heap)
+            .expect("heap allocation does not fail"))
+    }
+
+    pub fn with_backend(table_entries: usize, backend: &TableBackend) -> io::Result<Self> {
+        Ok(Self::from_table(TranspositionTable::new(table_entries, backend)?))
+    }
+
+    fn from_table(table: TranspositionTable) -> Self {
+        Self {
+            table,
+            nodes: NodeCounter::new(current_threads()),
+            weak: false,
+            book: None,
+            history: MoveHistory::new(),
+            parallel_depth: default_parallel_depth(current_threads()),
+            auto_parallel_depth: true,
+            #[cfg(feature = "parallel")]
+            pool: None,
+            stop: AtomicBool::new(false),
+            node_limit: usize::MAX,
+            time_limit: None,
+            limit_stop: AtomicBool::new(false),
+            created: Instant::now(),
+            search_start: AtomicU64::new(0),
+            search_base_nodes: AtomicUsize::new(0),
+            min_store_depth: 0,
+            knowledge_depth: KNOWLEDGE_DEPTH,
+            algorithm: Algorithm::AlphaBeta,
+            proof_table: OnceLock::new(),
+            proof_table_bytes: None,
+            remote: None,
+            trace: None,
+            searches: AtomicUsize::new(0),
+        }
+    }
+
+    // 弱解決モード: [-1, 1] の窓で勝ち/引き分け/負け (1/0/-1) だけを求める
+    pub fn with_weak(mut self, weak: bool) -> Self {
+        self.weak = weak;
+        self
+    }
+
+    pub fn is_weak(&self) -> bool { self.weak }
+
+    // 根からこの深さ未満のノードでは子を rayon で並列に探索する
+    pub fn with_parallel_depth(mut self, depth: u32) -> Self {
+        self.parallel_depth = depth;
+        self.auto_parallel_depth = false;
+        self
+    }
+
+    pub fn parallel_depth(&self) -> u32 { self.parallel_depth }
+
+    // threads 本のスレッド（スタックは DEFAULT_STACK_SIZE）のプールを作り、この Solver の探索専用に使う。
+    // 設定の違う Solver を同じプロセスに置いても、互いのスレッドを取り合わない。
+    #[cfg(feature = "parallel")]
+    pub fn with_threads(self, threads: usize) -> io::Result<Self> {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).stack_size(DEFAULT_STACK_SIZE).build()
+            .map_err(io::Error::other)?;
+        Ok(self.with_thread_pool(Arc::new(pool)))
+    }
+
+    // 探索に pool を使う（スレッドの固定などを呼び出し側で設定したプールや、複数の Solver で共有するプール）
+    #[cfg(feature = "parallel")]
+    pub fn with_thread_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+        let threads = pool.current_num_threads();
+        self.nodes = NodeCounter::new(threads);
+        self.table.set_threads(threads);
+        if self.auto_parallel_depth { self.parallel_depth = default_parallel_depth(threads); }
+        self.pool = Some(pool);
+        self
+    }
+
+    // 探索に使うスレッドの数
+    pub fn threads(&self) -> usize {
+        #[cfg(feature = "parallel")]
+        if let Some(pool) = &self.pool { return pool.current_num_threads(); }
+        current_threads()
+    }
+
+    // f をこの Solver のスレッドプールで実行する。中で rayon の並列イテレータを使うと、そのプールのスレッドで動く。
+    // 探索はいつもこのプールで動くので、複数の局面を並列に解くときはこの中で回すとスレッドが増えない。
+    #[cfg(feature = "parallel")]
+    pub fn install<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => {
+                let scope = counter::current_scope();
+                pool.install(move || counter::in_scope(scope, f))
+            }
+            None => f(),
+        }
+    }
+    #[cfg(not(feature = "parallel"))]
+    pub fn install<R>(&self, f: impl FnOnce() -> R) -> R { f() }
+
+    // 探索木のトレースを書く（trace.rs）。同じ SearchTrace を複数の Solver で共有してもよい。
+    pub fn with_trace(mut self, trace: Arc<SearchTrace>) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    // 解く盤の大きさを TT に記録する。--tt-save/--tt-load のファイルや --tt-file の TT は、
+    // 同じ大きさの盤にしか使えない（ファイルバックの TT が別の大きさなら InvalidData）。
+    pub fn with_geometry(mut self, width: u32, height: u32, connect: u32) -> io::Result<Self> {
+        self.table.set_geometry([width as u8, height as u8, connect as u8])?;
+        Ok(self)
+    }
+
+    // 外側の探索（begin_search）1 回あたりのノード数の上限。達したらその探索を打ち切る。
+    pub fn with_node_limit(mut self, limit: usize) -> Self {
+        self.node_limit = limit;
+        self
+    }
+
+    // 外側の探索 1 回あたりの時間の上限。過ぎたらその探索を打ち切る。
+    pub fn with_time_limit(mut self, limit: Option<Duration>) -> Self {
+        self.time_limit = limit;
+        self
+    }
+
+    // TT の添字と検証に使うハッシュ関数
+    pub fn with_hash_function(mut self, hash: HashFunction) -> io::Result<Self> {
+        self.table.set_hash_function(hash)?;
+        Ok(self)
+    }
+
+    // 残りの手数が depth 未満の局面は TT を引かず、書きもしない。終盤の局面は探索し直しても安いので、
+    // 高くつく結果のエントリを追い出させないようにして TT の実効容量を上げる。
+    pub fn with_min_store_depth(mut self, depth: u32) -> Self {
+        self.min_store_depth = depth;
+        self
+    }
+
+    // 残りの手数が depth 以上の局面では、探索する前に Claimeven と Baseinverse で引き分け以上を示せないか調べ、
+    // 示せれば手番側の評価値の上限を 0 にする (knowledge.rs)。u32::MAX で調べない。
+    pub fn with_knowledge_depth(mut self, depth: u32) -> Self {
+        self.knowledge_depth = depth;
+        self
+    }
+
+    #[inline(always)]
+    pub(crate) fn knowledge_depth(&self) -> u32 { self.knowledge_depth }
+
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn algorithm(&self) -> Algorithm { self.algorithm }
+
+    // 証明数探索の (pn, dn) の表の大きさ。既定は TT の 1/4。初めて使うときに確保するので、その前に決めること。
+    pub fn with_proof_table_bytes(mut self, bytes: usize) -> Self {
+        self.proof_table_bytes = Some(bytes);
+        self
+    }
+
+    // 証明数探索の表が確保する（した）バイト数
+    pub fn proof_table_bytes(&self) -> usize {
+        ProofTable::rounded_bytes(self.proof_table_bytes.unwrap_or(self.table_bytes() / 4))
+    }
+
+    pub(crate) fn proof_table(&self) -> &Mutex<ProofTable> {
+        self.proof_table.get_or_init(|| Mutex::new(ProofTable::with_bytes(self.proof_table_bytes(), self.table.hash_function())))
+    }
+
+    // 残り remaining 手の局面で TT を使うか
+    #[inline(always)]
+    pub(crate) fn uses_table(&self, remaining: u32) -> bool { remaining >= self.min_store_depth }
+
+    // 枝刈り後の分岐数をおよそ 4 とみて、並列に走るタスクがスレッド数の 4 倍程度になる深さ
+    pub fn auto_parallel_depth(threads: usize) -> u32 {
+        let mut depth = 0;
+        while 4usize.pow(depth) < threads.max(1) * 4 { depth += 1; }
+        depth
+    }
+
+    // 定跡を持たせると、収録局面は探索せずに定跡の値を返す。
+    // 弱解決の定跡は勝敗しか持たないので、弱解決モードでしか使わない。
+    pub fn with_book(mut self, book: Arc<Book>) -> Self {
+        self.book = Some(book);
+        self
+    }
+
+    #[inline]
+    pub(crate) fn probe_book<B: Bits>(&self, board: &Board<B>) -> Option<(u32, i8)> {
+        let book = self.book.as_ref()?;
+        if board.moves() > book.depth() || (book.is_weak() && !self.weak) { return None; }
+        book.get(board)
+    }
+
+    // TT に無かった局面をさらに cache で引き、TT に書く結果を cache にも書く。
+    // 通信は 1 局面ごとに往復するので、残り min_depth 手以上の（探索の根に近い、数の少ない）局面だけに使う。
+    pub fn with_remote_cache(mut self, cache: Arc<dyn RemoteCache>, min_depth: u32) -> Self {
+        self.remote = Some(Remote {
+            cache, min_depth, probes: AtomicUsize::new(0), hits: AtomicUsize::new(0), stores: AtomicUsize::new(0),
+        });
+        self
+    }
+
+    pub fn remote_stats(&self) -> Option<RemoteStats> {
+        self.remote.as_ref().map(|remote| RemoteStats {
+            probes: remote.probes.load(Ordering::Relaxed),
+            hits: remote.hits.load(Ordering::Relaxed),
+            stores: remote.stores.load(Ordering::Relaxed),
+        })
+    }
+
+    // TT で見つからなかった局面をリモートキャッシュで引き、見つかれば TT にも入れる。
+    // 値はネットワーク越しに来るので、board に打てない列や取りえない評価値は見つからなかったことにする。
+    fn probe_remote<B: Bits>(&self, key: u64, board: &Board<B>) -> Option<(i8, Bound, u32)> {
+        let depth = board.size() - board.moves();
+        let remote = self.remote.as_ref().filter(|remote| depth >= remote.min_depth)?;
+        remote.probes.fetch_add(1, Ordering::Relaxed);
+        let (score, bound, best_col) = remote.cache.get(key)?;
+        if best_col >= board.width() || score.unsigned_abs() as u32 > depth.div_ceil(2) { return None; }
+        remote.hits.fetch_add(1, Ordering::Relaxed);
+        self.table.store(key, score, bound, best_col, depth);
+        Some((score, bound, best_col))
+    }
+
+    #[inline]
+    pub(crate) fn report(&self, score: i8) -> i8 {
+        if self.weak { score.signum() } else { score }
+    }
+
+    pub fn table_entries(&self) -> usize { self.table.len() }
+    pub fn table_bytes(&self) -> usize { self.table.len() * ENTRY_BYTES }
+    pub fn table_huge_pages(&self) -> HugePages { self.table.huge_pages() }
+    pub fn table_numa(&self) -> NumaPolicy { self.table.numa() }
+    pub fn table_hash_function(&self) -> HashFunction { self.table.hash_function() }
+    pub fn nodes(&self) -> usize { self.nodes.total() }
+    pub fn table_stats(&self) -> TableStats { self.table.stats() }
+
+    // f の中で（並列に分けた子の探索も含めて）探索したノード数と TT の引き当て、かかった時間を測る。
+    // 複数のスレッドから同時に呼んでも、それぞれの f の分だけを数える。入れ子にすると内側の分は外側に数えない。
+    pub fn measure<R>(&self, f: impl FnOnce() -> R) -> (R, SearchStats) {
+        let tally = Arc::new(Tally::default());
+        let start = Instant::now();
+        let result = counter::in_scope(Some(Arc::clone(&tally)), f);
+        let (nodes, tt_probes, tt_hits) = tally.totals();
+        (result, SearchStats { nodes, time: start.elapsed(), tt_probes, tt_hits })
+    }
+
+    // 戻り値を持っている間を 1 回の外側の探索にする。best_move や読み筋のように中で solve を何度も呼ぶ場合や、
+    // 並列に解く場合も、走っている探索が全て終わるまで TT の世代を進めない（3 ビットの世代が 1 回の探索の中で一周しないように）。
+    // ノード数と時間の上限はここから数える。探索がほかに走っていれば、その探索と同じ上限を使う。
+    // 上限で打ち切られた探索の is_aborted() は、次の外側の探索を始めるまで true のまま。
+    pub fn begin_search(&self) -> SearchScope<'_> {
+        if self.searches.fetch_add(1, Ordering::Relaxed) == 0 {
+            self.table.new_search();
+            self.search_start.store(self.created.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            self.search_base_nodes.store(self.nodes.total(), Ordering::Relaxed);
+            self.limit_stop.store(false, Ordering::Relaxed);
+        }
+        SearchScope(&self.searches)
+    }
+
+    // 実行中の探索を（他スレッドから）止める。clear_abort() を呼ぶまで以降の探索もすぐに打ち切られる。
+    // 中断された探索の結果は TT に書き込まれないので、TT はそのまま使い続けられる。
+    pub fn abort(&self) { self.stop.store(true, Ordering::Relaxed); }
+    // abort() されたか、実行中（直前）の探索が上限に達した
+    pub fn is_aborted(&self) -> bool { self.stop.load(Ordering::Relaxed) || self.limit_stop.load(Ordering::Relaxed) }
+    // 上限ではなく abort() で止められた
+    pub fn is_stopped(&self) -> bool { self.stop.load(Ordering::Relaxed) }
+    pub fn clear_abort(&self) {
+        self.stop.store(false, Ordering::Relaxed);
+        self.limit_stop.store(false, Ordering::Relaxed);
+    }
+
+    // 任意の局面を解く。手番側から見たスコアを返す。
+    // 中断された場合は途中までに証明した範囲の下限を返す。
+    pub fn solve<B: Bits>(&self, board: Board<B>) -> i8 {
+        self.solve_with_bounds(board, |_, _| {}).0
+    }
+
+    // 全幅の窓で一度に探索する代わりに、null window 探索を繰り返して
+    // スコアの範囲 [min, max] を狭めていく (Pascal Pons の iterative narrowing)。
+    // 範囲が狭まるたびに on_bounds(min, max) を呼ぶ。
+    // 証明済みの範囲 (min, max) を返す。解き切れば min == max、中断されると min < max のまま返る。
+    pub fn solve_with_bounds<B: Bits>(&self, board: Board<B>, mut on_bounds: impl FnMut(i8, i8)) -> (i8, i8) {
+        if board.is_win() {
+            let score = self.report(-((board.size() + 2 - board.moves()) as i8 / 2));
+            return (score, score);
+        }
+        let _search = self.begin_search();
+        let (mut min, mut max) = if self.weak {
+            (-1, 1)
+        } else {
+            (-((board.size() - board.moves()) as i8) / 2, (board.size() + 1 - board.moves()) as i8 / 2)
+        };
+        while min < max {
+            // 0 付近を先に調べると勝敗がはっきりした局面で早く決着する
+            let mut med = min + (max - min) / 2;
+            if med <= 0 && min / 2 < med { med = min / 2; }
+            else if med >= 0 && max / 2 > med { med = max / 2; }
+            let r = self.install(|| match self.algorithm {
+                Algorithm::AlphaBeta => self.report(self.negamax(board, board.table_key(), med, med + 1, 0)),
+                Algorithm::ProofNumber => if self.prove(board, med + 1) { med + 1 } else { med },
+            });
+            if self.is_aborted() { break; }
+            if r <= med { max = r; } else { min = r; }
+            on_bounds(min, max);
+        }
+        (min, max)
+    }
+
+    // 手番側の最善手（0 始まりの列番号）とその評価値を返す。
+    // TT の最善手を優先して検証し、評価値と一致しなければ他の手を解き直す。
+    // 終局済みの局面に対して呼ぶと panic する。中断された場合の結果は当てにならない。
+    pub fn best_move<B: Bits>(&self, board: &Board<B>) -> (u32, i8) {
+        assert!(!board.is_over(), "best_move called on a finished game");
+        let _search = self.begin_search();
+        for col in board.legal_moves() {
+            let mut next = *board;
+            next.play(col);
+            if next.is_win() { return (col, self.report((board.size() + 1 - board.moves()) as i8 / 2)); }
+        }
+
+        if let Some((col, score)) = self.probe_book(board) { return (col, self.report(score)); }
+
+        let target = self.solve(*board);
+        let mut order = board.legal_moves().to_vec();
+        let (key, mirrored) = board.table_key();
+        if let Some((_, _, bc)) = self.table.lookup(key)
+            && let Some(pos) = order.iter().position(|&x| x == if mirrored { board.width() - 1 - bc } else { bc }) {
+            order.swap(0, pos);
+        }
+        let mut best: Option<(u32, i8)> = None;
+        for &col in &order {
+            let mut next = *board;
+            next.play(col);
+            let score = -self.solve(next);
+            if best.is_none_or(|(_, s)| score > s) { best = Some((col, score)); }
+            if score == target { break; }
+        }
+        best.expect("unfinished game has a legal move")
+    }
+
+    // 列ごとに、そこに打った場合の手番側の評価値。打てない列は None。
+    // 中断された場合の結果は当てにならない。
+    pub fn move_scores<B: Bits>(&self, board: &Board<B>) -> Vec<Option<i8>> {
+        let _search = self.begin_search();
+        (0..board.width()).map(|col| {
+            if !board.can_play(col) || board.is_win() { return None; }
+            let mut next = *board;
+            next.play(col);
+            Some(if next.is_win() { self.report((board.size() + 1 - board.moves()) as i8 / 2) } else { -self.solve(next) })
+        }).collect()
+    }
+
+    // TT に残っている最善手の候補。中断した探索からでも手を選べるように、
+    // TT に無ければ相手に即勝ちを与えない手のうち中央寄りの手を返す。
+    pub fn hint_move<B: Bits>(&self, board: &Board<B>) -> u32 {
+        let (key, mirrored) = board.table_key();
+        if let Some((_, _, bc)) = self.table.lookup(key) {
+            let col = if mirrored { board.width() - 1 - bc } else { bc };
+            if board.can_play(col) { return col; }
+        }
+        let safe = board.non_losing_moves();
+        board.column_order().find(|&col| safe & board.column_mask(col) != B::ZERO)
+            .or_else(|| board.legal_moves().first().copied())
+            .expect("unfinished game has a legal move")
+    }
+
+    // 局面から終局までの最善手順（0 始まりの列番号）を best_move を辿って復元する
+    pub fn principal_variation<B: Bits>(&self, board: Board<B>) -> Vec<u32> {
+        self.principal_variation_plies(board, usize::MAX)
+    }
+
+    // principal_variation の最初の plies 手だけ。探索した直後なら各手は TT からすぐに決まる。
+    pub fn principal_variation_plies<B: Bits>(&self, board: Board<B>, plies: usize) -> Vec<u32> {
+        let _search = self.begin_search();
+        let mut pv = Vec::new();
+        let mut b = board;
+        while !b.is_over() && pv.len() < plies {
+            let (col, _) = self.best_move(&b);
+            if self.is_aborted() { break; }
+            pv.push(col);
+            b.play(col);
+        }
+        pv
+    }
+
+    // 評価値・最善手・読み筋をまとめて求める。中断された場合の結果は当てにならない。
+    pub fn solve_result<B: Bits>(&self, board: Board<B>) -> SolveResult {
+        let _search = self.begin_search();
+        let nodes = self.nodes();
+        if board.is_over() {
+            return SolveResult { score: self.solve(board), best_move: None, pv: Vec::new(), nodes: 0 };
+        }
+        let (best_move, score) = self.best_move(&board);
+        let pv = self.principal_variation(board);
+        SolveResult { score, best_move: Some(best_move), pv, nodes: self.nodes() - nodes }
+    }
+
+    pub fn save_table(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.table.save(path.as_ref())
+    }
+
+    pub fn load_table(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.table.load(path.as_ref())
+    }
+
+    // ノードを 1 つ数える。探索の上限に達したか中断されていれば false。
+    // 上限は各スレッドが NODE_LIMIT_INTERVAL ノードごとに判定するので、その分だけ超えることがある。
+    #[inline(always)]
+    pub(crate) fn count_node(&self) -> bool {
+        let local = self.nodes.increment();
+        if local.is_multiple_of(NODE_LIMIT_INTERVAL) && self.over_limit() {
+            self.limit_stop.store(true, Ordering::Relaxed);
+        }
+        !self.is_aborted()
+    }
+
+    // 実行中の外側の探索が、ノード数か時間の上限を超えたか
+    fn over_limit(&self) -> bool {
+        if self.node_limit != usize::MAX
+            && self.nodes.total().saturating_sub(self.search_base_nodes.load(Ordering::Relaxed)) >= self.node_limit {
+            return true;
+        }
+        self.time_limit.is_some_and(|limit| {
+            let start = Duration::from_nanos(self.search_start.load(Ordering::Relaxed));
+            self.created.elapsed().saturating_sub(start) >= limit
+        })
+    }
+
+    // 別のルールの探索（PopOut）と TT を共有する
+    pub(crate) fn table(&self) -> &TranspositionTable { &self.table }
+
+    // 子の局面の TT のキーを求めて、そのバケットを先読みする（子の探索で TT を引く場合だけ）
+    #[inline(always)]
+    fn prefetch<B: Bits>(&self, next: &Board<B>) -> (u64, bool) {
+        let key = next.table_key();
+        if self.uses_table(next.size() - next.moves()) { self.table.prefetch(key.0); }
+        key
+    }
+
+    // key は board.table_key()（親が先読みのついでに求めたもの）。
+    // 並列に分ける浅いノードだけを再帰で探索し、それより深いところは search の明示的なスタックで探索する。
+    fn negamax<B: Bits>(&self, board: Board<B>, key: (u64, bool), alpha: i8, beta: i8, p_depth: u32) -> i8 {
+        if p_depth >= self.parallel_depth { return self.search(board, key, alpha, beta); }
+        let mut node = match self.enter(board, key, alpha, beta) {
+            Ok(node) => node,
+            Err(score) => { self.trace(&board, key, (alpha, beta), score, None); return score; }
+        };
+
+        // Young Brothers Wait: 並列に分けるノードでも最初の子だけは逐次に探索して alpha を上げてから、
+        // 残りの兄弟を並列に探索する
+        let order = node.order;
+        let (first, rest) = order[..node.len].split_at(1);
+        let col = first[0];
+        let mut next = board;
+        next.play(col);
+        let score = -self.negamax(next, self.prefetch(&next), -node.beta, -node.alpha, p_depth + 1);
+        if !node.update(self, col, score) && !rest.is_empty() {
+            let beta = node.beta;
+            let results = search_siblings(rest, node.alpha, beta, |col, alpha| {
+                let mut next = board;
+                next.play(col);
+                -self.negamax(next, self.prefetch(&next), -beta, -alpha, p_depth + 1)
+            });
+            for (score, col) in results {
+                if node.update(self, col, score) { break; }
+            }
+        }
+        self.finish(&node)
+    }
+
+    // 再帰の代わりに、探索中のノードを親から順に積んだスタックで深さ優先に探索する。
+    // 深さは盤の空きマス数までで、スレッドのスタックの大きさに依存しない。
+    fn search<B: Bits>(&self, board: Board<B>, key: (u64, bool), alpha: i8, beta: i8) -> i8 {
+        let mut stack = Vec::with_capacity((board.size() - board.moves()) as usize);
+        match self.enter(board, key, alpha, beta) {
+            Ok(node) => stack.push(node),
+            Err(score) => { self.trace(&board, key, (alpha, beta), score, None); return score; }
+        }
+        loop {
+            // スタックの先頭のノードの次の子に入る
+            let node = stack.last_mut().expect("the stack holds the root until it finishes");
+            let col = node.order[node.next];
+            node.next += 1;
+            let mut next = node.board;
+            next.play(col);
+            let (alpha, beta) = (node.alpha, node.beta);
+            let key = self.prefetch(&next);
+            let mut score = match self.enter(next, key, -beta, -alpha) {
+                Ok(child) => {
+                    stack.push(child);
+                    continue;
+                }
+                Err(score) => { self.trace(&next, key, (-beta, -alpha), score, None); score }
+            };
+            // 評価値が決まった子を親に反映し、親も調べ終えたらさらにその親へ戻す
+            loop {
+                let node = stack.last_mut().expect("a finished child has a parent");
+                let col = node.order[node.next - 1];
+                if !node.update(self, col, -score) && node.next < node.len { break; }
+                score = self.finish(node);
+                stack.pop();
+                if stack.is_empty() { return score; }
+            }
+        }
+    }
+
+    // ノードに入る。子を調べずに評価値が決まれば Err でそれを返し、そうでなければ子を調べる順に並べたノードを返す。
+    #[inline(always)]
+    fn enter<B: Bits>(&self, board: Board<B>, (key, mirrored): (u64, bool), mut alpha: i8, mut beta: i8) -> Result<Node<B>, i8> {
+        let window = (alpha, beta);
+        // 中断時の戻り値は使われない
+        if !self.count_node() { return Err(0); }
+        let size = board.size();
+        if board.moves() == size { return Err(0); }
+        if let Some((_, score)) = self.probe_book(&board) { return Err(score); }
+        if board.can_win_next() { return Err((size + 1 - board.moves()) as i8 / 2); }
+
+        // 相手の即勝ちマスが 2 つ以上なら塞ぎきれずに負け、1 つならそこに打つしかない
+        let loss = -((size - board.moves()) as i8) / 2;
+        let forced = board.forced_moves();
+        if forced & forced.wrapping_sub(B::ONE) != B::ZERO { return Err(loss); }
+
+        // 相手に即勝ちを与えない手だけを探索する。無ければ次の相手の手で負け。
+        let mut candidates = board.non_losing_moves();
+        if forced != B::ZERO { candidates &= forced; }
+        if candidates == B::ZERO { return Err(loss); }
+        if board.moves() >= size - 2 { return Err(0); }
+
+        // 相手は次の一手では勝てないので、負けるとしても 2 手以上先
+        let min_p = -((size - 2 - board.moves()) as i8) / 2;
+        if alpha < min_p {
+            alpha = min_p;
+            if alpha >= beta { return Err(alpha); }
+        }
+        let max_p = (size - 1 - board.moves()) as i8 / 2;
+        if beta > max_p {
+            beta = max_p;
+            if alpha >= beta { return Err(beta); }
+        }
+
+        // 左右対称な局面は TT のエントリを共有する。最善手の列は正規化した側の向きで持つ。
+        let flip = |col: u32| if mirrored { board.width() - 1 - col } else { col };
+        let mut best_col = None;
+        let use_table = self.uses_table(size - board.moves());
+        if use_table && let Some((score, bound, bc)) = self.table.lookup(key).or_else(|| self.probe_remote(key, &board)) {
+            if let Some(score) = bound.narrow(score, &mut alpha, &mut beta) { return Err(score); }
+            best_col = Some(flip(bc));
+        }
+        if beta > 0 && size - board.moves() >= self.knowledge_depth && knowledge::follower_holds(&board) {
+            beta = 0;
+            if alpha >= beta { return Err(beta); }
+        }
+
+        // 打った後にできる自分の勝ちマスが多い手から順に調べる。
+        // 同数なら中央に近い列、killer、history の順（中央寄りより先に killer を見ると探索量が倍増した）。
+        // TT の最善手は最優先。
+        let moves = board.moves();
+        let mut scored = [(0u32, (0u32, 0u32, 0u8, 0u64)); MAX_WIDTH as usize];
+        let mut n = 0;
+        for col in board.column_order() {
+            let mv = candidates & board.column_mask(col);
+            if mv != B::ZERO {
+                let center = board.width() - (2 * col + 1).abs_diff(board.width());
+                let rank = (board.move_score(mv), center, self.history.killer_rank(moves, col), self.history.score(moves, mv));
+                scored[n] = (col, rank);
+                n += 1;
+            }
+        }
+        scored[..n].sort_by_key(|&(_, rank)| std::cmp::Reverse(rank));
+        if let Some(bc) = best_col
+            && let Some(pos) = scored[..n].iter().position(|&(col, _)| col == bc) {
+            scored[..=pos].rotate_right(1);
+        }
+        let mut order = [0u32; MAX_WIDTH as usize];
+        for (slot, &(col, _)) in order.iter_mut().zip(&scored[..n]) { *slot = col; }
+
+        Ok(Node {
+            board, key, mirrored, candidates, use_table, window, alpha, beta, alpha_orig: alpha, beta_orig: beta,
+            order, len: n, next: 0, best_score: i8::MIN, best_col: order[0],
+        })
+    }
+
+    // 全ての子を調べ終えたか βカットしたノードの評価値を TT に書いて返す
+    #[inline(always)]
+    fn finish<B: Bits>(&self, node: &Node<B>) -> i8 {
+        if self.is_aborted() { return 0; }
+        let bound = Bound::of(node.best_score, node.alpha_orig, node.beta_orig);
+        if node.use_table {
+            let board = &node.board;
+            let best = if node.mirrored { board.width() - 1 - node.best_col } else { node.best_col };
+            self.table.store(node.key, node.best_score, bound, best, board.size() - board.moves());
+            if let Some(remote) = self.remote.as_ref().filter(|remote| board.size() - board.moves() >= remote.min_depth) {
+                remote.cache.put(node.key, node.best_score, bound, best);
+                remote.stores.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.trace(&node.board, (node.key, node.mirrored), node.window, node.best_score, Some(node.best_col));
+        node.best_score
+    }
+
+    // 評価値が決まったノードをトレースに書く（with_trace があり、石の数がその上限以下のとき）
+    #[inline(always)]
+    fn trace<B: Bits>(&self, board: &Board<B>, (key, mirrored): (u64, bool), (alpha, beta): (i8, i8), score: i8, best_move: Option<u32>) {
+        if let Some(trace) = &self.trace && trace.wants(board.moves()) && !self.is_aborted() {
+            trace.record(&TraceRecord {
+                key, mirrored, ply: board.moves(), alpha, beta, score, best_move, thread: counter::thread_slot() as u16,
+            });
+        }
+    }
+}
+
+// 子を調べている途中のノード
+struct Node<B: Bits> {
+    board: Board<B>,
+    key: u64,
+    mirrored: bool,
+    candidates: B,
+    use_table: bool,
+    // 入ったときの窓（トレース用）。alpha_orig, beta_orig は範囲や TT で狭めた後の窓。
+    window: (i8, i8),
+    alpha: i8,
+    beta: i8,
+    alpha_orig: i8,
+    beta_orig: i8,
+    // 子を調べる順の列と、その数、次に調べる子の番号
+    order: [u32; MAX_WIDTH as usize],
+    len: usize,
+    next: usize,
+    best_score: i8,
+    best_col: u32,
+}
+
+impl<B: Bits> Node<B> {
+    // 子 col の評価値 score を反映する。βカットなら true。
+    #[inline(always)]
+    fn update(&mut self, solver: &Solver, col: u32, score: i8) -> bool {
+        if score > self.best_score { self.best_score = score; self.best_col = col; }
+        if score > self.alpha { self.alpha = score; }
+        if self.alpha < self.beta { return false; }
+        let board = &self.board;
+        solver.history.record_cutoff(board.moves(), board.size(), col, self.candidates & board.column_mask(col));
+        true
+    }
+}
+
+// parallel feature が無ければ（wasm など）並列探索はしない
+#[cfg(feature = "parallel")]
+fn default_parallel_depth(threads: usize) -> u32 { Solver::auto_parallel_depth(threads) }
+#[cfg(not(feature = "parallel"))]
+fn default_parallel_depth(_threads: usize) -> u32 { 0 }
+
+// Young Brothers Wait で最初の子を調べた後の残りの兄弟を並列に探索し、(評価値, 手) を返す。
+// 兄弟の結果で上がった alpha は後から始まる探索に引き継ぎ、βカットが起きたら残りは探索しない。
+// child(mv, alpha) は窓 (alpha, beta) で探索した mv の評価値。
+pub(crate) fn search_siblings<M: Copy + Send + Sync>(rest: &[M], alpha: i8, beta: i8, child: impl Fn(M, i8) -> i8 + Sync + Send) -> Vec<(i8, M)> {
+    let shared_alpha = AtomicI8::new(alpha);
+    map_children(rest, |mv| {
+        let a = shared_alpha.load(Ordering::Relaxed);
+        if a >= beta { return None; }
+        let score = child(mv, a);
+        shared_alpha.fetch_max(score, Ordering::Relaxed);
+        Some((score, mv))
+    })
+}
+
+#[cfg(feature = "parallel")]
+pub(crate) fn map_children<M: Copy + Sync, T: Send>(moves: &[M], f: impl Fn(M) -> Option<T> + Sync + Send) -> Vec<T> {
+    let scope = counter::current_scope();
+    moves.par_iter().filter_map(|&mv| counter::in_scope(scope.clone(), || f(mv))).collect()
+}
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn map_children<M: Copy, T>(moves: &[M], f: impl Fn(M) -> Option<T>) -> Vec<T> {
+    moves.iter().filter_map(|&mv| f(mv)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pascal Pons のテストセット (bench.rs の SETS) の endgame_easy と middle_easy の先頭の行
+    const KNOWN: [(&str, i8); 2] = [
+        ("2252576253462244111563365343671351441", -1),
+        ("7422341735647741166133573473242566", 1),
+    ];
+
+    #[test]
+    fn known_scores() {
+        let solver = Solver::new(1 << 20);
+        for (moves, score) in KNOWN {
+            assert_eq!(solver.solve(Board::from_moves(moves).unwrap()), score, "{}", moves);
+        }
+    }
+
+    // 素朴な alpha-beta だけの negamax
+    fn reference(board: Board, mut alpha: i8, beta: i8) -> i8 {
+        if board.moves() == board.size() { return 0; }
+        let moves: Vec<u32> = (0..board.width()).filter(|&col| board.can_play(col)).collect();
+        for &col in &moves {
+            let mut next = board;
+            next.play(col);
+            if next.is_win() { return ((board.size() + 1 - board.moves()) / 2) as i8; }
+        }
+        let mut best = i8::MIN;
+        for col in moves {
+            let mut next = board;
+            next.play(col);
+            best = best.max(-reference(next, -beta, -alpha));
+            alpha = alpha.max(best);
+            if alpha >= beta { break; }
+        }
+        best
+    }
+
+    // 乱数で打った終盤の局面（空きマス 14）で、探索の枝刈りや TT が素朴な negamax と同じ評価値を出すこと
+    #[test]
+    fn matches_reference_on_endgames() {
+        let solver = Solver::new(1 << 16);
+        let mut rng = 0x2545_f491_4f6c_dd1d;
+        let mut checked = 0;
+        while checked < 40 {
+            let (board, _) = Board::new().random_unfinished(28, &mut rng).unwrap();
+            // 次の一手で並べられる局面と、相手の並びを塞ぐしかない局面は探索するまでもないので飛ばす
+            if board.can_win_next() || board.forced_moves() != 0 { continue; }
+            assert_eq!(solver.solve(board), reference(board, -100, 100), "{:?}", board.to_grid());
+            checked += 1;
+        }
+    }
+
+    #[test]
+    fn probes_the_book() {
+        // 手番側の評価値が 4（勝ち）の局面から 2 手先までの定跡
+        let root = Board::from_moves("12361272223627356163536615").unwrap();
+        let plain = Solver::new(1 << 16);
+        let score = plain.solve(root);
+        assert_eq!(score, 4);
+        let book = Arc::new(Book::build(&Solver::new(1 << 16), root, 2, |_, _| {}));
+        // 根は定跡にあるので探索せずに引ける
+        let with_book = Solver::new(1 << 16).with_book(book.clone());
+        assert_eq!(with_book.solve(root), score);
+        assert!(with_book.nodes() < plain.nodes());
+        assert_eq!(with_book.best_move(&root), book.get(&root).unwrap());
+        // 定跡より深い局面は探索する
+        let deep = crate::perft::positions_at(root, 3, false).find(|board| !board.is_over()).unwrap();
+        assert_eq!(book.get(&deep), None);
+        assert_eq!(with_book.solve(deep), plain.solve(deep));
+        // 弱解決の定跡は勝敗しか持たないので、弱解決の Solver でしか使わない
+        let weak_book = Arc::new(Book::build(&Solver::new(1 << 16).with_weak(true), root, 2, |_, _| {}));
+        assert_eq!(Solver::new(1 << 16).with_book(weak_book.clone()).solve(root), score);
+        assert_eq!(Solver::new(1 << 16).with_weak(true).with_book(weak_book).solve(root), 1);
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::{Duration, Instant};
+
+use connect4_solver::{heuristic, Bits, Board, Level, Mcts, Player, Solver};
+
+use crate::output::{self, JsonObject};
+
+// 2 つの設定の対局。開局の手順ごとに先後を入れ替えて 2 局ずつ打ち、勝ち・引き分け・負けと
+// レーティング差 (Elo) の推定値とその 95% 信頼区間を出す。
+//
+// 設定は次のどれか:
+//   random, easy, medium, hard, perfect   --level と同じ強さ
+//   depth:<n>                             静的評価で n 手先まで読む（engine の go depth と同じ）
+//   mcts:<ms>                             1 手 ms ミリ秒のモンテカルロ木探索（--mcts-* の設定を使う）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Contestant {
+    Level(Level),
+    Depth(u32),
+    Mcts(Duration),
+}
+
+impl Contestant {
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        if let Some(level) = Level::from_name(spec) { return Ok(Contestant::Level(level)); }
+        let invalid = || format!("invalid player '{}' (expected a level, depth:<n> or mcts:<ms>)", spec);
+        match spec.split_once(':') {
+            Some(("depth", n)) => n.parse().ok().filter(|&n| n > 0).map(Contestant::Depth).ok_or_else(invalid),
+            Some(("mcts", ms)) => ms.parse().ok().filter(|&ms| ms > 0).map(|ms| Contestant::Mcts(Duration::from_millis(ms))).ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Contestant::Level(level) => level.name().to_string(),
+            Contestant::Depth(depth) => format!("depth:{}", depth),
+            Contestant::Mcts(time) => format!("mcts:{}", time.as_millis()),
+        }
+    }
+
+    fn choose_move<B: Bits>(&self, board: &Board<B>, solver: &Solver, new_mcts: &impl Fn(Board<B>) -> Mcts<B>, seed: u64) -> u32 {
+        match *self {
+            Contestant::Level(level) => Player::new(level, seed).choose_move(board, solver),
+            Contestant::Depth(depth) => heuristic::search(board, depth, || true).expect("the search is never stopped").col,
+            Contestant::Mcts(time) => {
+                let start = Instant::now();
+                let mut mcts = new_mcts(*board).with_seed(seed);
+                mcts.run(|_| start.elapsed() < time && !solver.is_aborted());
+                mcts.best_move()
+            }
+        }
+    }
+}
+
+// a から見た勝ち・引き分け・負けの数
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Tally {
+    pub fn games(&self) -> u32 { self.wins + self.draws + self.losses }
+
+    // 1 局あたりの得点 (勝ち 1、引き分け 0.5)
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    // (Elo 差, 95% 信頼区間の下限, 上限)。1 局ごとの得点の標準誤差から得点の区間を求め、Elo に直す。
+    // 全勝・全敗では無限大になる。
+    pub fn elo(&self) -> (f64, f64, f64) {
+        let n = self.games().max(1) as f64;
+        let p = self.score();
+        let variance = (self.wins as f64 * (1.0 - p).powi(2) + self.draws as f64 * (0.5 - p).powi(2)
+            + self.losses as f64 * p.powi(2)) / n;
+        let margin = 1.96 * (variance / n).sqrt();
+        (elo(p), elo(p - margin), elo(p + margin))
+    }
+}
+
+// 得点率 p に対応するレーティング差
+fn elo(p: f64) -> f64 {
+    if p <= 0.0 { f64::NEG_INFINITY } else if p >= 1.0 { f64::INFINITY } else { -400.0 * (1.0 / p - 1.0).log10() }
+}
+
+fn format_elo(elo: f64) -> String {
+    if elo.is_infinite() { (if elo > 0.0 { "+inf" } else { "-inf" }).to_string() } else { format!("{:+.0}", elo) }
+}
+
+// root から opening_plies 手をランダムに打った開局。終局してしまう手順は（100 回までは）選び直す。
+fn random_opening<B: Bits>(root: Board<B>, opening_plies: u32, rng: &mut u64) -> Vec<u32> {
+    root.random_unfinished(opening_plies, rng).unwrap_or_else(|| root.random(opening_plies, rng)).1
+}
+
+// a と b を games 局対局させて a から見た結果を返す。Ctrl-C で止められたら、途中の対局を数えずに止める。
+#[allow(clippy::too_many_arguments)]
+pub fn run<B: Bits>(solver: &Solver, root: Board<B>, a: Contestant, b: Contestant, games: u32, opening_plies: u32,
+    seed: u64, new_mcts: impl Fn(Board<B>) -> Mcts<B>) -> Tally {
+    let mut rng = seed;
+    let mut tally = Tally::default();
+    let mut opening = Vec::new();
+    for game in 0..games {
+        // 偶数局目で開局を選び、奇数局目は同じ開局で先後を入れ替える
+        if game.is_multiple_of(2) { opening = random_opening(root, opening_plies, &mut rng); }
+        let a_first = game.is_multiple_of(2);
+        let mut board = root;
+        for &col in &opening { board.play(col); }
+        let mut moves = opening.clone();
+        while !board.is_over() {
+            // 開始局面の手番側が「先」
+            let a_to_move = (board.moves() - root.moves()).is_multiple_of(2) == a_first;
+            let player = if a_to_move { a } else { b };
+            let col = player.choose_move(&board, solver, &new_mcts, seed.wrapping_add(game as u64 * 1000 + board.moves() as u64));
+            solver.clear_abort();
+            if crate::signal::interrupted() { return tally; }
+            board.play(col);
+            moves.push(col);
+        }
+        // 最後に打った側が勝ち
+        let result = if !board.is_win() {
+            tally.draws += 1;
+            "1/2-1/2"
+        } else if (board.moves() - 1 - root.moves()).is_multiple_of(2) == a_first {
+            tally.wins += 1;
+            if a_first { "1-0" } else { "0-1" }
+        } else {
+            tally.losses += 1;
+            if a_first { "0-1" } else { "1-0" }
+        };
+        let (first, second) = if a_first { (a, b) } else { (b, a) };
+        let moves: String = moves.iter().map(|c| (c + 1).to_string()).collect();
+        if output::is_json() {
+            JsonObject::new("game").num("game", game + 1).str("first", &first.name()).str("second", &second.name())
+                .str("moves", &moves).str("result", result).emit();
+        } else {
+            println!("Game {}: {} vs {}: {} {}", game + 1, first.name(), second.name(), moves, result);
+        }
+    }
+    tally
+}
+
+pub fn report(a: Contestant, b: Contestant, tally: &Tally) {
+    let (elo, lo, hi) = tally.elo();
+    if output::is_json() {
+        JsonObject::new("result").str("a", &a.name()).str("b", &b.name()).num("wins", tally.wins).num("draws", tally.draws)
+            .num("losses", tally.losses).num("score", format!("{:.4}", tally.score()))
+            .opt_num("elo", elo.is_finite().then_some(elo.round())).opt_num("elo_low", lo.is_finite().then_some(lo.round()))
+            .opt_num("elo_high", hi.is_finite().then_some(hi.round())).emit();
+    } else {
+        println!("{} vs {}: +{} ={} -{} | Score: {:.1}% | Elo: {} [{}, {}] (95%)", a.name(), b.name(), tally.wins, tally.draws,
+            tally.losses, tally.score() * 100.0, format_elo(elo), format_elo(lo), format_elo(hi));
+    }
+}
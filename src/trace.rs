@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+// 探索木のトレース（Solver::with_trace）。盤の石の数が max_ply 以下のノードについて、評価値が決まるたびに
+// 局面のキー・探索の窓・返した評価値・最善手を 1 件ずつファイルに書く。枝刈りの誤りを後から調べたり、探索を図にしたりするのに使う。
+// 記録は子が親より先（帰りがけ順）。並列探索ではスレッドごとの列が混ざるので、thread で分けてから ply で木に戻す。
+//
+//   ndjson: 1 行 1 件
+//     {"key":1234,"mirrored":false,"ply":5,"alpha":-1,"beta":0,"score":0,"move":4,"thread":2}
+//     move は 1 始まりの列で、子を調べずに決まったノード（TT・即勝ちなど）では null。
+//   binary: 先頭に MAGIC と盤の幅・高さ（各 1 バイト）、以降は 1 件 16 バイト（リトルエンディアン）
+//     key: u64, ply: u8, alpha: i8, beta: i8, score: i8, move: u8（0 始まり、なければ 0xFF）,
+//     flags: u8（bit 0: key は左右反転した側のもの）, thread: u16（rayon のワーカー番号 + 1、それ以外は 0）
+pub const TRACE_MAGIC: &[u8; 8] = b"C4TRACE1";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    #[default]
+    Ndjson,
+    Binary,
+}
+
+impl TraceFormat {
+    pub const ALL: [TraceFormat; 2] = [TraceFormat::Ndjson, TraceFormat::Binary];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TraceFormat::Ndjson => "ndjson",
+            TraceFormat::Binary => "binary",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+}
+
+// 評価値が決まったノード 1 つ
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    // Board::table_key の値（左右対称な局面で共通）と、この局面がその反転側か
+    pub key: u64,
+    pub mirrored: bool,
+    // 盤の石の数
+    pub ply: u32,
+    // 入ったときの探索の窓 (alpha, beta)
+    pub alpha: i8,
+    pub beta: i8,
+    pub score: i8,
+    // 最善手（0 始まりの列）。子を調べずに決まったノードでは None。
+    pub best_move: Option<u32>,
+    pub thread: u16,
+}
+
+pub struct SearchTrace {
+    format: TraceFormat,
+    max_ply: u32,
+    out: Mutex<BufWriter<File>>,
+    // 最初の書き込みの失敗（finish で返す）
+    error: Mutex<Option<io::Error>>,
+}
+
+impl SearchTrace {
+    // path を作り直して書き始める。width, height は binary の先頭に書く盤の大きさ。
+    pub fn create(path: impl AsRef<Path>, format: TraceFormat, max_ply: u32, width: u32, height: u32) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        if format == TraceFormat::Binary {
+            out.write_all(TRACE_MAGIC)?;
+            out.write_all(&[width as u8, height as u8])?;
+        }
+        Ok(Self { format, max_ply, out: Mutex::new(out), error: Mutex::new(None) })
+    }
+
+    #[inline(always)]
+    pub(crate) fn wants(&self, ply: u32) -> bool { ply <= self.max_ply }
+
+    pub(crate) fn record(&self, record: &TraceRecord) {
+        let mut out = self.out.lock().unwrap();
+        let result = match self.format {
+            TraceFormat::Ndjson => writeln!(out,
+                "{{\"key\":{},\"mirrored\":{},\"ply\":{},\"alpha\":{},\"beta\":{},\"score\":{},\"move\":{},\"thread\":{}}}",
+                record.key, record.mirrored, record.ply, record.alpha, record.beta, record.score,
+                record.best_move.map_or("null".to_string(), |col| (col + 1).to_string()), record.thread),
+            TraceFormat::Binary => {
+                let mut bytes = [0u8; 16];
+                bytes[..8].copy_from_slice(&record.key.to_le_bytes());
+                bytes[8] = record.ply as u8;
+                bytes[9] = record.alpha as u8;
+                bytes[10] = record.beta as u8;
+                bytes[11] = record.score as u8;
+                bytes[12] = record.best_move.map_or(0xFF, |col| col as u8);
+                bytes[13] = record.mirrored as u8;
+                bytes[14..].copy_from_slice(&record.thread.to_le_bytes());
+                out.write_all(&bytes)
+            }
+        };
+        if let Err(e) = result {
+            self.error.lock().unwrap().get_or_insert(e);
+        }
+    }
+
+    // バッファを書き出し、それまでの書き込みの失敗があれば返す
+    pub fn finish(&self) -> io::Result<()> {
+        if let Some(e) = self.error.lock().unwrap().take() { return Err(e); }
+        self.out.lock().unwrap().flush()
+    }
+}
@@ -0,0 +1,756 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::counter::{self, ProbeCounter};
+use crate::numa::{self, NumaPolicy};
+
+// 既定は 8byte * 2^32 = 32GB。32 ビット環境 (wasm32) では 2^32 を表せないので 2^31 に抑える。
+#[cfg(target_pointer_width = "64")]
+pub const DEFAULT_TABLE_ENTRIES: usize = 1 << 32;
+#[cfg(not(target_pointer_width = "64"))]
+pub const DEFAULT_TABLE_ENTRIES: usize = 1 << 31;
+pub const ENTRY_BYTES: usize = std::mem::size_of::<Entry>();
+
+// TT ファイルのヘッダ。エントリのレイアウトや HashFunction の中身、キーの作り方を変えたら TABLE_VERSION を上げること。
+const TABLE_MAGIC: &[u8; 4] = b"C4TT";
+const TABLE_VERSION: u32 = 7;
+const HEADER_BYTES: usize = 32;
+// 盤のキーの作り方 (0: position + mask, 1: Zobrist)。違うビルドの TT は読み込めない。
+const KEY_SCHEME: u32 = if cfg!(feature = "zobrist") { 1 } else { 0 };
+// ファイルバックの mmap ではヘッダを 1 ページ分確保し、その後ろにエントリを並べる
+const MMAP_HEADER_BYTES: usize = 4096;
+
+// 1 エントリ 8 バイト。読み書きが 1 回の atomic 操作で済むので、別スレッドの書き込みと混ざることもない。
+pub(crate) struct Entry(AtomicU64);
+
+// 8 エントリ (64 バイト) で 1 バケット。キャッシュラインに揃えて置き、1 回のメモリアクセスでまとめて調べる。
+pub(crate) const BUCKET_ENTRIES: usize = 8;
+#[repr(align(64))]
+struct Bucket([Entry; BUCKET_ENTRIES]);
+
+// エントリのレイアウト:
+//   bit  0- 7: 残り深さ (SIZE - moves)
+//   bit  8-15: 評価値 (i8)
+//   bit 16-23: 最善手の列
+//   bit 24-25: 評価値の種類（0 は未使用）
+//   bit 26-28: 書き込んだ探索の世代（置き換えで古いエントリを優先して捨てる）
+//   bit 29-63: 検証用のキーのハッシュの上位 35 ビット
+// 照合するのはハッシュの bit 3 以上のうち、バケットの添字になる分と bit 29 以上の検証ビット（bit 0-2 は見ない）。
+// TT に無い局面が同じバケットの 8 エントリのどれかと誤って一致する確率は、1 回の引きあたりエントリ数が 2^29 未満なら
+// 8 / 2^35（約 2e-10）、2^29 以上なら 8 / 2^61。HashFunction は全単射なので、ほかに誤るのはキー自体が衝突したときだけで、
+// 64 ビットを超える盤（Bits::fold）と立体四目並べ（Cube::key）のキーは単射でなく、n 個の局面のうち約 n^2 / 2^65 組が衝突する。
+// 誤った一致は検出しないので、評価値が狂いうる確率としてこれらに頼っている。hashstats で両方を測れる
+// （Check collisions はバケットと検証ビットの衝突、Key collisions は盤のキーの衝突）。
+const AGE_SHIFT: u32 = 26;
+const AGES: u8 = 8;
+pub(crate) const CHECK_SHIFT: u32 = 29;
+const CHECK_MASK: u64 = !0 << CHECK_SHIFT;
+// 評価値の種類: ちょうどその値か、それ以上（下限）か、それ以下（上限）か
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact = 1,
+    Lower = 2,
+    Upper = 3,
+}
+
+impl Bound {
+    fn from_bits(bits: u64) -> Option<Self> {
+        match bits & 3 {
+            1 => Some(Bound::Exact),
+            2 => Some(Bound::Lower),
+            3 => Some(Bound::Upper),
+            _ => None,
+        }
+    }
+
+    // 窓 (alpha, beta) の探索で得た評価値 score がどの種類の境界か
+    pub(crate) fn of(score: i8, alpha: i8, beta: i8) -> Self {
+        if score <= alpha { Bound::Upper } else if score >= beta { Bound::Lower } else { Bound::Exact }
+    }
+
+    // TT の評価値 score（この種類の境界）で窓を狭める。子を調べずに評価値が決まればそれを返す。
+    #[inline(always)]
+    pub(crate) fn narrow(self, score: i8, alpha: &mut i8, beta: &mut i8) -> Option<i8> {
+        match self {
+            Bound::Exact => return Some(score),
+            Bound::Lower => if score > *alpha { *alpha = score; },
+            Bound::Upper => if score < *beta { *beta = score; },
+        }
+        (*alpha >= *beta).then_some(score)
+    }
+}
+
+// キーを混ぜて 64 ビットのハッシュにする関数。どれも全単射で、下位ビットを添字、上位ビットを検証に使う。
+// 速さと偏りの少なさのどちらが効くかは TT の大きさで変わるので、hashstats コマンドで測って選ぶ。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashFunction {
+    // splitmix64 の finalizer
+    #[default]
+    SplitMix64,
+    // xxHash64 の 8 バイト入力の処理 (seed 0)
+    XxHash,
+    // 奇数の定数を掛けるだけ。上位ビットほどよく混ざるので、バイト順を逆にして上位を添字に使う。
+    MultiplyShift,
+}
+
+impl HashFunction {
+    pub const ALL: [HashFunction; 3] = [HashFunction::SplitMix64, HashFunction::XxHash, HashFunction::MultiplyShift];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashFunction::SplitMix64 => "splitmix64",
+            HashFunction::XxHash => "xxhash",
+            HashFunction::MultiplyShift => "multiply-shift",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|hash| hash.name() == name)
+    }
+
+    #[inline(always)]
+    pub fn mix(self, key: u64) -> u64 {
+        match self {
+            HashFunction::SplitMix64 => {
+                let mut x = key;
+                x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+                x ^ (x >> 31)
+            }
+            HashFunction::XxHash => {
+                const P1: u64 = 0x9e3779b185ebca87;
+                const P2: u64 = 0xc2b2ae3d27d4eb4f;
+                const P3: u64 = 0x165667b19e3779f9;
+                const P4: u64 = 0x85ebca77c2b2ae63;
+                const P5: u64 = 0x27d4eb2f165667c5;
+                let k = key.wrapping_mul(P2).rotate_left(31).wrapping_mul(P1);
+                let mut h = (P5.wrapping_add(8) ^ k).rotate_left(27).wrapping_mul(P1).wrapping_add(P4);
+                h = (h ^ (h >> 33)).wrapping_mul(P2);
+                h = (h ^ (h >> 29)).wrapping_mul(P3);
+                h ^ (h >> 32)
+            }
+            HashFunction::MultiplyShift => key.wrapping_mul(0x9e3779b97f4a7c15).swap_bytes(),
+        }
+    }
+
+    fn id(self) -> u32 {
+        match self {
+            HashFunction::SplitMix64 => 0,
+            HashFunction::XxHash => 1,
+            HashFunction::MultiplyShift => 2,
+        }
+    }
+
+    fn from_id(id: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|hash| hash.id() == id)
+    }
+}
+
+#[inline(always)]
+pub(crate) fn hash_key(hash: HashFunction, key: u64, index_mask: usize) -> usize {
+    (hash.mix(key) as usize) & index_mask
+}
+
+// TT の確保方法
+#[derive(Clone, Debug, Default)]
+pub enum TableBackend {
+    // Vec に全エントリを書き込んで物理メモリを確実に確保する（従来の方式）
+    #[default]
+    Heap,
+    // 匿名 mmap。ゼロページを遅延確保するので起動は一瞬。populate で MAP_POPULATE を付ける。
+    // numa で NUMA ノードへの置き方を指定する。
+    Mmap { populate: bool, huge_pages: HugePages, numa: NumaPolicy },
+    // ファイルバックの mmap。内容はそのままファイルに残り、次回起動時に再利用される。
+    File { path: PathBuf, populate: bool },
+}
+
+// 巨大な TT へのランダムアクセスは TLB ミスが支配的なので huge page を使う。
+// 明示的な huge page (hugetlbfs) が確保できなければ THP、それも駄目なら通常ページに落とす。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HugePages {
+    #[default]
+    Off,
+    // madvise(MADV_HUGEPAGE) による transparent huge page
+    Transparent,
+    // MAP_HUGETLB による 2MB / 1GB ページ（事前に vm.nr_hugepages の設定が必要）
+    Explicit2M,
+    Explicit1G,
+}
+
+enum Storage {
+    Heap(#[allow(dead_code)] Vec<Bucket>),
+    Mmap { base: *mut u8, bytes: usize },
+}
+
+pub(crate) struct TranspositionTable {
+    ptr: *const Entry,
+    len: usize,
+    // NumaPolicy::Partition では TT を partitions 個に等分し、バケットはスレッドの部分の中からハッシュで選ぶ。
+    // partition_len はその 1 つ分のエントリ数で、index_mask は partition_len - 1。分けなければ partitions は 1。
+    partitions: usize,
+    partition_len: usize,
+    index_mask: usize,
+    storage: Storage,
+    huge_pages: HugePages,
+    numa: NumaPolicy,
+    hash: HashFunction,
+    // 既存のファイルバックの TT なら、そのエントリを書いたハッシュ関数（変えるとエントリが見つからなくなる）
+    file_hash: Option<HashFunction>,
+    // 盤の (幅, 高さ, 何目並べ)。ほかの盤の TT のエントリは評価値が違うので読み込まない。0 は未設定。
+    geometry: [u8; 3],
+    // ファイルバックの mmap ならファイル先頭のヘッダ
+    header: Option<*mut u8>,
+    generation: AtomicU8,
+    probes: ProbeCounter,
+}
+
+// TT の利用状況。サイズや置換方式の調整に使う。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TableStats {
+    pub probes: usize,
+    pub hits: usize,
+    // バケットに別の局面が入っていて見つからなかった回数
+    pub collisions: usize,
+    // 使用中のエントリの割合 (0〜1) の推定値
+    pub fill: f64,
+}
+
+impl TableStats {
+    pub fn hit_rate(&self) -> f64 { self.hits as f64 / self.probes.max(1) as f64 }
+    pub fn collision_rate(&self) -> f64 { self.collisions as f64 / self.probes.max(1) as f64 }
+}
+
+// エントリはすべて atomic なので共有しても安全
+unsafe impl Send for TranspositionTable {}
+unsafe impl Sync for TranspositionTable {}
+
+impl Drop for TranspositionTable {
+    fn drop(&mut self) {
+        if let Storage::Mmap { base, bytes } = self.storage {
+            unsafe { sys::munmap(base.cast(), bytes); }
+        }
+    }
+}
+
+// 4KB ページごとのバケット数
+const PAGE_BUCKETS: usize = 4096 / std::mem::size_of::<Bucket>();
+
+#[cfg(feature = "parallel")]
+fn prefault(buckets: &[Bucket]) {
+    use rayon::prelude::*;
+    buckets.par_chunks(PAGE_BUCKETS).for_each(|page| page[0].0[0].0.store(0, Ordering::Relaxed));
+}
+#[cfg(not(feature = "parallel"))]
+fn prefault(buckets: &[Bucket]) {
+    for page in buckets.chunks(PAGE_BUCKETS) { page[0].0[0].0.store(0, Ordering::Relaxed); }
+}
+
+impl TranspositionTable {
+    // エントリ数は 2 のべき乗に切り下げる（バケット 1 つ分の最低 8）
+    pub(crate) fn round_entries(table_entries: usize) -> usize {
+        match table_entries {
+            0..=BUCKET_ENTRIES => BUCKET_ENTRIES,
+            n if n.is_power_of_two() => n,
+            n => n.next_power_of_two() >> 1,
+        }
+    }
+
+    pub(crate) fn new(table_entries: usize, backend: &TableBackend) -> io::Result<Self> {
+        let entries = Self::round_entries(table_entries);
+        match backend {
+            TableBackend::Heap => Ok(Self::heap(entries)),
+            TableBackend::Mmap { populate, huge_pages, numa } => {
+                // NUMA の置き方はページに触れる前に決めるので、MAP_POPULATE ではなく後で書き込んで確保する
+                let map_populate = *populate && *numa == NumaPolicy::Off;
+                let (base, bytes, huge_pages) = sys::map(entries * ENTRY_BYTES, None, map_populate, *huge_pages)?;
+                let mut table = Self::from_mapping(base, bytes, 0, entries);
+                table.huge_pages = huge_pages;
+                // 置けなければ（ノードが 1 つの環境などでも）通常の確保のまま使う
+                if let Ok((numa, partitions)) = numa::bind(base, bytes, *numa) {
+                    table.numa = numa;
+                    table.partitions = partitions;
+                    table.partition_len = entries / partitions;
+                    table.index_mask = table.partition_len - 1;
+                }
+                if *populate && !map_populate { prefault(table.buckets()); }
+                Ok(table)
+            }
+            TableBackend::File { path, populate } => Self::file(entries, path, *populate),
+        }
+    }
+
+    fn heap(entries: usize) -> Self {
+        // ゼロ埋めで確保すれば 0 の空エントリとしてそのまま使える (calloc と同じく OS のゼロページを使う)
+        let buckets = entries / BUCKET_ENTRIES;
+        let layout = std::alloc::Layout::array::<Bucket>(buckets).expect("table size overflows");
+        let table_vec = unsafe {
+            let ptr = std::alloc::alloc_zeroed(layout).cast::<Bucket>();
+            if ptr.is_null() { std::alloc::handle_alloc_error(layout); }
+            Vec::from_raw_parts(ptr, buckets, buckets)
+        };
+        // 探索中のページフォールトを避けるため、物理メモリへの割り当てをここで済ませておく。
+        // ページごとに 1 回書き込めばよく、rayon で分担すれば 32GB でも数秒で終わる。
+        prefault(&table_vec);
+
+        Self {
+            ptr: table_vec.as_ptr().cast(),
+            len: entries,
+            partitions: 1,
+            partition_len: entries,
+            index_mask: entries - 1,
+            storage: Storage::Heap(table_vec),
+            huge_pages: HugePages::Off,
+            numa: NumaPolicy::Off,
+            hash: HashFunction::default(),
+            file_hash: None,
+            geometry: [0; 3],
+            header: None,
+            generation: AtomicU8::new(0),
+            probes: ProbeCounter::new(counter::current_threads()),
+        }
+    }
+
+    fn file(entries: usize, path: &Path, populate: bool) -> io::Result<Self> {
+        let bytes = MMAP_HEADER_BYTES + entries * ENTRY_BYTES;
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let existing = file.metadata()?.len() as usize;
+        if existing != 0 && existing != bytes {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+                "{} holds a table of {} bytes, but {} bytes were requested",
+                path.display(), existing, bytes)));
+        }
+        if existing == 0 { file.set_len(bytes as u64)?; }
+
+        let (base, bytes, _) = sys::map(bytes, Some(&file), populate, HugePages::Off)?;
+        let header = unsafe { std::slice::from_raw_parts_mut(base, MMAP_HEADER_BYTES) };
+        let (hash, geometry) = if existing == 0 {
+            header[..HEADER_BYTES].copy_from_slice(&encode_header(entries, HashFunction::default(), [0; 3]));
+            (HashFunction::default(), [0; 3])
+        } else {
+            match decode_header(&header[..HEADER_BYTES]).and_then(|(n, hash, geometry)| {
+                if n == entries { Ok((hash, geometry)) } else { Err(invalid(format!("table file has {} entries", n))) }
+            }) {
+                Ok(header) => header,
+                Err(e) => {
+                    unsafe { sys::munmap(base.cast(), bytes); }
+                    return Err(e);
+                }
+            }
+        };
+        let mut table = Self::from_mapping(base, bytes, MMAP_HEADER_BYTES, entries);
+        table.hash = hash;
+        table.file_hash = (existing != 0).then_some(hash);
+        table.geometry = geometry;
+        table.header = Some(base);
+        Ok(table)
+    }
+
+    fn from_mapping(base: *mut u8, bytes: usize, offset: usize, entries: usize) -> Self {
+        // ゼロ埋めされたページは 0 の空エントリとしてそのまま使える。
+        // mmap はページ境界、ヘッダは 1 ページ分なので、バケットはキャッシュラインに揃う。
+        let ptr = unsafe { base.add(offset) } as *const Entry;
+        Self {
+            ptr,
+            len: entries,
+            partitions: 1,
+            partition_len: entries,
+            index_mask: entries - 1,
+            storage: Storage::Mmap { base, bytes },
+            huge_pages: HugePages::Off,
+            numa: NumaPolicy::Off,
+            hash: HashFunction::default(),
+            file_hash: None,
+            geometry: [0; 3],
+            header: None,
+            generation: AtomicU8::new(0),
+            probes: ProbeCounter::new(counter::current_threads()),
+        }
+    }
+
+    // 実際に確保できた huge page の種類
+    pub(crate) fn huge_pages(&self) -> HugePages { self.huge_pages }
+    // 実際に使えた NUMA ノードへの置き方
+    pub(crate) fn numa(&self) -> NumaPolicy { self.numa }
+
+    pub(crate) fn hash_function(&self) -> HashFunction { self.hash }
+
+    // 探索を始める前に呼ぶ。既存のファイルバックの TT を別の関数で引こうとしたら InvalidData。
+    pub(crate) fn set_hash_function(&mut self, hash: HashFunction) -> io::Result<()> {
+        if let Some(file_hash) = self.file_hash && file_hash != hash {
+            return Err(invalid(format!("table file uses the {} hash function (pass --tt-hash {} or omit it)", file_hash.name(), file_hash.name())));
+        }
+        self.hash = hash;
+        if let Some(base) = self.header {
+            let header = unsafe { std::slice::from_raw_parts_mut(base, HEADER_BYTES) };
+            header.copy_from_slice(&encode_header(self.len, hash, self.geometry));
+        }
+        Ok(())
+    }
+
+    // 盤の大きさを決める。ファイルバックの TT に別の大きさの盤のエントリが入っていれば InvalidData。
+    pub(crate) fn set_geometry(&mut self, geometry: [u8; 3]) -> io::Result<()> {
+        if self.geometry != [0; 3] && self.geometry != geometry {
+            return Err(invalid(format!("table file holds {}", geometry_name(self.geometry))));
+        }
+        self.geometry = geometry;
+        if let Some(base) = self.header {
+            let header = unsafe { std::slice::from_raw_parts_mut(base, HEADER_BYTES) };
+            header.copy_from_slice(&encode_header(self.len, self.hash, geometry));
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn entries(&self) -> &[Entry] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    // 先頭はキャッシュライン（mmap ならページ）境界にある
+    fn buckets(&self) -> &[Bucket] {
+        unsafe { std::slice::from_raw_parts(self.ptr.cast(), self.len / BUCKET_ENTRIES) }
+    }
+
+    pub(crate) fn len(&self) -> usize { self.len }
+
+    // 使用中のエントリの割合。全体を数えると重いので等間隔に最大 samples 個だけ調べる。
+    pub(crate) fn fill_rate(&self, samples: usize) -> f64 {
+        let step = (self.len / samples.max(1)).max(1);
+        let (used, total) = self.entries().iter().step_by(step)
+            .fold((0usize, 0usize), |(used, total), e| (used + (e.0.load(Ordering::Relaxed) != 0) as usize, total + 1));
+        used as f64 / total as f64
+    }
+
+    // TT をファイルに書き出す。ヘッダ (magic, version, entry bytes, entries, hash function, key scheme, 盤の幅・高さ・何目並べ) の後に
+    // 各エントリを little endian で順に並べる。
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        let mut w = BufWriter::with_capacity(1 << 20, File::create(path)?);
+        w.write_all(&encode_header(self.len, self.hash, self.geometry))?;
+        for entry in self.entries() {
+            w.write_all(&entry.0.load(Ordering::Relaxed).to_le_bytes())?;
+        }
+        w.flush()
+    }
+
+    // save で書き出した TT を読み込む。サイズが異なる場合は各エントリを入れ直す。
+    // エントリからはハッシュのうちバケットを選ぶ bit 3 以上と検証用の上位ビットしか分からないので、
+    // 2^29 エントリ未満のファイルから、より大きい TT には読み込めない。
+    pub(crate) fn load(&self, path: &Path) -> io::Result<()> {
+        let mut r = BufReader::with_capacity(1 << 20, File::open(path)?);
+        let mut header = [0u8; HEADER_BYTES];
+        r.read_exact(&mut header)?;
+        let (entries, hash, geometry) = decode_header(&header)?;
+        if hash != self.hash {
+            return Err(invalid(format!("table was saved with the {} hash function (pass --tt-hash {})", hash.name(), hash.name())));
+        }
+        if geometry != self.geometry {
+            return Err(invalid(format!("table was saved for {}, not {}", geometry_name(geometry), geometry_name(self.geometry))));
+        }
+
+        let mut read_entry = || -> io::Result<u64> {
+            let mut buf = [0u8; 8];
+            r.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf))
+        };
+        if entries == self.len {
+            for slot in self.entries() {
+                slot.0.store(read_entry()?, Ordering::Relaxed);
+            }
+        } else {
+            if entries < self.len && entries < 1 << CHECK_SHIFT {
+                return Err(invalid(format!("cannot grow a table of {} entries to {} entries", entries, self.len)));
+            }
+            for i in 0..entries {
+                let word = read_entry()?;
+                if word != 0 { self.store_word((word & CHECK_MASK) | (i & !(BUCKET_ENTRIES - 1)) as u64, word); }
+            }
+        }
+        Ok(())
+    }
+
+    // 外側の探索を始めるたびに呼ぶ（Solver::begin_search）。以前の探索で書き込んだエントリを置き換えやすくする。
+    pub(crate) fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // 今のスレッドが使う TT の部分。Partition で TT を分けていれば、探索のワーカーは番号からノード（pin_worker と同じ割り振り）の部分を使う。
+    // ワーカー以外のスレッドは None（どの部分も使う）。
+    #[inline(always)]
+    fn worker_partition(&self) -> Option<usize> {
+        if self.partitions == 1 { return Some(0); }
+        #[cfg(feature = "parallel")]
+        let index = rayon::current_thread_index();
+        #[cfg(not(feature = "parallel"))]
+        let index: Option<usize> = None;
+        index.map(|index| index % self.partitions)
+    }
+
+    // hash の局面を書く部分。ワーカー以外のスレッドはハッシュで選ぶ（lookup では全ての部分を引く）。
+    #[inline(always)]
+    fn home_partition(&self, hash: u64) -> usize {
+        self.worker_partition().unwrap_or_else(|| (hash >> 32) as usize % self.partitions)
+    }
+
+    #[inline(always)]
+    fn bucket_in(&self, hash: u64, partition: usize) -> &[Entry] {
+        let start = partition * self.partition_len + ((hash as usize) & self.index_mask & !(BUCKET_ENTRIES - 1));
+        &self.entries()[start..start + BUCKET_ENTRIES]
+    }
+
+    // key のバケットをキャッシュに読み込み始める。巨大な TT の lookup はほぼ毎回メインメモリまで行くので、
+    // 子の局面を作った時点で呼んでおけば、子の探索が lookup に着くまでの処理と読み込みが重なる。
+    #[inline(always)]
+    pub(crate) fn prefetch(&self, key: u64) {
+        let hash = self.hash.mix(key);
+        let start = self.home_partition(hash) * self.partition_len + ((hash as usize) & self.index_mask & !(BUCKET_ENTRIES - 1));
+        let addr = self.ptr.wrapping_add(start).cast::<i8>();
+        #[cfg(target_arch = "x86_64")]
+        unsafe { std::arch::x86_64::_mm_prefetch::<{ std::arch::x86_64::_MM_HINT_T0 }>(addr); }
+        #[cfg(target_arch = "aarch64")]
+        unsafe { std::arch::asm!("prfm pldl1keep, [{0}]", in(reg) addr, options(nostack, readonly, preserves_flags)); }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        let _ = addr;
+    }
+
+    pub(crate) fn store(&self, key: u64, score: i8, bound: Bound, best_col: u32, depth: u32) {
+        let hash = self.hash.mix(key);
+        let age = (self.generation.load(Ordering::Relaxed) % AGES) as u64;
+        let word = (hash & CHECK_MASK) | (age << AGE_SHIFT) | ((bound as u64) << 24) | ((best_col as u64) << 16)
+            | ((score as u8 as u64) << 8) | depth as u64;
+        self.store_word(hash, word);
+    }
+
+    // 同じ局面のエントリか空きがあればそこに書き、無ければ残り深さから古さを引いた値が最も小さい
+    // （探索し直すコストが小さいか、古い探索の）エントリを置き換える。
+    // hash はバケットを選ぶ bit 3 以上と検証用の上位ビットだけ正しければよい。
+    fn store_word(&self, hash: u64, word: u64) {
+        let generation = (word >> AGE_SHIFT) as u8 % AGES;
+        let value = |w: u64| {
+            let age = generation.wrapping_sub((w >> AGE_SHIFT) as u8) % AGES;
+            (w as u8) as i32 - 4 * age as i32
+        };
+        let bucket = self.bucket_in(hash, self.home_partition(hash));
+        let mut victim = &bucket[0];
+        let mut victim_value = i32::MAX;
+        for slot in bucket {
+            let w = slot.0.load(Ordering::Relaxed);
+            if w == 0 || (w ^ word) & CHECK_MASK == 0 { victim = slot; break; }
+            let v = value(w);
+            if v < victim_value { victim = slot; victim_value = v; }
+        }
+        victim.0.store(word, Ordering::Relaxed);
+    }
+
+    // 引き当ての回数を数える欄を、探索に使うスレッド数に合わせて作り直す（数え直しになる）
+    #[cfg(feature = "parallel")]
+    pub(crate) fn set_threads(&mut self, threads: usize) {
+        self.probes = ProbeCounter::new(threads);
+    }
+
+    pub(crate) fn lookup(&self, key: u64) -> Option<(i8, Bound, u32)> {
+        let hash = self.hash.mix(key);
+        let mut occupied = false;
+        let found = match self.worker_partition() {
+            Some(partition) => self.lookup_in(hash, partition, &mut occupied),
+            None => (0..self.partitions).find_map(|partition| self.lookup_in(hash, partition, &mut occupied)),
+        };
+        self.probes.record(found.is_some(), found.is_none() && occupied);
+        found
+    }
+
+    #[inline(always)]
+    fn lookup_in(&self, hash: u64, partition: usize, occupied: &mut bool) -> Option<(i8, Bound, u32)> {
+        for slot in self.bucket_in(hash, partition) {
+            let word = slot.0.load(Ordering::Relaxed);
+            if (word ^ hash) & CHECK_MASK == 0
+                && let Some(bound) = Bound::from_bits(word >> 24) {
+                return Some(((word >> 8) as u8 as i8, bound, (word >> 16) as u8 as u32));
+            }
+            *occupied |= word != 0;
+        }
+        None
+    }
+
+    pub(crate) fn stats(&self) -> TableStats {
+        let (probes, hits, collisions) = self.probes.totals();
+        TableStats { probes, hits, collisions, fill: self.fill_rate(1 << 16) }
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn encode_header(entries: usize, hash: HashFunction, geometry: [u8; 3]) -> [u8; HEADER_BYTES] {
+    let mut header = [0u8; HEADER_BYTES];
+    header[0..4].copy_from_slice(TABLE_MAGIC);
+    header[4..8].copy_from_slice(&TABLE_VERSION.to_le_bytes());
+    header[8..12].copy_from_slice(&(ENTRY_BYTES as u32).to_le_bytes());
+    header[12..20].copy_from_slice(&(entries as u64).to_le_bytes());
+    header[20..24].copy_from_slice(&hash.id().to_le_bytes());
+    header[24..28].copy_from_slice(&KEY_SCHEME.to_le_bytes());
+    header[28..31].copy_from_slice(&geometry);
+    header
+}
+
+// ヘッダを検証してエントリ数とハッシュ関数、盤の大きさを返す
+fn decode_header(header: &[u8]) -> io::Result<(usize, HashFunction, [u8; 3])> {
+    if &header[0..4] != TABLE_MAGIC { return Err(invalid("not a transposition table file".into())); }
+    let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    let entry_bytes = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    if version != TABLE_VERSION || entry_bytes as usize != ENTRY_BYTES {
+        return Err(invalid(format!("unsupported table version {} (expected {})", version, TABLE_VERSION)));
+    }
+    if u32::from_le_bytes(header[24..28].try_into().unwrap()) != KEY_SCHEME {
+        return Err(invalid("table was built with a different key scheme (the zobrist feature must match)".into()));
+    }
+    let id = u32::from_le_bytes(header[20..24].try_into().unwrap());
+    let hash = HashFunction::from_id(id).ok_or_else(|| invalid(format!("unknown hash function {}", id)))?;
+    let geometry = header[28..31].try_into().unwrap();
+    Ok((u64::from_le_bytes(header[12..20].try_into().unwrap()) as usize, hash, geometry))
+}
+
+fn geometry_name([width, height, connect]: [u8; 3]) -> String {
+    if width == 0 { "an unknown board".to_string() } else { format!("a {}x{} connect-{} board", width, height, connect) }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::HugePages;
+    use std::ffi::{c_int, c_long, c_void};
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::AsRawFd;
+
+    const PROT_READ: c_int = 0x1;
+    const PROT_WRITE: c_int = 0x2;
+    const MAP_SHARED: c_int = 0x01;
+    const MAP_PRIVATE: c_int = 0x02;
+    const MAP_ANONYMOUS: c_int = 0x20;
+    const MAP_NORESERVE: c_int = 0x4000;
+    const MAP_POPULATE: c_int = 0x8000;
+    const MAP_HUGETLB: c_int = 0x40000;
+    const MAP_HUGE_SHIFT: c_int = 26;
+    const MADV_RANDOM: c_int = 1;
+    const MADV_HUGEPAGE: c_int = 14;
+
+    unsafe extern "C" {
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: c_long) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        fn madvise(addr: *mut c_void, len: usize, advice: c_int) -> c_int;
+    }
+
+    // (先頭アドレス, 実際にマップしたバイト数, 使えた huge page の種類) を返す
+    pub fn map(bytes: usize, file: Option<&File>, populate: bool, huge_pages: HugePages)
+        -> io::Result<(*mut u8, usize, HugePages)> {
+        let mut flags = match file {
+            Some(_) => MAP_SHARED,
+            None => MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE,
+        };
+        if populate { flags |= MAP_POPULATE; }
+        let fd = file.map_or(-1, |f| f.as_raw_fd());
+
+        let explicit = match huge_pages {
+            HugePages::Explicit2M => Some(21),
+            HugePages::Explicit1G => Some(30),
+            _ => None,
+        };
+        if let Some(shift) = explicit && file.is_none() {
+            // hugetlb のマッピング長はページサイズの倍数でなければならない。
+            // NORESERVE だとページ不足がアクセス時の SIGBUS になるので、予約して mmap で失敗させる。
+            let page = 1usize << shift;
+            let len = bytes.div_ceil(page) * page;
+            let huge_flags = (flags & !MAP_NORESERVE) | MAP_HUGETLB | (shift << MAP_HUGE_SHIFT);
+            let base = unsafe { mmap(std::ptr::null_mut(), len, PROT_READ | PROT_WRITE, huge_flags, -1, 0) };
+            if base as isize != -1 { return Ok((base.cast(), len, huge_pages)); }
+        }
+
+        let base = unsafe { mmap(std::ptr::null_mut(), bytes, PROT_READ | PROT_WRITE, flags, fd, 0) };
+        if base as isize == -1 { return Err(io::Error::last_os_error()); }
+        // TT へのアクセスは完全にランダムなので先読みを止める（失敗しても問題ない）
+        unsafe { madvise(base, bytes, MADV_RANDOM); }
+        let used = if huge_pages != HugePages::Off && unsafe { madvise(base, bytes, MADV_HUGEPAGE) } == 0 {
+            HugePages::Transparent
+        } else {
+            HugePages::Off
+        };
+        Ok((base.cast(), bytes, used))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use super::HugePages;
+    use std::ffi::{c_int, c_void};
+    use std::fs::File;
+    use std::io;
+
+    pub fn map(_bytes: usize, _file: Option<&File>, _populate: bool, _huge_pages: HugePages)
+        -> io::Result<(*mut u8, usize, HugePages)> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "mmap tables are only supported on Linux"))
+    }
+
+    pub unsafe fn munmap(_addr: *mut c_void, _len: usize) -> c_int { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> TranspositionTable {
+        TranspositionTable::new(1 << 10, &TableBackend::Heap).unwrap()
+    }
+
+    // 書いた 1 エントリの生の値
+    fn only_word(table: &TranspositionTable) -> u64 {
+        let used: Vec<u64> = table.entries().iter().map(|e| e.0.load(Ordering::Relaxed)).filter(|&w| w != 0).collect();
+        assert_eq!(used.len(), 1);
+        used[0]
+    }
+
+    #[test]
+    fn entry_layout() {
+        let table = table();
+        table.new_search();
+        table.new_search();
+        table.store(12345, -7, Bound::Upper, 6, 41);
+        let word = only_word(&table);
+        assert_eq!(word & 0xff, 41);
+        assert_eq!((word >> 8) as u8 as i8, -7);
+        assert_eq!((word >> 16) & 0xff, 6);
+        assert_eq!(Bound::from_bits(word >> 24), Some(Bound::Upper));
+        assert_eq!((word >> AGE_SHIFT) & 7, 2);
+        assert_eq!(word & CHECK_MASK, HashFunction::default().mix(12345) & CHECK_MASK);
+    }
+
+    #[test]
+    fn store_and_lookup() {
+        let table = table();
+        let cases = [(1u64, 0i8, Bound::Exact, 0u32), (2, 18, Bound::Lower, 3), (3, -18, Bound::Upper, 8), (4, -1, Bound::Exact, 255)];
+        for &(key, score, bound, col) in &cases { table.store(key, score, bound, col, 10); }
+        for &(key, score, bound, col) in &cases { assert_eq!(table.lookup(key), Some((score, bound, col)), "key {}", key); }
+        assert_eq!(table.lookup(5), None);
+        // 同じ局面は上書きする
+        table.store(2, 5, Bound::Exact, 1, 10);
+        assert_eq!(table.lookup(2), Some((5, Bound::Exact, 1)));
+    }
+
+    // 満杯のバケットでは、残り深さが浅いか古い探索のエントリを捨てる
+    #[test]
+    fn replaces_shallow_and_old_entries() {
+        let table = TranspositionTable::new(BUCKET_ENTRIES, &TableBackend::Heap).unwrap();
+        for key in 0..BUCKET_ENTRIES as u64 { table.store(key, 1, Bound::Exact, 0, 20 + key as u32); }
+        table.store(100, 1, Bound::Exact, 0, 30);
+        assert_eq!(table.lookup(0), None);
+        assert!((1..BUCKET_ENTRIES as u64).all(|key| table.lookup(key).is_some()));
+        table.new_search();
+        table.new_search();
+        // 古いエントリは世代の差の 4 倍だけ浅いものとして比べる。2 世代前の深さ 22 (22 - 8) は今の深さ 16 より先に捨てる。
+        table.store(101, 1, Bound::Exact, 0, 16);
+        assert_eq!(table.lookup(1), None);
+        table.store(102, 1, Bound::Exact, 0, 5);
+        assert_eq!(table.lookup(2), None);
+        assert!(table.lookup(101).is_some() && table.lookup(100).is_some());
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::VecDeque;
+use std::io::{self, Stdout};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use connect4_solver::{Bits, Board, Progress, ProgressMonitor, Solver};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::{cursor, execute, terminal};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Axis, Block, Chart, Dataset, Gauge, GraphType, Paragraph, Sparkline};
+use ratatui::{Frame, Terminal};
+
+// --tui: 'solve <moves>' の間、端末全体に盤・列ごとの評価値・速度とノード数のグラフ・TT の埋まり具合・経過時間と残り時間の
+// 見込みを ratatui で描き直す（代替画面に切り替え、終わったら元の画面に戻す）。
+// raw モードにはしないので、Ctrl-C はそのまま探索の中断になる。
+const INTERVAL: Duration = Duration::from_millis(500);
+// グラフに残すサンプル数（INTERVAL ごとに 1 つ）
+const HISTORY: usize = 120;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColumnState {
+    Full,
+    Pending,
+    Solving,
+    Score(i8),
+    Stopped,
+}
+
+struct State {
+    title: String,
+    rows: Vec<String>,
+    columns: Vec<ColumnState>,
+    // ノード毎秒と、(経過秒, ノード数)
+    nps: VecDeque<u64>,
+    nodes: VecDeque<(f64, f64)>,
+    last: Option<Progress>,
+    start: Instant,
+}
+
+struct Screen {
+    state: State,
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl Screen {
+    fn draw(&mut self) {
+        let state = &self.state;
+        let _ = self.terminal.draw(|frame| render(frame, state));
+    }
+}
+
+pub struct Dashboard {
+    screen: Arc<Mutex<Screen>>,
+    // drop で画面を戻す前に止めるので Option
+    monitor: Option<ProgressMonitor>,
+}
+
+impl Dashboard {
+    pub fn start<B: Bits>(solver: &Arc<Solver>, board: &Board<B>, moves: &str) -> io::Result<Self> {
+        let title = format!("Position {} | {}x{} connect {} | {} to move", if moves.is_empty() { "(empty)" } else { moves },
+            board.width(), board.height(), board.connect(), board.side_to_move().symbol());
+        let mut rows: Vec<String> = board.to_grid().iter().rev()
+            .map(|row| row.iter().map(|c| c.symbol().to_string()).collect::<Vec<_>>().join(" ")).collect();
+        rows.push((1..=board.width()).map(|col| (col % 10).to_string()).collect::<Vec<_>>().join(" "));
+        let columns = (0..board.width()).map(|col| if board.can_play(col) { ColumnState::Pending } else { ColumnState::Full }).collect();
+        let state = State {
+            title, rows, columns, nps: VecDeque::new(), nodes: VecDeque::new(), last: None, start: Instant::now(),
+        };
+
+        let mut stdout = io::stdout();
+        execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+        let terminal = match Terminal::new(CrosstermBackend::new(stdout)) {
+            Ok(terminal) => terminal,
+            Err(e) => {
+                let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+                return Err(e);
+            }
+        };
+        let screen = Arc::new(Mutex::new(Screen { state, terminal }));
+        screen.lock().unwrap().draw();
+        let sink_screen = Arc::clone(&screen);
+        let monitor = ProgressMonitor::spawn(Arc::clone(solver), INTERVAL, move |p: &Progress| {
+            let mut screen = sink_screen.lock().unwrap();
+            let state = &mut screen.state;
+            if state.nps.len() == HISTORY {
+                state.nps.pop_front();
+                state.nodes.pop_front();
+            }
+            state.nps.push_back(p.nps as u64);
+            state.nodes.push_back((p.elapsed.as_secs_f64(), p.nodes as f64));
+            state.last = Some(p.clone());
+            screen.draw();
+        });
+        Ok(Self { screen, monitor: Some(monitor) })
+    }
+
+    pub fn set_column(&self, col: u32, column: ColumnState) {
+        if column == ColumnState::Solving && let Some(monitor) = &self.monitor { monitor.set_root_move(Some(col)); }
+        let mut screen = self.screen.lock().unwrap();
+        screen.state.columns[col as usize] = column;
+        screen.draw();
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        // 監視スレッドを先に止めてから画面を戻す
+        drop(self.monitor.take());
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+    }
+}
+
+// 解き終えた列の平均時間から、残りの列にかかる時間を見込む（列ごとの難しさは違うので目安）
+fn eta(state: &State) -> String {
+    let playable = state.columns.iter().filter(|&&c| c != ColumnState::Full).count();
+    let done = state.columns.iter().filter(|c| matches!(c, ColumnState::Score(_))).count();
+    if done == 0 || done == playable { return "-".to_string(); }
+    format!("{:.0?}", state.start.elapsed().mul_f64((playable - done) as f64 / done as f64))
+}
+
+fn render(frame: &mut Frame, state: &State) {
+    let [title, top, speed, nodes, table, status] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(state.rows.len().max(state.columns.len()) as u16 + 2),
+        Constraint::Min(5),
+        Constraint::Min(8),
+        Constraint::Length(3),
+        Constraint::Length(2),
+    ]).areas(frame.area());
+    frame.render_widget(Paragraph::new(state.title.as_str()).style(Style::new().add_modifier(Modifier::BOLD)), title);
+
+    // 盤の右に列ごとの評価値を並べる
+    let board_width = state.rows.first().map_or(0, |r| r.chars().count()) as u16 + 4;
+    let [board, scores] = Layout::horizontal([Constraint::Length(board_width), Constraint::Fill(1)]).areas(top);
+    let rows: Vec<Line> = state.rows.iter().map(|r| Line::from(r.as_str())).collect();
+    frame.render_widget(Paragraph::new(rows).block(Block::bordered().title("Board")), board);
+    let lines: Vec<Line> = state.columns.iter().enumerate().map(|(col, c)| {
+        let value = match c {
+            ColumnState::Full => Span::styled("full", Style::new().fg(Color::DarkGray)),
+            ColumnState::Pending => Span::raw("..."),
+            ColumnState::Solving => Span::styled("solving", Style::new().fg(Color::Yellow)),
+            ColumnState::Score(s) => Span::styled(format!("{:+}", s), Style::new().add_modifier(Modifier::BOLD)),
+            ColumnState::Stopped => Span::styled("stopped", Style::new().fg(Color::Red)),
+        };
+        Line::from(vec![Span::raw(format!("Column {}: ", col + 1)), value])
+    }).collect();
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title("Scores")), scores);
+
+    let nps: Vec<u64> = state.nps.iter().copied().collect();
+    let speed_title = match &state.last {
+        Some(p) => format!("Speed {:.2} MNPS", p.nps / 1_000_000.0),
+        None => "Speed".to_string(),
+    };
+    frame.render_widget(Sparkline::default().block(Block::bordered().title(speed_title)).data(&nps)
+        .style(Style::new().fg(Color::Cyan)), speed);
+
+    let points: Vec<(f64, f64)> = state.nodes.iter().copied().collect();
+    let (x0, x1) = (points.first().map_or(0.0, |p| p.0), points.last().map_or(1.0, |p| p.0).max(1.0));
+    let y1 = points.last().map_or(1.0, |p| p.1).max(1.0);
+    let dataset = Dataset::default().marker(symbols::Marker::Braille).graph_type(GraphType::Line)
+        .style(Style::new().fg(Color::Green)).data(&points);
+    let nodes_title = format!("Nodes {}", state.last.as_ref().map_or(0, |p| p.nodes));
+    frame.render_widget(Chart::new(vec![dataset]).block(Block::bordered().title(nodes_title))
+        .x_axis(Axis::default().bounds([x0, x1]).labels([format!("{:.0}s", x0), format!("{:.0}s", x1)]))
+        .y_axis(Axis::default().bounds([0.0, y1]).labels(["0".to_string(), format!("{:.2e}", y1)])), nodes);
+
+    let (fill, label) = match &state.last {
+        Some(p) => (p.table.fill, format!("fill {:5.1}%   hit {:5.1}%   collision {:5.1}%",
+            p.table.fill * 100.0, p.table.hit_rate() * 100.0, p.table.collision_rate() * 100.0)),
+        None => (0.0, "-".to_string()),
+    };
+    frame.render_widget(Gauge::default().block(Block::bordered().title("TT")).ratio(fill.clamp(0.0, 1.0)).label(label)
+        .gauge_style(Style::new().fg(Color::Blue)), table);
+
+    let playable = state.columns.iter().filter(|&&c| c != ColumnState::Full).count();
+    let done = state.columns.iter().filter(|c| matches!(c, ColumnState::Score(_))).count();
+    frame.render_widget(Paragraph::new(vec![
+        Line::from(format!("Elapsed: {:.1?}   Columns: {}/{}   ETA: {}", state.start.elapsed(), done, playable, eta(state))),
+        Line::from("Ctrl-C stops the search.").style(Style::new().fg(Color::DarkGray)),
+    ]), status);
+}
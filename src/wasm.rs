@@ -0,0 +1,42 @@
+// ブラウザ向けの最小限の API。wasm-bindgen で書き出すので、JS からは文字列の手順をそのまま渡せる:
+//
+//   import init, { solve, bestMove } from "./pkg/connect4_solver.js";
+//   await init();
+//   const score = solve("4453");     // 手番側から見た評価値
+//   const col = bestMove("4453");    // 1 始まりの列番号
+//
+// wasm-pack build --target web --no-default-features --features wasm
+// （または cargo build --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm
+//   の後に wasm-bindgen --target web）
+use std::sync::OnceLock;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::board::Board;
+use crate::solver::Solver;
+use crate::INVALID_POSITION;
+
+// 1 エントリ 8 バイトなので 4MB
+pub const WASM_TABLE_ENTRIES: usize = 1 << 19;
+
+fn solver() -> &'static Solver {
+    static SOLVER: OnceLock<Solver> = OnceLock::new();
+    SOLVER.get_or_init(|| Solver::new(WASM_TABLE_ENTRIES))
+}
+
+fn board(moves: &str) -> Option<Board> {
+    let board = Board::from_moves(moves).ok()?;
+    (!board.is_over()).then_some(board)
+}
+
+// 手順 moves の局面の評価値。終局済みか不正な手順なら INVALID_POSITION。
+#[wasm_bindgen]
+pub fn solve(moves: &str) -> i32 {
+    board(moves).map_or(INVALID_POSITION, |b| solver().solve(b) as i32)
+}
+
+// 最善手（1 始まりの列番号）。終局済みか不正な手順なら 0。
+#[wasm_bindgen(js_name = bestMove)]
+pub fn best_move(moves: &str) -> i32 {
+    board(moves).map_or(0, |b| solver().best_move(&b).0 as i32 + 1)
+}
@@ -0,0 +1,135 @@
+use std::io::{self, Read, Write};
+
+// server の WebSocket 用の最小限の実装 (RFC 6455)。断片化されたメッセージと拡張には対応しない。
+
+pub const OP_TEXT: u8 = 0x1;
+pub const OP_CLOSE: u8 = 0x8;
+pub const OP_PING: u8 = 0x9;
+pub const OP_PONG: u8 = 0xA;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// クライアントから受け取る 1 フレームの上限。局面の手順しか受け取らないので小さくてよい。
+const MAX_PAYLOAD: u64 = 1 << 16;
+
+// ハンドシェイクの Sec-WebSocket-Accept
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+// (opcode, payload) を読む。クライアントのフレームはマスクされている。
+pub fn read_frame(r: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    r.read_exact(&mut head)?;
+    let opcode = head[0] & 0x0F;
+    let masked = head[1] & 0x80 != 0;
+    let len = match head[1] & 0x7F {
+        126 => { let mut b = [0u8; 2]; r.read_exact(&mut b)?; u16::from_be_bytes(b) as u64 }
+        127 => { let mut b = [0u8; 8]; r.read_exact(&mut b)?; u64::from_be_bytes(b) }
+        n => n as u64,
+    };
+    if len > MAX_PAYLOAD { return Err(io::Error::new(io::ErrorKind::InvalidData, "websocket frame too large")); }
+    let mut mask = [0u8; 4];
+    if masked { r.read_exact(&mut mask)?; }
+    let mut payload = vec![0u8; len as usize];
+    r.read_exact(&mut payload)?;
+    if masked {
+        for (i, b) in payload.iter_mut().enumerate() { *b ^= mask[i % 4]; }
+    }
+    Ok((opcode, payload))
+}
+
+// サーバからのフレームはマスクしない
+pub fn write_frame(w: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut head = vec![0x80 | opcode];
+    match payload.len() {
+        n if n < 126 => head.push(n as u8),
+        n if n <= u16::MAX as usize => { head.push(126); head.extend_from_slice(&(n as u16).to_be_bytes()); }
+        n => { head.push(127); head.extend_from_slice(&(n as u64).to_be_bytes()); }
+    }
+    w.write_all(&head)?;
+    w.write_all(payload)?;
+    w.flush()
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 { msg.push(0); }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for chunk in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 { w[i] = u32::from_be_bytes(chunk[4 * i..4 * i + 4].try_into().unwrap()); }
+        for i in 16..80 { w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1); }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(wi);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, t);
+        }
+        for (x, y) in h.iter_mut().zip([a, b, c, d, e]) { *x = x.wrapping_add(y); }
+    }
+    let mut out = [0u8; 20];
+    for (i, x) in h.iter().enumerate() { out[4 * i..4 * i + 4].copy_from_slice(&x.to_be_bytes()); }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() { out.push(TABLE[(n >> (18 - 6 * i)) as usize & 63] as char); } else { out.push('='); }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6455 1.3 の例
+    #[test]
+    fn handshake_key() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(accept_key(" dGhlIHNhbXBsZSBub25jZQ==\r"), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn sha1_and_base64() {
+        let hex = |b: [u8; 20]| b.iter().map(|x| format!("{:02x}", x)).collect::<String>();
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // 2 ブロックにまたがる入力
+        assert_eq!(hex(sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+        assert_eq!([base64(b""), base64(b"f"), base64(b"fo"), base64(b"foo"), base64(b"foob")], ["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg=="]);
+    }
+
+    // RFC 6455 5.7 の例
+    #[test]
+    fn frames() {
+        let masked = [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+        assert_eq!(read_frame(&mut &masked[..]).unwrap(), (OP_TEXT, b"Hello".to_vec()));
+        let mut out = Vec::new();
+        write_frame(&mut out, OP_TEXT, b"Hello").unwrap();
+        assert_eq!(out, [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]);
+
+        // 126 以上は 16 ビットの長さ
+        let payload = vec![7u8; 300];
+        let mut out = Vec::new();
+        write_frame(&mut out, OP_PING, &payload).unwrap();
+        assert_eq!(out[..4], [0x89, 126, 0x01, 0x2c]);
+        assert_eq!(read_frame(&mut &out[..]).unwrap(), (OP_PING, payload));
+
+        let mut huge = vec![0x81, 127];
+        huge.extend_from_slice(&(MAX_PAYLOAD + 1).to_be_bytes());
+        assert_eq!(read_frame(&mut &huge[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
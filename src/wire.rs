@@ -0,0 +1,80 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::bits::Bits;
+use crate::board::Board;
+
+// serde での盤の形式（serde feature）。フィールド名と意味は変えないこと。
+//   width, height, connect  盤の大きさと何目並べ
+//   to_move                 手番の側 "X" か "O"（手数が偶数なら X）
+//   rows                    上の段から 1 行ずつの X / O / . の図（with_grid と同じ）
+// 打った手の順は持たないので、同じ局面なら手順が違っても同じ値になる。
+// 他の型 (SolveResult, BookEntry, DbRecord, DbHeader, MoveReview, MoveScore, Outcome) はフィールドをそのまま使い、列番号は 0 始まり。
+// serve の応答 (ServeMessage) と checkpoint のファイル (CheckpointHeader, CheckpointTask) もここで決め、
+// バイナリはこれを serde_json で書き出す。
+#[derive(Serialize, Deserialize)]
+struct BoardRepr {
+    width: u32,
+    height: u32,
+    connect: u32,
+    to_move: char,
+    rows: Vec<String>,
+}
+
+impl<B: Bits> Serialize for Board<B> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let rows = self.to_grid().iter().rev().map(|row| row.iter().map(|cell| cell.symbol()).collect()).collect();
+        BoardRepr {
+            width: self.width(), height: self.height(), connect: self.connect(), to_move: self.side_to_move().symbol(), rows,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de, B: Bits> Deserialize<'de> for Board<B> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = BoardRepr::deserialize(deserializer)?;
+        Board::with_size(repr.width, repr.height)
+            .and_then(|board| board.with_connect(repr.connect))
+            .and_then(|board| board.with_grid(&repr.rows.join("\n"), Some(repr.to_move)))
+            .map_err(D::Error::custom)
+    }
+}
+
+// serve の HTTP の応答と WebSocket のメッセージ。type で種類を分け、列番号は 1 始まり、評価値は局面の手番の側から見た値。
+// nodes と seconds はその要求の探索にかかった分。
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServeMessage {
+    // GET /solve
+    Solve { position: String, score: i8, best_move: u32, pv: Vec<u32>, nodes: u64, seconds: f64 },
+    // GET /analyze。打てない列は null。
+    Analyze { position: String, scores: Vec<Option<i8>>, nodes: u64, seconds: f64 },
+    // WebSocket: 評価値の範囲が狭まるたび。best_move はその時点の TT の最善手。
+    Bounds { position: String, min: i8, max: i8, nodes: u64, best_move: u32, seconds: f64 },
+    // WebSocket: 0.5 秒ごと
+    Progress { position: String, nodes: u64, best_move: u32, seconds: f64 },
+    // WebSocket: 解き終えたとき
+    Result { position: String, score: i8, best_move: u32, pv: Vec<u32>, nodes: u64, seconds: f64 },
+    Error { error: String },
+}
+
+// 初手の全探索の checkpoint ファイルの 1 行目。盤の大きさや何目並べか、弱解決かが違えば評価値の意味が変わる。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointHeader {
+    pub checkpoint: u32,
+    pub width: u32,
+    pub height: u32,
+    pub connect: u32,
+    pub weak: bool,
+}
+
+impl CheckpointHeader {
+    pub const VERSION: u32 = 2;
+}
+
+// checkpoint ファイルの 2 行目以降。解き終えたタスク（1〜3 手目の列）とその評価値。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointTask {
+    pub task: [u32; 3],
+    pub score: i8,
+}
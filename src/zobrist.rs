@@ -0,0 +1,82 @@
+// Zobrist キー (zobrist feature)。マスごと・石の持ち主ごとの乱数の xor で、Board::play で 1 手ずつ更新する。
+// position + mask と違ってキーの全ビットが局面全体に依存するので、TT の添字と検証ビットが相関しない。
+// 持ち主は手数の偶奇で区別する。手番側の石は moves % 2、相手の石はもう一方の乱数を使う
+// (Board では常に moves == mask の石の数なので、局面から一意に決まる)。
+use crate::bits::Bits;
+use crate::board::{splitmix64, MAX_BITS};
+
+const fn build_table() -> [[u64; MAX_BITS as usize]; 2] {
+    let mut table = [[0u64; MAX_BITS as usize]; 2];
+    // splitmix64 の出力列
+    let mut state: u64 = 0x0c4f_7a2e_5d31_b968;
+    let mut i = 0;
+    while i < 2 * MAX_BITS as usize {
+        table[i % 2][i / 2] = splitmix64(&mut state);
+        i += 1;
+    }
+    table
+}
+
+const TABLE: [[u64; MAX_BITS as usize]; 2] = build_table();
+
+// 持ち主 owner (0/1) の石を列 col の row 段目に置いたときに xor する値。[そのままの盤, 左右反転した盤]
+#[inline(always)]
+pub(crate) fn stone(owner: u32, col: u32, row: u32, width: u32, height: u32) -> [u64; 2] {
+    let table = &TABLE[owner as usize];
+    [table[(col * (height + 1) + row) as usize], table[((width - 1 - col) * (height + 1) + row) as usize]]
+}
+
+// 石の並び全体からのキー。mover は手番側の石 (position) の持ち主。
+pub(crate) fn full_key<B: Bits>(position: B, mask: B, mover: u32, width: u32, height: u32) -> [u64; 2] {
+    let mut key = [0, 0];
+    for col in 0..width {
+        for row in 0..height {
+            let bit = B::ONE << (col * (height + 1) + row);
+            if mask & bit == B::ZERO { continue; }
+            let owner = if position & bit != B::ZERO { mover } else { 1 - mover };
+            let [k, m] = stone(owner, col, row, width, height);
+            key[0] ^= k;
+            key[1] ^= m;
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    // Board が 1 手ずつ更新したキーが、石の並びから求め直したキーと同じであること
+    fn check<B: Bits>(board: &Board<B>) {
+        let (position, mask) = board.stones();
+        let [key, mirrored] = full_key(position, mask, board.moves() % 2, board.width(), board.height());
+        let expected = if mirrored < key { (mirrored, true) } else { (key, false) };
+        assert_eq!(board.table_key(), expected);
+        assert_eq!(board.mirror().table_key().0, expected.0);
+    }
+
+    #[test]
+    fn incremental_keys_match_recomputed() {
+        let mut rng = 0x5851_f42d_4c95_7f2d;
+        for (width, height) in [(7, 6), (6, 5), (9, 7)] {
+            let empty = Board::<u128>::with_size(width, height).unwrap();
+            for _ in 0..20 {
+                let (mut board, moves) = empty.random(width * height, &mut rng);
+                check(&board);
+                // 打った手を後ろから取り消していく
+                for &col in moves.iter().rev() {
+                    board.undo(col);
+                    check(&board);
+                }
+                assert!(board == empty);
+                // 打ち直すと同じキーになる
+                let mut replayed = empty;
+                for &col in &moves {
+                    replayed.play(col);
+                    check(&replayed);
+                }
+            }
+        }
+    }
+}